@@ -0,0 +1,1325 @@
+use std::collections::HashMap;
+
+use crate::image::{Image, Segment, Symbol};
+use crate::instructions::REGISTER_NAMES;
+use crate::loader::{DATA_BASE, TEXT_BASE};
+use crate::{Endian, EXCEPTION_VECTOR};
+
+// Where .kdata starts, as in MARS and SPIM; .ktext starts at the exception
+// vector so a handler needs no address
+const KERNEL_DATA_BASE: u32 = 0x90000000;
+
+// Assembles MARS/SPIM-style source into an image:
+//
+//   directives   .text/.data/.ktext/.kdata [address], .word, .half, .byte, .ascii, .asciiz,
+//                .space, .align, .globl, .set reorder/noreorder
+//   instructions the integer MIPS32 set, plus mfc0/mtc0/eret
+//   pseudos      nop, move, li, la, b, beqz, bnez, blt/bgt/ble/bge (and their
+//                unsigned forms), neg, negu, not, three-operand div/divu,
+//                rem/remu, loads and stores of a label, and an immediate as
+//                the last operand of the arithmetic and logic instructions
+//
+// Pseudos that need a scratch register use $at. Branches and jumps get a nop
+// in their delay slot, as MARS and SPIM programs expect none, unless
+// `.set noreorder` says the source fills the slots itself. The program
+// starts at `main` when there is one, at the start of .text otherwise.
+// Words and halfwords are laid out in the given byte order.
+pub fn assemble(source: &str, endian: Endian) -> Result<Image, String> {
+    link(&[source], endian).map_err(|(_, error)| error)
+}
+
+// Assembles several files into one program, as MARS does a project: each
+// file's .text and .data follow the previous file's, its labels are its own,
+// and those it names in .globl are seen by the other files too. A global
+// `main` is where the program starts. Errors come with the index of the
+// file they are in.
+pub fn link(sources: &[&str], endian: Endian) -> Result<Image, (usize, String)> {
+    let files = sources
+        .iter()
+        .enumerate()
+        .map(|(index, source)| parse(source).map_err(|error| (index, error)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // The first pass finds where the labels are, the second encodes with them
+    let mut assembler = Assembler {
+        endian,
+        units: files.iter().map(|_| Unit::default()).collect(),
+        ..Assembler::default()
+    };
+    assembler.pass(&files)?;
+    assembler.export()?;
+    assembler.resolved = true;
+    assembler.pass(&files)?;
+
+    let mut symbols: Vec<Symbol> = assembler
+        .units
+        .iter()
+        .flat_map(|unit| &unit.symbols)
+        .map(|(name, &address)| Symbol {
+            name: name.clone(),
+            address,
+        })
+        .collect();
+    symbols.sort_by(|a, b| (a.address, &a.name).cmp(&(b.address, &b.name)));
+    symbols.dedup();
+
+    let entry = assembler
+        .exports
+        .get("main")
+        .or_else(|| {
+            assembler
+                .units
+                .iter()
+                .find_map(|unit| unit.symbols.get("main"))
+        })
+        .copied()
+        .unwrap_or(assembler.text.base);
+    let mut segments = vec![
+        assembler.text.into_segment("text", true),
+        assembler.data.into_segment("data", false),
+    ];
+    for (section, name, executable) in [
+        (assembler.ktext, "ktext", true),
+        (assembler.kdata, "kdata", false),
+    ] {
+        if !section.bytes.is_empty() {
+            segments.push(section.into_segment(name, executable));
+        }
+    }
+    Ok(Image {
+        entry,
+        segments,
+        symbols,
+        endian: None,
+    })
+}
+
+fn parse(source: &str) -> Result<Vec<Line<'_>>, String> {
+    source
+        .lines()
+        .enumerate()
+        .map(|(number, text)| parse_line(text).map_err(|error| (number, error)))
+        .collect::<Result<Vec<Line>, _>>()
+        .map_err(|(number, error)| format!("line {}: {}", number + 1, error))
+}
+
+#[derive(Debug, Default)]
+struct Line<'a> {
+    labels: Vec<&'a str>,
+    mnemonic: Option<&'a str>,
+    operands: Vec<&'a str>,
+}
+
+fn parse_line(text: &str) -> Result<Line<'_>, String> {
+    let mut rest = strip_comment(text).trim();
+    let mut line = Line::default();
+
+    while let Some((label, after)) = rest.split_once(':') {
+        if label.is_empty()
+            || !label
+                .chars()
+                .all(|c| c.is_alphanumeric() || "_.$".contains(c))
+        {
+            break;
+        }
+        line.labels.push(label);
+        rest = after.trim_start();
+    }
+
+    if !rest.is_empty() {
+        let (mnemonic, operands) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        line.mnemonic = Some(mnemonic);
+        line.operands = split_operands(operands.trim())?;
+    }
+    Ok(line)
+}
+
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = None;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match (quoted, c) {
+            _ if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(quote), _) if c == quote => quoted = None,
+            (None, '"' | '\'') => quoted = Some(c),
+            (None, '#') => return &text[..index],
+            _ => {}
+        }
+    }
+    text
+}
+
+// Commas outside of string and character literals
+fn split_operands(text: &str) -> Result<Vec<&str>, String> {
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut operands = Vec::new();
+    let mut start = 0;
+    let mut quoted = None;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match (quoted, c) {
+            _ if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(quote), _) if c == quote => quoted = None,
+            (None, '"' | '\'') => quoted = Some(c),
+            (None, ',') => {
+                operands.push(text[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    if quoted.is_some() {
+        return Err(String::from("unterminated string"));
+    }
+    operands.push(text[start..].trim());
+
+    if operands.iter().any(|operand| operand.is_empty()) {
+        return Err(String::from("empty operand"));
+    }
+    Ok(operands)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Data,
+    KernelText,
+    KernelData,
+}
+
+#[derive(Debug)]
+struct Section {
+    base: u32,
+    bytes: Vec<u8>,
+}
+
+impl Section {
+    fn new(base: u32) -> Section {
+        Section {
+            base,
+            bytes: Vec::new(),
+        }
+    }
+
+    fn address(&self) -> u32 {
+        self.base.wrapping_add(self.bytes.len() as u32)
+    }
+
+    // Appends `count` copies of `bytes`, as long as they end below 4 GiB
+    fn extend(&mut self, bytes: &[u8], count: u64) -> Result<(), String> {
+        let room = (1u64 << 32) - (self.base as u64 + self.bytes.len() as u64);
+        let size = bytes.len() as u64 * count;
+        if size > room {
+            return Err(format!(
+                "{} bytes don't fit, only {} are left in the address space",
+                size, room
+            ));
+        }
+        self.bytes.extend(bytes.iter().cycle().take(size as usize));
+        Ok(())
+    }
+
+    fn align(&mut self, size: u32) {
+        while !self.address().is_multiple_of(size) {
+            self.bytes.push(0);
+        }
+    }
+
+    fn into_segment(self, name: &str, executable: bool) -> Segment {
+        Segment {
+            name: name.to_string(),
+            address: self.base,
+            bytes: self.bytes,
+            executable,
+        }
+    }
+}
+
+// One file's labels, and the ones it shares with the other files
+#[derive(Debug, Default)]
+struct Unit {
+    symbols: HashMap<String, u32>,
+    globals: Vec<String>,
+}
+
+#[derive(Debug)]
+struct Assembler {
+    text: Section,
+    data: Section,
+    ktext: Section,
+    kdata: Section,
+    current: Kind,
+    units: Vec<Unit>,
+    // The file being assembled
+    unit: usize,
+    // Every file's globals, known once the first pass is done
+    exports: HashMap<String, u32>,
+    // Set for the second pass, when every label has its address
+    resolved: bool,
+    // Whether delay slots are filled with nops
+    reorder: bool,
+    endian: Endian,
+}
+
+impl Default for Assembler {
+    fn default() -> Assembler {
+        Assembler {
+            text: Section::new(TEXT_BASE),
+            data: Section::new(DATA_BASE),
+            ktext: Section::new(EXCEPTION_VECTOR),
+            kdata: Section::new(KERNEL_DATA_BASE),
+            current: Kind::Text,
+            units: vec![Unit::default()],
+            unit: 0,
+            exports: HashMap::new(),
+            resolved: false,
+            reorder: true,
+            endian: Endian::Little,
+        }
+    }
+}
+
+impl Assembler {
+    fn pass(&mut self, files: &[Vec<Line>]) -> Result<(), (usize, String)> {
+        self.text = Section::new(TEXT_BASE);
+        self.data = Section::new(DATA_BASE);
+        self.ktext = Section::new(EXCEPTION_VECTOR);
+        self.kdata = Section::new(KERNEL_DATA_BASE);
+
+        for (index, lines) in files.iter().enumerate() {
+            self.unit = index;
+            self.current = Kind::Text;
+            self.reorder = true;
+            for (number, line) in lines.iter().enumerate() {
+                self.line(line)
+                    .map_err(|error| (index, format!("line {}: {}", number + 1, error)))?;
+            }
+        }
+        Ok(())
+    }
+
+    // A .globl label with no definition in its file is one it uses from
+    // another file
+    fn export(&mut self) -> Result<(), (usize, String)> {
+        for (index, unit) in self.units.iter().enumerate() {
+            for name in &unit.globals {
+                let Some(&address) = unit.symbols.get(name) else {
+                    continue;
+                };
+                if self.exports.insert(name.clone(), address).is_some() {
+                    return Err((
+                        index,
+                        format!("global '{}' is defined in another file too", name),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn section(&mut self) -> &mut Section {
+        match self.current {
+            Kind::Text => &mut self.text,
+            Kind::Data => &mut self.data,
+            Kind::KernelText => &mut self.ktext,
+            Kind::KernelData => &mut self.kdata,
+        }
+    }
+
+    fn line(&mut self, line: &Line) -> Result<(), String> {
+        // Data directives align before the label is placed, so a label on
+        // `.word` names the word
+        if let Some(size) = line.mnemonic.and_then(natural_alignment) {
+            self.section().align(size);
+        }
+
+        for &label in &line.labels {
+            let address = self.section().address();
+            let symbols = &mut self.units[self.unit].symbols;
+            if !self.resolved && symbols.insert(label.to_string(), address).is_some() {
+                return Err(format!("label '{}' defined twice", label));
+            }
+        }
+
+        let Some(mnemonic) = line.mnemonic else {
+            return Ok(());
+        };
+        let operands = &line.operands;
+        if mnemonic.starts_with('.') {
+            return self.directive(mnemonic, operands);
+        }
+
+        if !matches!(self.current, Kind::Text | Kind::KernelText) {
+            return Err(format!("'{}' outside of .text", mnemonic));
+        }
+        let address = self.section().address();
+        let words = self.instruction(&mnemonic.to_lowercase(), operands, address)?;
+        for word in words {
+            let bytes = match self.endian {
+                Endian::Little => word.to_le_bytes(),
+                Endian::Big => word.to_be_bytes(),
+            };
+            self.section().bytes.extend_from_slice(&bytes);
+        }
+        if self.reorder && has_delay_slot(&mnemonic.to_lowercase()) {
+            self.section().bytes.extend_from_slice(&[0; 4]);
+        }
+        Ok(())
+    }
+
+    fn directive(&mut self, name: &str, operands: &[&str]) -> Result<(), String> {
+        match name {
+            ".text" | ".data" | ".ktext" | ".kdata" => {
+                self.current = match name {
+                    ".text" => Kind::Text,
+                    ".data" => Kind::Data,
+                    ".ktext" => Kind::KernelText,
+                    _ => Kind::KernelData,
+                };
+                if let Some(operand) = operands.first() {
+                    let address = number(operand)?;
+                    let section = self.section();
+                    if section.bytes.is_empty() {
+                        section.base = address;
+                    } else if address >= section.address() {
+                        section.bytes.resize((address - section.base) as usize, 0);
+                    } else {
+                        return Err(format!(
+                            "{} {:#x} is behind what is already there",
+                            name, address
+                        ));
+                    }
+                }
+            }
+            ".globl" | ".global" => {
+                let globals = &mut self.units[self.unit].globals;
+                for &operand in operands {
+                    if !globals.iter().any(|global| global == operand) {
+                        globals.push(operand.to_string());
+                    }
+                }
+            }
+            ".word" | ".half" | ".byte" => {
+                let size = natural_alignment(name).unwrap_or(1) as usize;
+                for operand in operands {
+                    let (value, count) = match split_repeat(operand) {
+                        Some((value, count)) => (value, number(count)? as u64),
+                        None => (*operand, 1),
+                    };
+                    let value = self.value(value)?;
+                    let bytes = match self.endian {
+                        Endian::Little => value.to_le_bytes()[..size].to_vec(),
+                        Endian::Big => value.to_be_bytes()[4 - size..].to_vec(),
+                    };
+                    self.section().extend(&bytes, count)?;
+                }
+            }
+            ".ascii" | ".asciiz" => {
+                for operand in operands {
+                    let mut bytes = string(operand)?;
+                    if name == ".asciiz" {
+                        bytes.push(0);
+                    }
+                    self.section().bytes.extend(bytes);
+                }
+            }
+            ".space" => {
+                let size = number(single(name, operands)?)?;
+                self.section().extend(&[0], size as u64)?;
+            }
+            ".set" => match single(name, operands)? {
+                "reorder" => self.reorder = true,
+                "noreorder" => self.reorder = false,
+                option => return Err(format!("unknown option .set {}", option)),
+            },
+            ".align" => {
+                let power = number(single(name, operands)?)?;
+                if power > 12 {
+                    return Err(format!(".align {} is too large", power));
+                }
+                self.section().align(1 << power);
+            }
+            _ => return Err(format!("unknown directive '{}'", name)),
+        }
+        Ok(())
+    }
+
+    // A number, or a label with an optional "+offset"/"-offset"
+    fn value(&self, text: &str) -> Result<u32, String> {
+        if let Ok(value) = number(text) {
+            return Ok(value);
+        }
+
+        let (label, offset) = match text.find(['+', '-']) {
+            Some(index) if index > 0 => {
+                let offset = number(text[index + 1..].trim())?;
+                let offset = if text[index..].starts_with('-') {
+                    offset.wrapping_neg()
+                } else {
+                    offset
+                };
+                (text[..index].trim(), offset)
+            }
+            _ => (text, 0),
+        };
+
+        if !label
+            .chars()
+            .all(|c| c.is_alphanumeric() || "_.$".contains(c))
+        {
+            return Err(format!("invalid operand '{}'", text));
+        }
+        let unit = &self.units[self.unit];
+        match unit.symbols.get(label).or_else(|| self.exports.get(label)) {
+            Some(address) => Ok(address.wrapping_add(offset)),
+            None if !self.resolved => Ok(0),
+            None => Err(format!("undefined label '{}'", label)),
+        }
+    }
+
+    fn branch_offset(&self, target: &str, address: u32) -> Result<u32, String> {
+        let target = self.value(target)?;
+        if !self.resolved {
+            return Ok(0);
+        }
+
+        let offset = (target.wrapping_sub(address.wrapping_add(4)) as i32) >> 2;
+        if target % 4 != 0 || !(-0x8000..0x8000).contains(&offset) {
+            return Err(format!("branch target {:#010x} out of reach", target));
+        }
+        Ok(offset as u32 & 0xffff)
+    }
+
+    fn jump_target(&self, target: &str, address: u32) -> Result<u32, String> {
+        let target = self.value(target)?;
+        if self.resolved
+            && (target % 4 != 0 || (target ^ address.wrapping_add(4)) & 0xf0000000 != 0)
+        {
+            return Err(format!("jump target {:#010x} out of reach", target));
+        }
+        Ok((target >> 2) & 0x3ffffff)
+    }
+
+    fn instruction(
+        &self,
+        mnemonic: &str,
+        operands: &[&str],
+        address: u32,
+    ) -> Result<Vec<u32>, String> {
+        let expect = |count: usize| {
+            if operands.len() == count {
+                Ok(())
+            } else {
+                Err(format!("'{}' takes {} operands", mnemonic, count))
+            }
+        };
+        let reg = |index: usize| register(operands[index]);
+        const AT: u32 = 1;
+
+        let words = match mnemonic {
+            "nop" => {
+                expect(0)?;
+                vec![0]
+            }
+            "syscall" | "eret" => {
+                expect(0)?;
+                vec![if mnemonic == "syscall" {
+                    0x0c
+                } else {
+                    0x42000018
+                }]
+            }
+            "break" => match operands {
+                [] => vec![0x0d],
+                [code] => vec![(number(code)? & 0xfffff) << 6 | 0x0d],
+                _ => return Err(String::from("'break' takes at most one operand")),
+            },
+
+            "add" | "addu" | "sub" | "subu" | "and" | "or" | "xor" | "nor" | "slt" | "sltu" => {
+                expect(3)?;
+                let funct = match mnemonic {
+                    "add" => 0x20,
+                    "addu" => 0x21,
+                    "sub" => 0x22,
+                    "subu" => 0x23,
+                    "and" => 0x24,
+                    "or" => 0x25,
+                    "xor" => 0x26,
+                    "nor" => 0x27,
+                    "slt" => 0x2a,
+                    _ => 0x2b,
+                };
+                if is_register(operands[2]) {
+                    vec![r_type(reg(1)?, reg(2)?, reg(0)?, 0, funct)]
+                } else {
+                    let immediate = number(operands[2])?;
+                    let (opcode, immediate) = match mnemonic {
+                        "add" => (0x08, immediate),
+                        "addu" => (0x09, immediate),
+                        "sub" => (0x08, immediate.wrapping_neg()),
+                        "subu" => (0x09, immediate.wrapping_neg()),
+                        "and" => (0x0c, immediate),
+                        "or" => (0x0d, immediate),
+                        "xor" => (0x0e, immediate),
+                        "slt" => (0x0a, immediate),
+                        "sltu" => (0x0b, immediate),
+                        _ => return Err(format!("'{}' takes no immediate", mnemonic)),
+                    };
+                    let immediate = if opcode >= 0x0c {
+                        unsigned16(immediate)?
+                    } else {
+                        signed16(immediate)?
+                    };
+                    vec![i_type(opcode, reg(1)?, reg(0)?, immediate)]
+                }
+            }
+            "sllv" | "srlv" | "srav" => {
+                expect(3)?;
+                let funct = match mnemonic {
+                    "sllv" => 0x04,
+                    "srlv" => 0x06,
+                    _ => 0x07,
+                };
+                vec![r_type(reg(2)?, reg(1)?, reg(0)?, 0, funct)]
+            }
+            "sll" | "srl" | "sra" => {
+                expect(3)?;
+                let funct = match mnemonic {
+                    "sll" => 0x00,
+                    "srl" => 0x02,
+                    _ => 0x03,
+                };
+                let shift = number(operands[2])?;
+                if shift > 31 {
+                    return Err(format!("shift amount {} out of range", shift));
+                }
+                vec![r_type(0, reg(1)?, reg(0)?, shift, funct)]
+            }
+            "movz" | "movn" => {
+                expect(3)?;
+                let funct = if mnemonic == "movz" { 0x0a } else { 0x0b };
+                vec![r_type(reg(1)?, reg(2)?, reg(0)?, 0, funct)]
+            }
+            "mult" | "multu" => {
+                expect(2)?;
+                let funct = if mnemonic == "mult" { 0x18 } else { 0x19 };
+                vec![r_type(reg(0)?, reg(1)?, 0, 0, funct)]
+            }
+            "div" | "divu" | "rem" | "remu" => {
+                let funct = if mnemonic.ends_with('u') { 0x1b } else { 0x1a };
+                match operands.len() {
+                    2 if mnemonic.starts_with("div") => vec![r_type(reg(0)?, reg(1)?, 0, 0, funct)],
+                    3 => {
+                        let result = if mnemonic.starts_with("div") {
+                            0x12
+                        } else {
+                            0x10
+                        };
+                        vec![
+                            r_type(reg(1)?, reg(2)?, 0, 0, funct),
+                            r_type(0, 0, reg(0)?, 0, result),
+                        ]
+                    }
+                    _ => return Err(format!("'{}' takes 3 operands", mnemonic)),
+                }
+            }
+            "mfhi" | "mflo" => {
+                expect(1)?;
+                let funct = if mnemonic == "mfhi" { 0x10 } else { 0x12 };
+                vec![r_type(0, 0, reg(0)?, 0, funct)]
+            }
+            "mthi" | "mtlo" => {
+                expect(1)?;
+                let funct = if mnemonic == "mthi" { 0x11 } else { 0x13 };
+                vec![r_type(reg(0)?, 0, 0, 0, funct)]
+            }
+            "jr" => {
+                expect(1)?;
+                vec![r_type(reg(0)?, 0, 0, 0, 0x08)]
+            }
+            "jalr" => match operands.len() {
+                1 => vec![r_type(reg(0)?, 0, 31, 0, 0x09)],
+                2 => vec![r_type(reg(1)?, 0, reg(0)?, 0, 0x09)],
+                _ => return Err(String::from("'jalr' takes 1 or 2 operands")),
+            },
+
+            "mul" | "madd" | "maddu" | "msub" | "msubu" => {
+                let funct = match mnemonic {
+                    "madd" => 0x00,
+                    "maddu" => 0x01,
+                    "mul" => 0x02,
+                    "msub" => 0x04,
+                    _ => 0x05,
+                };
+                if mnemonic == "mul" {
+                    expect(3)?;
+                    vec![special2(reg(1)?, reg(2)?, reg(0)?, funct)]
+                } else {
+                    expect(2)?;
+                    vec![special2(reg(0)?, reg(1)?, 0, funct)]
+                }
+            }
+            "clz" | "clo" => {
+                expect(2)?;
+                let funct = if mnemonic == "clz" { 0x20 } else { 0x21 };
+                vec![special2(reg(1)?, reg(0)?, reg(0)?, funct)]
+            }
+
+            "addi" | "addiu" | "slti" | "sltiu" | "andi" | "ori" | "xori" => {
+                expect(3)?;
+                let (opcode, signed) = match mnemonic {
+                    "addi" => (0x08, true),
+                    "addiu" => (0x09, true),
+                    "slti" => (0x0a, true),
+                    "sltiu" => (0x0b, true),
+                    "andi" => (0x0c, false),
+                    "ori" => (0x0d, false),
+                    _ => (0x0e, false),
+                };
+                let immediate = number(operands[2])?;
+                let immediate = if signed {
+                    signed16(immediate)?
+                } else {
+                    unsigned16(immediate)?
+                };
+                vec![i_type(opcode, reg(1)?, reg(0)?, immediate)]
+            }
+            "lui" => {
+                expect(2)?;
+                vec![i_type(0x0f, 0, reg(0)?, unsigned16(number(operands[1])?)?)]
+            }
+
+            "lb" | "lh" | "lw" | "lbu" | "lhu" | "sb" | "sh" | "sw" | "ll" | "sc" => {
+                expect(2)?;
+                let opcode = match mnemonic {
+                    "lb" => 0x20,
+                    "lh" => 0x21,
+                    "lw" => 0x23,
+                    "lbu" => 0x24,
+                    "lhu" => 0x25,
+                    "sb" => 0x28,
+                    "sh" => 0x29,
+                    "sw" => 0x2b,
+                    "ll" => 0x30,
+                    _ => 0x38,
+                };
+                let target = operands[1];
+                match target.find('(') {
+                    Some(open) => {
+                        let base = target[open + 1..]
+                            .strip_suffix(')')
+                            .ok_or_else(|| format!("invalid address '{}'", target))?;
+                        let offset = match target[..open].trim() {
+                            "" => 0,
+                            offset => signed16(number(offset)?)?,
+                        };
+                        vec![i_type(opcode, register(base.trim())?, reg(0)?, offset)]
+                    }
+                    // lw $t0, label: through $at, with the high half rounded
+                    // up for a negative low half
+                    None => {
+                        let address = self.value(target)?;
+                        vec![
+                            i_type(0x0f, 0, AT, address.wrapping_add(0x8000) >> 16),
+                            i_type(opcode, AT, reg(0)?, address & 0xffff),
+                        ]
+                    }
+                }
+            }
+
+            "beq" | "bne" => {
+                expect(3)?;
+                let opcode = if mnemonic == "beq" { 0x04 } else { 0x05 };
+                if is_register(operands[1]) {
+                    let offset = self.branch_offset(operands[2], address)?;
+                    vec![i_type(opcode, reg(0)?, reg(1)?, offset)]
+                } else {
+                    let immediate = signed16(number(operands[1])?)?;
+                    let offset = self.branch_offset(operands[2], address.wrapping_add(4))?;
+                    vec![
+                        i_type(0x08, 0, AT, immediate),
+                        i_type(opcode, reg(0)?, AT, offset),
+                    ]
+                }
+            }
+            "blez" | "bgtz" => {
+                expect(2)?;
+                let opcode = if mnemonic == "blez" { 0x06 } else { 0x07 };
+                vec![i_type(
+                    opcode,
+                    reg(0)?,
+                    0,
+                    self.branch_offset(operands[1], address)?,
+                )]
+            }
+            "bltz" | "bgez" | "bltzal" | "bgezal" => {
+                expect(2)?;
+                let rt = match mnemonic {
+                    "bltz" => 0,
+                    "bgez" => 1,
+                    "bltzal" => 16,
+                    _ => 17,
+                };
+                vec![i_type(
+                    0x01,
+                    reg(0)?,
+                    rt,
+                    self.branch_offset(operands[1], address)?,
+                )]
+            }
+            "j" | "jal" => {
+                expect(1)?;
+                let opcode = if mnemonic == "j" { 0x02 } else { 0x03 };
+                vec![opcode << 26 | self.jump_target(operands[0], address)?]
+            }
+
+            "mfc0" | "mtc0" => {
+                expect(2)?;
+                let rs = if mnemonic == "mfc0" { 0x00 } else { 0x04 };
+                vec![0x10 << 26 | rs << 21 | reg(0)? << 16 | reg(1)? << 11]
+            }
+
+            "move" => {
+                expect(2)?;
+                vec![r_type(0, reg(1)?, reg(0)?, 0, 0x21)]
+            }
+            "neg" | "negu" => {
+                expect(2)?;
+                let funct = if mnemonic == "neg" { 0x22 } else { 0x23 };
+                vec![r_type(0, reg(1)?, reg(0)?, 0, funct)]
+            }
+            "not" => {
+                expect(2)?;
+                vec![r_type(reg(1)?, 0, reg(0)?, 0, 0x27)]
+            }
+            "li" => {
+                expect(2)?;
+                let rt = reg(0)?;
+                let value = number(operands[1])?;
+                if (value as i32) >= -0x8000 && (value as i32) < 0x8000 {
+                    vec![i_type(0x09, 0, rt, value & 0xffff)]
+                } else if value <= 0xffff {
+                    vec![i_type(0x0d, 0, rt, value)]
+                } else if value & 0xffff == 0 {
+                    vec![i_type(0x0f, 0, rt, value >> 16)]
+                } else {
+                    vec![
+                        i_type(0x0f, 0, rt, value >> 16),
+                        i_type(0x0d, rt, rt, value & 0xffff),
+                    ]
+                }
+            }
+            "la" => {
+                expect(2)?;
+                let rt = reg(0)?;
+                let address = self.value(operands[1])?;
+                vec![
+                    i_type(0x0f, 0, rt, address >> 16),
+                    i_type(0x0d, rt, rt, address & 0xffff),
+                ]
+            }
+            "b" => {
+                expect(1)?;
+                vec![i_type(
+                    0x04,
+                    0,
+                    0,
+                    self.branch_offset(operands[0], address)?,
+                )]
+            }
+            "beqz" | "bnez" => {
+                expect(2)?;
+                let opcode = if mnemonic == "beqz" { 0x04 } else { 0x05 };
+                vec![i_type(
+                    opcode,
+                    reg(0)?,
+                    0,
+                    self.branch_offset(operands[1], address)?,
+                )]
+            }
+            "blt" | "bge" | "bgt" | "ble" | "bltu" | "bgeu" | "bgtu" | "bleu" => {
+                expect(3)?;
+                let unsigned = mnemonic.ends_with('u');
+                // bgt and ble compare the other way round
+                let swapped = mnemonic.starts_with("bgt") || mnemonic.starts_with("ble");
+                // slt leaves 1 in $at when the branch is taken by blt/bgt
+                let opcode = if mnemonic.starts_with("blt") || mnemonic.starts_with("bgt") {
+                    0x05
+                } else {
+                    0x04
+                };
+                let rs = reg(0)?;
+
+                let mut words = Vec::new();
+                let compare = if is_register(operands[1]) {
+                    let rt = reg(1)?;
+                    let (left, right) = if swapped { (rt, rs) } else { (rs, rt) };
+                    r_type(left, right, AT, 0, if unsigned { 0x2b } else { 0x2a })
+                } else {
+                    let immediate = signed16(number(operands[1])?)?;
+                    if swapped {
+                        words.push(i_type(0x08, 0, AT, immediate));
+                        r_type(AT, rs, AT, 0, if unsigned { 0x2b } else { 0x2a })
+                    } else {
+                        i_type(if unsigned { 0x0b } else { 0x0a }, rs, AT, immediate)
+                    }
+                };
+                words.push(compare);
+                let branch = address.wrapping_add(4 * words.len() as u32);
+                words.push(i_type(
+                    opcode,
+                    AT,
+                    0,
+                    self.branch_offset(operands[2], branch)?,
+                ));
+                words
+            }
+
+            _ => return Err(format!("unknown instruction '{}'", mnemonic)),
+        };
+        Ok(words)
+    }
+}
+
+fn r_type(rs: u32, rt: u32, rd: u32, shift: u32, funct: u32) -> u32 {
+    rs << 21 | rt << 16 | rd << 11 | shift << 6 | funct
+}
+
+fn i_type(opcode: u32, rs: u32, rt: u32, immediate: u32) -> u32 {
+    opcode << 26 | rs << 21 | rt << 16 | (immediate & 0xffff)
+}
+
+fn special2(rs: u32, rt: u32, rd: u32, funct: u32) -> u32 {
+    0x1c << 26 | rs << 21 | rt << 16 | rd << 11 | funct
+}
+
+fn has_delay_slot(mnemonic: &str) -> bool {
+    matches!(
+        mnemonic,
+        "j" | "jal"
+            | "jr"
+            | "jalr"
+            | "beq"
+            | "bne"
+            | "blez"
+            | "bgtz"
+            | "bltz"
+            | "bgez"
+            | "bltzal"
+            | "bgezal"
+            | "b"
+            | "beqz"
+            | "bnez"
+            | "blt"
+            | "bge"
+            | "bgt"
+            | "ble"
+            | "bltu"
+            | "bgeu"
+            | "bgtu"
+            | "bleu"
+    )
+}
+
+// How data directives align what they emit
+fn natural_alignment(directive: &str) -> Option<u32> {
+    match directive {
+        ".word" => Some(4),
+        ".half" => Some(2),
+        ".byte" => Some(1),
+        _ => None,
+    }
+}
+
+// MARS's "value:count" repeats a value. A ':' inside a character literal
+// is part of the value.
+fn split_repeat(operand: &str) -> Option<(&str, &str)> {
+    let mut colon = None;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in operand.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '\'' => quoted = !quoted,
+            ':' if !quoted => colon = Some(index),
+            _ => {}
+        }
+    }
+    colon.map(|index| (&operand[..index], &operand[index + 1..]))
+}
+
+fn single<'a>(name: &str, operands: &[&'a str]) -> Result<&'a str, String> {
+    match operands {
+        [operand] => Ok(operand),
+        _ => Err(format!("{} takes one operand", name)),
+    }
+}
+
+fn is_register(text: &str) -> bool {
+    text.starts_with('$')
+}
+
+fn register(text: &str) -> Result<u32, String> {
+    let name = text
+        .strip_prefix('$')
+        .ok_or_else(|| format!("expected a register, found '{}'", text))?;
+    match name.parse::<u32>() {
+        Ok(number) if number < 32 => Ok(number),
+        _ => REGISTER_NAMES
+            .iter()
+            .position(|&register| register == name || (register == "fp" && name == "s8"))
+            .map(|number| number as u32)
+            .ok_or_else(|| format!("unknown register '{}'", text)),
+    }
+}
+
+// Decimal, 0x hex or a character literal; negative numbers wrap
+fn number(text: &str) -> Result<u32, String> {
+    let invalid = || format!("invalid number '{}'", text);
+    if let Some(literal) = text
+        .strip_prefix('\'')
+        .and_then(|rest| rest.strip_suffix('\''))
+    {
+        return match unescape(literal)?.as_slice() {
+            [byte] => Ok(*byte as u32),
+            _ => Err(invalid()),
+        };
+    }
+
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).map_err(|_| invalid())?,
+        None => digits.parse::<u32>().map_err(|_| invalid())?,
+    };
+    Ok(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+fn signed16(value: u32) -> Result<u32, String> {
+    if (-0x8000..0x8000).contains(&(value as i32)) {
+        Ok(value & 0xffff)
+    } else {
+        Err(format!("immediate {} out of range", value as i32))
+    }
+}
+
+fn unsigned16(value: u32) -> Result<u32, String> {
+    if value <= 0xffff {
+        Ok(value)
+    } else {
+        Err(format!("immediate {:#x} out of range", value))
+    }
+}
+
+fn string(text: &str) -> Result<Vec<u8>, String> {
+    text.strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| format!("expected a string, found '{}'", text))
+        .and_then(unescape)
+}
+
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next() {
+            Some('n') => b'\n',
+            Some('t') => b'\t',
+            Some('r') => b'\r',
+            Some('0') => 0,
+            Some('\\') => b'\\',
+            Some('"') => b'"',
+            Some('\'') => b'\'',
+            other => return Err(format!("unknown escape '\\{}'", other.unwrap_or(' '))),
+        });
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble, link};
+    use crate::loader::{self, Format};
+    use crate::{Endian, ExitReason, CPU};
+
+    fn words(source: &str) -> Vec<u32> {
+        let image = assemble(source, Endian::Little).unwrap();
+        image.segments[0]
+            .bytes
+            .chunks(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(
+            words(
+                "addi $v0, $zero, 10
+                 sw $a0, 0($gp)
+                 lw $t0, ($sp)
+                 lui $t0, 0x1234
+                 sll $t1, $t2, 3
+                 jr $ra
+                 mul $t0, $t1, $t2
+                 mfc0 $k0, $13
+                 syscall"
+            ),
+            [
+                0x2002000a, 0xaf840000, 0x8fa80000, 0x3c081234, 0x000a48c0, 0x03e00008, 0,
+                0x712a4002, 0x401a6800, 0x0000000c
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pseudo_instructions() {
+        assert_eq!(words("li $t0, -1"), [0x2408ffff]);
+        assert_eq!(words("li $t0, 0xffff"), [0x3408ffff]);
+        assert_eq!(words("li $t0, 0x12345678"), [0x3c081234, 0x35085678]);
+        assert_eq!(words("move $a0, $v0"), [0x00022021]);
+        assert_eq!(words("addi $t0, $t0, 1\nadd $t0, $t0, 1"), [0x21080001; 2]);
+        // blt $t0, $t1, back: slt $at, $t0, $t1 / bne $at, $zero, back
+        assert_eq!(
+            words("back: blt $t0, $t1, back"),
+            [0x0109082a, 0x1420fffe, 0]
+        );
+        assert_eq!(
+            words(".set noreorder\nback: blt $t0, $t1, back"),
+            [0x0109082a, 0x1420fffe]
+        );
+        assert_eq!(words("div $t0, $t1, $t2"), [0x012a001a, 0x00004012]);
+    }
+
+    #[test]
+    fn test_big_endian() {
+        let image = assemble(
+            ".data\n.half 0x1234\n.word 5\n.text\nli $v0, 10",
+            Endian::Big,
+        )
+        .unwrap();
+        assert_eq!(image.segments[0].bytes, [0x24, 0x02, 0x00, 0x0a]);
+        assert_eq!(image.segments[1].bytes, [0x12, 0x34, 0, 0, 0, 0, 0, 5]);
+    }
+
+    #[test]
+    fn test_labels_and_data() {
+        let image = assemble(
+            r#"
+            .data
+            message: .asciiz "hi, \"you\"\n"  # a comment
+            .align 2
+            table: .word 1, 2, message
+            count: .half 7
+            value: .word 0x10:2
+            .text
+            helper: jr $ra
+            main: la $a0, table
+            "#,
+            Endian::Little,
+        )
+        .unwrap();
+
+        let data = &image.segments[1];
+        assert_eq!(&data.bytes[..11], b"hi, \"you\"\n\0");
+        assert_eq!(data.bytes[12..16], 1u32.to_le_bytes());
+        assert_eq!(data.bytes[20..24], 0x10010000u32.to_le_bytes());
+        assert_eq!(data.bytes[24..26], 7u16.to_le_bytes());
+        assert_eq!(data.bytes.len(), 36);
+        assert_eq!(image.entry, 0x00400008);
+
+        let address = |name: &str| {
+            image
+                .symbols
+                .iter()
+                .find(|symbol| symbol.name == name)
+                .unwrap()
+                .address
+        };
+        assert_eq!(address("table"), 0x1001000c);
+        assert_eq!(address("value"), 0x1001001c);
+    }
+
+    #[test]
+    fn test_repeats() {
+        let image = assemble(".data\n.byte ':', ':':2, '\\'':1\n.space 2", Endian::Little).unwrap();
+        let data = image.segments.iter().find(|segment| segment.name == "data");
+        assert_eq!(data.unwrap().bytes, b":::'\0\0");
+
+        let error = |source: &str| assemble(source, Endian::Little).unwrap_err();
+        assert_eq!(
+            error(".data\n.word 0:4000000000"),
+            "line 2: 16000000000 bytes don't fit, only 4026466304 are left in the address space"
+        );
+        assert_eq!(
+            error(".data\n.space 0xffffffff"),
+            "line 2: 4294967295 bytes don't fit, only 4026466304 are left in the address space"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| assemble(source, Endian::Little).unwrap_err();
+        assert_eq!(error("nop\nj nowhere"), "line 2: undefined label 'nowhere'");
+        assert_eq!(error("add $t0, $t1"), "line 1: 'add' takes 3 operands");
+        assert_eq!(error("addi $t0, $q1, 1"), "line 1: unknown register '$q1'");
+        assert_eq!(
+            error("addi $t0, $t0, 40000"),
+            "line 1: immediate 40000 out of range"
+        );
+        assert_eq!(error("a: nop\na: nop"), "line 2: label 'a' defined twice");
+        assert_eq!(error(".data\nnop"), "line 2: 'nop' outside of .text");
+        assert_eq!(error(".asciiz \"open"), "line 1: unterminated string");
+        assert_eq!(
+            error("frobnicate"),
+            "line 1: unknown instruction 'frobnicate'"
+        );
+    }
+
+    #[test]
+    fn test_link() {
+        let main = r#"
+                    .globl main
+                    .data
+            value:  .word 7
+                    .text
+            main:   lw   $a0, value
+                    jal  square
+                    sw   $v0, result
+                    li   $v0, 10
+                    syscall
+        "#;
+        let library = r#"
+                    .globl square, result
+                    .data
+            result: .word 0
+                    .text
+            square: mul  $v0, $a0, $a0
+                    jr   $ra
+            value:  nop
+        "#;
+        let image = link(&[library, main], Endian::Little).unwrap();
+        assert_eq!(image.entry, 0x00400010);
+
+        let image = link(&[main, library], Endian::Little).unwrap();
+        assert_eq!(image.entry, 0x00400000);
+        assert!(image
+            .symbols
+            .iter()
+            .any(|symbol| symbol.name == "value" && symbol.address == 0x0040002c));
+
+        let mut cpu = CPU::new();
+        loader::load_image(&mut cpu, &image, "prog").unwrap();
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.memory.read(0x10010004), 49);
+    }
+
+    #[test]
+    fn test_link_errors() {
+        assert_eq!(
+            link(&["helper: nop", "j helper"], Endian::Little).unwrap_err(),
+            (1, String::from("line 1: undefined label 'helper'"))
+        );
+        assert_eq!(
+            link(&[".globl f\nf: nop", ".globl f\nf: nop"], Endian::Little).unwrap_err(),
+            (1, String::from("global 'f' is defined in another file too"))
+        );
+    }
+
+    #[test]
+    fn test_kernel_segments() {
+        let source = r#"
+            .kdata
+            count:  .word 0
+            .ktext
+            handler:
+                lw    $k1, count
+                addiu $k1, $k1, 1
+                sw    $k1, count
+                mfc0  $k0, $14
+                addiu $k0, $k0, 4
+                mtc0  $k0, $14
+                eret
+            .text
+            main:
+                li    $t0, 0x7fffffff
+                addi  $t0, $t0, 1
+                addi  $t0, $t0, 0
+                li    $v0, 10
+                syscall
+        "#;
+        let image = assemble(source, Endian::Little).unwrap();
+        assert_eq!(image.segments[2].name, "ktext");
+        assert_eq!(image.segments[2].address, 0x80000180);
+        assert!(image.segments[2].executable);
+        assert_eq!(image.segments[3].name, "kdata");
+        assert_eq!(image.segments[3].address, 0x90000000);
+        assert_eq!(assemble("nop", Endian::Little).unwrap().segments.len(), 2);
+
+        let mut cpu = CPU::new();
+        loader::load_image(&mut cpu, &image, "prog").unwrap();
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.memory.read(0x90000000), 1);
+    }
+
+    #[test]
+    fn test_run() {
+        let source = r#"
+            .data
+            numbers: .word 3, -4, 10, 25
+            result:  .word 0
+            .text
+            main:
+                la   $t0, numbers
+                li   $t1, 4
+                move $t2, $zero
+            loop:
+                beqz $t1, done
+                lw   $t3, 0($t0)
+                bge  $t3, 5, big
+                addu $t2, $t2, $t3
+            big:
+                addi $t0, $t0, 4
+                subu $t1, $t1, 1
+                b    loop
+            done:
+                sw   $t2, result
+                li   $v0, 10
+                syscall
+        "#;
+        let path = std::env::temp_dir().join("rustinmips_test_assembler.asm");
+        std::fs::write(&path, source).unwrap();
+        let path = path.to_str().unwrap();
+
+        let mut cpu = CPU::new();
+        assert_eq!(loader::detect(path).unwrap(), Format::Assembly);
+        loader::load(&mut cpu, path, Format::Assembly).unwrap();
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.memory.read(0x10010010), (-1i32) as u32);
+        assert_eq!(cpu.symbols.lookup("loop").unwrap(), 0x00400010);
+        assert_eq!(cpu.symbol_at(0x00400014).unwrap(), "loop+0x4");
+        assert_eq!(cpu.symbol_at(0x10010010).unwrap(), "result");
+        assert_eq!(cpu.symbol_at(0x10010014), None);
+    }
+}
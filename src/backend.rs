@@ -0,0 +1,125 @@
+use crate::error::Error;
+use crate::image::Image;
+use crate::loader;
+use crate::{ExitReason, CPU};
+
+// What the debugger, frontends and tests need from an engine, so the
+// functional interpreter and later ones (pipeline, JIT) can be swapped
+pub trait ExecutionBackend {
+    fn name(&self) -> &str;
+
+    // Installs the image and points the machine at its entry point
+    fn load(&mut self, image: &Image) -> Result<(), Error>;
+
+    // Runs one instruction; Some when the machine stopped
+    fn step(&mut self) -> Result<Option<ExitReason>, Error>;
+
+    // Runs from the current state until the machine stops
+    fn run(&mut self) -> Result<ExitReason, Error>;
+
+    fn pc(&self) -> u32;
+    fn read_register(&self, register: usize) -> u32;
+    fn write_register(&mut self, register: usize, value: u32);
+    fn read_memory(&self, address: u32) -> u32;
+    fn write_memory(&mut self, address: u32, value: u32);
+}
+
+// The plain fetch-execute interpreter
+impl ExecutionBackend for CPU {
+    fn name(&self) -> &str {
+        "functional"
+    }
+
+    fn load(&mut self, image: &Image) -> Result<(), Error> {
+        loader::load_image(self, image, "")?;
+        self.start();
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<Option<ExitReason>, Error> {
+        let reason = self.step_instruction();
+        self.check_trace()?;
+        if reason.is_some() {
+            self.flush_trace()?;
+        }
+        Ok(reason)
+    }
+
+    fn run(&mut self) -> Result<ExitReason, Error> {
+        self.resume()
+    }
+
+    fn pc(&self) -> u32 {
+        self.pc
+    }
+
+    fn read_register(&self, register: usize) -> u32 {
+        CPU::read_register(self, register)
+    }
+
+    fn write_register(&mut self, register: usize, value: u32) {
+        CPU::write_register(self, register, value)
+    }
+
+    fn read_memory(&self, address: u32) -> u32 {
+        self.memory.read(address)
+    }
+
+    fn write_memory(&mut self, address: u32, value: u32) {
+        self.memory.write(address, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExecutionBackend;
+    use crate::image::Image;
+    use crate::trace::{TraceFormat, Tracer};
+    use crate::{ExitReason, CPU};
+    use std::io::{self, Write};
+
+    // addi $t0, $zero, 3 ; sw $t0, 0($gp) ; exit
+    fn image() -> Image {
+        let words: [u32; 4] = [0x20080003, 0xaf880000, 0x2002000a, 0x0000000c];
+        Image::with_text(words.iter().flat_map(|word| word.to_le_bytes()).collect())
+    }
+
+    // Written against the trait so every backend can share it
+    fn check_backend(backend: &mut dyn ExecutionBackend) {
+        backend.load(&image()).unwrap();
+        assert_eq!(backend.pc(), 0x00400000);
+
+        assert_eq!(backend.step().unwrap(), None);
+        assert_eq!(backend.pc(), 0x00400004);
+        assert_eq!(backend.read_register(8), 3);
+
+        assert_eq!(backend.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(backend.read_memory(0x10008000), 3);
+    }
+
+    #[test]
+    fn test_functional_backend() {
+        let mut cpu = CPU::new();
+        check_backend(&mut cpu);
+    }
+
+    #[test]
+    fn test_step_reports_trace_errors() {
+        struct Full;
+        impl Write for Full {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("no space left"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut cpu = CPU::new();
+        ExecutionBackend::load(&mut cpu, &image()).unwrap();
+        cpu.set_tracer(Tracer::new(TraceFormat::Spike, Box::new(Full)));
+
+        let error = ExecutionBackend::step(&mut cpu).unwrap_err();
+        assert_eq!(error.to_string(), "cannot write the trace: no space left");
+    }
+}
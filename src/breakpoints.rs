@@ -0,0 +1,498 @@
+use std::fmt;
+
+use crate::hooks::{AccessKind, MemoryAccess};
+use crate::instructions::REGISTER_NAMES;
+use crate::{parse_number, CPU};
+
+// Where a run stops before fetching, if its condition holds
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub number: usize,
+    pub address: u32,
+    pub condition: Option<Condition>,
+}
+
+// Which accesses stop a run at a watchpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Read,
+    Write,
+    Access,
+}
+
+// Where a run stops after an instruction loads or stores any byte of
+// `start..start + length`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub number: usize,
+    pub start: u32,
+    pub length: u32,
+    pub watch: Watch,
+}
+
+// The machine's breakpoints and watchpoints, numbered together as they are
+// set so deleting one leaves the others' numbers alone
+#[derive(Debug, Clone)]
+pub struct Breakpoints {
+    list: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    next: usize,
+}
+
+impl Default for Breakpoints {
+    fn default() -> Breakpoints {
+        Breakpoints {
+            list: Vec::new(),
+            watchpoints: Vec::new(),
+            next: 1,
+        }
+    }
+}
+
+impl Watch {
+    fn matches(self, kind: AccessKind) -> bool {
+        matches!(
+            (self, kind),
+            (Watch::Access, _)
+                | (Watch::Read, AccessKind::Load)
+                | (Watch::Write, AccessKind::Store)
+        )
+    }
+}
+
+impl Watchpoint {
+    fn covers(&self, access: &MemoryAccess) -> bool {
+        let start = self.start as u64;
+        let address = access.address as u64;
+        self.watch.matches(access.kind)
+            && address < start + self.length as u64
+            && start < address + access.size as u64
+    }
+}
+
+impl Breakpoints {
+    pub fn add(&mut self, address: u32, condition: Option<Condition>) -> usize {
+        let number = self.next;
+        self.next += 1;
+        self.list.push(Breakpoint {
+            number,
+            address,
+            condition,
+        });
+        number
+    }
+
+    pub fn watch(&mut self, start: u32, length: u32, watch: Watch) -> usize {
+        let number = self.next;
+        self.next += 1;
+        self.watchpoints.push(Watchpoint {
+            number,
+            start,
+            length: length.max(1),
+            watch,
+        });
+        number
+    }
+
+    // A breakpoint or a watchpoint
+    pub fn remove(&mut self, number: usize) -> bool {
+        let before = self.list.len() + self.watchpoints.len();
+        self.list.retain(|breakpoint| breakpoint.number != number);
+        self.watchpoints
+            .retain(|watchpoint| watchpoint.number != number);
+        self.list.len() + self.watchpoints.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.watchpoints.clear();
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    // The first watchpoint the access touches
+    pub fn watched(&self, access: &MemoryAccess) -> Option<usize> {
+        self.watchpoints
+            .iter()
+            .find(|watchpoint| watchpoint.covers(access))
+            .map(|watchpoint| watchpoint.number)
+    }
+
+    pub fn list(&self) -> &[Breakpoint] {
+        &self.list
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    // The first breakpoint at `pc` whose condition holds
+    pub fn hit(&self, cpu: &CPU, pc: u32) -> Option<usize> {
+        self.list
+            .iter()
+            .filter(|breakpoint| breakpoint.address == pc)
+            .find(|breakpoint| {
+                breakpoint
+                    .condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.holds(cpu))
+            })
+            .map(|breakpoint| breakpoint.number)
+    }
+}
+
+// An expression over the machine's state, e.g. `$t0 == 5` or
+// `*($sp + 4) != 0 && $a0 < 0`:
+//
+//   operands   numbers (decimal or 0x hex), registers by name or number,
+//              $pc, $hi, $lo, and *address for the word in memory there
+//   operators  * & (tightest), + -, == != < <= > >=, &&, || (loosest),
+//              unary - and !, and parentheses
+//
+// Comparisons are signed. A condition holds when it isn't zero.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    text: String,
+    expression: Expression,
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Condition, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let expression = parser.or()?;
+        if let Some(token) = parser.tokens.get(parser.next) {
+            return Err(format!("unexpected '{}'", token));
+        }
+        Ok(Condition {
+            text: text.trim().to_string(),
+            expression,
+        })
+    }
+
+    pub fn evaluate(&self, cpu: &CPU) -> u32 {
+        self.expression.evaluate(cpu)
+    }
+
+    pub fn holds(&self, cpu: &CPU) -> bool {
+        self.evaluate(cpu) != 0
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Multiply,
+    And,
+    Add,
+    Subtract,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    LogicalAnd,
+    LogicalOr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expression {
+    Number(u32),
+    Register(usize),
+    Pc,
+    Hi,
+    Lo,
+    Memory(Box<Expression>),
+    Negate(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    fn evaluate(&self, cpu: &CPU) -> u32 {
+        match self {
+            Expression::Number(value) => *value,
+            Expression::Register(register) => cpu.read_register(*register),
+            Expression::Pc => cpu.pc,
+            Expression::Hi => cpu.hi,
+            Expression::Lo => cpu.lo,
+            Expression::Memory(address) => cpu.memory.read(address.evaluate(cpu)),
+            Expression::Negate(operand) => operand.evaluate(cpu).wrapping_neg(),
+            Expression::Not(operand) => (operand.evaluate(cpu) == 0) as u32,
+            Expression::Binary(operator, left, right) => {
+                let left = left.evaluate(cpu);
+                // && and || only look right when they need to
+                match operator {
+                    Operator::LogicalAnd if left == 0 => return 0,
+                    Operator::LogicalOr if left != 0 => return 1,
+                    _ => {}
+                }
+                let right = right.evaluate(cpu);
+                match operator {
+                    Operator::Multiply => left.wrapping_mul(right),
+                    Operator::And => left & right,
+                    Operator::Add => left.wrapping_add(right),
+                    Operator::Subtract => left.wrapping_sub(right),
+                    Operator::Equal => (left == right) as u32,
+                    Operator::NotEqual => (left != right) as u32,
+                    Operator::Less => ((left as i32) < right as i32) as u32,
+                    Operator::LessOrEqual => (left as i32 <= right as i32) as u32,
+                    Operator::Greater => (left as i32 > right as i32) as u32,
+                    Operator::GreaterOrEqual => (left as i32 >= right as i32) as u32,
+                    Operator::LogicalAnd | Operator::LogicalOr => (right != 0) as u32,
+                }
+            }
+        }
+    }
+}
+
+// Operators of two characters first, so "<=" isn't read as "<" and "="
+const SYMBOLS: [&str; 15] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "&", "!", "(", ")",
+];
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let length = match SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            Some(symbol) => symbol.len(),
+            None => rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '$' || c == '_'))
+                .unwrap_or(rest.len()),
+        };
+        if length == 0 {
+            return Err(format!(
+                "unexpected '{}'",
+                &rest[..rest.chars().next().unwrap().len_utf8()]
+            ));
+        }
+        tokens.push(rest[..length].to_string());
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.next).map(String::as_str)
+    }
+
+    fn take(&mut self) -> Result<String, String> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or_else(|| String::from("unexpected end of condition"))?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    // One precedence level: operands from `operand` joined by `operators`
+    fn level(
+        &mut self,
+        operators: &[(&str, Operator)],
+        operand: fn(&mut Parser) -> Result<Expression, String>,
+    ) -> Result<Expression, String> {
+        let mut left = operand(self)?;
+        while let Some(&(_, operator)) = operators
+            .iter()
+            .find(|(symbol, _)| self.peek() == Some(*symbol))
+        {
+            self.next += 1;
+            left = Expression::Binary(operator, Box::new(left), Box::new(operand(self)?));
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Expression, String> {
+        self.level(&[("||", Operator::LogicalOr)], Parser::and)
+    }
+
+    fn and(&mut self) -> Result<Expression, String> {
+        self.level(&[("&&", Operator::LogicalAnd)], Parser::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expression, String> {
+        self.level(
+            &[
+                ("==", Operator::Equal),
+                ("!=", Operator::NotEqual),
+                ("<", Operator::Less),
+                ("<=", Operator::LessOrEqual),
+                (">", Operator::Greater),
+                (">=", Operator::GreaterOrEqual),
+            ],
+            Parser::sum,
+        )
+    }
+
+    fn sum(&mut self) -> Result<Expression, String> {
+        self.level(
+            &[("+", Operator::Add), ("-", Operator::Subtract)],
+            Parser::product,
+        )
+    }
+
+    fn product(&mut self) -> Result<Expression, String> {
+        self.level(
+            &[("*", Operator::Multiply), ("&", Operator::And)],
+            Parser::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        let token = self.take()?;
+        match token.as_str() {
+            "-" => Ok(Expression::Negate(Box::new(self.unary()?))),
+            "!" => Ok(Expression::Not(Box::new(self.unary()?))),
+            "*" => Ok(Expression::Memory(Box::new(self.unary()?))),
+            "(" => {
+                let inner = self.or()?;
+                match self.take()?.as_str() {
+                    ")" => Ok(inner),
+                    other => Err(format!("expected ')', found '{}'", other)),
+                }
+            }
+            _ => operand(&token),
+        }
+    }
+}
+
+fn operand(token: &str) -> Result<Expression, String> {
+    if let Some(name) = token.strip_prefix('$') {
+        return match name {
+            "pc" => Ok(Expression::Pc),
+            "hi" => Ok(Expression::Hi),
+            "lo" => Ok(Expression::Lo),
+            _ => match name.parse::<usize>() {
+                Ok(number) if number < 32 => Ok(Expression::Register(number)),
+                _ => REGISTER_NAMES
+                    .iter()
+                    .position(|&register| register == name || (register == "fp" && name == "s8"))
+                    .map(Expression::Register)
+                    .ok_or_else(|| format!("unknown register '{}'", token)),
+            },
+        };
+    }
+
+    parse_number(token)
+        .map(Expression::Number)
+        .ok_or_else(|| format!("unexpected '{}'", token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Breakpoints, Condition, Watch};
+    use crate::hooks::{AccessKind, MemoryAccess};
+    use crate::CPU;
+
+    fn evaluate(cpu: &CPU, text: &str) -> u32 {
+        Condition::parse(text).unwrap().evaluate(cpu)
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut cpu = CPU::new();
+        cpu.write_register(8, 5);
+        cpu.write_register(9, -3i32 as u32);
+        cpu.memory.write(0x10010004, 42);
+        cpu.write_register(16, 0x10010000);
+
+        assert_eq!(evaluate(&cpu, "$t0 == 5"), 1);
+        assert_eq!(evaluate(&cpu, "$8 != 5"), 0);
+        assert_eq!(evaluate(&cpu, "$t1 < 0 && $t0 >= 5"), 1);
+        assert_eq!(evaluate(&cpu, "$t1 > 0 || !$zero"), 1);
+        assert_eq!(evaluate(&cpu, "1 + 2 * 3"), 7);
+        assert_eq!(evaluate(&cpu, "(1 + 2) * 3"), 9);
+        assert_eq!(evaluate(&cpu, "$t0 & 1 == 1"), 1);
+        assert_eq!(evaluate(&cpu, "*($s0 + 4)"), 42);
+        assert_eq!(evaluate(&cpu, "*0x10010004 == 42"), 1);
+        assert_eq!(evaluate(&cpu, "-$t0"), -5i32 as u32);
+        assert_eq!(evaluate(&cpu, "$sp"), 0x7fffeffc);
+        assert_eq!(Condition::parse("  $t0==5 ").unwrap().to_string(), "$t0==5");
+    }
+
+    #[test]
+    fn test_condition_errors() {
+        let error = |text: &str| Condition::parse(text).unwrap_err();
+        assert_eq!(error("$q0 == 1"), "unknown register '$q0'");
+        assert_eq!(error("$t0 =="), "unexpected end of condition");
+        assert_eq!(error("($t0 == 1"), "unexpected end of condition");
+        assert_eq!(error("1 2"), "unexpected '2'");
+        assert_eq!(error("$t0 = 1"), "unexpected '='");
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut cpu = CPU::new();
+        let mut breakpoints = Breakpoints::default();
+        let first = breakpoints.add(0x00400000, None);
+        let second = breakpoints.add(0x00400008, Condition::parse("$t0 == 1").ok());
+
+        assert_eq!((first, second), (1, 2));
+        assert_eq!(breakpoints.hit(&cpu, 0x00400000), Some(1));
+        assert_eq!(breakpoints.hit(&cpu, 0x00400008), None);
+        cpu.write_register(8, 1);
+        assert_eq!(breakpoints.hit(&cpu, 0x00400008), Some(2));
+
+        assert!(breakpoints.remove(1));
+        assert!(!breakpoints.remove(1));
+        assert_eq!(breakpoints.add(0x00400000, None), 3);
+        assert_eq!(breakpoints.list().len(), 2);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut breakpoints = Breakpoints::default();
+        let stores = breakpoints.watch(0x10010004, 4, Watch::Write);
+        let loads = breakpoints.watch(0x10010010, 1, Watch::Read);
+        let access = |kind, address, size| MemoryAccess {
+            kind,
+            address,
+            size,
+            value: 0,
+        };
+
+        assert_eq!((stores, loads), (1, 2));
+        assert_eq!(
+            breakpoints.watched(&access(AccessKind::Store, 0x10010004, 4)),
+            Some(1)
+        );
+        // A byte of the word counts; the word before it doesn't
+        assert_eq!(
+            breakpoints.watched(&access(AccessKind::Store, 0x10010007, 1)),
+            Some(1)
+        );
+        assert_eq!(
+            breakpoints.watched(&access(AccessKind::Store, 0x10010000, 4)),
+            None
+        );
+        assert_eq!(
+            breakpoints.watched(&access(AccessKind::Load, 0x10010004, 4)),
+            None
+        );
+        assert_eq!(
+            breakpoints.watched(&access(AccessKind::Load, 0x10010010, 4)),
+            Some(2)
+        );
+
+        assert!(breakpoints.remove(stores));
+        assert_eq!(breakpoints.watchpoints().len(), 1);
+        breakpoints.clear();
+        assert!(breakpoints.watchpoints().is_empty());
+    }
+}
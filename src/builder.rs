@@ -0,0 +1,141 @@
+use crate::loader::Layout;
+use crate::syscall::{self, Compatibility};
+use crate::{Endian, CPU};
+
+// A CPU with its own memory map, entry point and starting registers, e.g.
+//
+//     CPU::builder().text_base(0x80000000).stack_top(0x80100000).build()
+//
+// Whatever is left unset is as in CPU::new.
+#[derive(Debug, Clone, Default)]
+pub struct CpuBuilder {
+    layout: Layout,
+    entry: Option<u32>,
+    registers: Vec<(usize, u32)>,
+    instruction_limit: Option<u64>,
+    endian: Endian,
+    syscalls: Compatibility,
+}
+
+impl CpuBuilder {
+    pub fn new() -> CpuBuilder {
+        CpuBuilder::default()
+    }
+
+    // Where flat programs are loaded, and the entry point unless set
+    pub fn text_base(mut self, address: u32) -> CpuBuilder {
+        self.layout.text_base = address;
+        self
+    }
+
+    // Where the data of split dumps is loaded
+    pub fn data_base(mut self, address: u32) -> CpuBuilder {
+        self.layout.data_base = address;
+        self
+    }
+
+    pub fn global_pointer(mut self, address: u32) -> CpuBuilder {
+        self.layout.global_pointer = address;
+        self
+    }
+
+    // One past the stack's highest word
+    pub fn stack_top(mut self, address: u32) -> CpuBuilder {
+        self.layout.stack_top = address;
+        self
+    }
+
+    // Loading a program that names its own entry point replaces this
+    pub fn entry(mut self, address: u32) -> CpuBuilder {
+        self.entry = Some(address);
+        self
+    }
+
+    // A value the register has at the start of every run; $gp and $sp set
+    // here win over the layout's
+    pub fn register(mut self, register: usize, value: u32) -> CpuBuilder {
+        assert!(register < 32, "no register ${}", register);
+        self.registers.push((register, value));
+        self
+    }
+
+    pub fn instruction_limit(mut self, limit: u64) -> CpuBuilder {
+        self.instruction_limit = Some(limit);
+        self
+    }
+
+    pub fn endian(mut self, endian: Endian) -> CpuBuilder {
+        self.endian = endian;
+        self
+    }
+
+    // Which built-in syscalls the machine offers
+    pub fn syscalls(mut self, compatibility: Compatibility) -> CpuBuilder {
+        self.syscalls = compatibility;
+        self
+    }
+
+    // All of the layout at once
+    pub fn layout(mut self, layout: Layout) -> CpuBuilder {
+        self.layout = layout;
+        self
+    }
+
+    pub fn build(self) -> CPU {
+        let mut cpu = CPU::with_layout(self.layout, self.registers);
+        if let Some(entry) = self.entry {
+            cpu.set_entry(entry);
+        }
+        cpu.set_instruction_limit(self.instruction_limit);
+        cpu.set_endian(self.endian);
+        cpu.syscalls = syscall::builtins_for(self.syscalls);
+        cpu
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ExitReason, CPU};
+
+    #[test]
+    fn test_custom_layout() {
+        // sw $a0, 0($gp) / lw $t0, 0($sp) / addi $v0, $zero, 10 / syscall
+        let text: [u32; 4] = [0xaf840000, 0x8fa80000, 0x2002000a, 0x0000000c];
+        let mut cpu = CPU::builder()
+            .text_base(0x80000000)
+            .data_base(0x80010000)
+            .global_pointer(0x80018000)
+            .stack_top(0x80200000)
+            .register(4, 0x1234)
+            .build();
+        let bytes: Vec<u8> = text.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory
+            .load_segment("text", 0x80000000, &bytes, true)
+            .unwrap();
+
+        assert_eq!(cpu.entry(), 0x80000000);
+        assert_eq!(cpu.read_register(29), 0x801ffffc);
+        assert!(cpu.memory.region(0x80100000).is_some());
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.memory.read(0x80018000), 0x1234);
+
+        // Presets survive a reset
+        cpu.write_register(4, 0);
+        cpu.reset(false);
+        assert_eq!(cpu.read_register(4), 0x1234);
+        assert_eq!(cpu.read_register(28), 0x80018000);
+    }
+
+    #[test]
+    fn test_entry_and_limit() {
+        let cpu = CPU::builder()
+            .entry(0x00400010)
+            .register(29, 0x1000)
+            .instruction_limit(5)
+            .build();
+
+        assert_eq!(cpu.entry(), 0x00400010);
+        assert_eq!(cpu.read_register(29), 0x1000);
+        assert_eq!(cpu.layout(), &Default::default());
+    }
+}
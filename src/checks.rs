@@ -0,0 +1,234 @@
+use crate::instructions::{self, REGISTER_NAMES};
+use crate::CPU;
+
+// Code that runs here but means different things on different simulators
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub address: u32,
+    pub message: String,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:#010x}: {}", self.address, self.message)
+    }
+}
+
+// Scans every executable region for branches whose delay slot is misused
+pub fn delay_slots(cpu: &CPU) -> Vec<Warning> {
+    scan(cpu, delay_slot)
+}
+
+// Scans for instructions that use a register loaded by the instruction right
+// before them, which only works without the MIPS I load delay
+pub fn load_delays(cpu: &CPU) -> Vec<Warning> {
+    scan(cpu, |word, next| {
+        if !is_load(word) {
+            return None;
+        }
+
+        let register = ((word >> 16) & 0b11111) as usize;
+        if register != 0 && reads(next).contains(&register) {
+            Some(load_delay_message(next, register))
+        } else {
+            None
+        }
+    })
+}
+
+// Runs `check` on every pair of consecutive instructions; warnings are
+// reported at the first of the pair
+fn scan(cpu: &CPU, check: impl Fn(u32, u32) -> Option<String>) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    for region in cpu.memory.regions.iter().filter(|region| region.executable) {
+        let mut address = region.start;
+        while address.wrapping_add(4) < region.end {
+            let word = cpu.memory.read(address);
+            let next = cpu.memory.read(address + 4);
+
+            if let Some(message) = check(word, next) {
+                warnings.push(Warning { address, message });
+            }
+            address += 4;
+        }
+    }
+
+    warnings
+}
+
+pub fn load_delay_message(word: u32, register: usize) -> String {
+    format!(
+        "{} reads ${} in the load delay slot",
+        instructions::get_instruction(word).name(),
+        REGISTER_NAMES[register]
+    )
+}
+
+// What is wrong with running `slot` in the delay slot of `word`, if anything
+pub fn delay_slot(word: u32, slot: u32) -> Option<String> {
+    if !is_control_transfer(word) {
+        return None;
+    }
+
+    let name = instructions::get_instruction(word).name().to_string();
+
+    if is_control_transfer(slot) {
+        let inner = instructions::get_instruction(slot).name().to_string();
+        return Some(format!("{} in the delay slot of {}", inner, name));
+    }
+
+    match link_register(word) {
+        Some(link) if link != 0 && reads(slot).contains(&link) => Some(format!(
+            "delay slot reads ${}, which {} also writes",
+            REGISTER_NAMES[link], name
+        )),
+        _ => None,
+    }
+}
+
+pub fn is_control_transfer(word: u32) -> bool {
+    let opcode = word >> 26;
+    let funct = word & 0b111111;
+
+    match opcode {
+        0 => funct == 0x08 || funct == 0x09,
+        1..=7 | 0x14..=0x17 => true,
+        // BC1F, BC1T and their likely forms
+        0x11 => (word >> 21) & 0b11111 == 0x08,
+        // ERET
+        0x10 => word == 0x4200_0018,
+        _ => false,
+    }
+}
+
+// LB, LH, LWL, LW, LBU, LHU, LWR
+pub fn is_load(word: u32) -> bool {
+    (0x20..=0x26).contains(&(word >> 26))
+}
+
+// The register a jump-and-link writes its return address to
+fn link_register(word: u32) -> Option<usize> {
+    let opcode = word >> 26;
+    let rt = (word >> 16) & 0b11111;
+
+    match opcode {
+        3 => Some(31),
+        // JALR, where rd 0 means $ra
+        0 if word & 0b111111 == 0x09 => match (word >> 11) & 0b11111 {
+            0 => Some(31),
+            rd => Some(rd as usize),
+        },
+        1 if rt & 0b11110 == 0b10000 || rt & 0b11110 == 0b10010 => Some(31),
+        _ => None,
+    }
+}
+
+// The general purpose registers an instruction reads
+pub fn reads(word: u32) -> Vec<usize> {
+    let opcode = word >> 26;
+    let rs = ((word >> 21) & 0b11111) as usize;
+    let rt = ((word >> 16) & 0b11111) as usize;
+    let funct = word & 0b111111;
+
+    let registers = match opcode {
+        0 => match funct {
+            // Shifts by shamt
+            0x00 | 0x02 | 0x03 => vec![rt],
+            0x08 | 0x09 => vec![rs],
+            // SYSCALL: service number and arguments
+            0x0c => vec![2, 4, 5, 6],
+            // MFHI, MFLO
+            0x10 | 0x12 => vec![],
+            _ => vec![rs, rt],
+        },
+        // J, JAL, LUI
+        2 | 3 | 0x0f => vec![],
+        // BEQ, BNE, stores
+        4 | 5 | 0x14 | 0x15 | 0x28..=0x2e | 0x38 => vec![rs, rt],
+        // SPECIAL2: CLZ, CLO read rs only
+        0x1c if funct == 0x20 || funct == 0x21 => vec![rs],
+        0x1c => vec![rs, rt],
+        // SPECIAL3: BSHFL (WSBH, SEB, SEH) reads rt, EXT rs, INS both
+        0x1f if funct == 0x20 => vec![rt],
+        0x1f if funct == 0x00 => vec![rs],
+        0x1f => vec![rs, rt],
+        // MTC1, CTC1; the rest of COP1 only touches FPRs and FCSR
+        0x11 if rs == 0x04 || rs == 0x06 => vec![rt],
+        0x11 => vec![],
+        // MTC0, WRPGPR
+        0x10 => match rs {
+            0x04 | 0x0e => vec![rt],
+            _ => vec![],
+        },
+        _ => vec![rs],
+    };
+
+    registers.into_iter().filter(|&register| register != 0).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{delay_slot, delay_slots, load_delays, Warning};
+    use crate::CPU;
+
+    #[test]
+    fn test_branch_in_delay_slot() {
+        // beq $zero, $zero, 2 ; j 0x400000
+        assert_eq!(
+            delay_slot(0x10000002, 0x08100000),
+            Some(String::from("J in the delay slot of BEQ"))
+        );
+    }
+
+    #[test]
+    fn test_delay_slot_reads_link_register() {
+        // jal 0x40000c ; addu $t0, $ra, $zero
+        assert_eq!(
+            delay_slot(0x0C100003, 0x03e04021),
+            Some(String::from("delay slot reads $ra, which JAL also writes"))
+        );
+    }
+
+    #[test]
+    fn test_ordinary_delay_slot() {
+        // jr $ra ; addi $v0, $zero, 10
+        assert_eq!(delay_slot(0x03e00008, 0x2002000a), None);
+        // addi $v0, $zero, 10 ; j 0x400000
+        assert_eq!(delay_slot(0x2002000a, 0x08100000), None);
+    }
+
+    fn cpu_with_text(words: &[u32]) -> CPU {
+        let mut cpu = CPU::new();
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory.load_segment("text", 0x00400000, &bytes, true).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn test_load_delay_hazard() {
+        // lw $t0, 0($sp) ; addi $t1, $t0, 1 ; lw $t2, 0($sp) ; nop ; addi $t3, $t2, 1
+        let cpu = cpu_with_text(&[0x8fa80000, 0x21090001, 0x8faa0000, 0, 0x214b0001]);
+
+        assert_eq!(
+            load_delays(&cpu),
+            vec![Warning {
+                address: 0x00400000,
+                message: String::from("ADDI reads $t0 in the load delay slot"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_scan_text() {
+        let cpu = cpu_with_text(&[0x2002000a, 0x03e00008, 0x08100000]);
+
+        assert_eq!(
+            delay_slots(&cpu),
+            vec![Warning {
+                address: 0x00400004,
+                message: String::from("J in the delay slot of JR"),
+            }]
+        );
+    }
+}
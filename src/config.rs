@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::builder::CpuBuilder;
+use crate::device::Device;
+use crate::disk::{self, Disk};
+use crate::dma::{self, Dma};
+use crate::error::Error;
+use crate::instructions::REGISTER_NAMES;
+use crate::loader::Layout;
+use crate::mmio_console::{self, MmioConsole};
+use crate::rtc::{self, Rtc};
+use crate::syscall::Compatibility;
+use crate::{Endian, CPU};
+
+// A machine described in a TOML file, so a setup can be kept with the
+// programs it runs:
+//
+//     endian = "little"
+//     entry = 0x80000000
+//     syscalls = "spim"          # mars (the default), spim or none
+//
+//     [memory]                   # any of these; the rest are the MARS map
+//     text_base = 0x80000000
+//     data_base = 0x80100000
+//     global_pointer = 0x80108000
+//     stack_top = 0x80200000
+//
+//     [registers]
+//     a0 = 1
+//
+//     [[devices]]
+//     kind = "disk"              # console, rtc, dma or disk
+//     path = "disk.img"          # relative to this file
+//     base = 0xffff0040          # optional, the device's usual address
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub endian: Endian,
+    pub entry: Option<u32>,
+    #[serde(default)]
+    pub syscalls: Compatibility,
+    #[serde(default)]
+    pub memory: Layout,
+    // By name ("sp", "$a0") or number ("29")
+    #[serde(default)]
+    pub registers: BTreeMap<String, u32>,
+    #[serde(default)]
+    pub devices: Vec<DeviceConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum DeviceConfig {
+    Console { base: Option<u32> },
+    Rtc { base: Option<u32> },
+    Dma { base: Option<u32> },
+    Disk { base: Option<u32>, path: PathBuf },
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Config, Error> {
+        let text = std::fs::read_to_string(path).map_err(|error| Error::io(path, error))?;
+        let mut config = Config::parse(&text).map_err(|error| Error::Malformed {
+            path: path.to_string(),
+            reason: error.to_string(),
+        })?;
+
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+        for device in config.devices.iter_mut() {
+            if let DeviceConfig::Disk { path, .. } = device {
+                *path = directory.join(&*path);
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Result<Config, Error> {
+        toml::from_str(text).map_err(|error| Error::Parse(error.message().to_string()))
+    }
+
+    // Everything but the devices, which can't be built ahead of the machine
+    pub fn builder(&self) -> Result<CpuBuilder, Error> {
+        let mut builder = CPU::builder()
+            .layout(self.memory)
+            .endian(self.endian)
+            .syscalls(self.syscalls);
+        if let Some(entry) = self.entry {
+            builder = builder.entry(entry);
+        }
+        for (name, &value) in &self.registers {
+            builder = builder.register(register_number(name)?, value);
+        }
+        Ok(builder)
+    }
+
+    // The machine with its devices attached
+    pub fn build(&self) -> Result<CPU, Error> {
+        let mut cpu = self.builder()?.build();
+        for device in &self.devices {
+            let (base, end, mapped): (Option<u32>, u32, Box<dyn Device>) = match device {
+                DeviceConfig::Console { base } => (
+                    *base,
+                    mmio_console::END - mmio_console::BASE,
+                    Box::new(MmioConsole::stdio()),
+                ),
+                DeviceConfig::Rtc { base } => {
+                    (*base, rtc::END - rtc::BASE, Box::new(Rtc::new(cpu.clock())))
+                }
+                DeviceConfig::Dma { base } => (*base, dma::END - dma::BASE, Box::new(Dma::new())),
+                DeviceConfig::Disk { base, path } => {
+                    let disk = Disk::open(path)
+                        .map_err(|error| Error::io(&path.to_string_lossy(), error))?;
+                    (*base, disk::END - disk::BASE, Box::new(disk))
+                }
+            };
+            let base = base.unwrap_or(device.default_base());
+            cpu.attach(base, base.wrapping_add(end), mapped)?;
+        }
+        Ok(cpu)
+    }
+}
+
+impl DeviceConfig {
+    fn default_base(&self) -> u32 {
+        match self {
+            DeviceConfig::Console { .. } => mmio_console::BASE,
+            DeviceConfig::Rtc { .. } => rtc::BASE,
+            DeviceConfig::Dma { .. } => dma::BASE,
+            DeviceConfig::Disk { .. } => disk::BASE,
+        }
+    }
+}
+
+fn register_number(name: &str) -> Result<usize, Error> {
+    let name = name.strip_prefix('$').unwrap_or(name);
+    match name.parse::<usize>() {
+        Ok(number) if number < 32 => Ok(number),
+        _ => REGISTER_NAMES
+            .iter()
+            .position(|&register| register == name || (register == "fp" && name == "s8"))
+            .ok_or_else(|| Error::Parse(format!("unknown register '{}'", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, DeviceConfig};
+    use crate::dma::Dma;
+    use crate::syscall::Compatibility;
+    use crate::{Endian, ExitReason};
+
+    #[test]
+    fn test_parse_and_build() {
+        let config = Config::parse(
+            r#"
+            endian = "big"
+            entry = 0x80000004
+            syscalls = "spim"
+
+            [memory]
+            text_base = 0x80000000
+            stack_top = 0x80200000
+
+            [registers]
+            a0 = 7
+            "$t0" = 0x10
+
+            [[devices]]
+            kind = "dma"
+            base = 0xffff1000
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.endian, Endian::Big);
+        assert_eq!(config.syscalls, Compatibility::Spim);
+        assert_eq!(config.memory.data_base, 0x10010000);
+        assert_eq!(
+            config.devices,
+            [DeviceConfig::Dma {
+                base: Some(0xffff1000)
+            }]
+        );
+
+        let mut cpu = config.build().unwrap();
+        assert_eq!(cpu.entry(), 0x80000004);
+        assert_eq!(cpu.read_register(4), 7);
+        assert_eq!(cpu.read_register(8), 0x10);
+        assert_eq!(cpu.read_register(29), 0x801ffffc);
+        assert!(cpu
+            .attach(0xffff1000, 0xffff1004, Box::new(Dma::new()))
+            .is_err());
+        assert!(!cpu.syscalls.contains_key(&30));
+
+        // lui $t0, 0x1234 / sw $t0, 0($sp) / addi $v0, $zero, 10 / syscall, big-endian
+        let text: [u32; 4] = [0x3c081234, 0xafa80000, 0x2002000a, 0x0000000c];
+        let bytes: Vec<u8> = text.iter().flat_map(|word| word.to_be_bytes()).collect();
+        cpu.memory
+            .load_segment("text", 0x80000000, &bytes, true)
+            .unwrap();
+        cpu.set_entry(0x80000000);
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.memory.read_u8(0x801ffffc), 0x12);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Config::parse("speed = 3").is_err());
+        assert!(Config::parse("[[devices]]\nkind = \"gpu\"").is_err());
+        let config = Config::parse("[registers]\nq9 = 1").unwrap();
+        assert_eq!(
+            config.builder().unwrap_err().to_string(),
+            "unknown register 'q9'"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+pub enum ReadLine {
+    Line(String),
+    Eof,
+    Interrupted,
+}
+
+// Where the console syscalls read and write. The terminal is the host's
+// stdio; embedders and tests can supply their own.
+pub trait Console {
+    fn write(&mut self, text: &str);
+
+    // A line with its newline, if it had one
+    fn read_line(&mut self) -> ReadLine;
+}
+
+// Input from a script, one line per read, and output kept in a buffer the
+// caller holds on to
+#[derive(Default)]
+pub struct ScriptedConsole {
+    input: VecDeque<String>,
+    output: Rc<RefCell<String>>,
+}
+
+impl ScriptedConsole {
+    pub fn new(input: &str) -> ScriptedConsole {
+        ScriptedConsole {
+            input: input.split_inclusive('\n').map(String::from).collect(),
+            output: Rc::default(),
+        }
+    }
+
+    // Everything written so far, and later
+    pub fn output(&self) -> Rc<RefCell<String>> {
+        self.output.clone()
+    }
+}
+
+impl Console for ScriptedConsole {
+    fn write(&mut self, text: &str) {
+        self.output.borrow_mut().push_str(text);
+    }
+
+    fn read_line(&mut self) -> ReadLine {
+        match self.input.pop_front() {
+            Some(line) => ReadLine::Line(line),
+            None => ReadLine::Eof,
+        }
+    }
+}
+
+// Lines from any reader and output to any writer, e.g. a fixture file for
+// input and a capture file for output in a grading run
+pub struct Streams {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+}
+
+impl Streams {
+    pub fn new(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Streams {
+        Streams { input, output }
+    }
+
+    // Host stdin and stdout, each replaced by a file when given. The output
+    // file is created or truncated.
+    pub fn files(input: Option<&Path>, output: Option<&Path>) -> io::Result<Streams> {
+        let input: Box<dyn BufRead> = match input {
+            Some(path) => Box::new(BufReader::new(File::open(path)?)),
+            None => Box::new(BufReader::new(io::stdin())),
+        };
+        let output: Box<dyn Write> = match output {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(io::stdout()),
+        };
+        Ok(Streams::new(input, output))
+    }
+}
+
+impl Console for Streams {
+    fn write(&mut self, text: &str) {
+        let _ = self.output.write_all(text.as_bytes());
+        let _ = self.output.flush();
+    }
+
+    fn read_line(&mut self) -> ReadLine {
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => ReadLine::Eof,
+            Ok(_) => ReadLine::Line(line),
+        }
+    }
+}
+
+// The CPU's side of its console: it counts reads for idle loop detection
+// and, once asked to, keeps what a console window would show, input included.
+// Left off, a long run's output doesn't pile up in memory.
+pub(crate) struct GuestIo {
+    console: Box<dyn Console>,
+    transcript: Option<String>,
+    reads: u64,
+}
+
+impl GuestIo {
+    pub fn new(console: Box<dyn Console>) -> GuestIo {
+        GuestIo {
+            console,
+            transcript: None,
+            reads: 0,
+        }
+    }
+
+    pub fn record(&mut self) {
+        self.transcript.get_or_insert_with(String::new);
+    }
+
+    pub fn set_console(&mut self, console: Box<dyn Console>) {
+        self.console = console;
+    }
+
+    pub fn transcript(&self) -> &str {
+        self.transcript.as_deref().unwrap_or_default()
+    }
+
+    pub fn reads(&self) -> u64 {
+        self.reads
+    }
+
+    pub fn write(&mut self, text: &str) {
+        if let Some(transcript) = &mut self.transcript {
+            transcript.push_str(text);
+        }
+        self.console.write(text);
+    }
+
+    pub fn read_line(&mut self) -> ReadLine {
+        self.reads += 1;
+        let line = self.console.read_line();
+        if let (ReadLine::Line(line), Some(transcript)) = (&line, &mut self.transcript) {
+            transcript.push_str(line);
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Console, ReadLine, ScriptedConsole, Streams};
+    use crate::{ExitReason, CPU};
+    use std::io::{self, Cursor};
+
+    #[test]
+    fn test_scripted_console() {
+        // addi $v0, $zero, 5 / syscall / addi $a0, $v0, 1 / addi $v0, $zero, 1 / syscall /
+        // addi $v0, $zero, 5 / syscall / addi $v0, $zero, 10 / syscall
+        let text: [u32; 9] = [
+            0x20020005, 0x0000000c, 0x20440001, 0x20020001, 0x0000000c, 0x20020005, 0x0000000c,
+            0x2002000a, 0x0000000c,
+        ];
+        let mut cpu = CPU::new();
+        let bytes: Vec<u8> = text.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory
+            .load_segment("text", 0x00400000, &bytes, true)
+            .unwrap();
+        let console = ScriptedConsole::new("41\n");
+        let output = console.output();
+        cpu.set_console(Box::new(console));
+        cpu.record_console();
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(*output.borrow(), "42");
+        assert_eq!(cpu.console(), "41\n42");
+    }
+
+    #[test]
+    fn test_recording_is_opt_in() {
+        let mut cpu = CPU::new();
+        let console = ScriptedConsole::new("");
+        let output = console.output();
+        cpu.set_console(Box::new(console));
+
+        cpu.io.write("before ");
+        cpu.record_console();
+        cpu.io.write("after");
+
+        assert_eq!(*output.borrow(), "before after");
+        assert_eq!(cpu.console(), "after");
+    }
+
+    #[test]
+    fn test_streams() {
+        let mut streams = Streams::new(Box::new(Cursor::new("first\nlast")), Box::new(io::sink()));
+
+        assert!(matches!(streams.read_line(), ReadLine::Line(line) if line == "first\n"));
+        assert!(matches!(streams.read_line(), ReadLine::Line(line) if line == "last"));
+        assert!(matches!(streams.read_line(), ReadLine::Eof));
+    }
+}
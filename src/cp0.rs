@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+
+use crate::tlb::{Tlb, TlbEntry, TlbFault};
+
+// Cause.ExcCode values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    Interrupt = 0,
+    TlbModified = 1,
+    TlbLoad = 2,
+    TlbStore = 3,
+    AddressLoad = 4,
+    AddressStore = 5,
+    Syscall = 8,
+    Breakpoint = 9,
+    ReservedInstruction = 10,
+    Overflow = 12,
+    Trap = 13,
+}
+
+// System control coprocessor registers, addressed by (register, select)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cp0 {
+    pub index: u32,
+    pub entry_lo0: u32,
+    pub entry_lo1: u32,
+    pub context: u32,
+    pub page_mask: u32,
+    pub wired: u32,
+    pub bad_vaddr: u32,
+    pub count: u32,
+    pub compare: u32,
+    pub entry_hi: u32,
+    pub status: u32,
+    pub cause: u32,
+    pub epc: u32,
+    pub srs_ctl: u32,
+    // The Cause.IP bits devices are holding raised
+    device_interrupts: u32,
+    // Empty unless the MMU is enabled
+    pub tlb: Tlb,
+}
+
+impl Cp0 {
+    // Register numbers, all select 0
+    pub const INDEX: u8 = 0;
+    pub const RANDOM: u8 = 1;
+    pub const ENTRY_LO0: u8 = 2;
+    pub const ENTRY_LO1: u8 = 3;
+    pub const CONTEXT: u8 = 4;
+    pub const PAGE_MASK: u8 = 5;
+    pub const WIRED: u8 = 6;
+    pub const BAD_VADDR: u8 = 8;
+    pub const COUNT: u8 = 9;
+    pub const ENTRY_HI: u8 = 10;
+    pub const COMPARE: u8 = 11;
+    pub const STATUS: u8 = 12;
+    pub const CAUSE: u8 = 13;
+    pub const EPC: u8 = 14;
+
+    // Cause: only the two software interrupt bits (IP1, IP0) are writable
+    pub const CAUSE_WRITABLE: u32 = 0b11 << 8;
+    pub const CAUSE_BD: u32 = 1 << 31;
+    pub const CAUSE_EXC_CODE: u32 = 0x1f << 2;
+    // The timer interrupt: Cause.TI, and the interrupt line it drives
+    pub const CAUSE_TI: u32 = 1 << 30;
+    pub const CAUSE_IP7: u32 = 1 << 15;
+
+    // Hardware interrupt lines 0-5 drive Cause.IP2-IP7
+    pub const IRQ_LINES: u32 = 6;
+    pub const IRQ_SHIFT: u32 = 10;
+
+    // Status.IM and Cause.IP line up bit for bit
+    pub const INTERRUPT_MASK: u32 = 0xff << 8;
+
+    pub const STATUS_IE: u32 = 1;
+    pub const STATUS_EXL: u32 = 1 << 1;
+    pub const STATUS_ERL: u32 = 1 << 2;
+    pub const STATUS_UM: u32 = 1 << 4;
+
+    // Index.P: the last TLBP found nothing
+    pub const INDEX_PROBE_FAILED: u32 = 1 << 31;
+
+    // SRSCtl fields
+    pub const HSS_SHIFT: u32 = 26;
+    pub const ESS_SHIFT: u32 = 12;
+    pub const PSS_SHIFT: u32 = 6;
+    pub const CSS_SHIFT: u32 = 0;
+
+    pub fn new() -> Cp0 {
+        Cp0::default()
+    }
+
+    pub fn read(&self, register: u8, select: u8) -> u32 {
+        match (register, select) {
+            (Self::INDEX, 0) => self.index,
+            (Self::RANDOM, 0) => self.random(),
+            (Self::ENTRY_LO0, 0) => self.entry_lo0,
+            (Self::ENTRY_LO1, 0) => self.entry_lo1,
+            (Self::CONTEXT, 0) => self.context,
+            (Self::PAGE_MASK, 0) => self.page_mask,
+            (Self::WIRED, 0) => self.wired,
+            (Self::BAD_VADDR, 0) => self.bad_vaddr,
+            (Self::COUNT, 0) => self.count,
+            (Self::ENTRY_HI, 0) => self.entry_hi,
+            (Self::COMPARE, 0) => self.compare,
+            (Self::STATUS, 0) => self.status,
+            (Self::CAUSE, 0) => self.cause,
+            (Self::EPC, 0) => self.epc,
+            (12, 2) => self.srs_ctl,
+            _ => 0,
+        }
+    }
+
+    pub fn write(&mut self, register: u8, select: u8, value: u32) {
+        match (register, select) {
+            // Random and BadVAddr are read-only
+            (Self::INDEX, 0) => self.index = value & 0x3f,
+            (Self::ENTRY_LO0, 0) => self.entry_lo0 = value & 0x3fff_ffff,
+            (Self::ENTRY_LO1, 0) => self.entry_lo1 = value & 0x3fff_ffff,
+            // Only PTEBase; BadVPN2 is filled in by TLB exceptions
+            (Self::CONTEXT, 0) => self.context = value & 0xff80_0000 | self.context & 0x007f_fff0,
+            (Self::PAGE_MASK, 0) => self.page_mask = value & 0x1fff_e000,
+            (Self::WIRED, 0) => self.wired = value & 0x3f,
+            (Self::COUNT, 0) => self.count = value,
+            (Self::ENTRY_HI, 0) => self.entry_hi = value & 0xffff_e0ff,
+            // Writing Compare acknowledges the timer interrupt
+            (Self::COMPARE, 0) => {
+                self.compare = value;
+                self.cause &= !(Self::CAUSE_TI | Self::CAUSE_IP7);
+            }
+            (Self::STATUS, 0) => self.status = value,
+            (Self::CAUSE, 0) => {
+                let writable = Self::CAUSE_WRITABLE;
+                self.cause = (value & writable) | (self.cause & !writable);
+            }
+            (Self::EPC, 0) => self.epc = value,
+
+            // SRSCtl: only ESS and PSS are writable, HSS and CSS are read-only
+            (12, 2) => {
+                let writable = 0xF << Self::ESS_SHIFT | 0xF << Self::PSS_SHIFT;
+                let highest = self.srs_field(Self::HSS_SHIFT);
+                self.srs_ctl = (value & writable) | (self.srs_ctl & !writable);
+
+                // Sets above HSS don't exist
+                for shift in [Self::ESS_SHIFT, Self::PSS_SHIFT] {
+                    if self.srs_field(shift) > highest {
+                        self.set_srs_field(shift, highest);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Exception entry. EPC and BD are only updated when EXL was clear, so a
+    // nested exception keeps the original return address.
+    pub fn enter_exception(&mut self, exception: Exception, pc: u32, in_delay_slot: bool) {
+        if self.status & Self::STATUS_EXL == 0 {
+            self.epc = pc;
+            self.cause &= !Self::CAUSE_BD;
+            if in_delay_slot {
+                self.cause |= Self::CAUSE_BD;
+            }
+        }
+
+        self.cause = (self.cause & !Self::CAUSE_EXC_CODE) | (exception as u32) << 2;
+        self.status |= Self::STATUS_EXL;
+    }
+
+    // Somewhere in [Wired, TLB size), derived from Count so runs repeat
+    fn random(&self) -> u32 {
+        let size = self.tlb.len() as u32;
+        if self.wired >= size {
+            return size.saturating_sub(1);
+        }
+        self.wired + self.count % (size - self.wired)
+    }
+
+    // kuseg goes through the TLB when there is one; the kernel segments and
+    // every address without an MMU map to themselves
+    pub fn translate(&self, address: u32, store: bool) -> Result<u32, TlbFault> {
+        if self.tlb.is_empty() || address >= 0x8000_0000 {
+            return Ok(address);
+        }
+
+        self.tlb.translate(address, self.entry_hi & 0xff, store)
+    }
+
+    // What the refill handler needs: the address in BadVAddr, and its page
+    // pair in Context.BadVPN2 and EntryHi.VPN2
+    pub fn enter_tlb_fault(&mut self, address: u32) {
+        self.bad_vaddr = address;
+        self.context = self.context & 0xff80_0000 | (address >> 13) << 4;
+        self.entry_hi = address & 0xffff_e000 | self.entry_hi & 0xff;
+    }
+
+    // TLBR
+    pub fn tlb_read(&mut self) {
+        let entry = self.tlb.read(self.index);
+        self.page_mask = entry.page_mask;
+        self.entry_hi = entry.entry_hi;
+        self.entry_lo0 = entry.entry_lo0;
+        self.entry_lo1 = entry.entry_lo1;
+    }
+
+    // TLBWI, and TLBWR with the index from Random
+    pub fn tlb_write(&mut self, random: bool) {
+        let index = if random { self.random() } else { self.index };
+        let entry = TlbEntry {
+            page_mask: self.page_mask,
+            entry_hi: self.entry_hi,
+            entry_lo0: self.entry_lo0,
+            entry_lo1: self.entry_lo1,
+        };
+        self.tlb.write(index, entry);
+    }
+
+    // TLBP
+    pub fn tlb_probe(&mut self) {
+        self.index = match self.tlb.probe(self.entry_hi) {
+            Some(index) => index as u32,
+            None => Self::INDEX_PROBE_FAILED,
+        };
+    }
+
+    // Count advances once per instruction; reaching Compare raises IP7
+    pub fn tick(&mut self) {
+        self.count = self.count.wrapping_add(1);
+        if self.count == self.compare {
+            self.cause |= Self::CAUSE_TI | Self::CAUSE_IP7;
+        }
+    }
+
+    // Level-triggered: IP stays set until the device lowers its line. Line 5
+    // shares IP7 with the timer, which keeps it raised while TI is set.
+    pub fn set_irq(&mut self, line: u32, raised: bool) {
+        assert!(line < Self::IRQ_LINES, "no interrupt line {}", line);
+
+        let bit = 1 << (Self::IRQ_SHIFT + line);
+        if raised {
+            self.cause |= bit;
+        } else if bit != Self::CAUSE_IP7 || self.cause & Self::CAUSE_TI == 0 {
+            self.cause &= !bit;
+        }
+    }
+
+    // Devices own the IP bits they raise: a bit a device lets go of is
+    // cleared, whoever else set it
+    pub fn set_device_interrupts(&mut self, lines: u32) {
+        let bits = (lines & 0xff) << 8;
+        self.cause = self.cause & !self.device_interrupts | bits;
+        self.device_interrupts = bits;
+    }
+
+    // Status.IE with neither EXL nor ERL set, and at least one line unmasked
+    pub fn interrupts_enabled(&self) -> bool {
+        let enabled = self.status & (Self::STATUS_IE | Self::STATUS_EXL | Self::STATUS_ERL);
+        enabled == Self::STATUS_IE && self.status & Self::INTERRUPT_MASK != 0
+    }
+
+    // An enabled interrupt line is raised in Cause.IP
+    pub fn interrupt_pending(&self) -> bool {
+        self.interrupts_enabled() && self.cause & self.status & Self::INTERRUPT_MASK != 0
+    }
+
+    // Status.UM with neither EXL nor ERL set
+    pub fn is_user_mode(&self) -> bool {
+        self.status & (Self::STATUS_UM | Self::STATUS_EXL | Self::STATUS_ERL) == Self::STATUS_UM
+    }
+
+    // Cause.ExcCode of the last exception
+    pub fn exception_code(&self) -> u32 {
+        (self.cause & Self::CAUSE_EXC_CODE) >> 2
+    }
+
+    pub fn srs_field(&self, shift: u32) -> u32 {
+        (self.srs_ctl >> shift) & 0xF
+    }
+
+    pub fn set_srs_field(&mut self, shift: u32, value: u32) {
+        self.srs_ctl = (self.srs_ctl & !(0xF << shift)) | (value & 0xF) << shift;
+    }
+}
+
+impl std::fmt::Display for Cp0 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Status:   {:#010x}", self.status)?;
+        writeln!(f, "Cause:    {:#010x}", self.cause)?;
+        writeln!(f, "EPC:      {:#010x}", self.epc)?;
+        writeln!(f, "Count:    {:#010x}", self.count)?;
+        writeln!(f, "Compare:  {:#010x}", self.compare)?;
+        writeln!(f, "BadVAddr: {:#010x}", self.bad_vaddr)
+    }
+}
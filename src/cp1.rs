@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+// FCSR.RM, the rounding mode used by CVT and by rounding to a narrower format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Nearest,
+    Zero,
+    Up,
+    Down,
+}
+
+// Floating-point coprocessor registers. FPRs hold raw bits; the
+// instructions decide how to interpret them. A double lives in an even/odd
+// pair, low word in the even register.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cp1 {
+    registers: [u32; 32],
+    pub fcsr: u32,
+}
+
+impl Cp1 {
+    pub fn new() -> Cp1 {
+        Cp1::default()
+    }
+
+    pub fn rounding(&self) -> Rounding {
+        match self.fcsr & 0b11 {
+            0 => Rounding::Nearest,
+            1 => Rounding::Zero,
+            2 => Rounding::Up,
+            _ => Rounding::Down,
+        }
+    }
+
+    // FCSR.FCC: condition code 0 is bit 23, codes 1 to 7 are bits 25 to 31
+    fn condition_bit(cc: u8) -> u32 {
+        match cc {
+            0 => 1 << 23,
+            cc => 1 << (24 + cc as u32),
+        }
+    }
+
+    pub fn condition(&self, cc: u8) -> bool {
+        self.fcsr & Self::condition_bit(cc) != 0
+    }
+
+    pub fn set_condition(&mut self, cc: u8, value: bool) {
+        if value {
+            self.fcsr |= Self::condition_bit(cc);
+        } else {
+            self.fcsr &= !Self::condition_bit(cc);
+        }
+    }
+
+    pub fn read(&self, register: u8) -> u32 {
+        self.registers[register as usize]
+    }
+
+    pub fn write(&mut self, register: u8, value: u32) {
+        self.registers[register as usize] = value;
+    }
+
+    pub fn read_single(&self, register: u8) -> f32 {
+        f32::from_bits(self.read(register))
+    }
+
+    pub fn write_single(&mut self, register: u8, value: f32) {
+        self.write(register, value.to_bits());
+    }
+
+    pub fn read_double(&self, register: u8) -> f64 {
+        let low = self.read(register & !1) as u64;
+        let high = self.read(register | 1) as u64;
+        f64::from_bits(high << 32 | low)
+    }
+
+    pub fn write_double(&mut self, register: u8, value: f64) {
+        let bits = value.to_bits();
+        self.write(register & !1, bits as u32);
+        self.write(register | 1, (bits >> 32) as u32);
+    }
+}
@@ -0,0 +1,793 @@
+use crate::breakpoints::{Condition, Watch};
+use crate::console::{Console, ReadLine};
+use crate::hooks::AccessKind;
+use crate::instructions::{self, REGISTER_NAMES};
+use crate::microstep::{MicroStepper, Phase};
+use crate::terminal::Terminal;
+use crate::{parse_number, ExitReason, CPU};
+
+pub enum Outcome {
+    Output(String),
+    Exit(ExitReason),
+}
+
+pub struct Debugger {
+    terminal: Terminal,
+    stepper: MicroStepper,
+    // Whether the program has begun, so continuing doesn't restart it
+    started: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            terminal: Terminal::new(),
+            stepper: MicroStepper::new(),
+            started: false,
+        }
+    }
+
+    pub fn repl(&mut self, cpu: &mut CPU) -> ExitReason {
+        loop {
+            self.terminal.write("(rim) ");
+            let line = match self.terminal.read_line() {
+                ReadLine::Line(line) => line,
+                ReadLine::Eof | ReadLine::Interrupted => return ExitReason::Interrupted,
+            };
+
+            match self.execute(cpu, &line) {
+                Outcome::Output(output) => self.terminal.write(&output),
+                Outcome::Exit(reason) => return reason,
+            }
+        }
+    }
+
+    pub fn execute(&mut self, cpu: &mut CPU, line: &str) -> Outcome {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Outcome::Output(String::new()),
+        };
+        let args: Vec<&str> = words.collect();
+
+        let output = match command {
+            "refs" | "findrefs" => find_references(cpu, &args),
+            "cp0" => Ok(cpu.cp0().to_string()),
+            "continue" | "c" | "run" | "r" => return self.resume(cpu),
+            "step" | "s" | "stepi" | "si" => match args.as_slice() {
+                [] => return self.step(cpu, 1),
+                [count] => match count.parse::<u64>() {
+                    Ok(count) if count > 0 => return self.step(cpu, count),
+                    _ => Err(format!("invalid count '{}'", count)),
+                },
+                _ => Err(String::from("usage: step [count]")),
+            },
+            "break" | "b" => set_breakpoint(cpu, &args),
+            "watch" | "w" => set_watchpoint(cpu, Watch::Write, &args),
+            "rwatch" => set_watchpoint(cpu, Watch::Read, &args),
+            "awatch" => set_watchpoint(cpu, Watch::Access, &args),
+            "delete" | "d" => delete(cpu, &args),
+            "info" | "i" => match args.as_slice() {
+                ["registers" | "r"] => Ok(registers(cpu)),
+                ["breakpoints" | "b"] => Ok(list_breakpoints(cpu)),
+                _ => Err(String::from("usage: info registers|breakpoints")),
+            },
+            "disas" | "disassemble" => self.disassemble(cpu, &args),
+            "backtrace" | "bt" => Ok(backtrace(cpu)),
+            _ if command == "x" || command.starts_with("x/") => examine(cpu, command, &args),
+            "ustep" | "u" => {
+                if !self.started {
+                    cpu.start();
+                    self.started = true;
+                }
+                match self.stepper.step(cpu) {
+                    Ok(phase) => Ok(format!("{}\n", phase)),
+                    Err(reason) => return self.stopped(cpu, reason),
+                }
+            }
+            "quit" | "q" => return Outcome::Exit(ExitReason::Interrupted),
+            "help" | "h" => Ok(String::from(HELP)),
+            _ => Err(format!("unknown command '{}', try 'help'", command)),
+        };
+
+        match output {
+            Ok(output) => Outcome::Output(output),
+            Err(message) => Outcome::Output(format!("error: {}\n", message)),
+        }
+    }
+
+    // Starts the program the first time, and finishes an instruction left
+    // halfway by ustep
+    fn prepare(&mut self, cpu: &mut CPU) -> Result<(), ExitReason> {
+        if !self.started {
+            cpu.start();
+            self.started = true;
+        }
+        while self.stepper.phase() != Phase::Fetch {
+            self.stepper.step(cpu)?;
+        }
+        Ok(())
+    }
+
+    // Runs until the program ends or reaches a breakpoint; the one it may be
+    // stopped at doesn't count
+    fn resume(&mut self, cpu: &mut CPU) -> Outcome {
+        if let Err(reason) = self.prepare(cpu) {
+            return self.stopped(cpu, reason);
+        }
+        match cpu.resume() {
+            Ok(reason) => self.stopped(cpu, reason),
+            Err(error) => Outcome::Output(format!("error: {}\n", error)),
+        }
+    }
+
+    // Steps run over breakpoints
+    fn step(&mut self, cpu: &mut CPU, count: u64) -> Outcome {
+        if let Err(reason) = self.prepare(cpu) {
+            return self.stopped(cpu, reason);
+        }
+        for _ in 0..count {
+            if let Some(reason) = cpu.step().exit {
+                return self.stopped(cpu, reason);
+            }
+        }
+        Outcome::Output(location(cpu, cpu.pc()))
+    }
+
+    // `disas [address] [count]`: from the pc (or the entry point before the
+    // program starts) by default, with "=>" at the pc
+    fn disassemble(&self, cpu: &CPU, args: &[&str]) -> Result<String, String> {
+        let pc = if self.started { cpu.pc() } else { cpu.entry() };
+        let (start, count) = match args {
+            [] => (pc, 8),
+            [start] => (parse_address(cpu, start)?, 8),
+            [start, count] => (
+                parse_address(cpu, start)?,
+                count
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid count '{}'", count))?,
+            ),
+            _ => return Err(String::from("usage: disas [address] [count]")),
+        };
+
+        let mut output = String::new();
+        for index in 0..count {
+            let address = start.wrapping_add(index * 4);
+            let marker = if address == pc { "=> " } else { "   " };
+            output.push_str(&format!("{}{}", marker, location(cpu, address)));
+        }
+        Ok(output)
+    }
+
+    // A BREAK, a breakpoint or a watchpoint hands control back to the
+    // prompt; anything else ends the session
+    fn stopped(&self, cpu: &CPU, reason: ExitReason) -> Outcome {
+        match reason {
+            ExitReason::Break { .. } => Outcome::Output(format!("{}\n", reason)),
+            ExitReason::Breakpoint { pc, number } => {
+                Outcome::Output(format!("breakpoint {}, {}", number, location(cpu, pc)))
+            }
+            ExitReason::Watchpoint { pc, number, access } => {
+                let (kind, preposition) = match access.kind {
+                    AccessKind::Load => ("load", "from"),
+                    AccessKind::Store => ("store", "to"),
+                };
+                Outcome::Output(format!(
+                    "watchpoint {}, {} of {:#x} {} {:#010x}{}\n  by {}",
+                    number,
+                    kind,
+                    access.value,
+                    preposition,
+                    access.address,
+                    cpu.label(access.address),
+                    location(cpu, pc)
+                ))
+            }
+            reason => Outcome::Exit(reason),
+        }
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const HELP: &str = "\
+step [n]             run one instruction (or n) and show the next
+continue             run the program until it ends, reaches a BREAK or a breakpoint
+break <address> [if <condition>]
+                     stop before the instruction at an address or symbol, if
+                     the condition (e.g. $t0 == 5 && *($sp+4) != 0) holds
+watch <address> [n]  stop after an instruction stores to the n bytes (default: 4)
+                     from an address or symbol; rwatch stops on loads, awatch
+                     on both
+delete [n...]        delete breakpoints and watchpoints by number (default: all)
+info registers       show the general purpose registers, pc, hi and lo
+info breakpoints     list the breakpoints and watchpoints
+x/<n>w <address>     show n memory words from an address
+disas [addr] [n]     disassemble n instructions (default: 8 from the pc)
+backtrace            show the calls that led to the pc
+refs <start> [end]   list memory words pointing into [start, end) (default: one word)
+ustep                advance one datapath phase (fetch, decode, read, execute, write back)
+cp0                  show Status, Cause, EPC, Count, Compare and BadVAddr
+quit                 leave the debugger
+";
+
+// `break <address> [if <condition>]`
+fn set_breakpoint(cpu: &mut CPU, args: &[&str]) -> Result<String, String> {
+    let (address, condition) = match args {
+        [address] => (address, None),
+        [address, "if", condition @ ..] if !condition.is_empty() => {
+            (address, Some(Condition::parse(&condition.join(" "))?))
+        }
+        _ => return Err(String::from("usage: break <address|symbol> [if <condition>]")),
+    };
+    let address = parse_address(cpu, address)?;
+    let number = cpu.add_breakpoint(address, condition);
+    Ok(format!("breakpoint {} at {:#010x}{}\n", number, address, cpu.label(address)))
+}
+
+// `watch <address> [length]`, and rwatch and awatch
+fn set_watchpoint(cpu: &mut CPU, watch: Watch, args: &[&str]) -> Result<String, String> {
+    let (address, length) = match args {
+        [address] => (address, 4),
+        [address, length] => match length.parse::<u32>() {
+            Ok(length) if length > 0 => (address, length),
+            _ => return Err(format!("invalid length '{}'", length)),
+        },
+        _ => return Err(String::from("usage: watch <address|symbol> [length]")),
+    };
+    let address = parse_address(cpu, address)?;
+    let number = cpu.add_watchpoint(address, length, watch);
+    Ok(format!(
+        "watchpoint {} on {:#010x}{}, {}\n",
+        number,
+        address,
+        cpu.label(address),
+        bytes(length)
+    ))
+}
+
+fn delete(cpu: &mut CPU, args: &[&str]) -> Result<String, String> {
+    if args.is_empty() {
+        cpu.clear_breakpoints();
+        return Ok(String::new());
+    }
+
+    for arg in args {
+        let removed = arg
+            .parse::<usize>()
+            .is_ok_and(|number| cpu.remove_breakpoint(number));
+        if !removed {
+            return Err(format!("no breakpoint {}", arg));
+        }
+    }
+    Ok(String::new())
+}
+
+// Breakpoints and watchpoints together, by number
+fn list_breakpoints(cpu: &CPU) -> String {
+    let breakpoints = cpu.breakpoints().iter().map(|breakpoint| {
+        let condition = breakpoint
+            .condition
+            .as_ref()
+            .map(|condition| format!(" if {}", condition))
+            .unwrap_or_default();
+        let line = format!(
+            "{:<3} {:#010x}{}{}\n",
+            breakpoint.number,
+            breakpoint.address,
+            cpu.label(breakpoint.address),
+            condition
+        );
+        (breakpoint.number, line)
+    });
+    let watchpoints = cpu.watchpoints().iter().map(|watchpoint| {
+        let watch = match watchpoint.watch {
+            Watch::Read => "read",
+            Watch::Write => "write",
+            Watch::Access => "access",
+        };
+        let line = format!(
+            "{:<3} {:#010x}{} {} of {}\n",
+            watchpoint.number,
+            watchpoint.start,
+            cpu.label(watchpoint.start),
+            watch,
+            bytes(watchpoint.length)
+        );
+        (watchpoint.number, line)
+    });
+
+    let mut lines: Vec<(usize, String)> = breakpoints.chain(watchpoints).collect();
+    if lines.is_empty() {
+        return String::from("no breakpoints\n");
+    }
+    lines.sort_by_key(|&(number, _)| number);
+    lines.into_iter().map(|(_, line)| line).collect()
+}
+
+fn bytes(count: u32) -> String {
+    match count {
+        1 => String::from("1 byte"),
+        _ => format!("{} bytes", count),
+    }
+}
+
+// "0x00400008 <main+0x8>: lw $t0, 4($sp)", and where a branch goes
+fn location(cpu: &CPU, address: u32) -> String {
+    let word = cpu.memory.read(address);
+    let target = instructions::target(word, address)
+        .map(|target| cpu.label(target))
+        .unwrap_or_default();
+    format!(
+        "{:#010x}{}: {}{}\n",
+        address,
+        cpu.label(address),
+        instructions::disassemble(word, address),
+        target
+    )
+}
+
+fn registers(cpu: &CPU) -> String {
+    let mut values: Vec<(&str, u32)> = REGISTER_NAMES
+        .iter()
+        .enumerate()
+        .map(|(register, &name)| (name, cpu.read_register(register)))
+        .collect();
+    values.extend([("pc", cpu.pc()), ("hi", cpu.hi), ("lo", cpu.lo)]);
+
+    let mut output = String::new();
+    for row in values.chunks(4) {
+        let row: Vec<String> = row
+            .iter()
+            .map(|(name, value)| format!("{:>4} {:#010x}", name, value))
+            .collect();
+        output.push_str(&row.join("  "));
+        output.push('\n');
+    }
+    output
+}
+
+// `x/<n>w <address>`, four words to a line
+// The most words one x command prints, so a mistyped count can't build a
+// dump of all of memory
+const EXAMINE_LIMIT: u32 = 4096;
+
+fn examine(cpu: &CPU, command: &str, args: &[&str]) -> Result<String, String> {
+    let format = command.strip_prefix("x/").unwrap_or("");
+    let count = match format.strip_suffix('w').unwrap_or(format) {
+        "" => 1,
+        count => count
+            .parse::<u32>()
+            .map_err(|_| format!("invalid format '{}', expected x/<n>w", format))?,
+    };
+    if count > EXAMINE_LIMIT {
+        return Err(format!("at most {} words at a time", EXAMINE_LIMIT));
+    }
+    let [address] = args else {
+        return Err(String::from("usage: x/<n>w <address>"));
+    };
+    let start = parse_address(cpu, address)? & !3;
+
+    let mut output = String::new();
+    for first in (0..count).step_by(4) {
+        let address = start.wrapping_add(first * 4);
+        output.push_str(&format!("{:#010x}{}:", address, cpu.label(address)));
+        for index in first..count.min(first + 4) {
+            let value = cpu.memory.read(start.wrapping_add(index * 4));
+            output.push_str(&format!(" {:#010x}", value));
+        }
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+// Innermost first, from the calls the machine has seen
+fn backtrace(cpu: &CPU) -> String {
+    let pc = cpu.pc();
+    let mut output = format!("#0 {:#010x}{}\n", pc, cpu.label(pc));
+    for (depth, frame) in cpu.backtrace().iter().rev().enumerate() {
+        output.push_str(&format!(
+            "#{} {:#010x}{} called {:#010x}{}\n",
+            depth + 1,
+            frame.call_site,
+            cpu.label(frame.call_site),
+            frame.target,
+            cpu.label(frame.target)
+        ));
+    }
+    output
+}
+
+fn find_references(cpu: &CPU, args: &[&str]) -> Result<String, String> {
+    let (start, end) = match args {
+        [start] => {
+            let start = parse_address(cpu, start)?;
+            (start, start.wrapping_add(4))
+        }
+        [start, end] => (parse_address(cpu, start)?, parse_address(cpu, end)?),
+        _ => return Err(String::from("usage: refs <start> [end]")),
+    };
+
+    if start >= end {
+        return Err(format!("empty range {:#010x}..{:#010x}", start, end));
+    }
+
+    let mut output = String::new();
+    for (address, value) in cpu.memory.find_references(start, end) {
+        let region = cpu
+            .symbol_at(address)
+            .or_else(|| cpu.memory.region(address).map(|region| region.name.clone()))
+            .unwrap_or_else(|| String::from("?"));
+        output.push_str(&format!(
+            "{:#010x} <{}>: {:#010x} (+{})\n",
+            address,
+            region,
+            value,
+            value - start
+        ));
+    }
+
+    if output.is_empty() {
+        output = format!("no references into {:#010x}..{:#010x}\n", start, end);
+    }
+
+    Ok(output)
+}
+
+// Accepts hex (0x...), decimal, a register ($sp, $29) whose value is used, or
+// a symbol (main, driver::main)
+fn parse_address(cpu: &CPU, text: &str) -> Result<u32, String> {
+    if let Some(register) = text.strip_prefix('$') {
+        let index = match register.parse::<usize>() {
+            Ok(index) if index < 32 => index,
+            _ => REGISTER_NAMES
+                .iter()
+                .position(|&name| name == register || (name == "fp" && register == "s8"))
+                .ok_or(format!("unknown register '{}'", text))?,
+        };
+        return Ok(cpu.read_register(index));
+    }
+
+    if let Some(address) = parse_number(text) {
+        return Ok(address);
+    }
+
+    if text.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        return cpu.symbols().lookup(text).map_err(|error| error.to_string());
+    }
+
+    Err(format!("invalid address '{}'", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Debugger, Outcome};
+    use crate::assembler;
+    use crate::image::Symbol;
+    use crate::loader;
+    use crate::{Endian, ExitReason, CPU};
+
+    fn load_data(cpu: &mut CPU, words: &[u32]) {
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory.load_segment("data", 0x10010000, &bytes, false).unwrap();
+    }
+
+    // main calls twice, then exits
+    fn program() -> CPU {
+        let image = assembler::assemble(
+            r#"
+            .data
+            values: .word 3, 4
+            .text
+            main:   li   $a0, 5
+                    jal  twice
+                    jal  twice
+                    li   $v0, 10
+                    syscall
+            twice:  add  $v0, $a0, $a0
+                    jr   $ra
+            "#,
+            Endian::Little,
+        )
+        .unwrap();
+        let mut cpu = CPU::new();
+        loader::load_image(&mut cpu, &image, "prog").unwrap();
+        cpu
+    }
+
+    fn output(outcome: Outcome) -> String {
+        match outcome {
+            Outcome::Output(output) => output,
+            Outcome::Exit(reason) => panic!("unexpected exit {:?}", reason),
+        }
+    }
+
+    #[test]
+    fn test_refs_into_range() {
+        let mut cpu = CPU::new();
+        load_data(&mut cpu, &[0x10010008, 5, 0x1001000c, 0x7fffeff0]);
+        let mut debugger = Debugger::new();
+
+        let result = output(debugger.execute(&mut cpu, "refs 0x10010008 0x10010010"));
+
+        assert_eq!(
+            result,
+            "0x10010000 <data>: 0x10010008 (+0)\n0x10010008 <data>: 0x1001000c (+4)\n"
+        );
+    }
+
+    #[test]
+    fn test_refs_into_stack_frame_by_register() {
+        let mut cpu = CPU::new();
+        load_data(&mut cpu, &[0x7fffeff0]);
+        cpu.write_register(30, 0x7ffff000);
+        cpu.write_register(29, 0x7fffefe0);
+        let mut debugger = Debugger::new();
+
+        let result = output(debugger.execute(&mut cpu, "refs $sp $fp"));
+
+        assert_eq!(result, "0x10010000 <data>: 0x7fffeff0 (+16)\n");
+    }
+
+    #[test]
+    fn test_refs_by_symbol() {
+        let mut cpu = CPU::new();
+        load_data(&mut cpu, &[0x10010004, 0]);
+        cpu.symbols.add(
+            "prog",
+            &[Symbol {
+                name: String::from("table"),
+                address: 0x10010004,
+            }],
+        );
+        let mut debugger = Debugger::new();
+
+        let result = output(debugger.execute(&mut cpu, "refs prog::table"));
+
+        assert_eq!(result, "0x10010000 <data>: 0x10010004 (+0)\n");
+    }
+
+    #[test]
+    fn test_refs_none() {
+        let mut cpu = CPU::new();
+        let mut debugger = Debugger::new();
+
+        let result = output(debugger.execute(&mut cpu, "refs 0x20000000"));
+
+        assert_eq!(result, "no references into 0x20000000..0x20000004\n");
+    }
+
+    #[test]
+    fn test_refs_bad_register() {
+        let mut cpu = CPU::new();
+        let mut debugger = Debugger::new();
+
+        let result = output(debugger.execute(&mut cpu, "refs $xx"));
+
+        assert_eq!(result, "error: unknown register '$xx'\n");
+    }
+
+    #[test]
+    fn test_cp0() {
+        let mut cpu = CPU::new();
+        cpu.cp0.epc = 0x00400010;
+        let mut debugger = Debugger::new();
+
+        let result = output(debugger.execute(&mut cpu, "cp0"));
+
+        assert_eq!(
+            result,
+            "Status:   0x00000000\nCause:    0x00000000\nEPC:      0x00400010\nCount:    0x00000000\nCompare:  0x00000000\nBadVAddr: 0x00000000\n"
+        );
+    }
+
+    #[test]
+    fn test_continue_stops_at_break() {
+        let mut cpu = CPU::new();
+        // break / addi $v0, $zero, 10 / syscall
+        let bytes: Vec<u8> = [0x0000000d, 0x2002000a, 0x0000000c]
+            .iter()
+            .flat_map(|word: &u32| word.to_le_bytes())
+            .collect();
+        cpu.memory.load_segment("text", 0x00400000, &bytes, true).unwrap();
+        let mut debugger = Debugger::new();
+
+        let result = output(debugger.execute(&mut cpu, "continue"));
+        assert_eq!(result, "break 0 at 0x00400000\n");
+
+        match debugger.execute(&mut cpu, "continue") {
+            Outcome::Exit(reason) => assert_eq!(reason, ExitReason::Exited(0)),
+            Outcome::Output(output) => panic!("unexpected output {}", output),
+        }
+    }
+
+    #[test]
+    fn test_step_and_disas() {
+        let mut cpu = program();
+        let mut debugger = Debugger::new();
+
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "disas main 2")),
+            "=> 0x00400000 <main>: addiu $a0, $zero, 5\n   0x00400004 <main+0x4>: jal 0x0040001c <twice>\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "step")),
+            "0x00400004 <main+0x4>: jal 0x0040001c <twice>\n"
+        );
+        // The delay slot goes with its jump
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "step 2")),
+            "0x00400020 <twice+0x4>: jr $ra\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "bt")),
+            "#0 0x00400020 <twice+0x4>\n#1 0x00400004 <main+0x4> called 0x0040001c <twice>\n"
+        );
+        assert!(output(debugger.execute(&mut cpu, "step x")).starts_with("error:"));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut cpu = program();
+        let mut debugger = Debugger::new();
+
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "break twice")),
+            "breakpoint 1 at 0x0040001c <twice>\n"
+        );
+        output(debugger.execute(&mut cpu, "break 0x00400014"));
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "continue")),
+            "breakpoint 1, 0x0040001c <twice>: add $v0, $a0, $a0\n"
+        );
+        // Continuing leaves the breakpoint it stopped at
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "c")),
+            "breakpoint 1, 0x0040001c <twice>: add $v0, $a0, $a0\n"
+        );
+
+        output(debugger.execute(&mut cpu, "delete 1"));
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "info breakpoints")),
+            "2   0x00400014 <main+0x14>\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "delete 1")),
+            "error: no breakpoint 1\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "c")),
+            "breakpoint 2, 0x00400014 <main+0x14>: addiu $v0, $zero, 10\n"
+        );
+        assert_eq!(cpu.read_register(2), 10);
+
+        output(debugger.execute(&mut cpu, "delete"));
+        match debugger.execute(&mut cpu, "continue") {
+            Outcome::Exit(reason) => assert_eq!(reason, ExitReason::Exited(0)),
+            Outcome::Output(output) => panic!("unexpected output {}", output),
+        }
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut cpu = program();
+        let mut debugger = Debugger::new();
+
+        // Only the second call returns to 0x00400014
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "break twice if $ra == 0x400014")),
+            "breakpoint 1 at 0x0040001c <twice>\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "info breakpoints")),
+            "1   0x0040001c <twice> if $ra == 0x400014\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "continue")),
+            "breakpoint 1, 0x0040001c <twice>: add $v0, $a0, $a0\n"
+        );
+        assert_eq!(cpu.read_register(31), 0x00400014);
+
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "break main if $t0 ==")),
+            "error: unexpected end of condition\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "break main if")),
+            "error: usage: break <address|symbol> [if <condition>]\n"
+        );
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let image = assembler::assemble(
+            r#"
+            .data
+            counter: .word 0
+            .text
+            main:    la   $t0, counter
+                     lw   $t1, 0($t0)
+                     addi $t1, $t1, 1
+                     sw   $t1, 0($t0)
+                     li   $v0, 10
+                     syscall
+            "#,
+            Endian::Little,
+        )
+        .unwrap();
+        let mut cpu = CPU::new();
+        loader::load_image(&mut cpu, &image, "prog").unwrap();
+        let mut debugger = Debugger::new();
+
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "watch counter")),
+            "watchpoint 1 on 0x10010000 <counter>, 4 bytes\n"
+        );
+        output(debugger.execute(&mut cpu, "rwatch counter 1"));
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "info breakpoints")),
+            "1   0x10010000 <counter> write of 4 bytes\n\
+             2   0x10010000 <counter> read of 1 byte\n"
+        );
+
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "continue")),
+            "watchpoint 2, load of 0x0 from 0x10010000 <counter>\n  \
+             by 0x00400008 <main+0x8>: lw $t1, 0($t0)\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "continue")),
+            "watchpoint 1, store of 0x1 to 0x10010000 <counter>\n  \
+             by 0x00400010 <main+0x10>: sw $t1, 0($t0)\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "watch counter 0")),
+            "error: invalid length '0'\n"
+        );
+
+        match debugger.execute(&mut cpu, "continue") {
+            Outcome::Exit(reason) => assert_eq!(reason, ExitReason::Exited(0)),
+            Outcome::Output(output) => panic!("unexpected output {}", output),
+        }
+    }
+
+    #[test]
+    fn test_info_registers_and_examine() {
+        let mut cpu = program();
+        cpu.write_register(8, 0x1234);
+        let mut debugger = Debugger::new();
+
+        let registers = output(debugger.execute(&mut cpu, "info registers"));
+        assert_eq!(registers.lines().count(), 9);
+        assert!(registers
+            .lines()
+            .nth(2)
+            .unwrap()
+            .starts_with("  t0 0x00001234    t1 0x00000000"));
+
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "x/2w values")),
+            "0x10010000 <values>: 0x00000003 0x00000004\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "x 0x10010004")),
+            "0x10010004 <values+0x4>: 0x00000004\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "x/2b values")),
+            "error: invalid format '2b', expected x/<n>w\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "x/4000000000w values")),
+            "error: at most 4096 words at a time\n"
+        );
+        assert_eq!(
+            output(debugger.execute(&mut cpu, "x/5w values")).lines().count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,39 @@
+// A peripheral mapped into the guest's address space. Accesses arrive as
+// whole words at offsets from the start of the device's window; byte and
+// halfword stores come in shifted into their lane, with the other lanes
+// zero.
+pub trait Device {
+    fn read(&mut self, offset: u32) -> u32;
+    fn write(&mut self, offset: u32, value: u32);
+
+    // Runs once per instruction, for devices that change on their own
+    fn tick(&mut self) {}
+
+    // Runs after `tick`, for devices that read and write guest memory
+    // themselves
+    fn access_memory(&mut self, _bus: &mut dyn Bus) {}
+
+    // The Cause.IP bits (bit 0 for IP0) this device is holding raised
+    fn interrupts(&self) -> u32 {
+        0
+    }
+}
+
+// Guest memory as a device sees it: physical addresses, no device windows
+pub trait Bus {
+    fn read_u8(&self, address: u32) -> u8;
+    fn write_u8(&mut self, address: u32, byte: u8);
+}
+
+// A device and the addresses [start, end) it answers to
+pub struct Mapping {
+    pub start: u32,
+    pub end: u32,
+    pub device: Box<dyn Device>,
+}
+
+impl Mapping {
+    pub fn contains(&self, address: u32) -> bool {
+        address >= self.start && address < self.end
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::device::{Bus, Device};
+
+// A disk of 512-byte sectors kept in a host file. The guest picks a sector
+// and a buffer in memory, issues a command, and the whole sector moves
+// between the two on the next instruction.
+pub const BASE: u32 = 0xffff0040;
+pub const END: u32 = 0xffff0054;
+
+pub const SECTOR_SIZE: usize = 512;
+
+const SECTOR: u32 = 0x0;
+const BUFFER: u32 = 0x4;
+const COMMAND: u32 = 0x8;
+const STATUS: u32 = 0xc;
+// Read only: how many sectors the disk has
+const SECTORS: u32 = 0x10;
+
+const READ: u32 = 1;
+const WRITE: u32 = 2;
+
+// Status bits. Only INTERRUPT_ENABLE is writable; any write to the status
+// register acknowledges DONE and ERROR.
+const BUSY: u32 = 1;
+const INTERRUPT_ENABLE: u32 = 1 << 1;
+const DONE: u32 = 1 << 2;
+// The sector was past the end or the host file failed
+const ERROR: u32 = 1 << 3;
+
+// Cause bit 12 (IP4), hardware interrupt 2
+const INTERRUPT: u32 = 1 << 4;
+
+pub struct Disk<F> {
+    file: F,
+    sectors: u32,
+    sector: u32,
+    buffer: u32,
+    command: u32,
+    status: u32,
+}
+
+impl Disk<File> {
+    // Writes go straight back to the file
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Disk<File>> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Disk::new(file)
+    }
+}
+
+impl<F: Read + Write + Seek> Disk<F> {
+    // A trailing partial sector is left out
+    pub fn new(mut file: F) -> io::Result<Disk<F>> {
+        let size = file.seek(SeekFrom::End(0))?;
+        Ok(Disk {
+            file,
+            sectors: (size / SECTOR_SIZE as u64).min(u32::MAX as u64) as u32,
+            sector: 0,
+            buffer: 0,
+            command: 0,
+            status: 0,
+        })
+    }
+
+    fn seek(&mut self) -> io::Result<()> {
+        let offset = self.sector as u64 * SECTOR_SIZE as u64;
+        self.file.seek(SeekFrom::Start(offset)).map(|_| ())
+    }
+
+    fn read_sector(&mut self, bus: &mut dyn Bus) -> io::Result<()> {
+        let mut bytes = [0; SECTOR_SIZE];
+        self.seek()?;
+        self.file.read_exact(&mut bytes)?;
+        for (i, &byte) in bytes.iter().enumerate() {
+            bus.write_u8(self.buffer.wrapping_add(i as u32), byte);
+        }
+        Ok(())
+    }
+
+    fn write_sector(&mut self, bus: &mut dyn Bus) -> io::Result<()> {
+        let bytes: Vec<u8> = (0..SECTOR_SIZE as u32)
+            .map(|i| bus.read_u8(self.buffer.wrapping_add(i)))
+            .collect();
+        self.seek()?;
+        self.file.write_all(&bytes)?;
+        self.file.flush()
+    }
+}
+
+impl<F: Read + Write + Seek> Device for Disk<F> {
+    fn read(&mut self, offset: u32) -> u32 {
+        match offset {
+            SECTOR => self.sector,
+            BUFFER => self.buffer,
+            COMMAND => self.command,
+            STATUS => self.status,
+            SECTORS => self.sectors,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u32, value: u32) {
+        // Nothing changes under a command in flight
+        let busy = self.status & BUSY != 0;
+        match offset {
+            SECTOR if !busy => self.sector = value,
+            BUFFER if !busy => self.buffer = value,
+            COMMAND if !busy => {
+                self.command = value;
+                self.status = self.status & INTERRUPT_ENABLE | BUSY;
+            }
+            STATUS => self.status = self.status & BUSY | value & INTERRUPT_ENABLE,
+            _ => {}
+        }
+    }
+
+    fn access_memory(&mut self, bus: &mut dyn Bus) {
+        if self.status & BUSY == 0 {
+            return;
+        }
+
+        let result = match self.command {
+            _ if self.sector >= self.sectors => Err(io::ErrorKind::InvalidInput.into()),
+            READ => self.read_sector(bus),
+            WRITE => self.write_sector(bus),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        };
+
+        let outcome = if result.is_ok() { DONE } else { DONE | ERROR };
+        self.status = self.status & INTERRUPT_ENABLE | outcome;
+    }
+
+    fn interrupts(&self) -> u32 {
+        if self.status & (DONE | INTERRUPT_ENABLE) == DONE | INTERRUPT_ENABLE {
+            INTERRUPT
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Disk, BUFFER, COMMAND, SECTOR, SECTORS, SECTOR_SIZE, STATUS};
+    use crate::device::{Bus, Device};
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    #[derive(Default)]
+    struct Bytes(HashMap<u32, u8>);
+
+    impl Bus for Bytes {
+        fn read_u8(&self, address: u32) -> u8 {
+            self.0.get(&address).copied().unwrap_or(0)
+        }
+
+        fn write_u8(&mut self, address: u32, byte: u8) {
+            self.0.insert(address, byte);
+        }
+    }
+
+    // Two sectors, the second one filled with 0xab
+    fn disk() -> Disk<Cursor<Vec<u8>>> {
+        let mut image = vec![0; SECTOR_SIZE * 2];
+        image[SECTOR_SIZE..].fill(0xab);
+        Disk::new(Cursor::new(image)).unwrap()
+    }
+
+    #[test]
+    fn test_read_then_write_a_sector() {
+        let mut disk = disk();
+        let mut bus = Bytes::default();
+        assert_eq!(disk.read(SECTORS), 2);
+
+        disk.write(SECTOR, 1);
+        disk.write(BUFFER, 0x1000);
+        disk.write(COMMAND, super::READ);
+        assert_eq!(disk.read(STATUS), 0b1);
+        disk.access_memory(&mut bus);
+        assert_eq!(disk.read(STATUS), 0b100);
+        assert_eq!(bus.read_u8(0x1000), 0xab);
+        assert_eq!(bus.read_u8(0x11ff), 0xab);
+
+        bus.write_u8(0x1000, 0x42);
+        disk.write(SECTOR, 0);
+        disk.write(COMMAND, super::WRITE);
+        disk.access_memory(&mut bus);
+        assert_eq!(disk.read(STATUS), 0b100);
+        assert_eq!(disk.file.get_ref()[0], 0x42);
+        assert_eq!(disk.file.get_ref()[1], 0xab);
+    }
+
+    #[test]
+    fn test_past_the_end_is_an_error_and_interrupts() {
+        let mut disk = disk();
+        let mut bus = Bytes::default();
+        disk.write(STATUS, 0b10);
+        disk.write(SECTOR, 2);
+        disk.write(COMMAND, super::READ);
+        disk.access_memory(&mut bus);
+
+        assert_eq!(disk.read(STATUS), 0b1110);
+        assert_eq!(disk.interrupts(), super::INTERRUPT);
+        assert!(bus.0.is_empty());
+
+        // Acknowledged
+        disk.write(STATUS, 0b10);
+        assert_eq!(disk.read(STATUS), 0b10);
+        assert_eq!(disk.interrupts(), 0);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::device::{Bus, Device};
+
+// A memory-to-memory DMA controller: the guest sets source, destination
+// and length, then starts it and waits for the done bit or the interrupt
+pub const BASE: u32 = 0xffff0030;
+pub const END: u32 = 0xffff0040;
+
+// Source, destination and length count as the copy goes, so a driver can
+// watch its progress
+const SOURCE: u32 = 0x0;
+const DESTINATION: u32 = 0x4;
+const LENGTH: u32 = 0x8;
+const CONTROL: u32 = 0xc;
+
+// Control bits. Writing START begins a copy unless one is running; any
+// write to the control register acknowledges DONE.
+const START: u32 = 1;
+const BUSY: u32 = 1;
+const INTERRUPT_ENABLE: u32 = 1 << 1;
+const DONE: u32 = 1 << 2;
+
+// Bytes copied per instruction
+const BURST: u32 = 4;
+
+// Cause bit 11 (IP3), hardware interrupt 1
+const INTERRUPT: u32 = 1 << 3;
+
+#[derive(Default)]
+pub struct Dma {
+    source: u32,
+    destination: u32,
+    length: u32,
+    control: u32,
+}
+
+impl Dma {
+    pub fn new() -> Dma {
+        Dma::default()
+    }
+}
+
+impl Device for Dma {
+    fn read(&mut self, offset: u32) -> u32 {
+        match offset {
+            SOURCE => self.source,
+            DESTINATION => self.destination,
+            LENGTH => self.length,
+            CONTROL => self.control,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u32, value: u32) {
+        // The addresses and length stay put while a copy runs
+        let busy = self.control & BUSY != 0;
+        match offset {
+            SOURCE if !busy => self.source = value,
+            DESTINATION if !busy => self.destination = value,
+            LENGTH if !busy => self.length = value,
+            CONTROL => {
+                let start = if busy || value & START != 0 { BUSY } else { 0 };
+                self.control = start | value & INTERRUPT_ENABLE;
+            }
+            _ => {}
+        }
+    }
+
+    fn access_memory(&mut self, bus: &mut dyn Bus) {
+        if self.control & BUSY == 0 {
+            return;
+        }
+
+        for _ in 0..BURST.min(self.length) {
+            let byte = bus.read_u8(self.source);
+            bus.write_u8(self.destination, byte);
+            self.source = self.source.wrapping_add(1);
+            self.destination = self.destination.wrapping_add(1);
+            self.length -= 1;
+        }
+
+        if self.length == 0 {
+            self.control = self.control & !BUSY | DONE;
+        }
+    }
+
+    fn interrupts(&self) -> u32 {
+        if self.control & (DONE | INTERRUPT_ENABLE) == DONE | INTERRUPT_ENABLE {
+            INTERRUPT
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dma, CONTROL, DESTINATION, LENGTH, SOURCE};
+    use crate::device::{Bus, Device};
+    use crate::{ExitReason, CPU};
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct Bytes(HashMap<u32, u8>);
+
+    impl Bus for Bytes {
+        fn read_u8(&self, address: u32) -> u8 {
+            self.0.get(&address).copied().unwrap_or(0)
+        }
+
+        fn write_u8(&mut self, address: u32, byte: u8) {
+            self.0.insert(address, byte);
+        }
+    }
+
+    #[test]
+    fn test_copy_in_bursts_then_interrupt() {
+        let mut bus = Bytes::default();
+        for i in 0..6 {
+            bus.write_u8(0x100 + i, i as u8 + 1);
+        }
+
+        let mut dma = Dma::new();
+        dma.write(SOURCE, 0x100);
+        dma.write(DESTINATION, 0x200);
+        dma.write(LENGTH, 6);
+        dma.write(CONTROL, 0b11);
+
+        dma.access_memory(&mut bus);
+        assert_eq!(dma.read(CONTROL), 0b11);
+        assert_eq!(dma.read(LENGTH), 2);
+        assert_eq!(dma.interrupts(), 0);
+
+        dma.access_memory(&mut bus);
+        assert_eq!(dma.read(CONTROL), 0b110);
+        assert_eq!(dma.interrupts(), super::INTERRUPT);
+        let copied: Vec<u8> = (0..6).map(|i| bus.read_u8(0x200 + i)).collect();
+        assert_eq!(copied, [1, 2, 3, 4, 5, 6]);
+
+        // Acknowledged
+        dma.write(CONTROL, 0b10);
+        assert_eq!(dma.interrupts(), 0);
+    }
+
+    #[test]
+    fn test_guest_polls_for_completion() {
+        // lui $t0, 0xffff / lui $t1, 0x1001 / sw $t1, 0x30($t0) /
+        // addi $t1, $t1, 0x100 / sw $t1, 0x34($t0) / addi $t1, $zero, 8 /
+        // sw $t1, 0x38($t0) / addi $t1, $zero, 1 / sw $t1, 0x3c($t0) /
+        // poll: lw $t2, 0x3c($t0) / andi $t2, $t2, 1 / bne $t2, $zero, poll /
+        // nop / addi $v0, $zero, 10 / syscall
+        let text: [u32; 15] = [
+            0x3c08ffff, 0x3c091001, 0xad090030, 0x21290100, 0xad090034, 0x20090008, 0xad090038,
+            0x20090001, 0xad09003c, 0x8d0a003c, 0x314a0001, 0x1540fffd, 0, 0x2002000a, 0x0000000c,
+        ];
+        let mut cpu = CPU::new();
+        let bytes: Vec<u8> = text.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory
+            .load_segment("text", 0x00400000, &bytes, true)
+            .unwrap();
+        cpu.memory
+            .load_segment("data", 0x10010000, &[1, 2, 3, 4, 5, 6, 7, 8], false)
+            .unwrap();
+        cpu.attach(super::BASE, super::END, Box::new(Dma::new()))
+            .unwrap();
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.memory.read(0x10010100), 0x0403_0201);
+        assert_eq!(cpu.memory.read(0x10010104), 0x0807_0605);
+    }
+}
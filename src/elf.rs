@@ -0,0 +1,379 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::image::{Image, Segment, Symbol};
+use crate::Endian;
+
+const CLASS_32: u8 = 1;
+const DATA_LITTLE: u8 = 1;
+const DATA_BIG: u8 = 2;
+const TYPE_EXECUTABLE: u16 = 2;
+const MACHINE_MIPS: u16 = 8;
+
+const PROGRAM_LOAD: u32 = 1;
+const FLAG_EXECUTE: u32 = 1;
+
+const SECTION_SYMBOLS: u32 = 2;
+const SYMBOL_SIZE: usize = 16;
+const SYMBOL_SECTION: u8 = 3;
+const SYMBOL_FILE: u8 = 4;
+
+// Reads a statically linked ELF32 MIPS executable: every PT_LOAD segment
+// with its bss zero-filled, the entry point, the byte order and whatever
+// .symtab names. Section headers are only looked at for the symbols.
+pub fn parse(bytes: &[u8]) -> Result<Image, String> {
+    if !bytes.starts_with(b"\x7fELF") {
+        return Err(String::from("not an ELF file"));
+    }
+    let endian = match (bytes.get(4), bytes.get(5)) {
+        (Some(&CLASS_32), Some(&DATA_LITTLE)) => Endian::Little,
+        (Some(&CLASS_32), Some(&DATA_BIG)) => Endian::Big,
+        (Some(&CLASS_32), _) => return Err(String::from("unknown byte order")),
+        _ => return Err(String::from("not a 32-bit ELF file")),
+    };
+    let file = File { bytes, endian };
+
+    match file.u16(16)? {
+        TYPE_EXECUTABLE => {}
+        1 => {
+            return Err(String::from(
+                "a relocatable object, not an executable (link it first)",
+            ))
+        }
+        3 => {
+            return Err(String::from(
+                "a position-independent executable (link with -static -no-pie)",
+            ))
+        }
+        kind => return Err(format!("unsupported ELF type {}", kind)),
+    }
+    if file.u16(18)? != MACHINE_MIPS {
+        return Err(format!("not a MIPS executable (machine {})", file.u16(18)?));
+    }
+
+    let mut image = Image {
+        entry: file.u32(24)?,
+        endian: Some(endian),
+        ..Image::default()
+    };
+
+    let program_headers = file.u32(28)? as usize;
+    let program_header_size = file.u16(42)? as usize;
+    for index in 0..file.u16(44)? as usize {
+        let header = program_headers + index * program_header_size;
+        if file.u32(header)? != PROGRAM_LOAD {
+            continue;
+        }
+
+        let offset = file.u32(header + 4)? as usize;
+        let address = file.u32(header + 8)?;
+        let file_size = file.u32(header + 16)? as usize;
+        let memory_size = file.u32(header + 20)? as usize;
+        let executable = file.u32(header + 24)? & FLAG_EXECUTE != 0;
+        if memory_size < file_size {
+            return Err(format!(
+                "segment {} is smaller in memory than in the file",
+                index
+            ));
+        }
+        if address as u64 + memory_size as u64 > 1 << 32 {
+            return Err(format!(
+                "segment {} runs past the end of the address space",
+                index
+            ));
+        }
+
+        let mut contents = file.slice(offset, file_size)?.to_vec();
+        contents.resize(memory_size, 0);
+
+        let kind = if executable { "text" } else { "data" };
+        let name = if image.segments.iter().any(|segment| segment.name == kind) {
+            format!("{}{}", kind, index)
+        } else {
+            kind.to_string()
+        };
+        image.segments.push(Segment {
+            name,
+            address,
+            bytes: contents,
+            executable,
+        });
+    }
+    if image.segments.is_empty() {
+        return Err(String::from("no loadable segments"));
+    }
+
+    image.symbols = file.symbols()?;
+    Ok(image)
+}
+
+struct File<'a> {
+    bytes: &'a [u8],
+    endian: Endian,
+}
+
+impl<'a> File<'a> {
+    fn slice(&self, offset: usize, length: usize) -> Result<&'a [u8], String> {
+        offset
+            .checked_add(length)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or_else(|| String::from("truncated ELF file"))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, String> {
+        let bytes = self.slice(offset, 2)?;
+        Ok(match self.endian {
+            Endian::Little => LittleEndian::read_u16(bytes),
+            Endian::Big => BigEndian::read_u16(bytes),
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, String> {
+        let bytes = self.slice(offset, 4)?;
+        Ok(match self.endian {
+            Endian::Little => LittleEndian::read_u32(bytes),
+            Endian::Big => BigEndian::read_u32(bytes),
+        })
+    }
+
+    // The named, defined symbols of .symtab (none if it was stripped)
+    fn symbols(&self) -> Result<Vec<Symbol>, String> {
+        let sections = self.u32(32)? as usize;
+        let section_size = self.u16(46)? as usize;
+        let count = self.u16(48)? as usize;
+
+        let mut symbols = Vec::new();
+        for index in 0..count {
+            let header = sections + index * section_size;
+            if self.u32(header + 4)? != SECTION_SYMBOLS {
+                continue;
+            }
+
+            let table = self.slice(
+                self.u32(header + 16)? as usize,
+                self.u32(header + 20)? as usize,
+            )?;
+            let strings_header = sections + self.u32(header + 24)? as usize * section_size;
+            let strings = self.slice(
+                self.u32(strings_header + 16)? as usize,
+                self.u32(strings_header + 20)? as usize,
+            )?;
+
+            for entry in table.chunks_exact(SYMBOL_SIZE) {
+                let entry = File {
+                    bytes: entry,
+                    endian: self.endian,
+                };
+                let name = entry.u32(0)? as usize;
+                let kind = entry.bytes[12] & 0xf;
+                let section = entry.u16(14)? as u32;
+                if name == 0 || section == 0 || kind == SYMBOL_SECTION || kind == SYMBOL_FILE {
+                    continue;
+                }
+
+                let name = strings.get(name..).unwrap_or_default();
+                let name = &name[..name
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(name.len())];
+                symbols.push(Symbol {
+                    name: String::from_utf8_lossy(name).to_string(),
+                    address: entry.u32(4)?,
+                });
+            }
+        }
+        Ok(symbols)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::image::Symbol;
+    use crate::loader::{self, Format};
+    use crate::{Endian, ExitReason, CPU};
+
+    // An executable with one text and one data+bss segment, and a .symtab
+    // naming "main" and "counter"
+    fn executable(big: bool, text: &[u32], data: &[u8], bss: usize) -> Vec<u8> {
+        let half = |value: u16| {
+            if big {
+                value.to_be_bytes().to_vec()
+            } else {
+                value.to_le_bytes().to_vec()
+            }
+        };
+        let word = |value: u32| {
+            if big {
+                value.to_be_bytes().to_vec()
+            } else {
+                value.to_le_bytes().to_vec()
+            }
+        };
+
+        let code: Vec<u8> = text
+            .iter()
+            .flat_map(|&instruction| word(instruction))
+            .collect();
+        let strings = b"\0main\0counter\0".to_vec();
+        let mut symbols = vec![0; 16];
+        for (name, address, kind, section) in [(1, 0x00400000, 2, 1), (6, 0x10010000, 1, 2)] {
+            symbols.extend(word(name));
+            symbols.extend(word(address));
+            symbols.extend(word(0));
+            symbols.extend([0x10 | kind, 0]);
+            symbols.extend(half(section));
+        }
+
+        let code_offset = 52 + 2 * 32;
+        let data_offset = code_offset + code.len();
+        let symbols_offset = data_offset + data.len();
+        let strings_offset = symbols_offset + symbols.len();
+        let sections_offset = strings_offset + strings.len();
+
+        let mut file = b"\x7fELF\x01".to_vec();
+        file.extend([if big { 2 } else { 1 }, 1]);
+        file.resize(16, 0);
+        file.extend(half(2));
+        file.extend(half(8));
+        file.extend(word(1));
+        file.extend(word(0x00400000));
+        file.extend(word(52));
+        file.extend(word(sections_offset as u32));
+        file.extend(word(0));
+        file.extend(half(52));
+        file.extend(half(32));
+        file.extend(half(2));
+        file.extend(half(40));
+        file.extend(half(3));
+        file.extend(half(0));
+
+        for (offset, address, size, memory, flags) in [
+            (code_offset, 0x00400000, code.len(), code.len(), 5),
+            (data_offset, 0x10010000, data.len(), data.len() + bss, 6),
+        ] {
+            for value in [
+                1,
+                offset as u32,
+                address,
+                address,
+                size as u32,
+                memory as u32,
+                flags,
+                4,
+            ] {
+                file.extend(word(value));
+            }
+        }
+        file.extend(&code);
+        file.extend(data);
+        file.extend(&symbols);
+        file.extend(&strings);
+
+        file.extend(vec![0; 40]);
+        for (kind, offset, size, link) in [
+            (2, symbols_offset, symbols.len(), 2),
+            (3, strings_offset, strings.len(), 0),
+        ] {
+            for value in [0, kind, 0, 0, offset as u32, size as u32, link, 0, 4, 0] {
+                file.extend(word(value));
+            }
+        }
+        file
+    }
+
+    #[test]
+    fn test_parse_little_endian() {
+        let image = parse(&executable(false, &[0x2002000a, 0x0000000c], b"abc", 5)).unwrap();
+
+        assert_eq!(image.entry, 0x00400000);
+        assert_eq!(image.endian, Some(Endian::Little));
+        assert_eq!(image.segments[0].name, "text");
+        assert!(image.segments[0].executable);
+        assert_eq!(
+            image.segments[0].bytes,
+            [0x0a, 0x00, 0x02, 0x20, 0x0c, 0, 0, 0]
+        );
+        assert_eq!(image.segments[1].name, "data");
+        assert_eq!(image.segments[1].bytes, b"abc\0\0\0\0\0");
+        assert_eq!(
+            image.symbols,
+            [
+                Symbol {
+                    name: String::from("main"),
+                    address: 0x00400000
+                },
+                Symbol {
+                    name: String::from("counter"),
+                    address: 0x10010000
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_big_endian() {
+        let image = parse(&executable(true, &[0x2002000a], b"", 4)).unwrap();
+
+        assert_eq!(image.endian, Some(Endian::Big));
+        assert_eq!(image.segments[0].bytes, [0x20, 0x02, 0x00, 0x0a]);
+        assert_eq!(image.segments[1].bytes, [0; 4]);
+        assert_eq!(image.symbols.len(), 2);
+    }
+
+    #[test]
+    fn test_load_runs_big_endian() {
+        // lui $at, 0x1001 / lw $t0, 0($at) / sw $t0, 4($at) / addi $v0, $zero, 10 / syscall
+        let text = [0x3c011001, 0x8c280000, 0xac280004, 0x2002000a, 0x0000000c];
+        let path = std::env::temp_dir().join("rustinmips_test_elf_big_endian");
+        std::fs::write(&path, executable(true, &text, b"abcd", 4)).unwrap();
+        let path = path.to_str().unwrap();
+
+        let mut cpu = CPU::new();
+        assert_eq!(loader::detect(path).unwrap(), Format::Elf);
+        loader::load(&mut cpu, path, Format::Elf).unwrap();
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(8), 0x61626364);
+        assert_eq!(cpu.memory.read_u8(0x10010004), b'a');
+        assert_eq!(cpu.symbols.lookup("counter").unwrap(), 0x10010000);
+    }
+
+    #[test]
+    fn test_parse_rejects() {
+        let mut object = executable(false, &[0], b"", 0);
+        object[16] = 1;
+        assert_eq!(
+            parse(&object).unwrap_err(),
+            "a relocatable object, not an executable (link it first)"
+        );
+
+        let mut x86 = executable(false, &[0], b"", 0);
+        x86[18] = 3;
+        assert_eq!(
+            parse(&x86).unwrap_err(),
+            "not a MIPS executable (machine 3)"
+        );
+
+        let mut wide = executable(false, &[0], b"", 0);
+        wide[4] = 2;
+        assert_eq!(parse(&wide).unwrap_err(), "not a 32-bit ELF file");
+
+        let file = executable(false, &[0], b"", 0);
+        assert_eq!(parse(&file[..60]).unwrap_err(), "truncated ELF file");
+
+        // The data segment's p_memsz
+        let mut short = executable(false, &[0], b"abcd", 0);
+        short[104..108].copy_from_slice(&2u32.to_le_bytes());
+        assert_eq!(
+            parse(&short).unwrap_err(),
+            "segment 1 is smaller in memory than in the file"
+        );
+
+        let mut huge = executable(false, &[0], b"", 0);
+        huge[104..108].copy_from_slice(&0xfff00000u32.to_le_bytes());
+        assert_eq!(
+            parse(&huge).unwrap_err(),
+            "segment 1 runs past the end of the address space"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::checks;
+use crate::error::Error;
+
+// Instruction classes that an energy model prices differently, in the order
+// of the per-class arrays below
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Alu,
+    Shift,
+    MulDiv,
+    Branch,
+    Load,
+    Store,
+    Syscall,
+}
+
+impl Class {
+    pub const ALL: [Class; 7] = [
+        Class::Alu,
+        Class::Shift,
+        Class::MulDiv,
+        Class::Branch,
+        Class::Load,
+        Class::Store,
+        Class::Syscall,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Alu => "alu",
+            Class::Shift => "shift",
+            Class::MulDiv => "muldiv",
+            Class::Branch => "branch",
+            Class::Load => "load",
+            Class::Store => "store",
+            Class::Syscall => "syscall",
+        }
+    }
+
+    pub fn of(word: u32) -> Class {
+        let opcode = word >> 26;
+        let funct = word & 0b111111;
+
+        match opcode {
+            0 => match funct {
+                0x00..=0x07 => Class::Shift,
+                0x0c | 0x0d => Class::Syscall,
+                0x10..=0x1b => Class::MulDiv,
+                _ if checks::is_control_transfer(word) => Class::Branch,
+                _ => Class::Alu,
+            },
+            // SPECIAL2: CLZ, CLO count bits; the rest multiply
+            0x1c if funct == 0x20 || funct == 0x21 => Class::Alu,
+            0x1c => Class::MulDiv,
+            _ if checks::is_control_transfer(word) => Class::Branch,
+            _ if checks::is_load(word) => Class::Load,
+            // LL, LWC1
+            0x30 | 0x31 => Class::Load,
+            0x28..=0x2e | 0x38 | 0x39 => Class::Store,
+            _ => Class::Alu,
+        }
+    }
+}
+
+// Energy per event, in picojoules. Every instruction pays for its fetch and
+// its class; loads and stores also pay for the data memory access.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyModel {
+    pub class: [f64; 7],
+    pub fetch: f64,
+    pub memory_read: f64,
+    pub memory_write: f64,
+}
+
+impl Default for EnergyModel {
+    // Rough figures for a small in-order core with on-chip SRAM
+    fn default() -> Self {
+        EnergyModel {
+            class: [1.0, 1.0, 4.0, 1.5, 1.0, 1.0, 2.0],
+            fetch: 2.5,
+            memory_read: 5.0,
+            memory_write: 6.0,
+        }
+    }
+}
+
+impl EnergyModel {
+    // Overrides defaults from "name=picojoules" pairs, e.g.
+    // "load=3,memory_read=20"; names are the classes plus fetch,
+    // memory_read and memory_write
+    pub fn parse(spec: &str) -> Result<EnergyModel, Error> {
+        let mut model = EnergyModel::default();
+
+        for pair in spec.split(',').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').ok_or(Error::Parse(format!(
+                "invalid energy weight '{}' (expected name=pJ)",
+                pair
+            )))?;
+            let value: f64 = value.parse().map_err(|_| {
+                Error::Parse(format!("invalid energy for '{}': '{}'", name, value))
+            })?;
+
+            let weight = match name {
+                "fetch" => &mut model.fetch,
+                "memory_read" => &mut model.memory_read,
+                "memory_write" => &mut model.memory_write,
+                _ => match Class::ALL.iter().position(|class| class.name() == name) {
+                    Some(index) => &mut model.class[index],
+                    None => {
+                        return Err(Error::Parse(format!("unknown energy weight '{}'", name)))
+                    }
+                },
+            };
+            *weight = value;
+        }
+
+        Ok(model)
+    }
+}
+
+// Event counts of one run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnergyMeter {
+    pub class: [u64; 7],
+}
+
+impl EnergyMeter {
+    pub fn record(&mut self, word: u32) {
+        self.class[Class::of(word) as usize] += 1;
+    }
+
+    pub fn count(&self, class: Class) -> u64 {
+        self.class[class as usize]
+    }
+
+    // Per-line breakdown in nanojoules, total last
+    pub fn report(&self, model: &EnergyModel) -> String {
+        let fetches: u64 = self.class.iter().sum();
+        let mut lines = Vec::new();
+        let mut total = 0.0;
+
+        let mut line = |name: &str, count: u64, weight: f64| {
+            let energy = count as f64 * weight / 1000.0;
+            total += energy;
+            if count > 0 {
+                lines.push(format!(
+                    "  {:<13}{:>10} x {:>6.2} pJ = {:>10.3} nJ",
+                    name, count, weight, energy
+                ));
+            }
+        };
+
+        line("fetch", fetches, model.fetch);
+        for (index, class) in Class::ALL.iter().enumerate() {
+            line(class.name(), self.class[index], model.class[index]);
+        }
+        line("memory_read", self.count(Class::Load), model.memory_read);
+        line("memory_write", self.count(Class::Store), model.memory_write);
+
+        lines.push(format!("  {:<13}{:>37.3} nJ", "total", total));
+        lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Class, EnergyMeter, EnergyModel};
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(Class::of(0x20080001), Class::Alu); // addi
+        assert_eq!(Class::of(0x00084080), Class::Shift); // sll
+        assert_eq!(Class::of(0x0100001a), Class::MulDiv); // div
+        assert_eq!(Class::of(0x03e00008), Class::Branch); // jr
+        assert_eq!(Class::of(0x8f880000), Class::Load); // lw
+        assert_eq!(Class::of(0xaf880000), Class::Store); // sw
+        assert_eq!(Class::of(0x0000000c), Class::Syscall);
+    }
+
+    #[test]
+    fn test_parse_weights() {
+        let model = EnergyModel::parse("load=3,memory_read=20").unwrap();
+        assert_eq!(model.class[4], 3.0);
+        assert_eq!(model.memory_read, 20.0);
+        assert_eq!(model.fetch, EnergyModel::default().fetch);
+
+        assert_eq!(
+            EnergyModel::parse("flux=1").unwrap_err().to_string(),
+            "unknown energy weight 'flux'"
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let mut meter = EnergyMeter::default();
+        meter.record(0x8f880000);
+        meter.record(0x20080001);
+        let model = EnergyModel::parse("fetch=1000,alu=1000,load=1000,memory_read=1000").unwrap();
+
+        let report = meter.report(&model);
+
+        let total = report.lines().last().unwrap();
+        assert!(total.starts_with("  total"));
+        assert!(total.ends_with(" 5.000 nJ"));
+    }
+}
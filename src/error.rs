@@ -0,0 +1,107 @@
+use std::fmt;
+use std::io;
+
+// What the library's fallible calls fail with, for hosts to match on. A guest
+// that misbehaves isn't an error: its run ends with an ExitReason instead.
+#[derive(Debug)]
+pub enum Error {
+    // A host file couldn't be read or written
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    // Neither the program nor a split dump next to it exists
+    NotFound {
+        path: String,
+        stem: String,
+    },
+    // A program file that isn't valid in its format
+    Malformed {
+        path: String,
+        reason: String,
+    },
+    // A rustinmips container that doesn't parse
+    Container(String),
+    // A segment would land on memory already loaded
+    Overlap {
+        namespace: String,
+        segment: String,
+        address: u32,
+        region: String,
+    },
+    // A segment that would run past the top of the address space
+    PastEnd {
+        segment: String,
+        address: u32,
+    },
+    // A device window over another one
+    DeviceOverlap {
+        start: u32,
+        end: u32,
+    },
+    // A fault, energy model or log that doesn't parse
+    Parse(String),
+    // Not defined, or defined by more than one image
+    Symbol(String),
+    // A saved machine state that doesn't fit this machine
+    State(String),
+    // The instruction trace couldn't be written, so the run stopped
+    Trace(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: &str, source: io::Error) -> Error {
+        Error::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path, source),
+            Error::NotFound { path, stem } => {
+                write!(f, "{}: no such file (nor {}.text)", path, stem)
+            }
+            Error::Malformed { path, reason } => write!(f, "{}: {}", path, reason),
+            Error::Container(reason)
+            | Error::Parse(reason)
+            | Error::Symbol(reason)
+            | Error::State(reason) => {
+                write!(f, "{}", reason)
+            }
+            Error::Overlap {
+                namespace,
+                segment,
+                address,
+                region,
+            } => write!(
+                f,
+                "{}: segment '{}' at {:#010x} overlaps region '{}'",
+                namespace, segment, address, region
+            ),
+            Error::PastEnd { segment, address } => write!(
+                f,
+                "segment '{}' at {:#010x} runs past the end of the address space",
+                segment, address
+            ),
+            Error::DeviceOverlap { start, end } => {
+                write!(f, "{:#010x}-{:#010x} overlaps another device", start, end)
+            }
+            Error::Trace(source) => write!(f, "cannot write the trace: {}", source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } | Error::Trace(source) => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::instructions;
+use crate::parse_number;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Skip,
+    Duplicate,
+    // XOR the instruction word with this mask before decoding it
+    Corrupt(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    // The nth execution (from 1) of any instruction with this mnemonic
+    Instruction { name: String, nth: u64 },
+    // The nth execution (from 1) of whatever sits at this address
+    Address { pc: u32, nth: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fault {
+    pub action: Action,
+    pub trigger: Trigger,
+}
+
+impl Fault {
+    // <action>:<where>[#n], e.g. "skip:0x00400010", "dup:addi#3",
+    // "corrupt=0x20:lw#2"
+    pub fn parse(spec: &str) -> Result<Fault, Error> {
+        let invalid = || {
+            Error::Parse(format!(
+                "invalid fault '{}' (expected <action>:<where>[#n])",
+                spec
+            ))
+        };
+
+        let (action, place) = spec.split_once(':').ok_or_else(invalid)?;
+        let action = match action.split_once('=') {
+            None if action == "skip" => Action::Skip,
+            None if action == "dup" => Action::Duplicate,
+            Some(("corrupt", mask)) => Action::Corrupt(parse_number(mask).ok_or_else(invalid)?),
+            _ => return Err(invalid()),
+        };
+
+        let (place, nth) = match place.split_once('#') {
+            Some((place, nth)) => (
+                place,
+                nth.parse()
+                    .ok()
+                    .filter(|&nth| nth > 0)
+                    .ok_or_else(invalid)?,
+            ),
+            None => (place, 1),
+        };
+        let trigger = match parse_number(place) {
+            Some(pc) if place.starts_with("0x") => Trigger::Address { pc, nth },
+            _ if !place.is_empty() => Trigger::Instruction {
+                name: place.to_uppercase(),
+                nth,
+            },
+            _ => return Err(invalid()),
+        };
+
+        Ok(Fault { action, trigger })
+    }
+}
+
+// One fault that fired, enough to reproduce the run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Injection {
+    // Instructions executed before this one
+    pub executed: u64,
+    pub pc: u32,
+    pub word: u32,
+    pub action: Action,
+}
+
+impl std::fmt::Display for Injection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = instructions::get_instruction(self.word).name().to_string();
+        write!(
+            f,
+            "#{} {:#010x} {:#010x} {}: ",
+            self.executed, self.pc, self.word, name
+        )?;
+        match self.action {
+            Action::Skip => write!(f, "skipped"),
+            Action::Duplicate => write!(f, "duplicated"),
+            Action::Corrupt(mask) => write!(
+                f,
+                "corrupted to {:#010x} ({})",
+                self.word ^ mask,
+                instructions::get_instruction(self.word ^ mask).name()
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct FaultInjector {
+    faults: Vec<Fault>,
+    by_name: HashMap<String, u64>,
+    by_address: HashMap<u32, u64>,
+    log: Vec<Injection>,
+}
+
+impl FaultInjector {
+    pub fn new(faults: Vec<Fault>) -> FaultInjector {
+        FaultInjector {
+            faults,
+            ..FaultInjector::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faults.is_empty()
+    }
+
+    pub fn log(&self) -> &[Injection] {
+        &self.log
+    }
+
+    // Counts this execution and returns the fault to inject into it, if any
+    pub fn check(&mut self, executed: u64, pc: u32, word: u32, name: &str) -> Option<Action> {
+        let by_name = self.by_name.entry(name.to_string()).or_insert(0);
+        *by_name += 1;
+        let by_name = *by_name;
+        let by_address = self.by_address.entry(pc).or_insert(0);
+        *by_address += 1;
+        let by_address = *by_address;
+
+        let fault = self.faults.iter().find(|fault| match &fault.trigger {
+            Trigger::Instruction { name: wanted, nth } => wanted == name && *nth == by_name,
+            Trigger::Address { pc: wanted, nth } => *wanted == pc && *nth == by_address,
+        })?;
+
+        self.log.push(Injection {
+            executed,
+            pc,
+            word,
+            action: fault.action,
+        });
+        Some(fault.action)
+    }
+
+    pub fn reset(&mut self) {
+        self.by_name.clear();
+        self.by_address.clear();
+        self.log.clear();
+    }
+}
+
+// A bit flipped in data memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flip {
+    // Instructions executed when it happened
+    pub cycle: u64,
+    pub address: u32,
+    pub bit: u32,
+    pub before: u32,
+}
+
+impl std::fmt::Display for Flip {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "cycle {} {:#010x} bit {}: {:#010x} -> {:#010x}",
+            self.cycle,
+            self.address,
+            self.bit,
+            self.before,
+            self.before ^ 1 << self.bit
+        )
+    }
+}
+
+// Flips random bits of data memory, either with a probability per executed
+// instruction or at given cycles. The same seed gives the same flips.
+#[derive(Debug, Clone, Default)]
+pub struct BitFlipper {
+    pub seed: u64,
+    pub rate: f64,
+    pub cycles: Vec<u64>,
+    state: u64,
+    log: Vec<Flip>,
+}
+
+impl BitFlipper {
+    pub fn new(seed: u64, rate: f64, cycles: Vec<u64>) -> BitFlipper {
+        let mut flipper = BitFlipper {
+            seed,
+            rate,
+            cycles,
+            ..BitFlipper::default()
+        };
+        flipper.reset();
+        flipper
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.rate > 0.0 || !self.cycles.is_empty()
+    }
+
+    pub fn log(&self) -> &[Flip] {
+        &self.log
+    }
+
+    pub fn reset(&mut self) {
+        // xorshift must not start from zero
+        self.state = self.seed ^ 0x9e37_79b9_7f4a_7c15;
+        self.log.clear();
+    }
+
+    // Whether a flip is due after `cycle` instructions
+    pub fn is_due(&mut self, cycle: u64) -> bool {
+        let scheduled = self.cycles.contains(&cycle);
+        // 53 random bits make a uniform float in [0, 1)
+        let sample = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        let random = self.rate > 0.0 && sample < self.rate;
+        scheduled || random
+    }
+
+    // Picks one of `words` (address, value) and a bit in it, and records the
+    // flip; returns the address and its new value
+    pub fn flip(&mut self, cycle: u64, words: &[(u32, u32)]) -> Option<(u32, u32)> {
+        if words.is_empty() {
+            return None;
+        }
+
+        let (address, before) = words[(self.next() % words.len() as u64) as usize];
+        let bit = (self.next() % 32) as u32;
+        self.log.push(Flip {
+            cycle,
+            address,
+            bit,
+            before,
+        });
+        Some((address, before ^ 1 << bit))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, BitFlipper, Fault, FaultInjector, Trigger};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Fault::parse("skip:0x00400010").unwrap(),
+            Fault {
+                action: Action::Skip,
+                trigger: Trigger::Address {
+                    pc: 0x00400010,
+                    nth: 1
+                },
+            }
+        );
+        assert_eq!(
+            Fault::parse("corrupt=0x20:lw#2").unwrap(),
+            Fault {
+                action: Action::Corrupt(0x20),
+                trigger: Trigger::Instruction {
+                    name: String::from("LW"),
+                    nth: 2
+                },
+            }
+        );
+        assert!(Fault::parse("explode:addi").is_err());
+        assert!(Fault::parse("dup:addi#0").is_err());
+    }
+
+    #[test]
+    fn test_bit_flips_are_reproducible() {
+        let words = [(0x10010000, 0), (0x10010004, 0xffff_ffff)];
+        let flips = |seed| {
+            let mut flipper = BitFlipper::new(seed, 0.5, vec![]);
+            for cycle in 0..20 {
+                if flipper.is_due(cycle) {
+                    flipper.flip(cycle, &words);
+                }
+            }
+            flipper.log().to_vec()
+        };
+
+        assert!(!flips(1).is_empty());
+        assert_eq!(flips(1), flips(1));
+        assert_ne!(flips(1), flips(2));
+    }
+
+    #[test]
+    fn test_bit_flip_at_cycle() {
+        let mut flipper = BitFlipper::new(7, 0.0, vec![3]);
+
+        assert!(!flipper.is_due(2));
+        assert!(flipper.is_due(3));
+
+        let (address, value) = flipper.flip(3, &[(0x10010000, 0)]).unwrap();
+        assert_eq!(address, 0x10010000);
+        assert_eq!(value.count_ones(), 1);
+        assert_eq!(flipper.log()[0].cycle, 3);
+    }
+
+    #[test]
+    fn test_nth_instance() {
+        let mut injector = FaultInjector::new(vec![Fault::parse("dup:addi#2").unwrap()]);
+
+        assert_eq!(injector.check(0, 0x00400000, 0x20080001, "ADDI"), None);
+        assert_eq!(injector.check(1, 0x00400004, 0x01004821, "ADDU"), None);
+        assert_eq!(
+            injector.check(2, 0x00400008, 0x20080001, "ADDI"),
+            Some(Action::Duplicate)
+        );
+        assert_eq!(injector.check(3, 0x0040000c, 0x20080001, "ADDI"), None);
+
+        assert_eq!(
+            injector.log()[0].to_string(),
+            "#2 0x00400008 0x20080001 ADDI: duplicated"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+// MARS open flags
+const READ_ONLY: u32 = 0;
+const WRITE_ONLY: u32 = 1;
+const APPEND: u32 = 9;
+
+// Descriptors 0 to 2 are the console
+const FIRST_FILE: u32 = 3;
+
+// Host files the guest opened with the file syscalls, by descriptor
+#[derive(Default)]
+pub struct FileTable {
+    files: HashMap<u32, File>,
+    // When set, guest paths are relative to this directory and may not
+    // leave it
+    root: Option<PathBuf>,
+}
+
+impl FileTable {
+    pub fn new() -> FileTable {
+        FileTable::default()
+    }
+
+    pub fn set_root(&mut self, root: PathBuf) {
+        self.root = Some(root);
+    }
+
+    // No files open, under the same sandbox
+    pub fn fork(&self) -> FileTable {
+        FileTable {
+            files: HashMap::new(),
+            root: self.root.clone(),
+        }
+    }
+
+    // The host path for a guest path, or None if the sandbox forbids it.
+    // Absolute paths and ".." are refused outright; symlinks are caught by
+    // checking where the file's directory really is.
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let Some(root) = &self.root else {
+            return Some(PathBuf::from(path));
+        };
+
+        let path = Path::new(path);
+        let plain = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !plain || path.file_name().is_none() {
+            return None;
+        }
+
+        let resolved = root.join(path);
+        let root = root.canonicalize().ok()?;
+        let directory = resolved.parent()?.canonicalize().ok()?;
+        if !directory.starts_with(&root) {
+            return None;
+        }
+        if let Ok(target) = resolved.canonicalize() {
+            if !target.starts_with(&root) {
+                return None;
+            }
+        }
+        Some(resolved)
+    }
+
+    // Syscall 13: flags 0 reads, 1 writes (creating or truncating) and 9
+    // appends (creating)
+    pub fn open(&mut self, path: &str, flags: u32) -> io::Result<u32> {
+        let path = self
+            .resolve(path)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::PermissionDenied))?;
+
+        let mut options = OpenOptions::new();
+        match flags {
+            READ_ONLY => options.read(true),
+            WRITE_ONLY => options.write(true).create(true).truncate(true),
+            APPEND => options.append(true).create(true),
+            _ => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+        let file = options.open(path)?;
+
+        let fd = (FIRST_FILE..)
+            .find(|fd| !self.files.contains_key(fd))
+            .unwrap();
+        self.files.insert(fd, file);
+        Ok(fd)
+    }
+
+    // Up to `max` bytes; fewer only at the end of the file
+    pub fn read(&mut self, fd: u32, max: u32) -> io::Result<Vec<u8>> {
+        let file = self.file(fd)?;
+        let mut bytes = Vec::new();
+        file.take(max as u64).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn write(&mut self, fd: u32, bytes: &[u8]) -> io::Result<usize> {
+        let file = self.file(fd)?;
+        file.write_all(bytes)?;
+        Ok(bytes.len())
+    }
+
+    pub fn close(&mut self, fd: u32) -> bool {
+        self.files.remove(&fd).is_some()
+    }
+
+    pub fn close_all(&mut self) {
+        self.files.clear();
+    }
+
+    fn file(&mut self, fd: u32) -> io::Result<&mut File> {
+        self.files
+            .get_mut(&fd)
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileTable;
+    use std::path::PathBuf;
+
+    // A fresh directory under the system temp dir
+    fn scratch(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("rustinmips-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn test_write_append_then_read() {
+        let directory = scratch("files");
+        let path = directory.join("out.txt");
+        let path = path.to_str().unwrap();
+        let mut files = FileTable::new();
+
+        let fd = files.open(path, 1).unwrap();
+        assert_eq!(fd, 3);
+        assert_eq!(files.write(fd, b"abc").unwrap(), 3);
+        assert!(files.close(fd));
+
+        let fd = files.open(path, 9).unwrap();
+        files.write(fd, b"def").unwrap();
+        files.close(fd);
+
+        let fd = files.open(path, 0).unwrap();
+        assert_eq!(files.read(fd, 4).unwrap(), b"abcd");
+        assert_eq!(files.read(fd, 4).unwrap(), b"ef");
+        assert_eq!(files.read(fd, 4).unwrap(), b"");
+        assert!(files.close(fd));
+        assert!(!files.close(fd));
+        assert!(files.read(fd, 4).is_err());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let directory = scratch("sandbox");
+        std::fs::create_dir(directory.join("inside")).unwrap();
+        let mut files = FileTable::new();
+        files.set_root(directory.join("inside"));
+
+        assert_eq!(files.open("data.txt", 1).unwrap(), 3);
+        assert!(directory.join("inside/data.txt").exists());
+        assert!(files.open("../escape.txt", 1).is_err());
+        assert!(files
+            .open(directory.join("x").to_str().unwrap(), 1)
+            .is_err());
+        assert!(files.open("missing/file.txt", 1).is_err());
+        assert!(!directory.join("escape.txt").exists());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+use crate::CPU;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Load,
+    Store,
+}
+
+// A load or store by the guest, after it went through: the address is the
+// one the program used, before translation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    pub address: u32,
+    // 1, 2 or 4 bytes
+    pub size: u32,
+    pub value: u32,
+}
+
+pub type InstructionHook = Box<dyn FnMut(&CPU, u32, u32)>;
+pub type MemoryHook = Box<dyn FnMut(&CPU, MemoryAccess)>;
+pub type RegisterHook = Box<dyn FnMut(&CPU, usize, u32)>;
+
+// Callbacks for tools that watch a run (profilers, tracers, taint
+// trackers). They see the machine but can't change it.
+pub enum Hook {
+    // The pc and word of an instruction about to run
+    PreInstruction(InstructionHook),
+    // The same, once it ran
+    PostInstruction(InstructionHook),
+    OnMemoryAccess(MemoryHook),
+    // A general purpose register and its new value, whoever wrote it
+    OnRegisterWrite(RegisterHook),
+}
+
+pub(crate) enum Event {
+    PreInstruction(u32, u32),
+    PostInstruction(u32, u32),
+    MemoryAccess(MemoryAccess),
+    RegisterWrite(usize, u32),
+}
+
+impl Hook {
+    pub(crate) fn notify(&mut self, cpu: &CPU, event: &Event) {
+        match (self, event) {
+            (Hook::PreInstruction(hook), &Event::PreInstruction(pc, word)) => hook(cpu, pc, word),
+            (Hook::PostInstruction(hook), &Event::PostInstruction(pc, word)) => hook(cpu, pc, word),
+            (Hook::OnMemoryAccess(hook), &Event::MemoryAccess(access)) => hook(cpu, access),
+            (Hook::OnRegisterWrite(hook), &Event::RegisterWrite(register, value)) => {
+                hook(cpu, register, value)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccessKind, Hook, MemoryAccess};
+    use crate::{ExitReason, CPU};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_hooks() {
+        // addi $t0, $zero, 5 / sw $t0, 4($gp) / lb $t1, 4($gp) / addi $v0, $zero, 10 /
+        // syscall
+        let text: [u32; 5] = [0x20080005, 0xaf880004, 0x83890004, 0x2002000a, 0x0000000c];
+        let mut cpu = CPU::new();
+        let bytes: Vec<u8> = text.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory
+            .load_segment("text", 0x00400000, &bytes, true)
+            .unwrap();
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let log = events.clone();
+        cpu.add_hook(Hook::PreInstruction(Box::new(move |cpu, pc, word| {
+            assert_eq!(cpu.pc(), pc);
+            log.borrow_mut()
+                .push(format!("pre {:#x} {:#010x}", pc, word));
+        })));
+        let log = events.clone();
+        cpu.add_hook(Hook::OnRegisterWrite(Box::new(
+            move |_, register, value| {
+                log.borrow_mut().push(format!("${} = {}", register, value));
+            },
+        )));
+        let accesses = Rc::new(RefCell::new(Vec::new()));
+        let log = accesses.clone();
+        cpu.add_hook(Hook::OnMemoryAccess(Box::new(move |_, access| {
+            log.borrow_mut().push(access);
+        })));
+        let retired = Rc::new(RefCell::new(0));
+        let count = retired.clone();
+        cpu.add_hook(Hook::PostInstruction(Box::new(move |_, _, _| {
+            *count.borrow_mut() += 1;
+        })));
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(
+            events.borrow()[..3],
+            [
+                "pre 0x400000 0x20080005",
+                "$8 = 5",
+                "pre 0x400004 0xaf880004"
+            ]
+        );
+        assert_eq!(
+            *accesses.borrow(),
+            [
+                MemoryAccess {
+                    kind: AccessKind::Store,
+                    address: 0x10008004,
+                    size: 4,
+                    value: 5
+                },
+                MemoryAccess {
+                    kind: AccessKind::Load,
+                    address: 0x10008004,
+                    size: 1,
+                    value: 5
+                },
+            ]
+        );
+        assert_eq!(*retired.borrow(), 5);
+    }
+
+    #[test]
+    fn test_register_hook_sees_links() {
+        // jal func / nop / nop / nop / func: addi $v0, $zero, 10 / syscall
+        let text: [u32; 6] = [0x0c100004, 0, 0, 0, 0x2002000a, 0x0000000c];
+        let mut cpu = CPU::new();
+        let bytes: Vec<u8> = text.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory
+            .load_segment("text", 0x00400000, &bytes, true)
+            .unwrap();
+
+        let writes = Rc::new(RefCell::new(Vec::new()));
+        let log = writes.clone();
+        cpu.add_hook(Hook::OnRegisterWrite(Box::new(
+            move |_, register, value| {
+                log.borrow_mut().push((register, value));
+            },
+        )));
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(*writes.borrow(), [(31, 0x00400008), (2, 10)]);
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::Error;
+use crate::loader::TEXT_BASE;
+use crate::Endian;
+
+// A loaded program, independent of the file format it came from. It is also
+// what the single-file container (.rim) stores:
+//
+//   header   "RIMG", version, entry, segment count, symbol count   (20 bytes)
+//   segments name[8], address, size, flags, file offset            (24 bytes each)
+//   symbols  address, name length, name bytes
+//   payloads the segment bytes, at the offsets given in the table
+//
+// All fields are little-endian u32. A big-endian program has the
+// SEGMENT_BIG_ENDIAN flag on its segments.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Image {
+    pub entry: u32,
+    pub segments: Vec<Segment>,
+    pub symbols: Vec<Symbol>,
+    // The byte order the program was built for, if the format says
+    pub endian: Option<Endian>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub name: String,
+    pub address: u32,
+    pub bytes: Vec<u8>,
+    pub executable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u32,
+}
+
+// The symbols of every image loaded into one machine, each kept under the
+// namespace of the image it came from. Entries are ordered by address.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    entries: Vec<(String, Symbol)>,
+    // How many images define each name
+    definitions: HashMap<String, usize>,
+}
+
+impl SymbolTable {
+    pub fn add(&mut self, namespace: &str, symbols: &[Symbol]) {
+        for symbol in symbols {
+            self.entries.push((namespace.to_string(), symbol.clone()));
+            *self.definitions.entry(symbol.name.clone()).or_default() += 1;
+        }
+        self.entries.sort_by_key(|(_, symbol)| symbol.address);
+    }
+
+    // The closest symbol at or below `address` and how far past it the
+    // address is. The name is qualified when another image defines it too.
+    pub fn nearest(&self, address: u32) -> Option<(String, u32)> {
+        let end = self
+            .entries
+            .partition_point(|(_, symbol)| symbol.address <= address);
+        let closest = self.entries.get(end.checked_sub(1)?)?.1.address;
+        // The first of the symbols sharing that address
+        let start = self
+            .entries
+            .partition_point(|(_, symbol)| symbol.address < closest);
+        let (namespace, symbol) = &self.entries[start];
+
+        let name = if self.definitions.get(&symbol.name) > Some(&1) {
+            format!("{}::{}", namespace, symbol.name)
+        } else {
+            symbol.name.clone()
+        };
+        Some((name, address - symbol.address))
+    }
+
+    // Either "namespace::name", or a bare name defined by only one image
+    pub fn lookup(&self, name: &str) -> Result<u32, Error> {
+        let (namespace, name) = match name.split_once("::") {
+            Some((namespace, name)) => (Some(namespace), name),
+            None => (None, name),
+        };
+
+        let mut matches = self.entries.iter().filter(|(entry_namespace, symbol)| {
+            symbol.name == name && namespace.is_none_or(|namespace| namespace == entry_namespace)
+        });
+
+        match (matches.next(), matches.next()) {
+            (Some((_, symbol)), None) => Ok(symbol.address),
+            (None, _) => Err(Error::Symbol(format!("unknown symbol '{}'", name))),
+            (Some((first, _)), Some((second, _))) => Err(Error::Symbol(format!(
+                "ambiguous symbol '{}' (defined in {} and {})",
+                name, first, second
+            ))),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.definitions.clear();
+    }
+}
+
+pub const MAGIC: &[u8; 4] = b"RIMG";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 20;
+const SEGMENT_ENTRY_SIZE: usize = 24;
+const SEGMENT_EXECUTABLE: u32 = 1;
+const SEGMENT_BIG_ENDIAN: u32 = 2;
+const SEGMENT_NAME_SIZE: usize = 8;
+
+impl Image {
+    // A lone text segment at the default base, as produced by flat dumps
+    pub fn with_text(bytes: Vec<u8>) -> Image {
+        Image {
+            entry: TEXT_BASE,
+            segments: vec![Segment {
+                name: String::from("text"),
+                address: TEXT_BASE,
+                bytes,
+                executable: true,
+            }],
+            symbols: Vec::new(),
+            endian: None,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut table = Vec::new();
+        let mut payloads = Vec::new();
+
+        let symbols_size: usize = self.symbols.iter().map(|symbol| 8 + symbol.name.len()).sum();
+        let payload_start = HEADER_SIZE + SEGMENT_ENTRY_SIZE * self.segments.len() + symbols_size;
+
+        for segment in &self.segments {
+            let mut name = [0u8; SEGMENT_NAME_SIZE];
+            let length = segment.name.len().min(SEGMENT_NAME_SIZE);
+            name[..length].copy_from_slice(&segment.name.as_bytes()[..length]);
+
+            table.extend_from_slice(&name);
+            push_u32(&mut table, segment.address);
+            push_u32(&mut table, segment.bytes.len() as u32);
+            let mut flags = if segment.executable {
+                SEGMENT_EXECUTABLE
+            } else {
+                0
+            };
+            if self.endian == Some(Endian::Big) {
+                flags |= SEGMENT_BIG_ENDIAN;
+            }
+            push_u32(&mut table, flags);
+            push_u32(&mut table, (payload_start + payloads.len()) as u32);
+            payloads.extend_from_slice(&segment.bytes);
+        }
+
+        for symbol in &self.symbols {
+            push_u32(&mut table, symbol.address);
+            push_u32(&mut table, symbol.name.len() as u32);
+            table.extend_from_slice(symbol.name.as_bytes());
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        push_u32(&mut bytes, VERSION);
+        push_u32(&mut bytes, self.entry);
+        push_u32(&mut bytes, self.segments.len() as u32);
+        push_u32(&mut bytes, self.symbols.len() as u32);
+        bytes.extend_from_slice(&table);
+        bytes.extend_from_slice(&payloads);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Image, Error> {
+        Image::parse(bytes).map_err(Error::Container)
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Image, String> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(String::from("not a rustinmips container"));
+        }
+
+        let mut reader = Reader { bytes, position: 4 };
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("unsupported container version {}", version));
+        }
+
+        let entry = reader.u32()?;
+        let segment_count = reader.u32()?;
+        let symbol_count = reader.u32()?;
+
+        let mut segments = Vec::new();
+        let mut endian = None;
+        for _ in 0..segment_count {
+            let name = reader.take(SEGMENT_NAME_SIZE)?;
+            let name = String::from_utf8_lossy(name).trim_end_matches('\0').to_string();
+            let address = reader.u32()?;
+            let size = reader.u32()? as usize;
+            let flags = reader.u32()?;
+            let offset = reader.u32()? as usize;
+
+            let payload = bytes
+                .get(offset..offset + size)
+                .ok_or(format!("segment '{}' runs past the end of the file", name))?;
+
+            if flags & SEGMENT_BIG_ENDIAN != 0 {
+                endian = Some(Endian::Big);
+            }
+            segments.push(Segment {
+                name,
+                address,
+                bytes: payload.to_vec(),
+                executable: flags & SEGMENT_EXECUTABLE != 0,
+            });
+        }
+
+        let mut symbols = Vec::new();
+        for _ in 0..symbol_count {
+            let address = reader.u32()?;
+            let length = reader.u32()? as usize;
+            let name = String::from_utf8_lossy(reader.take(length)?).to_string();
+            symbols.push(Symbol { name, address });
+        }
+
+        Ok(Image {
+            entry,
+            segments,
+            symbols,
+            endian,
+        })
+    }
+}
+
+fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    let mut word = [0u8; 4];
+    LittleEndian::write_u32(&mut word, value);
+    bytes.extend_from_slice(&word);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let slice = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or("truncated container")?;
+        self.position += length;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        self.take(4).map(LittleEndian::read_u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Image, Segment, Symbol, SymbolTable};
+    use crate::Endian;
+
+    fn image() -> Image {
+        Image {
+            entry: 0x00400008,
+            segments: vec![
+                Segment {
+                    name: String::from("text"),
+                    address: 0x00400000,
+                    bytes: vec![0x03, 0x00, 0x08, 0x20, 0x0c, 0x00, 0x00, 0x00],
+                    executable: true,
+                },
+                Segment {
+                    name: String::from("data"),
+                    address: 0x10010000,
+                    bytes: b"Hi\0".to_vec(),
+                    executable: false,
+                },
+            ],
+            symbols: vec![Symbol {
+                name: String::from("main"),
+                address: 0x00400008,
+            }],
+            endian: None,
+        }
+    }
+
+    #[test]
+    fn test_symbol_namespaces() {
+        let symbol = |name: &str, address| Symbol {
+            name: String::from(name),
+            address,
+        };
+        let mut table = SymbolTable::default();
+        table.add("driver", &[symbol("main", 0x00400000), symbol("check", 0x00400010)]);
+        table.add("sort", &[symbol("main", 0x00500000), symbol("sort", 0x00500020)]);
+
+        assert_eq!(table.lookup("check").unwrap(), 0x00400010);
+        assert_eq!(table.lookup("sort::main").unwrap(), 0x00500000);
+        assert_eq!(
+            table.lookup("main").unwrap_err().to_string(),
+            "ambiguous symbol 'main' (defined in driver and sort)"
+        );
+        assert_eq!(
+            table.lookup("driver::sort").unwrap_err().to_string(),
+            "unknown symbol 'sort'"
+        );
+
+        assert_eq!(table.nearest(0x00400014), Some((String::from("check"), 4)));
+        assert_eq!(
+            table.nearest(0x00500000),
+            Some((String::from("sort::main"), 0))
+        );
+        assert_eq!(table.nearest(0x003ffffc), None);
+    }
+
+    #[test]
+    fn test_container_round_trip() {
+        let image = image();
+        assert_eq!(Image::from_bytes(&image.to_bytes()).unwrap(), image);
+
+        let big = Image {
+            endian: Some(Endian::Big),
+            ..image
+        };
+        assert_eq!(Image::from_bytes(&big.to_bytes()).unwrap(), big);
+    }
+
+    #[test]
+    fn test_container_bad_magic() {
+        assert_eq!(
+            Image::from_bytes(b"\x7fELF").unwrap_err().to_string(),
+            "not a rustinmips container"
+        );
+    }
+
+    #[test]
+    fn test_container_truncated() {
+        let bytes = image().to_bytes();
+        assert_eq!(
+            Image::from_bytes(&bytes[..30]).unwrap_err().to_string(),
+            "truncated container"
+        );
+    }
+}
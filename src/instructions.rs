@@ -1,10 +1,25 @@
 use crate::CPU;
 
+pub mod cop0_instructions;
+mod disassembler;
+pub mod fp_instructions;
 pub mod i_instructions;
 pub mod j_instructions;
 pub mod r_instructions;
+pub mod special2_instructions;
+pub mod special3_instructions;
+
+pub use disassembler::{disassemble, target};
+
+pub const REGISTER_NAMES: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3",
+    "t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7",
+    "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7",
+    "t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+];
 
 pub trait Instruction {
+    fn name(&self) -> &str;
     fn decode(&self, cpu: &mut CPU) -> String;
     fn execute(&self, cpu: &mut CPU);
 }
@@ -13,11 +28,21 @@ trait Executable<T> {
 }
 
 
+// Whether a word decodes to an instruction this emulator knows. Zero, the
+// canonical NOP, counts as known.
+pub fn is_known(word: u32) -> bool {
+    word == 0 || !get_instruction(word).name().to_lowercase().starts_with("unknown")
+}
+
 pub fn get_instruction(word: u32) -> Box<dyn Instruction> {
     let opcode = word >> 26;
     match opcode {
         0 => Box::new(r_instructions::RTypeInstruction::new(word)),
         2 | 3 => Box::new(j_instructions::JTypeInstruction::new(word)),
+        0b010000 => Box::new(cop0_instructions::Cop0Instruction::new(word)),
+        0b010001 => Box::new(fp_instructions::FpInstruction::new(word)),
+        0b011100 => Box::new(special2_instructions::Special2Instruction::new(word)),
+        0b011111 => Box::new(special3_instructions::Special3Instruction::new(word)),
         _ => Box::new(i_instructions::ITypeInstruction::new(word)),
     }
 }
\ No newline at end of file
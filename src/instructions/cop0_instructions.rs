@@ -0,0 +1,266 @@
+use crate::cp0::Cp0;
+use crate::CPU;
+
+use crate::instructions::Executable;
+use crate::instructions::Instruction;
+
+#[derive(Clone)]
+pub struct Cop0Instruction {
+    pub opcode: u8,
+    name: String,
+    pub rs: u8,
+    pub rt: u8,
+    pub rd: u8,
+    // Low bits of funct; all of funct for the CO instructions (rs >= 0x10)
+    sel: u8,
+    funct: Cop0Function,
+}
+
+impl Instruction for Cop0Instruction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn decode(&self, cpu: &mut CPU) -> String {
+        let rt_value = cpu.read_register(self.rt as usize);
+        format!(
+            "{} rt {}: {}, rd {}, sel {}",
+            self.name, self.rt, rt_value, self.rd, self.sel
+        )
+    }
+
+    fn execute(&self, cpu: &mut CPU) {
+        self.funct.execute(self.clone(), cpu);
+    }
+}
+
+impl Cop0Instruction {
+    pub fn new(instruction: u32) -> Cop0Instruction {
+        let rs = ((instruction >> 21) & 0b11111) as u8;
+        Cop0Instruction {
+            opcode: (instruction >> 26) as u8,
+            name: Cop0Function::new(rs, (instruction & 0b111111) as u8).name.clone(),
+            rs,
+            rt: ((instruction >> 16) & 0b11111) as u8,
+            rd: ((instruction >> 11) & 0b11111) as u8,
+            sel: (instruction & 0b111) as u8,
+            funct: Cop0Function::new(rs, (instruction & 0b111111) as u8),
+        }
+    }
+
+    pub fn build(rs: u8, rt: u8, rd: u8, sel: u8) -> Cop0Instruction {
+        Cop0Instruction {
+            opcode: 0b010000,
+            name: Cop0Function::new(rs, sel).name.clone(),
+            rs,
+            rt,
+            rd,
+            sel,
+            funct: Cop0Function::new(rs, sel),
+        }
+    }
+}
+
+// COP0 instructions are selected by the rs field, and by funct once the CO
+// bit (rs >= 0x10) is set
+#[derive(Clone)]
+struct Cop0Function {
+    rs: u8,
+    funct: u8,
+    name: String,
+}
+
+impl Cop0Function {
+    fn new(rs: u8, funct: u8) -> Cop0Function {
+        Cop0Function {
+            rs,
+            funct,
+            name: match (rs, funct) {
+                (0x00, _) => String::from("MFC0"),
+                (0x04, _) => String::from("MTC0"),
+                (0x0a, _) => String::from("RDPGPR"),
+                (0x0e, _) => String::from("WRPGPR"),
+                (0x10..=0x1f, 0x01) => String::from("TLBR"),
+                (0x10..=0x1f, 0x02) => String::from("TLBWI"),
+                (0x10..=0x1f, 0x06) => String::from("TLBWR"),
+                (0x10..=0x1f, 0x08) => String::from("TLBP"),
+                (0x10..=0x1f, 0x18) => String::from("ERET"),
+                _ => String::from("UNKNOWN"),
+            },
+        }
+    }
+}
+
+impl Executable<Cop0Instruction> for Cop0Function {
+    fn execute(&self, instruction: Cop0Instruction, cpu: &mut CPU) {
+        match (self.rs, self.funct) {
+            // MFC0
+            (0x00, _) => {
+                let value = cpu.cp0.read(instruction.rd, instruction.sel);
+                cpu.write_register(instruction.rt as usize, value);
+            }
+
+            // MTC0
+            (0x04, _) => {
+                let value = cpu.read_register(instruction.rt as usize);
+                cpu.cp0.write(instruction.rd, instruction.sel, value);
+            }
+
+            // RDPGPR: rd <- previous set's rt
+            (0x0a, _) => {
+                let set = cpu.cp0.srs_field(Cp0::PSS_SHIFT) as usize;
+                let value = cpu.read_shadow_register(set, instruction.rt as usize);
+                cpu.write_register(instruction.rd as usize, value);
+            }
+
+            // WRPGPR: previous set's rd <- rt
+            (0x0e, _) => {
+                let set = cpu.cp0.srs_field(Cp0::PSS_SHIFT) as usize;
+                let value = cpu.read_register(instruction.rt as usize);
+                cpu.write_shadow_register(set, instruction.rd as usize, value);
+            }
+
+            // The TLB instructions don't exist without an MMU
+            (0x10..=0x1f, 0x01 | 0x02 | 0x06 | 0x08) if cpu.cp0.tlb.is_empty() => {
+                cpu.reserved_instruction()
+            }
+            // TLBR
+            (0x10..=0x1f, 0x01) => cpu.cp0.tlb_read(),
+            // TLBWI
+            (0x10..=0x1f, 0x02) => cpu.cp0.tlb_write(false),
+            // TLBWR
+            (0x10..=0x1f, 0x06) => cpu.cp0.tlb_write(true),
+            // TLBP
+            (0x10..=0x1f, 0x08) => cpu.cp0.tlb_probe(),
+
+            // ERET
+            (0x10..=0x1f, 0x18) => cpu.return_from_exception(),
+            _ => cpu.reserved_instruction(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cp0::Cp0;
+    use crate::instructions::Instruction;
+    use crate::CPU;
+
+    #[test]
+    fn test_mtc0_mfc0_srsctl() {
+        let mut cpu = CPU::new();
+        cpu.set_shadow_sets(3);
+        cpu.write_register(8, 2 << Cp0::ESS_SHIFT | 1 << Cp0::PSS_SHIFT);
+
+        super::Cop0Instruction::build(0x04, 8, 12, 2).execute(&mut cpu);
+        super::Cop0Instruction::build(0x00, 9, 12, 2).execute(&mut cpu);
+
+        assert_eq!(
+            cpu.read_register(9),
+            3 << Cp0::HSS_SHIFT | 2 << Cp0::ESS_SHIFT | 1 << Cp0::PSS_SHIFT
+        );
+    }
+
+    #[test]
+    fn test_mtc0_mfc0_status_epc() {
+        let mut cpu = CPU::new();
+        cpu.write_register(8, 0x0000_ff01);
+        cpu.write_register(9, 0x0040_0020);
+
+        super::Cop0Instruction::build(0x04, 8, Cp0::STATUS, 0).execute(&mut cpu);
+        super::Cop0Instruction::build(0x04, 9, Cp0::EPC, 0).execute(&mut cpu);
+        super::Cop0Instruction::build(0x00, 10, Cp0::STATUS, 0).execute(&mut cpu);
+        super::Cop0Instruction::build(0x00, 11, Cp0::EPC, 0).execute(&mut cpu);
+
+        assert_eq!(cpu.read_register(10), 0x0000_ff01);
+        assert_eq!(cpu.read_register(11), 0x0040_0020);
+    }
+
+    #[test]
+    fn test_mtc0_cause_and_bad_vaddr_are_mostly_read_only() {
+        let mut cpu = CPU::new();
+        cpu.cp0.bad_vaddr = 0x1001_0002;
+        cpu.write_register(8, 0xffff_ffff);
+
+        super::Cop0Instruction::build(0x04, 8, Cp0::CAUSE, 0).execute(&mut cpu);
+        super::Cop0Instruction::build(0x04, 8, Cp0::BAD_VADDR, 0).execute(&mut cpu);
+
+        assert_eq!(cpu.cp0.cause, 0x300);
+        assert_eq!(cpu.cp0.bad_vaddr, 0x1001_0002);
+    }
+
+    #[test]
+    fn test_eret() {
+        let mut cpu = CPU::new();
+        cpu.cp0.epc = 0x0040_0010;
+        cpu.cp0.status = Cp0::STATUS_EXL | 1;
+
+        let instruction = crate::instructions::get_instruction(0x4200_0018);
+        assert_eq!(instruction.name(), "ERET");
+        instruction.execute(&mut cpu);
+
+        assert_eq!(cpu.pc, 0x0040_0010);
+        assert_eq!(cpu.cp0.status, 1);
+    }
+
+    #[test]
+    fn test_tlbwi_tlbp_tlbr() {
+        let mut cpu = CPU::new();
+        cpu.enable_mmu(8);
+        cpu.cp0.index = 5;
+        cpu.cp0.entry_hi = 0x0040_0003;
+        cpu.cp0.entry_lo0 = 0x10 << 6 | 0b111;
+        cpu.cp0.entry_lo1 = 0x20 << 6 | 0b110;
+
+        // tlbwi, then tlbp finds it again
+        crate::instructions::get_instruction(0x4200_0002).execute(&mut cpu);
+        cpu.cp0.index = 0;
+        crate::instructions::get_instruction(0x4200_0008).execute(&mut cpu);
+        assert_eq!(cpu.cp0.index, 5);
+
+        // tlbr; G survives only when both halves have it
+        cpu.cp0.entry_lo0 = 0;
+        crate::instructions::get_instruction(0x4200_0001).execute(&mut cpu);
+        assert_eq!(cpu.cp0.entry_lo0, 0x10 << 6 | 0b110);
+
+        // Another ASID misses
+        cpu.cp0.entry_hi = 0x0040_0004;
+        crate::instructions::get_instruction(0x4200_0008).execute(&mut cpu);
+        assert_eq!(cpu.cp0.index, Cp0::INDEX_PROBE_FAILED);
+    }
+
+    #[test]
+    fn test_tlb_instructions_need_an_mmu() {
+        let mut cpu = CPU::new();
+        crate::instructions::get_instruction(0x4200_0002).execute(&mut cpu);
+        assert_eq!(cpu.exit, Some(crate::ExitReason::ReservedInstruction(0)));
+    }
+
+    #[test]
+    fn test_srsctl_clamps_to_highest_set() {
+        let mut cpu = CPU::new();
+        cpu.set_shadow_sets(1);
+        cpu.write_register(8, 7 << Cp0::ESS_SHIFT);
+
+        super::Cop0Instruction::build(0x04, 8, 12, 2).execute(&mut cpu);
+
+        assert_eq!(cpu.cp0.srs_field(Cp0::ESS_SHIFT), 1);
+    }
+
+    #[test]
+    fn test_wrpgpr_rdpgpr() {
+        let mut cpu = CPU::new();
+        cpu.set_shadow_sets(1);
+        cpu.cp0.set_srs_field(Cp0::PSS_SHIFT, 1);
+        cpu.write_register(8, 42);
+
+        // wrpgpr $t1, $t0 writes set 1's $t1, leaving ours alone
+        super::Cop0Instruction::build(0x0e, 8, 9, 0).execute(&mut cpu);
+        assert_eq!(cpu.read_register(9), 0);
+        assert_eq!(cpu.read_shadow_register(1, 9), 42);
+
+        // rdpgpr $t2, $t1
+        super::Cop0Instruction::build(0x0a, 9, 10, 0).execute(&mut cpu);
+        assert_eq!(cpu.read_register(10), 42);
+    }
+}
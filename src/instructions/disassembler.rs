@@ -0,0 +1,151 @@
+use crate::instructions::{get_instruction, is_known, REGISTER_NAMES};
+
+// One instruction in the usual assembler syntax, e.g. "lw $t0, 8($sp)" or
+// "beq $a0, $zero, 0x00400010", with `pc` the instruction's own address.
+// Words that aren't instructions come out as ".word 0x...".
+pub fn disassemble(word: u32, pc: u32) -> String {
+    if word == 0 {
+        return String::from("nop");
+    }
+    if !is_known(word) {
+        return format!(".word {:#010x}", word);
+    }
+
+    let name = get_instruction(word).name().to_lowercase();
+    let rs = reg(word >> 21);
+    let rt = reg(word >> 16);
+    let rd = reg(word >> 11);
+    let sa = (word >> 6) & 0x1f;
+    let fs = format!("$f{}", (word >> 11) & 0x1f);
+    let ft = format!("$f{}", (word >> 16) & 0x1f);
+    let fd = format!("$f{}", (word >> 6) & 0x1f);
+    let immediate = word as u16;
+    let signed = immediate as i16;
+    let target = target(word, pc).map(|target| format!("{:#010x}", target));
+
+    let operands = match name.as_str() {
+        "syscall" | "eret" | "tlbr" | "tlbwi" | "tlbwr" | "tlbp" => String::new(),
+        "break" => match (word >> 6) & 0xfffff {
+            0 => String::new(),
+            code => code.to_string(),
+        },
+        "sll" | "srl" | "sra" | "rotr" => format!("{}, {}, {}", rd, rt, sa),
+        "sllv" | "srlv" | "srav" | "rotrv" => format!("{}, {}, {}", rd, rt, rs),
+        "jr" | "mthi" | "mtlo" => rs,
+        "jalr" if (word >> 11) & 0x1f == 31 => rs,
+        "jalr" => format!("{}, {}", rd, rs),
+        "mfhi" | "mflo" => rd,
+        "mult" | "multu" | "div" | "divu" | "madd" | "maddu" | "msub" | "msubu" | "tge"
+        | "tgeu" | "tlt" | "tltu" | "teq" | "tne" => format!("{}, {}", rs, rt),
+        "clz" | "clo" => format!("{}, {}", rd, rs),
+        "wsbh" | "seb" | "seh" | "rdpgpr" | "wrpgpr" => format!("{}, {}", rd, rt),
+        "ext" => format!("{}, {}, {}, {}", rt, rs, sa, ((word >> 11) & 0x1f) + 1),
+        "ins" => format!(
+            "{}, {}, {}, {}",
+            rt,
+            rs,
+            sa,
+            ((word >> 11) & 0x1f) as i32 + 1 - sa as i32
+        ),
+        "j" | "jal" | "bc1f" | "bc1t" | "bc1fl" | "bc1tl" => target.unwrap_or_default(),
+        "beq" | "bne" | "beql" | "bnel" => {
+            format!("{}, {}, {}", rs, rt, target.unwrap_or_default())
+        }
+        "blez" | "bgtz" | "bltz" | "bgez" | "blezl" | "bgtzl" | "bltzl" | "bgezl" | "bltzal"
+        | "bgezal" | "bltzall" | "bgezall" => format!("{}, {}", rs, target.unwrap_or_default()),
+        "andi" | "ori" | "xori" => format!("{}, {}, {:#x}", rt, rs, immediate),
+        "lui" => format!("{}, {:#x}", rt, immediate),
+        "addi" | "addiu" | "slti" | "sltiu" => format!("{}, {}, {}", rt, rs, signed),
+        "lb" | "lh" | "lw" | "lbu" | "lhu" | "sb" | "sh" | "sw" | "ll" | "sc" => {
+            format!("{}, {}({})", rt, signed, rs)
+        }
+        "lwc1" | "swc1" => format!("{}, {}({})", ft, signed, rs),
+        "mfc0" | "mtc0" => match word & 0x7 {
+            0 => format!("{}, ${}", rt, (word >> 11) & 0x1f),
+            select => format!("{}, ${}, {}", rt, (word >> 11) & 0x1f, select),
+        },
+        "mfc1" | "mtc1" => format!("{}, {}", rt, fs),
+        "cfc1" | "ctc1" => format!("{}, ${}", rt, (word >> 11) & 0x1f),
+        _ if name.starts_with("c.") => match (word >> 8) & 0x7 {
+            0 => format!("{}, {}", fs, ft),
+            cc => format!("{}, {}, {}", cc, fs, ft),
+        },
+        _ if name.contains('.') && ["add", "sub", "mul", "div"].contains(&&name[..3]) => {
+            format!("{}, {}, {}", fd, fs, ft)
+        }
+        _ if name.contains('.') => format!("{}, {}", fd, fs),
+        // The rest of SPECIAL and SPECIAL2: add, and, slt, movz, mul, ...
+        _ => format!("{}, {}, {}", rd, rs, rt),
+    };
+
+    if operands.is_empty() {
+        name
+    } else {
+        format!("{} {}", name, operands)
+    }
+}
+
+// Where a branch or jump at `pc` goes, or None for any other instruction
+// (and for jr and jalr, whose target is only known at run time)
+pub fn target(word: u32, pc: u32) -> Option<u32> {
+    let next = pc.wrapping_add(4);
+    let branch = next.wrapping_add(((word as u16 as i16 as i32) << 2) as u32);
+    match word >> 26 {
+        0b000010 | 0b000011 => Some(next & 0xf0000000 | (word & 0x3ffffff) << 2),
+        0b000001 if !is_known(word) => None,
+        0b000001 | 0b000100..=0b000111 | 0b010100..=0b010111 => Some(branch),
+        // BC1F/BC1T and their likely forms
+        0b010001 if (word >> 21) & 0x1f == 0x08 => Some(branch),
+        _ => None,
+    }
+}
+
+fn reg(field: u32) -> String {
+    format!("${}", REGISTER_NAMES[(field & 0x1f) as usize])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disassemble, target};
+
+    #[test]
+    fn test_disassemble() {
+        for (word, expected) in [
+            (0x00000000, "nop"),
+            (0x2002000a, "addi $v0, $zero, 10"),
+            (0x8fa80004, "lw $t0, 4($sp)"),
+            (0xafbffffc, "sw $ra, -4($sp)"),
+            (0x3c081234, "lui $t0, 0x1234"),
+            (0x3508ffff, "ori $t0, $t0, 0xffff"),
+            (0x000a48c0, "sll $t1, $t2, 3"),
+            (0x012a4021, "addu $t0, $t1, $t2"),
+            (0x03e00008, "jr $ra"),
+            (0x0120f809, "jalr $t1"),
+            (0x00004012, "mflo $t0"),
+            (0x012a001a, "div $t1, $t2"),
+            (0x0000000c, "syscall"),
+            (0x712a4002, "mul $t0, $t1, $t2"),
+            (0x401a6800, "mfc0 $k0, $13"),
+            (0x42000018, "eret"),
+            (0x46020800, "add.s $f0, $f1, $f2"),
+            (0x44880000, "mtc1 $t0, $f0"),
+            (0xfc000000, ".word 0xfc000000"),
+        ] {
+            assert_eq!(disassemble(word, 0x00400000), expected, "{:#010x}", word);
+        }
+    }
+
+    #[test]
+    fn test_targets() {
+        // A branch to itself, one forward and a jump
+        assert_eq!(
+            disassemble(0x1100ffff, 0x00400008),
+            "beq $t0, $zero, 0x00400008"
+        );
+        assert_eq!(disassemble(0x05010002, 0x00400000), "bgez $t0, 0x0040000c");
+        assert_eq!(disassemble(0x0c100004, 0x00400000), "jal 0x00400010");
+        assert_eq!(target(0x0c100004, 0x00400000), Some(0x00400010));
+        assert_eq!(target(0x03e00008, 0x00400000), None);
+        assert_eq!(target(0x2002000a, 0x00400000), None);
+    }
+}
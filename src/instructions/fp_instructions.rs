@@ -0,0 +1,633 @@
+use crate::cp1::Rounding;
+use crate::CPU;
+
+use crate::instructions::Executable;
+use crate::instructions::Instruction;
+
+// COP1 (opcode 0x11): fd <- fs op ft, with the operand format in the fmt
+// field. BC1 (fmt 0x08) branches instead, on a condition code in ft, and
+// the moves (fmt 0x00 to 0x06) use ft as a general purpose register.
+#[derive(Clone)]
+pub struct FpInstruction {
+    pub opcode: u8,
+    name: String,
+    pub fmt: u8,
+    pub ft: u8,
+    pub fs: u8,
+    pub fd: u8,
+    funct: FpFunction,
+}
+
+impl Instruction for FpInstruction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn decode(&self, cpu: &mut CPU) -> String {
+        let (fs_value, ft_value) = match self.fmt {
+            D => (cpu.cp1.read_double(self.fs), cpu.cp1.read_double(self.ft)),
+            _ => (
+                cpu.cp1.read_single(self.fs) as f64,
+                cpu.cp1.read_single(self.ft) as f64,
+            ),
+        };
+        format!(
+            "{} fd {}, fs {}: {}, ft {}: {}",
+            self.name, self.fd, self.fs, fs_value, self.ft, ft_value
+        )
+    }
+
+    fn execute(&self, cpu: &mut CPU) {
+        self.funct.execute(self.clone(), cpu);
+    }
+}
+
+impl FpInstruction {
+    pub fn new(instruction: u32) -> FpInstruction {
+        let fmt = ((instruction >> 21) & 0b11111) as u8;
+        let funct = (instruction & 0b111111) as u8;
+        FpInstruction {
+            opcode: (instruction >> 26) as u8,
+            name: Self::name_for(fmt, ((instruction >> 16) & 0b11111) as u8, funct),
+            fmt,
+            ft: ((instruction >> 16) & 0b11111) as u8,
+            fs: ((instruction >> 11) & 0b11111) as u8,
+            fd: ((instruction >> 6) & 0b11111) as u8,
+            funct: FpFunction::new(fmt, funct),
+        }
+    }
+
+    pub fn build(fmt: u8, ft: u8, fs: u8, fd: u8, funct: u8) -> FpInstruction {
+        FpInstruction {
+            opcode: 0b010001,
+            name: Self::name_for(fmt, ft, funct),
+            fmt,
+            ft,
+            fs,
+            fd,
+            funct: FpFunction::new(fmt, funct),
+        }
+    }
+
+    // BC1 instructions are told apart by the nd and tf bits of ft
+    fn name_for(fmt: u8, ft: u8, funct: u8) -> String {
+        match fmt {
+            0x00 => return String::from("MFC1"),
+            0x02 => return String::from("CFC1"),
+            0x04 => return String::from("MTC1"),
+            0x06 => return String::from("CTC1"),
+            BC => {}
+            _ => return FpFunction::new(fmt, funct).name,
+        }
+
+        match ft & 0b11 {
+            0b00 => String::from("BC1F"),
+            0b01 => String::from("BC1T"),
+            0b10 => String::from("BC1FL"),
+            _ => String::from("BC1TL"),
+        }
+    }
+
+    // BC1: the 16-bit offset spans fs, fd and funct
+    fn offset(&self) -> i16 {
+        ((self.fs as u16) << 11 | (self.fd as u16) << 6 | self.funct.funct as u16) as i16
+    }
+}
+
+// fmt values
+const S: u8 = 0x10;
+const D: u8 = 0x11;
+const W: u8 = 0x14;
+const BC: u8 = 0x08;
+
+// C.cond.fmt predicates, by the low four bits of funct
+const CONDITIONS: [&str; 16] = [
+    "F", "UN", "EQ", "UEQ", "OLT", "ULT", "OLE", "ULE", "SF", "NGLE", "SEQ", "NGL", "LT", "NGE",
+    "LE", "NGT",
+];
+
+#[derive(Clone)]
+struct FpFunction {
+    fmt: u8,
+    funct: u8,
+    name: String,
+}
+
+impl FpFunction {
+    fn new(fmt: u8, funct: u8) -> FpFunction {
+        FpFunction {
+            fmt,
+            funct,
+            name: match (funct, fmt) {
+                (0x00, S | D) => format!("ADD.{}", format_name(fmt)),
+                (0x01, S | D) => format!("SUB.{}", format_name(fmt)),
+                (0x02, S | D) => format!("MUL.{}", format_name(fmt)),
+                (0x03, S | D) => format!("DIV.{}", format_name(fmt)),
+                (0x04, S | D) => format!("SQRT.{}", format_name(fmt)),
+                (0x05, S | D) => format!("ABS.{}", format_name(fmt)),
+                (0x06, S | D) => format!("MOV.{}", format_name(fmt)),
+                (0x07, S | D) => format!("NEG.{}", format_name(fmt)),
+                (0x0c, S | D) => format!("ROUND.W.{}", format_name(fmt)),
+                (0x0d, S | D) => format!("TRUNC.W.{}", format_name(fmt)),
+                (0x0e, S | D) => format!("CEIL.W.{}", format_name(fmt)),
+                (0x0f, S | D) => format!("FLOOR.W.{}", format_name(fmt)),
+                (0x20, D | W) => format!("CVT.S.{}", format_name(fmt)),
+                (0x21, S | W) => format!("CVT.D.{}", format_name(fmt)),
+                (0x24, S | D) => format!("CVT.W.{}", format_name(fmt)),
+                (0x30..=0x3f, S | D) => format!(
+                    "C.{}.{}",
+                    CONDITIONS[funct as usize & 0xf],
+                    format_name(fmt)
+                ),
+                _ => String::from("UNKNOWN"),
+            },
+        }
+    }
+}
+
+// The suffix of a floating-point format in mnemonics
+fn format_name(fmt: u8) -> &'static str {
+    match fmt {
+        S => "S",
+        D => "D",
+        _ => "W",
+    }
+}
+
+// fs in any format, widened; every single and word is exact as a double
+fn read_value(cpu: &CPU, fmt: u8, register: u8) -> f64 {
+    match fmt {
+        S => cpu.cp1.read_single(register) as f64,
+        D => cpu.cp1.read_double(register),
+        _ => cpu.cp1.read(register) as i32 as f64,
+    }
+}
+
+fn round(value: f64, rounding: Rounding) -> f64 {
+    match rounding {
+        Rounding::Nearest => value.round_ties_even(),
+        Rounding::Zero => value.trunc(),
+        Rounding::Up => value.ceil(),
+        Rounding::Down => value.floor(),
+    }
+}
+
+// NaN and values outside the word range give the invalid result, 2^31 - 1
+fn to_word(value: f64) -> u32 {
+    if value.is_nan() || value < i32::MIN as f64 || value > i32::MAX as f64 {
+        return i32::MAX as u32;
+    }
+
+    value as i32 as u32
+}
+
+// `as` rounds to nearest; the other modes step to the neighbour on the
+// right side of an inexact result
+fn to_single(value: f64, rounding: Rounding) -> f32 {
+    let nearest = value as f32;
+    if value.is_nan() || nearest as f64 == value {
+        return nearest;
+    }
+
+    let up = match rounding {
+        Rounding::Nearest => return nearest,
+        Rounding::Zero => value < 0.0,
+        Rounding::Up => true,
+        Rounding::Down => false,
+    };
+    match (up, (nearest as f64) < value) {
+        (true, true) => nearest.next_up(),
+        (false, false) => nearest.next_down(),
+        _ => nearest,
+    }
+}
+
+impl Executable<FpInstruction> for FpFunction {
+    fn execute(&self, instruction: FpInstruction, cpu: &mut CPU) {
+        match (self.fmt, self.funct) {
+            // MOV.S and MOV.D copy the bits untouched
+            (S, 0x06) => {
+                let bits = cpu.cp1.read(instruction.fs);
+                cpu.cp1.write(instruction.fd, bits);
+            }
+            (D, 0x06) => {
+                let value = cpu.cp1.read_double(instruction.fs);
+                cpu.cp1.write_double(instruction.fd, value);
+            }
+
+            (S, 0x00..=0x07) => {
+                let fs = cpu.cp1.read_single(instruction.fs);
+                let ft = cpu.cp1.read_single(instruction.ft);
+                let result = match self.funct {
+                    0x00 => fs + ft,
+                    0x01 => fs - ft,
+                    0x02 => fs * ft,
+                    0x03 => fs / ft,
+                    0x04 => fs.sqrt(),
+                    0x05 => fs.abs(),
+                    _ => -fs,
+                };
+                cpu.cp1.write_single(instruction.fd, result);
+            }
+
+            (D, 0x00..=0x07) => {
+                let fs = cpu.cp1.read_double(instruction.fs);
+                let ft = cpu.cp1.read_double(instruction.ft);
+                let result = match self.funct {
+                    0x00 => fs + ft,
+                    0x01 => fs - ft,
+                    0x02 => fs * ft,
+                    0x03 => fs / ft,
+                    0x04 => fs.sqrt(),
+                    0x05 => fs.abs(),
+                    _ => -fs,
+                };
+                cpu.cp1.write_double(instruction.fd, result);
+            }
+            // ROUND.W, TRUNC.W, CEIL.W, FLOOR.W
+            (S | D, 0x0c..=0x0f) => {
+                let rounding = [
+                    Rounding::Nearest,
+                    Rounding::Zero,
+                    Rounding::Up,
+                    Rounding::Down,
+                ][self.funct as usize - 0x0c];
+                let value = read_value(cpu, self.fmt, instruction.fs);
+                cpu.cp1
+                    .write(instruction.fd, to_word(round(value, rounding)));
+            }
+
+            // CVT.S
+            (D | W, 0x20) => {
+                let value = read_value(cpu, self.fmt, instruction.fs);
+                let rounding = cpu.cp1.rounding();
+                cpu.cp1
+                    .write_single(instruction.fd, to_single(value, rounding));
+            }
+
+            // CVT.D
+            (S | W, 0x21) => {
+                let value = read_value(cpu, self.fmt, instruction.fs);
+                cpu.cp1.write_double(instruction.fd, value);
+            }
+
+            // CVT.W
+            (S | D, 0x24) => {
+                let value = read_value(cpu, self.fmt, instruction.fs);
+                let rounding = cpu.cp1.rounding();
+                cpu.cp1
+                    .write(instruction.fd, to_word(round(value, rounding)));
+            }
+            // C.cond: bit 0 of the predicate accepts unordered, bit 1
+            // equal and bit 2 less than; cc sits in the top bits of fd
+            (S | D, 0x30..=0x3f) => {
+                let fs = read_value(cpu, self.fmt, instruction.fs);
+                let ft = read_value(cpu, self.fmt, instruction.ft);
+                let unordered = fs.is_nan() || ft.is_nan();
+                let result = (self.funct & 0b001 != 0 && unordered)
+                    || (self.funct & 0b010 != 0 && fs == ft)
+                    || (self.funct & 0b100 != 0 && fs < ft);
+                cpu.cp1.set_condition(instruction.fd >> 2, result);
+            }
+
+            // MFC1
+            (0x00, _) => {
+                let value = cpu.cp1.read(instruction.fs);
+                cpu.write_register(instruction.ft as usize, value);
+            }
+
+            // CFC1: FIR (0) reads as zero, FCSR is 31
+            (0x02, _) => {
+                let value = if instruction.fs == 31 {
+                    cpu.cp1.fcsr
+                } else {
+                    0
+                };
+                cpu.write_register(instruction.ft as usize, value);
+            }
+
+            // MTC1
+            (0x04, _) => {
+                let value = cpu.read_register(instruction.ft as usize);
+                cpu.cp1.write(instruction.fs, value);
+            }
+
+            // CTC1
+            (0x06, _) => {
+                if instruction.fs == 31 {
+                    cpu.cp1.fcsr = cpu.read_register(instruction.ft as usize);
+                }
+            }
+
+            // BC1F, BC1T, BC1FL, BC1TL
+            (BC, _) => {
+                let taken = cpu.cp1.condition(instruction.ft >> 2) == (instruction.ft & 1 == 1);
+                if instruction.ft & 0b10 != 0 {
+                    cpu.branch_likely(taken, instruction.offset());
+                } else if taken {
+                    cpu.run_branch_delayed();
+                    cpu.pc = cpu.pc.wrapping_add((instruction.offset() as u32) << 2);
+                }
+            }
+            _ => cpu.reserved_instruction(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instructions::{self, Instruction};
+    use crate::CPU;
+
+    #[test]
+    fn test_add_s() {
+        let mut cpu = CPU::new();
+        // add.s $f0, $f2, $f4
+        let instruction = instructions::get_instruction(0x4604_1000);
+        assert_eq!(instruction.name(), "ADD.S");
+
+        cpu.cp1.write_single(2, 1.5);
+        cpu.cp1.write_single(4, 2.25);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_single(0), 3.75);
+    }
+
+    #[test]
+    fn test_sub_mul_div_s() {
+        let mut cpu = CPU::new();
+        cpu.cp1.write_single(2, 6.0);
+        cpu.cp1.write_single(4, -1.5);
+
+        super::FpInstruction::build(0x10, 4, 2, 0, 0x01).execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_single(0), 7.5);
+
+        super::FpInstruction::build(0x10, 4, 2, 0, 0x02).execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_single(0), -9.0);
+
+        super::FpInstruction::build(0x10, 4, 2, 0, 0x03).execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_single(0), -4.0);
+    }
+
+    #[test]
+    fn test_div_s_by_zero_is_infinite() {
+        let mut cpu = CPU::new();
+        cpu.cp1.write_single(2, 1.0);
+
+        super::FpInstruction::build(0x10, 4, 2, 0, 0x03).execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_single(0), f32::INFINITY);
+    }
+
+    #[test]
+    fn test_abs_neg_mov_s() {
+        let mut cpu = CPU::new();
+        cpu.cp1.write_single(2, -2.5);
+
+        super::FpInstruction::build(0x10, 0, 2, 0, 0x05).execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_single(0), 2.5);
+
+        super::FpInstruction::build(0x10, 0, 0, 4, 0x07).execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_single(4), -2.5);
+
+        // MOV.S keeps a NaN's payload
+        cpu.cp1.write(6, 0x7fc0_1234);
+        super::FpInstruction::build(0x10, 0, 6, 8, 0x06).execute(&mut cpu);
+        assert_eq!(cpu.cp1.read(8), 0x7fc0_1234);
+    }
+
+    #[test]
+    fn test_add_d() {
+        let mut cpu = CPU::new();
+        // add.d $f0, $f2, $f4
+        let instruction = instructions::get_instruction(0x4624_1000);
+        assert_eq!(instruction.name(), "ADD.D");
+
+        cpu.cp1.write_double(2, 0.1);
+        cpu.cp1.write_double(4, 0.2);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_double(0), 0.1 + 0.2);
+    }
+
+    #[test]
+    fn test_double_uses_an_even_odd_pair() {
+        let mut cpu = CPU::new();
+        cpu.cp1.write_double(2, 1.0);
+
+        // 1.0 is 0x3ff00000_00000000
+        assert_eq!(cpu.cp1.read(2), 0);
+        assert_eq!(cpu.cp1.read(3), 0x3ff0_0000);
+    }
+
+    #[test]
+    fn test_sub_mul_div_sqrt_d() {
+        let mut cpu = CPU::new();
+        cpu.cp1.write_double(2, 9.0);
+        cpu.cp1.write_double(4, 0.5);
+
+        super::FpInstruction::build(0x11, 4, 2, 0, 0x01).execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_double(0), 8.5);
+
+        super::FpInstruction::build(0x11, 4, 2, 0, 0x02).execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_double(0), 4.5);
+
+        super::FpInstruction::build(0x11, 4, 2, 0, 0x03).execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_double(0), 18.0);
+
+        let sqrt = super::FpInstruction::build(0x11, 0, 2, 0, 0x04);
+        assert_eq!(sqrt.name(), "SQRT.D");
+        sqrt.execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_double(0), 3.0);
+    }
+
+    #[test]
+    fn test_cvt_s_w_and_back() {
+        let mut cpu = CPU::new();
+        // cvt.s.w $f0, $f2
+        let instruction = instructions::get_instruction(0x4680_1020);
+        assert_eq!(instruction.name(), "CVT.S.W");
+
+        cpu.cp1.write(2, -7i32 as u32);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_single(0), -7.0);
+
+        let cvt_d_s = super::FpInstruction::build(0x10, 0, 0, 4, 0x21);
+        assert_eq!(cvt_d_s.name(), "CVT.D.S");
+        cvt_d_s.execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_double(4), -7.0);
+    }
+
+    #[test]
+    fn test_cvt_w_honors_fcsr_rounding() {
+        let mut cpu = CPU::new();
+        cpu.cp1.write_single(2, -2.5);
+        let cvt_w_s = super::FpInstruction::build(0x10, 0, 2, 0, 0x24);
+        assert_eq!(cvt_w_s.name(), "CVT.W.S");
+
+        // nearest (ties to even), toward zero, up, down
+        for (mode, expected) in [(0, -2), (1, -2), (2, -2), (3, -3)] {
+            cpu.cp1.fcsr = mode;
+            cvt_w_s.execute(&mut cpu);
+            assert_eq!(cpu.cp1.read(0) as i32, expected);
+        }
+
+        cpu.cp1.write_single(2, 3.5);
+        cpu.cp1.fcsr = 0;
+        cvt_w_s.execute(&mut cpu);
+        assert_eq!(cpu.cp1.read(0), 4);
+    }
+
+    #[test]
+    fn test_round_trunc_ceil_floor_ignore_fcsr() {
+        let mut cpu = CPU::new();
+        cpu.cp1.fcsr = 2;
+        cpu.cp1.write_double(2, -1.75);
+
+        for (funct, name, expected) in [
+            (0x0c, "ROUND.W.D", -2),
+            (0x0d, "TRUNC.W.D", -1),
+            (0x0e, "CEIL.W.D", -1),
+            (0x0f, "FLOOR.W.D", -2),
+        ] {
+            let instruction = super::FpInstruction::build(0x11, 0, 2, 0, funct);
+            assert_eq!(instruction.name(), name);
+            instruction.execute(&mut cpu);
+            assert_eq!(cpu.cp1.read(0) as i32, expected);
+        }
+    }
+
+    #[test]
+    fn test_trunc_w_out_of_range_is_invalid() {
+        let mut cpu = CPU::new();
+        let instruction = super::FpInstruction::build(0x10, 0, 2, 0, 0x0d);
+
+        for value in [3e9, -3e9, f32::NAN] {
+            cpu.cp1.write_single(2, value);
+            instruction.execute(&mut cpu);
+            assert_eq!(cpu.cp1.read(0), 0x7fff_ffff);
+        }
+    }
+
+    #[test]
+    fn test_cvt_s_d_honors_fcsr_rounding() {
+        let mut cpu = CPU::new();
+        let instruction = super::FpInstruction::build(0x11, 0, 2, 0, 0x20);
+        assert_eq!(instruction.name(), "CVT.S.D");
+        cpu.cp1.write_double(2, 0.1);
+
+        cpu.cp1.fcsr = 0;
+        instruction.execute(&mut cpu);
+        let nearest = cpu.cp1.read_single(0);
+        assert_eq!(nearest, 0.1f32);
+
+        // 0.1f32 is above 0.1, so rounding down or toward zero goes one lower
+        cpu.cp1.fcsr = 3;
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_single(0), nearest.next_down());
+
+        cpu.cp1.fcsr = 2;
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_single(0), nearest);
+    }
+
+    #[test]
+    fn test_c_cond_sets_condition_codes() {
+        let mut cpu = CPU::new();
+        cpu.cp1.write_single(2, 1.0);
+        cpu.cp1.write_single(4, 2.0);
+
+        // c.lt.s $f2, $f4
+        let instruction = instructions::get_instruction(0x4604_103c);
+        assert_eq!(instruction.name(), "C.LT.S");
+        instruction.execute(&mut cpu);
+        assert!(cpu.cp1.condition(0));
+
+        // c.le.s $fcc3, $f4, $f2
+        let instruction = super::FpInstruction::build(0x10, 2, 4, 3 << 2, 0x3e);
+        assert_eq!(instruction.name(), "C.LE.S");
+        instruction.execute(&mut cpu);
+        assert!(!cpu.cp1.condition(3));
+        assert_eq!(cpu.cp1.fcsr, 1 << 23);
+
+        // c.eq.d
+        cpu.cp1.write_double(6, 0.5);
+        cpu.cp1.write_double(8, 0.5);
+        super::FpInstruction::build(0x11, 8, 6, 0, 0x32).execute(&mut cpu);
+        assert!(cpu.cp1.condition(0));
+    }
+
+    #[test]
+    fn test_c_cond_unordered() {
+        let mut cpu = CPU::new();
+        cpu.cp1.write_single(2, f32::NAN);
+
+        // c.eq.s is false, c.ueq.s true when either side is NaN
+        super::FpInstruction::build(0x10, 2, 2, 0, 0x32).execute(&mut cpu);
+        assert!(!cpu.cp1.condition(0));
+        super::FpInstruction::build(0x10, 2, 2, 0, 0x33).execute(&mut cpu);
+        assert!(cpu.cp1.condition(0));
+    }
+
+    #[test]
+    fn test_bc1t_bc1f() {
+        let mut cpu = CPU::new();
+        // bc1t 2
+        let bc1t = instructions::get_instruction(0x4501_0002);
+        assert_eq!(bc1t.name(), "BC1T");
+        let bc1f = super::FpInstruction::build(0x08, 0, 0, 0, 2);
+        assert_eq!(bc1f.name(), "BC1F");
+
+        cpu.pc = 8;
+        bc1t.execute(&mut cpu);
+        assert_eq!(cpu.pc, 8);
+        bc1f.execute(&mut cpu);
+        assert_eq!(cpu.pc, 16);
+
+        cpu.cp1.set_condition(0, true);
+        cpu.pc = 8;
+        bc1t.execute(&mut cpu);
+        assert_eq!(cpu.pc, 16);
+    }
+
+    #[test]
+    fn test_bc1fl_skips_delay_slot_when_not_taken() {
+        let mut cpu = CPU::new();
+        cpu.cp1.set_condition(0, true);
+        cpu.pc = 8;
+        // addi $t0, $zero, 1 in the delay slot
+        cpu.memory.write(12, 0x20080001);
+
+        let instruction = super::FpInstruction::build(0x08, 0b10, 0, 0, 2);
+        assert_eq!(instruction.name(), "BC1FL");
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 12);
+        assert_eq!(cpu.read_register(8), 0);
+    }
+
+    #[test]
+    fn test_mtc1_mfc1() {
+        let mut cpu = CPU::new();
+        cpu.write_register(8, 0x4049_0fdb);
+
+        // mtc1 $t0, $f2 ; mfc1 $t1, $f2
+        let mtc1 = instructions::get_instruction(0x4488_1000);
+        assert_eq!(mtc1.name(), "MTC1");
+        mtc1.execute(&mut cpu);
+        assert_eq!(cpu.cp1.read_single(2), std::f32::consts::PI);
+
+        let mfc1 = instructions::get_instruction(0x4409_1000);
+        assert_eq!(mfc1.name(), "MFC1");
+        mfc1.execute(&mut cpu);
+        assert_eq!(cpu.read_register(9), 0x4049_0fdb);
+    }
+
+    #[test]
+    fn test_ctc1_sets_rounding() {
+        let mut cpu = CPU::new();
+        cpu.write_register(8, 1);
+
+        // ctc1 $t0, $31 ; cfc1 $t1, $31
+        super::FpInstruction::build(0x06, 8, 31, 0, 0).execute(&mut cpu);
+        assert_eq!(cpu.cp1.rounding(), crate::cp1::Rounding::Zero);
+        super::FpInstruction::build(0x02, 9, 31, 0, 0).execute(&mut cpu);
+        assert_eq!(cpu.read_register(9), 1);
+    }
+}
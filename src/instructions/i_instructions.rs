@@ -1,11 +1,12 @@
-use crate::CPU;
+use crate::cp0::Exception;
+use crate::{ExitReason, CPU};
 
 use crate::instructions::Executable;
 use crate::instructions::Instruction;
 
 #[derive(Clone)]
 pub struct ITypeInstruction {
-    opcode: u8,
+    pub opcode: u8,
     name: String,
     rs: u8,
     rt: u8,
@@ -15,9 +16,11 @@ pub struct ITypeInstruction {
 
 impl ITypeInstruction {
     pub fn new(instruction: u32) -> ITypeInstruction {
+        let opcode = (instruction >> 26) as u8;
+        let rt = ((instruction >> 16) & 0b11111) as u8;
         ITypeInstruction {
-            opcode: (instruction >> 26) as u8,
-            name: IFunction::new((instruction >> 26) as u8).name.clone(),
+            opcode,
+            name: Self::name_for(opcode, rt),
             rs: ((instruction >> 21) & 0b11111) as u8,
             rt: ((instruction >> 16) & 0b11111) as u8,
             imm: (instruction & 0xFFFF) as i16,
@@ -25,19 +28,42 @@ impl ITypeInstruction {
         }
     }
 
-    fn build(opcode: u8, rs: u8, rt: u8, imm: i16) -> ITypeInstruction {
+    pub fn build(opcode: u8, rs: u8, rt: u8, imm: i16) -> ITypeInstruction {
         ITypeInstruction {
             opcode,
-            name: IFunction::new(opcode).name.clone(),
+            name: Self::name_for(opcode, rt),
             rs,
             rt,
             imm,
             funct: IFunction::new(opcode),
         }
     }
+
+    // REGIMM (opcode 1) instructions are told apart by rt
+    fn name_for(opcode: u8, rt: u8) -> String {
+        if opcode != 0b000001 {
+            return IFunction::new(opcode).name;
+        }
+
+        match rt {
+            0x00 => String::from("BLTZ"),
+            0x01 => String::from("BGEZ"),
+            0x02 => String::from("BLTZL"),
+            0x03 => String::from("BGEZL"),
+            0x10 => String::from("BLTZAL"),
+            0x11 => String::from("BGEZAL"),
+            0x12 => String::from("BLTZALL"),
+            0x13 => String::from("BGEZALL"),
+            _ => String::from("UNKNOWN"),
+        }
+    }
 }
 
 impl Instruction for ITypeInstruction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
     fn decode(&self, cpu: &mut CPU) -> String {
         let rs_value = cpu.read_register(self.rs as usize);
         let rt_value = cpu.read_register(self.rt as usize);
@@ -65,35 +91,48 @@ impl IFunction {
                 0b001001 => String::from("ADDIU"),
                 0b001100 => String::from("ANDI"),
                 0b001101 => String::from("ORI"),
+                0b001110 => String::from("XORI"),
                 0b000100 => String::from("BEQ"),
                 0b000101 => String::from("BNE"),
                 0b000001 => String::from("BGEZ"),
                 0b000110 => String::from("BLEZ"),
+                0b000111 => String::from("BGTZ"),
+                0b010100 => String::from("BEQL"),
+                0b010101 => String::from("BNEL"),
+                0b010110 => String::from("BLEZL"),
+                0b010111 => String::from("BGTZL"),
                 0b100000 => String::from("LB"),
-                0b100101 => String::from("LH"),
+                0b100001 => String::from("LH"),
+                0b100100 => String::from("LBU"),
                 0b100101 => String::from("LHU"),
                 0b001111 => String::from("LUI"),
                 0b100011 => String::from("LW"),
                 0b001010 => String::from("SLTI"),
+                0b001011 => String::from("SLTIU"),
+                0b101000 => String::from("SB"),
+                0b101001 => String::from("SH"),
                 0b101011 => String::from("SW"),
+                0b110000 => String::from("LL"),
+                0b111000 => String::from("SC"),
+                0b110001 => String::from("LWC1"),
+                0b111001 => String::from("SWC1"),
                 _ => String::from("UNKNOWN"),
             },
         }
     }
 
-    fn decode(&self) -> String {
-        format!("{} {}", self.name, self.funct)
-    }
 }
 
 impl Executable<ITypeInstruction> for IFunction {
     fn execute(&self, instruction: ITypeInstruction, cpu: &mut crate::CPU) {
         match self.funct {
-            // ADDI
+            // ADDI: rt is left alone on overflow
             0b001000 => {
                 let rs = cpu.registers[instruction.rs as usize].read();
-                let imm = instruction.imm as u32;
-                cpu.write_register(instruction.rt as usize,rs.wrapping_add(imm));
+                match (rs as i32).checked_add(instruction.imm as i32) {
+                    Some(sum) => cpu.write_register(instruction.rt as usize, sum as u32),
+                    None => cpu.raise(Exception::Overflow, ExitReason::Overflow(cpu.pc)),
+                }
             }
 
             // ADDIU
@@ -104,20 +143,27 @@ impl Executable<ITypeInstruction> for IFunction {
                 cpu.write_register(instruction.rt as usize,rs.wrapping_add(imm));
             }
 
-            // ANDI
+            // ANDI: the immediate is zero-extended
             0b001100 => {
                 let rs = cpu.registers[instruction.rs as usize].read();
-                let imm = instruction.imm as u32;
+                let imm = instruction.imm as u16 as u32;
                 cpu.write_register(instruction.rt as usize,rs & imm);
             }
 
-            // ORI
+            // ORI: the immediate is zero-extended
             0b001101 => {
                 let rs = cpu.registers[instruction.rs as usize].read();
-                let imm = instruction.imm as u32;
+                let imm = instruction.imm as u16 as u32;
                 cpu.write_register(instruction.rt as usize,rs | imm);
             }
 
+            // XORI: the immediate is zero-extended
+            0b001110 => {
+                let rs = cpu.registers[instruction.rs as usize].read();
+                let imm = instruction.imm as u16 as u32;
+                cpu.write_register(instruction.rt as usize, rs ^ imm);
+            }
+
             // BEQ
             0b000100 => {
                 let rs = cpu.registers[instruction.rs as usize].read();
@@ -141,33 +187,137 @@ impl Executable<ITypeInstruction> for IFunction {
                 }
             }
 
-            // BGEZ
+            // REGIMM: rt picks the instruction; its low bit branches on
+            // rs >= 0 instead of rs < 0
             0b000001 => {
-                let rs = cpu.registers[instruction.rs as usize].read();
-                let rt = cpu.registers[instruction.rt as usize].read();
-                if rs >= rt {
-                    cpu.run_branch_delayed();
-                    cpu.pc = cpu.pc.wrapping_add((instruction.imm as u32) << 2);
+                let rs = cpu.read_register(instruction.rs as usize) as i32;
+                let taken = if instruction.rt & 1 == 0 { rs < 0 } else { rs >= 0 };
+
+                match instruction.rt {
+                    // BLTZ, BGEZ
+                    0x00 | 0x01 => {
+                        if taken {
+                            cpu.run_branch_delayed();
+                            cpu.pc = cpu.pc.wrapping_add((instruction.imm as u32) << 2);
+                        }
+                    }
+
+                    // BLTZAL, BGEZAL: link whether or not the branch is
+                    // taken, before the delay slot runs
+                    0x10 | 0x11 => {
+                        let ra = cpu.pc.wrapping_add(8);
+                        cpu.write_register(31, ra);
+                        if taken {
+                            cpu.run_branch_delayed();
+                            cpu.pc = cpu.pc.wrapping_add((instruction.imm as u32) << 2);
+                        }
+                    }
+
+                    // BLTZL, BGEZL, BLTZALL, BGEZALL
+                    0x02 | 0x03 | 0x12 | 0x13 => {
+                        if instruction.rt & 0x10 != 0 {
+                            let ra = cpu.pc.wrapping_add(8);
+                            cpu.write_register(31, ra);
+                        }
+                        cpu.branch_likely(taken, instruction.imm);
+                    }
+                    _ => cpu.reserved_instruction(),
                 }
             }
 
             // BLEZ
             0b000110 => {
-                let rs = cpu.registers[instruction.rs as usize].read();
-                let rt = cpu.registers[instruction.rt as usize].read();
-                if rs <= rt {
+                let rs = cpu.read_register(instruction.rs as usize) as i32;
+                if rs <= 0 {
+                    cpu.run_branch_delayed();
+                    cpu.pc = cpu.pc.wrapping_add((instruction.imm as u32) << 2);
+                }
+            }
+
+            // BGTZ
+            0b000111 => {
+                let rs = cpu.read_register(instruction.rs as usize) as i32;
+                if rs > 0 {
                     cpu.run_branch_delayed();
                     cpu.pc = cpu.pc.wrapping_add((instruction.imm as u32) << 2);
                 }
             }
 
-            // LB
-            0b100000 => {
+            // BEQL
+            0b010100 => {
+                let rs = cpu.read_register(instruction.rs as usize);
+                let rt = cpu.read_register(instruction.rt as usize);
+                cpu.branch_likely(rs == rt, instruction.imm);
+            }
+
+            // BNEL
+            0b010101 => {
+                let rs = cpu.read_register(instruction.rs as usize);
+                let rt = cpu.read_register(instruction.rt as usize);
+                cpu.branch_likely(rs != rt, instruction.imm);
+            }
+
+            // BLEZL
+            0b010110 => {
+                let rs = cpu.read_register(instruction.rs as usize) as i32;
+                cpu.branch_likely(rs <= 0, instruction.imm);
+            }
+
+            // BGTZL
+            0b010111 => {
+                let rs = cpu.read_register(instruction.rs as usize) as i32;
+                cpu.branch_likely(rs > 0, instruction.imm);
+            }
+
+            // LB, LH: sign-extended
+            0b100000 | 0b100001 => {
                 let rs = cpu.registers[instruction.rs as usize].read();
-                let imm = instruction.imm as u32;
-                let address = rs.wrapping_add(imm);
-                let value = cpu.memory.read_byte(address);
-                cpu.write_register(instruction.rt as usize,value as u32);
+                let address = rs.wrapping_add(instruction.imm as u32);
+                let value = if self.funct == 0b100000 {
+                    cpu.load(address, 1).map(|byte| byte as u8 as i8 as u32)
+                } else {
+                    cpu.load(address, 2).map(|half| half as u16 as i16 as u32)
+                };
+
+                if let Some(value) = value {
+                    cpu.write_loaded(instruction.rt as usize, value);
+                }
+            }
+
+            // LBU, LHU: zero-extended
+            0b100100 | 0b100101 => {
+                let rs = cpu.registers[instruction.rs as usize].read();
+                let address = rs.wrapping_add(instruction.imm as u32);
+                let size = if self.funct == 0b100100 { 1 } else { 2 };
+
+                if let Some(value) = cpu.load(address, size) {
+                    cpu.write_loaded(instruction.rt as usize, value);
+                }
+            }
+
+            // SB, SH
+            0b101000 | 0b101001 => {
+                let rs = cpu.registers[instruction.rs as usize].read();
+                let address = rs.wrapping_add(instruction.imm as u32);
+                let size = if self.funct == 0b101000 { 1 } else { 2 };
+                let value = cpu.read_register(instruction.rt as usize);
+
+                cpu.store(address, size, value);
+            }
+
+            // SLTI
+            0b001010 => {
+                let rs = cpu.read_register(instruction.rs as usize) as i32;
+                let imm = instruction.imm as i32;
+                cpu.write_register(instruction.rt as usize, (rs < imm) as u32);
+            }
+
+            // SLTIU: the immediate is sign-extended like every arithmetic
+            // immediate, then both sides compare as unsigned
+            0b001011 => {
+                let rs = cpu.read_register(instruction.rs as usize);
+                let imm = instruction.imm as i32 as u32;
+                cpu.write_register(instruction.rt as usize, (rs < imm) as u32);
             }
 
             // LUI
@@ -180,10 +330,11 @@ impl Executable<ITypeInstruction> for IFunction {
             0b100011 => {
                 let rs_address = cpu.read_register(instruction.rs as usize);
                 let imm = instruction.imm as u32;
-                let word = cpu.memory.read_byte(rs_address + imm);
 
                 //println!("LW - Loading from address {} + {} the data: {} - into register: {}", rs_address, imm, word, instruction.rt);
-                cpu.write_register(instruction.rt as usize, word);
+                if let Some(word) = cpu.load(rs_address.wrapping_add(imm), 4) {
+                    cpu.write_loaded(instruction.rt as usize, word);
+                }
             }
 
             // SW
@@ -193,9 +344,53 @@ impl Executable<ITypeInstruction> for IFunction {
 
                 let word = cpu.read_register(instruction.rt as usize);
 
-                cpu.memory.write((rs_address + imm), word);
+                cpu.store(rs_address.wrapping_add(imm), 4, word);
+            }
+
+            // LWC1: rt names the FPR
+            0b110001 => {
+                let rs = cpu.read_register(instruction.rs as usize);
+                let address = rs.wrapping_add(instruction.imm as u32);
+
+                if let Some(word) = cpu.load(address, 4) {
+                    cpu.cp1.write(instruction.rt, word);
+                }
+            }
+
+            // SWC1
+            0b111001 => {
+                let rs = cpu.read_register(instruction.rs as usize);
+                let address = rs.wrapping_add(instruction.imm as u32);
+
+                let word = cpu.cp1.read(instruction.rt);
+                cpu.store(address, 4, word);
+            }
+
+            // LL: a word load that links its address
+            0b110000 => {
+                let rs = cpu.read_register(instruction.rs as usize);
+                let address = rs.wrapping_add(instruction.imm as u32);
+
+                if let Some(word) = cpu.load(address, 4) {
+                    cpu.link = Some(address);
+                    cpu.write_register(instruction.rt as usize, word);
+                }
+            }
+
+            // SC: stores only if nothing was stored to the linked word since
+            // its LL; rt gets 1 on success and 0 on failure
+            0b111000 => {
+                let rs = cpu.read_register(instruction.rs as usize);
+                let address = rs.wrapping_add(instruction.imm as u32);
+                let linked = cpu.link.take() == Some(address);
+
+                if linked {
+                    let word = cpu.read_register(instruction.rt as usize);
+                    cpu.store(address, 4, word);
+                }
+                cpu.write_register(instruction.rt as usize, linked as u32);
             }
-            _ => panic!("Unknown IType instruction, {:#06x}", self.funct),
+            _ => cpu.reserved_instruction(),
         }
     }
 }
@@ -204,6 +399,35 @@ impl Executable<ITypeInstruction> for IFunction {
 mod tests {
     use crate::instructions::Instruction;
 
+    #[test]
+    fn test_slti() {
+        let mut cpu = super::CPU::new();
+        // slti $t0, $t1, -1: -5 is below, 0 is not
+        let instruction = super::ITypeInstruction::build(0b001010, 9, 8, -1);
+        cpu.registers[9].write(-5i32 as u32);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[8].read(), 1);
+
+        cpu.registers[9].write(0);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[8].read(), 0);
+    }
+
+    #[test]
+    fn test_sltiu() {
+        let mut cpu = super::CPU::new();
+        // sltiu $t0, $t1, -1: below 0xffffffff for anything but itself
+        let instruction = super::ITypeInstruction::build(0b001011, 9, 8, -1);
+        cpu.registers[9].write(0xffff_fffe);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[8].read(), 1);
+
+        let instruction = super::ITypeInstruction::build(0b001011, 9, 8, 10);
+        cpu.registers[9].write(-5i32 as u32);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[8].read(), 0);
+    }
+
     #[test]
     fn test_addi() {
         let mut cpu = super::CPU::new();
@@ -215,6 +439,19 @@ mod tests {
         assert_eq!(cpu.registers[instruction.rt as usize].read(), 10);
     }
 
+    #[test]
+    fn test_addi_overflow() {
+        let mut cpu = super::CPU::new();
+        cpu.pc = 0x00400004;
+        cpu.write_register(2, 0x8000_0000);
+        cpu.write_register(3, 99);
+
+        super::ITypeInstruction::build(0b001000, 2, 3, -1).execute(&mut cpu);
+
+        assert_eq!(cpu.exit, Some(crate::ExitReason::Overflow(0x00400004)));
+        assert_eq!(cpu.read_register(3), 99);
+    }
+
     #[test]
     fn test_addiu() {
         let mut cpu = super::CPU::new();
@@ -237,6 +474,16 @@ mod tests {
         assert_eq!(cpu.registers[instruction.rt as usize].read(), 0b0100);
     }
 
+    #[test]
+    fn test_andi_zero_extends() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::ITypeInstruction::build(0b001100, 2, 3, -0x8000);
+
+        cpu.registers[2].write(0xffff_ffff);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[3].read(), 0x8000);
+    }
+
     #[test]
     fn test_ori() {
         let mut cpu = super::CPU::new();
@@ -248,6 +495,16 @@ mod tests {
         assert_eq!(cpu.registers[instruction.rt as usize].read(), 0b1110);
     }
 
+    #[test]
+    fn test_xori() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::ITypeInstruction::build(0b001110, 2, 3, -1);
+
+        cpu.registers[instruction.rs as usize].write(0xffff_0ff0);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[instruction.rt as usize].read(), 0xffff_f00f);
+    }
+
     #[test]
     fn test_beq() {
         let mut cpu = super::CPU::new();
@@ -281,10 +538,9 @@ mod tests {
         let mut cpu = super::CPU::new();
         cpu.pc = 8;
 
-        let instruction = super::ITypeInstruction::build(0b000001, 2, 3, 2);
+        let instruction = super::ITypeInstruction::build(0b000001, 2, 1, 2);
 
-        cpu.registers[instruction.rs as usize].write(3 as u32);
-        cpu.write_register(instruction.rt as usize,2 as u32);
+        cpu.registers[instruction.rs as usize].write(0);
         instruction.execute(&mut cpu);
         assert_eq!(cpu.pc, 16);
     }
@@ -294,23 +550,47 @@ mod tests {
         let mut cpu = super::CPU::new();
         cpu.pc = 8;
 
-        let instruction = super::ITypeInstruction::build(0b000001, 2, 3, 2);
+        let instruction = super::ITypeInstruction::build(0b000001, 2, 1, 2);
 
-        cpu.registers[instruction.rs as usize].write(1 as u32);
-        cpu.write_register(instruction.rt as usize,2 as u32);
+        cpu.registers[instruction.rs as usize].write(-1i32 as u32);
         instruction.execute(&mut cpu);
         assert_eq!(cpu.pc, 8);
     }
 
+    #[test]
+    fn test_bltz() {
+        let instruction = super::ITypeInstruction::build(0b000001, 2, 0, 2);
+        assert_eq!(instruction.name(), "BLTZ");
+
+        let mut cpu = super::CPU::new();
+        cpu.pc = 8;
+        cpu.registers[2].write(-1i32 as u32);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 16);
+
+        cpu.pc = 8;
+        cpu.registers[2].write(0);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 8);
+    }
+
+    #[test]
+    fn test_regimm_names() {
+        let names: Vec<String> = [0x00, 0x01, 0x10, 0x11, 0x1f]
+            .iter()
+            .map(|&rt| super::ITypeInstruction::build(0b000001, 2, rt, 0).name().to_string())
+            .collect();
+        assert_eq!(names, ["BLTZ", "BGEZ", "BLTZAL", "BGEZAL", "UNKNOWN"]);
+    }
+
     #[test]
     fn test_blez() {
         let mut cpu = super::CPU::new();
         cpu.pc = 8;
 
-        let instruction = super::ITypeInstruction::build(0b000110, 2, 3, 2);
+        let instruction = super::ITypeInstruction::build(0b000110, 2, 0, 2);
 
-        cpu.registers[instruction.rs as usize].write(1 as u32);
-        cpu.write_register(instruction.rt as usize,2 as u32);
+        cpu.registers[instruction.rs as usize].write(-1i32 as u32);
         instruction.execute(&mut cpu);
         assert_eq!(cpu.pc, 16);
     }
@@ -320,12 +600,236 @@ mod tests {
         let mut cpu = super::CPU::new();
         cpu.pc = 8;
 
-        let instruction = super::ITypeInstruction::build(0b000110, 2, 3, 2);
+        let instruction = super::ITypeInstruction::build(0b000110, 2, 0, 2);
+
+        cpu.registers[instruction.rs as usize].write(3);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 8);
+    }
+
+    #[test]
+    fn test_bgtz() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::ITypeInstruction::build(0b000111, 2, 0, 2);
+
+        cpu.pc = 8;
+        cpu.registers[2].write(1);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 16);
+
+        cpu.pc = 8;
+        cpu.registers[2].write(0);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 8);
+    }
+
+    #[test]
+    fn test_beql() {
+        let mut cpu = super::CPU::new();
+        cpu.pc = 8;
+
+        let instruction = super::ITypeInstruction::build(0b010100, 2, 3, 2);
+
+        cpu.write_register(2, 5);
+        cpu.write_register(3, 5);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 16);
+    }
+
+    #[test]
+    fn test_bnel_false_skips_delay_slot() {
+        let mut cpu = super::CPU::new();
+        cpu.pc = 8;
+        // addi $t0, $zero, 1 in the delay slot
+        cpu.memory.write(12, 0x20080001);
+
+        let instruction = super::ITypeInstruction::build(0b010101, 2, 3, 2);
 
-        cpu.registers[instruction.rs as usize].write(3 as u32);
-        cpu.write_register(instruction.rt as usize,2 as u32);
         instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 12);
+        assert_eq!(cpu.read_register(8), 0);
+    }
+
+    #[test]
+    fn test_blezl_bgtzl() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, -1i32 as u32);
+
+        cpu.pc = 8;
+        super::ITypeInstruction::build(0b010110, 2, 0, 2).execute(&mut cpu);
+        assert_eq!(cpu.pc, 16);
+
+        cpu.pc = 8;
+        super::ITypeInstruction::build(0b010111, 2, 0, 2).execute(&mut cpu);
+        assert_eq!(cpu.pc, 12);
+    }
+
+    #[test]
+    fn test_bltzall() {
+        let mut cpu = super::CPU::new();
+        cpu.pc = 8;
+
+        let instruction = super::ITypeInstruction::new(0x0452_0002);
+        assert_eq!(instruction.name(), "BLTZALL");
+
+        cpu.write_register(2, 1);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 12);
+        assert_eq!(cpu.read_register(31), 16);
+    }
+
+    #[test]
+    fn test_bgezal() {
+        let mut cpu = super::CPU::new();
+        cpu.pc = 8;
+
+        // bgezal $v0, 2
+        let instruction = super::ITypeInstruction::new(0x0451_0002);
+        assert_eq!(instruction.name(), "BGEZAL");
+
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 16);
+        assert_eq!(cpu.read_register(31), 16);
+    }
+
+    #[test]
+    fn test_delay_slot_sees_the_link() {
+        // bgezal $zero, 2 and bgezall $zero, 2 with addu $t0, $ra, $zero in
+        // the delay slot
+        for word in [0x0411_0002, 0x0413_0002] {
+            let mut cpu = super::CPU::new();
+            cpu.pc = 8;
+            cpu.memory.write(12, 0x03e0_4021);
+
+            super::ITypeInstruction::new(word).execute(&mut cpu);
+            assert_eq!(cpu.pc, 16);
+            assert_eq!(cpu.read_register(8), 16, "{:#010x}", word);
+        }
+    }
+
+    #[test]
+    fn test_bltzal_links_when_not_taken() {
+        let mut cpu = super::CPU::new();
+        cpu.pc = 8;
+        cpu.write_register(2, 1);
+
+        // bltzal $v0, 2
+        super::ITypeInstruction::new(0x0450_0002).execute(&mut cpu);
         assert_eq!(cpu.pc, 8);
+        assert_eq!(cpu.read_register(31), 16);
+    }
+
+    #[test]
+    fn test_lw_sw() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, 0x10010000);
+        cpu.write_register(3, 0xdeadbeef);
+
+        super::ITypeInstruction::build(0b101011, 2, 3, 4).execute(&mut cpu);
+        super::ITypeInstruction::build(0b100011, 2, 4, 4).execute(&mut cpu);
+
+        assert_eq!(cpu.read_register(4), 0xdeadbeef);
+    }
+
+    #[test]
+    fn test_lw_sw_negative_offset() {
+        let mut cpu = super::CPU::new();
+        // sw $t1, -8($sp) ; lw $t2, -8($sp)
+        cpu.write_register(9, 0x0bad_cafe);
+        super::ITypeInstruction::build(0b101011, 29, 9, -8).execute(&mut cpu);
+        super::ITypeInstruction::build(0b100011, 29, 10, -8).execute(&mut cpu);
+
+        assert_eq!(cpu.memory.read(0x7fffeff4), 0x0bad_cafe);
+        assert_eq!(cpu.read_register(10), 0x0bad_cafe);
+    }
+
+    #[test]
+    fn test_ll_sc() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, 0x10010000);
+        cpu.memory.write(0x10010000, 41);
+
+        // ll $t0, 0($v0) ; addi $t0, $t0, 1 ; sc $t0, 0($v0)
+        super::ITypeInstruction::build(0b110000, 2, 8, 0).execute(&mut cpu);
+        super::ITypeInstruction::build(0b001000, 8, 8, 1).execute(&mut cpu);
+        super::ITypeInstruction::build(0b111000, 2, 8, 0).execute(&mut cpu);
+
+        assert_eq!(cpu.memory.read(0x10010000), 42);
+        assert_eq!(cpu.read_register(8), 1);
+    }
+
+    #[test]
+    fn test_sc_fails_after_intervening_store() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, 0x10010000);
+        cpu.write_register(3, 7);
+        cpu.write_register(8, 99);
+
+        super::ITypeInstruction::build(0b110000, 2, 9, 0).execute(&mut cpu);
+        // sb $v1, 2($v0) touches the linked word
+        super::ITypeInstruction::build(0b101000, 2, 3, 2).execute(&mut cpu);
+        super::ITypeInstruction::build(0b111000, 2, 8, 0).execute(&mut cpu);
+
+        assert_eq!(cpu.memory.read(0x10010000), 0x0007_0000);
+        assert_eq!(cpu.read_register(8), 0);
+
+        // A second SC without an LL fails too
+        cpu.write_register(8, 99);
+        super::ITypeInstruction::build(0b111000, 2, 8, 0).execute(&mut cpu);
+        assert_eq!(cpu.read_register(8), 0);
+    }
+
+    #[test]
+    fn test_lwc1_swc1() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, 0x10010000);
+        cpu.cp1.write_single(4, 1.5);
+
+        // swc1 $f4, 8($v0) ; lwc1 $f6, 8($v0)
+        let swc1 = super::ITypeInstruction::build(0b111001, 2, 4, 8);
+        assert_eq!(swc1.name(), "SWC1");
+        swc1.execute(&mut cpu);
+        super::ITypeInstruction::build(0b110001, 2, 6, 8).execute(&mut cpu);
+
+        assert_eq!(cpu.memory.read(0x10010008), 0x3fc0_0000);
+        assert_eq!(cpu.cp1.read_single(6), 1.5);
+        assert_eq!(cpu.read_register(6), 0);
+    }
+
+    #[test]
+    fn test_lwc1_unaligned_faults() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, 0x10010000);
+
+        super::ITypeInstruction::build(0b110001, 2, 6, 2).execute(&mut cpu);
+
+        assert_eq!(cpu.exit, Some(crate::ExitReason::UnalignedAccess(0x10010002)));
+    }
+
+    #[test]
+    fn test_lw_unaligned_faults() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, 0x10010000);
+
+        super::ITypeInstruction::build(0b100011, 2, 4, 2).execute(&mut cpu);
+
+        assert_eq!(cpu.exit, Some(crate::ExitReason::UnalignedAccess(0x10010002)));
+    }
+
+    #[test]
+    fn test_unaligned_emulated() {
+        let mut cpu = super::CPU::new();
+        cpu.set_alignment(crate::Alignment::Emulate);
+        cpu.write_register(2, 0x10010000);
+        cpu.write_register(3, 0x44332211);
+
+        super::ITypeInstruction::build(0b101011, 2, 3, 3).execute(&mut cpu);
+        assert_eq!(cpu.memory.read(0x10010000), 0x11000000);
+        assert_eq!(cpu.memory.read(0x10010004), 0x00443322);
+
+        super::ITypeInstruction::build(0b100011, 2, 4, 3).execute(&mut cpu);
+        assert_eq!(cpu.read_register(4), 0x44332211);
+        assert_eq!(cpu.unaligned_accesses(), 2);
     }
 
     #[test]
@@ -335,14 +839,85 @@ mod tests {
 
         let value: u32 = "d".as_bytes()[0] as u32;
         cpu.registers[instruction.rs as usize].write(0);
-        cpu.memory.write(2, value as u32);
+        cpu.memory.write_u8(2, value as u8);
         instruction.execute(&mut cpu);
         assert_eq!(cpu.registers[instruction.rt as usize].read(), value);
     }
 
+    #[test]
+    fn test_lb_lbu_extension() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, 0x10010000);
+        cpu.memory.write(0x10010000, 0x8081_7f80);
+
+        super::ITypeInstruction::build(0b100000, 2, 3, 0).execute(&mut cpu);
+        super::ITypeInstruction::build(0b100100, 2, 4, 0).execute(&mut cpu);
+        super::ITypeInstruction::build(0b100000, 2, 5, 1).execute(&mut cpu);
+
+        assert_eq!(cpu.read_register(3), 0xffff_ff80);
+        assert_eq!(cpu.read_register(4), 0x80);
+        assert_eq!(cpu.read_register(5), 0x7f);
+    }
+
+    #[test]
+    fn test_lh_lhu_extension() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, 0x10010000);
+        cpu.memory.write(0x10010000, 0x8081_7f80);
+
+        super::ITypeInstruction::build(0b100001, 2, 3, 2).execute(&mut cpu);
+        super::ITypeInstruction::build(0b100101, 2, 4, 2).execute(&mut cpu);
+        super::ITypeInstruction::build(0b100001, 2, 5, 0).execute(&mut cpu);
+
+        assert_eq!(cpu.read_register(3), 0xffff_8081);
+        assert_eq!(cpu.read_register(4), 0x8081);
+        assert_eq!(cpu.read_register(5), 0x7f80);
+    }
+
+    #[test]
+    fn test_negative_byte_and_half_round_trip() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, 0x10010000);
+        cpu.write_register(3, -100i32 as u32);
+
+        super::ITypeInstruction::build(0b101000, 2, 3, 3).execute(&mut cpu);
+        super::ITypeInstruction::build(0b101001, 2, 3, 6).execute(&mut cpu);
+        super::ITypeInstruction::build(0b100000, 2, 4, 3).execute(&mut cpu);
+        super::ITypeInstruction::build(0b100001, 2, 5, 6).execute(&mut cpu);
+        super::ITypeInstruction::build(0b100100, 2, 6, 3).execute(&mut cpu);
+        super::ITypeInstruction::build(0b100101, 2, 7, 6).execute(&mut cpu);
+
+        assert_eq!(cpu.read_register(4) as i32, -100);
+        assert_eq!(cpu.read_register(5) as i32, -100);
+        assert_eq!(cpu.read_register(6), 0x9c);
+        assert_eq!(cpu.read_register(7), 0xff9c);
+    }
+
+    #[test]
+    fn test_sb_sh() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, 0x10010000);
+        cpu.write_register(3, 0x1234_5678);
+
+        super::ITypeInstruction::build(0b101000, 2, 3, 1).execute(&mut cpu);
+        super::ITypeInstruction::build(0b101001, 2, 3, 2).execute(&mut cpu);
+
+        assert_eq!(cpu.memory.read(0x10010000), 0x5678_7800);
+    }
+
+    #[test]
+    fn test_lh_unaligned_faults() {
+        let mut cpu = super::CPU::new();
+        cpu.write_register(2, 0x10010000);
+
+        super::ITypeInstruction::build(0b100001, 2, 3, 1).execute(&mut cpu);
+
+        assert_eq!(cpu.exit, Some(crate::ExitReason::UnalignedAccess(0x10010001)));
+    }
+
     #[test]
     fn test_shift() {
-        let imm = 10 as u32;
+        let imm: u32 = 10;
         assert_eq!(imm << 2, imm * 4)
     }
 }
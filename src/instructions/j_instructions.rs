@@ -1,10 +1,9 @@
 use crate::instructions::Executable;
 use crate::instructions::Instruction;
-use crate::CPU;
 
 #[derive(Clone)]
 pub struct JTypeInstruction {
-    opcode: u8,
+    pub opcode: u8,
     name: String,
     address: u32,
     funct: JFunction
@@ -15,14 +14,14 @@ impl JTypeInstruction {
         let opcode = (instruction >> 26) as u8;
         let function = JFunction::new(opcode);
         JTypeInstruction {
-            opcode: opcode,
+            opcode,
             name: function.name.clone(),
             address: instruction & 0x3FFFFFF,
             funct: function
         }
     }
 
-    fn build(opcode: u8, address: u32) -> JTypeInstruction {
+    pub fn build(opcode: u8, address: u32) -> JTypeInstruction {
         let function = JFunction::new(opcode);
         JTypeInstruction {
             opcode,
@@ -34,7 +33,11 @@ impl JTypeInstruction {
 }
 
 impl Instruction for JTypeInstruction {
-    fn decode(&self, cpu: &mut crate::CPU) -> String {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn decode(&self, _cpu: &mut crate::CPU) -> String {
         format!("{} {} {}", self.name, self.funct.decode(), self.address << 2)
     }
 
@@ -72,10 +75,10 @@ impl Executable<JTypeInstruction> for JFunction {
     fn execute(&self, instruction: JTypeInstruction, cpu: &mut crate::CPU) {
         match self.opcode {
 
-            // J
+            // J: the target keeps the top four bits of the delay slot's address
             0b000010 => {
                 cpu.run_branch_delayed();
-                let next = (cpu.pc & 0xf000000) | (instruction.address << 2);
+                let next = (cpu.pc.wrapping_add(4) & 0xf0000000) | (instruction.address << 2);
                 cpu.pc = next;
                 cpu.jump = true;
             }
@@ -83,13 +86,13 @@ impl Executable<JTypeInstruction> for JFunction {
             // JAL
             0b000011 => {
                 cpu.run_branch_delayed();
-                let next = (cpu.pc & 0xf000000) | (instruction.address << 2);
+                let next = (cpu.pc.wrapping_add(4) & 0xf0000000) | (instruction.address << 2);
                 let ra = cpu.pc + 8;
-                cpu.registers[31].write(ra);
+                cpu.write_register(31, ra);
                 cpu.pc = next;
                 cpu.jump = true
             }
-            _ => panic!("Invalid J-Type instruction")
+            _ => cpu.reserved_instruction(),
         }
     }
 }
@@ -100,7 +103,7 @@ mod test {
 
     #[test]
     fn test_j_type_instruction() {
-        let mut cpu = super::CPU::new();
+        let mut cpu = crate::CPU::new();
         let instruction = super::JTypeInstruction::new(0x08000001);
         assert_eq!(instruction.opcode, 0b000010);
         assert_eq!(instruction.address, 0x000001);
@@ -112,7 +115,7 @@ mod test {
 
     #[test]
     fn test_jal(){
-        let mut cpu = super::CPU::new();
+        let mut cpu = crate::CPU::new();
         let instruction = super::JTypeInstruction::new(0x0C000001);
         assert_eq!(instruction.opcode, 0b000011);
         assert_eq!(instruction.address, 0x000001);
@@ -122,4 +125,28 @@ mod test {
         assert_eq!(cpu.pc, 0x000001 << 2);
         assert_eq!(cpu.registers[31].read(), 0x00000008);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_j_keeps_region_bits() {
+        let mut cpu = crate::CPU::new();
+        cpu.pc = 0x1040_0000;
+        let instruction = super::JTypeInstruction::new(0x0810_0010);
+
+        instruction.execute(&mut cpu);
+
+        assert_eq!(cpu.pc, 0x1040_0040);
+    }
+
+    #[test]
+    fn test_jal_region_comes_from_the_delay_slot() {
+        let mut cpu = crate::CPU::new();
+        // the jump sits in the last word of a 256 MB region
+        cpu.pc = 0x1fff_fffc;
+        let instruction = super::JTypeInstruction::new(0x0C00_0004);
+
+        instruction.execute(&mut cpu);
+
+        assert_eq!(cpu.pc, 0x2000_0010);
+        assert_eq!(cpu.registers[31].read(), 0x2000_0004);
+    }
+}
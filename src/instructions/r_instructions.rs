@@ -1,14 +1,13 @@
-use std::io;
-use std::io::Write;
-
-use crate::CPU;
+use crate::cp0::Exception;
+use crate::syscall;
+use crate::{ExitReason, CPU};
 
 use crate::instructions::Executable;
 use crate::instructions::Instruction;
 
 #[derive(Clone)]
 pub struct RTypeInstruction {
-    opcode: u8,
+    pub opcode: u8,
     name: String,
     pub rd: u8,
     pub rs: u8,
@@ -18,7 +17,16 @@ pub struct RTypeInstruction {
 }
 
 impl Instruction for RTypeInstruction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
     fn decode(&self, cpu: &mut CPU) -> String {
+        if self.funct.funct == 0x09 {
+            let rs_value = cpu.read_register(self.rs as usize);
+            return format!("{} rd {}, rs {}: {:#010x}", self.name, self.link_register(), self.rs, rs_value);
+        }
+
         let rd_value = cpu.read_register(self.rd as usize);
         let rs_value = cpu.read_register(self.rs as usize);
         let rt_value = cpu.read_register(self.rt as usize);
@@ -34,7 +42,11 @@ impl RTypeInstruction {
     pub fn new(instruction: u32) -> RTypeInstruction {
         RTypeInstruction {
             opcode: (instruction >> 26) as u8,
-            name: RFunction::new((instruction & 0b111111) as u8).name.clone(),
+            name: Self::name_for(
+                (instruction & 0b111111) as u8,
+                ((instruction >> 21) & 0b11111) as u8,
+                ((instruction >> 6) & 0b11111) as u8,
+            ),
             rs: ((instruction >> 21) & 0b11111) as u8,
             rt: ((instruction >> 16) & 0b11111) as u8,
             rd: ((instruction >> 11) & 0b11111) as u8,
@@ -45,15 +57,32 @@ impl RTypeInstruction {
 
     pub fn build(opcode: u8, rd: u8, rs: u8, rt: u8, shamt: u8, funct: u8) -> RTypeInstruction {
         RTypeInstruction {
-            opcode: opcode,
-            name: RFunction::new(funct).name.clone(),
-            rd: rd,
-            rs: rs,
-            rt: rt,
-            shamt: shamt,
+            opcode,
+            name: Self::name_for(funct, rs, shamt),
+            rd,
+            rs,
+            rt,
+            shamt,
             funct: RFunction::new(funct),
         }
     }
+
+    // JALR with rd 0 links through $ra, as the one-operand form does
+    fn link_register(&self) -> u8 {
+        match self.rd {
+            0 => 31,
+            rd => rd,
+        }
+    }
+
+    // ROTR and ROTRV reuse SRL and SRLV, marked by bit 0 of rs and shamt
+    fn name_for(funct: u8, rs: u8, shamt: u8) -> String {
+        match funct {
+            0x02 if rs & 1 == 1 => String::from("ROTR"),
+            0x06 if shamt & 1 == 1 => String::from("ROTRV"),
+            _ => RFunction::new(funct).name,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -65,40 +94,62 @@ struct RFunction {
 impl RFunction {
     fn new(funct: u8) -> RFunction {
         RFunction {
-            funct: funct,
+            funct,
             name: match funct {
                 0x20 => String::from("ADD"),
                 0x21 => String::from("ADDU"),
                 0x22 => String::from("SUB"),
+                0x23 => String::from("SUBU"),
                 0x24 => String::from("AND"),
                 0x25 => String::from("OR"),
                 0x26 => String::from("XOR"),
                 0x27 => String::from("NOR"),
                 0x2A => String::from("SLT"),
+                0x2B => String::from("SLTU"),
+                0x30 => String::from("TGE"),
+                0x31 => String::from("TGEU"),
+                0x32 => String::from("TLT"),
+                0x33 => String::from("TLTU"),
+                0x34 => String::from("TEQ"),
+                0x36 => String::from("TNE"),
                 0x00 => String::from("SLL"),
-                0x0d => String::from("NOOP"),
+                0x0d => String::from("BREAK"),
                 0x02 => String::from("SRL"),
                 0x03 => String::from("SRA"),
+                0x04 => String::from("SLLV"),
+                0x06 => String::from("SRLV"),
+                0x07 => String::from("SRAV"),
                 0x08 => String::from("JR"),
+                0x0a => String::from("MOVZ"),
+                0x0b => String::from("MOVN"),
+                0x09 => String::from("JALR"),
                 0x0c => String::from("SYSCALL"),
-                _ => String::from(format!("unknown {} ||||", funct)),
+                0x10 => String::from("MFHI"),
+                0x11 => String::from("MTHI"),
+                0x12 => String::from("MFLO"),
+                0x13 => String::from("MTLO"),
+                0x18 => String::from("MULT"),
+                0x19 => String::from("MULTU"),
+                0x1a => String::from("DIV"),
+                0x1b => String::from("DIVU"),
+                _ => format!("unknown {} ||||", funct),
             },
         }
     }
 
-    fn decode(&self) -> String {
-        format!("funct: {}, name: {}", self.funct, self.name)
-    }
 }
 
 impl Executable<RTypeInstruction> for RFunction {
     fn execute(&self, r_instruction: RTypeInstruction, cpu: &mut CPU) {
         match self.funct {
-            // Add
+            // Add: rd is left alone on overflow
             0x20 => {
                 let rs = cpu.registers[r_instruction.rs as usize].read();
                 let rt = cpu.registers[r_instruction.rt as usize].read();
-                cpu.write_register(r_instruction.rd as usize,rs.wrapping_add(rt));
+                match (rs as i32).checked_add(rt as i32) {
+                    Some(sum) => cpu.write_register(r_instruction.rd as usize, sum as u32),
+                    None => cpu.raise(Exception::Overflow, ExitReason::Overflow(cpu.pc)),
+                }
             }
 
             // Add Unsigned
@@ -112,7 +163,17 @@ impl Executable<RTypeInstruction> for RFunction {
             0x22 => {
                 let rs = cpu.registers[r_instruction.rs as usize].read();
                 let rt = cpu.registers[r_instruction.rt as usize].read();
-                cpu.write_register(r_instruction.rd as usize,rs.wrapping_sub(rt));
+                match (rs as i32).checked_sub(rt as i32) {
+                    Some(difference) => cpu.write_register(r_instruction.rd as usize, difference as u32),
+                    None => cpu.raise(Exception::Overflow, ExitReason::Overflow(cpu.pc)),
+                }
+            }
+
+            // Subtract Unsigned
+            0x23 => {
+                let rs = cpu.registers[r_instruction.rs as usize].read();
+                let rt = cpu.registers[r_instruction.rt as usize].read();
+                cpu.write_register(r_instruction.rd as usize, rs.wrapping_sub(rt));
             }
 
             // And
@@ -152,6 +213,32 @@ impl Executable<RTypeInstruction> for RFunction {
                 cpu.write_register(r_instruction.rd as usize,result as u32);
             }
 
+            // Set on Less Than Unsigned
+            0x2B => {
+                let rs = cpu.registers[r_instruction.rs as usize].read();
+                let rt = cpu.registers[r_instruction.rt as usize].read();
+                cpu.write_register(r_instruction.rd as usize, (rs < rt) as u32);
+            }
+
+            // TGE, TGEU, TLT, TLTU, TEQ, TNE: raise a trap when the condition
+            // holds (GCC guards divisions with TEQ rt, $zero, 7)
+            0x30..=0x34 | 0x36 => {
+                let rs = cpu.registers[r_instruction.rs as usize].read();
+                let rt = cpu.registers[r_instruction.rt as usize].read();
+                let trap = match self.funct {
+                    0x30 => rs as i32 >= rt as i32,
+                    0x31 => rs >= rt,
+                    0x32 => (rs as i32) < rt as i32,
+                    0x33 => rs < rt,
+                    0x34 => rs == rt,
+                    _ => rs != rt,
+                };
+
+                if trap {
+                    cpu.raise(Exception::Trap, ExitReason::Trap(cpu.pc));
+                }
+            }
+
             // Shift Left Logical
             0x00 => {
                 if r_instruction.rd == 0 && r_instruction.rt == 0 {
@@ -162,14 +249,23 @@ impl Executable<RTypeInstruction> for RFunction {
                 cpu.write_register(r_instruction.rd as usize,rt << r_instruction.shamt);
             }
 
+            // Break: stops the run with the 20-bit code between rs and funct
             0x0d => {
-                // No operation
+                let code = (r_instruction.rs as u32) << 15
+                    | (r_instruction.rt as u32) << 10
+                    | (r_instruction.rd as u32) << 5
+                    | r_instruction.shamt as u32;
+                cpu.exit = Some(ExitReason::Break { pc: cpu.pc, code });
             }
 
-            // Shift Right Logical
+            // Shift Right Logical, or Rotate Right when rs is 1
             0x02 => {
                 let rt = cpu.registers[r_instruction.rt as usize].read();
-                cpu.write_register(r_instruction.rd as usize,rt >> r_instruction.shamt);
+                if r_instruction.rs & 1 == 1 {
+                    cpu.write_register(r_instruction.rd as usize, rt.rotate_right(r_instruction.shamt as u32));
+                } else {
+                    cpu.write_register(r_instruction.rd as usize,rt >> r_instruction.shamt);
+                }
             }
 
             // Shift Right Arithmetic
@@ -178,114 +274,136 @@ impl Executable<RTypeInstruction> for RFunction {
                 cpu.write_register(r_instruction.rd as usize, (rt as i32 >> r_instruction.shamt as i32) as u32);
             }
 
-            // Jump Register
-            0x08 => {
-                cpu.run_branch_delayed();
-                cpu.pc = cpu.registers[r_instruction.rs as usize].read();
-                cpu.jump = true;
+            // Shift Left Logical Variable
+            0x04 => {
+                let rs = cpu.registers[r_instruction.rs as usize].read() & 0b11111;
+                let rt = cpu.registers[r_instruction.rt as usize].read();
+                cpu.write_register(r_instruction.rd as usize, rt << rs);
             }
 
-            // Jump and Link Register
-            0x09 => {
-                let rs = cpu.registers[r_instruction.rs as usize].read();
-                let ra = cpu.pc + 8;
-                cpu.run_branch_delayed();
-                cpu.write_register(r_instruction.rd as usize, ra);
-                cpu.pc = rs;
-                cpu.jump = true;
+            // Shift Right Logical Variable, or Rotate Right Variable when shamt is 1
+            0x06 => {
+                let rs = cpu.registers[r_instruction.rs as usize].read() & 0b11111;
+                let rt = cpu.registers[r_instruction.rt as usize].read();
+                if r_instruction.shamt & 1 == 1 {
+                    cpu.write_register(r_instruction.rd as usize, rt.rotate_right(rs));
+                } else {
+                    cpu.write_register(r_instruction.rd as usize, rt >> rs);
+                }
             }
 
-            // Syscall
-            0x0c => {
-                let v0 = cpu.registers[2].read();
-                let a0 = cpu.registers[4].read();
+            // Shift Right Arithmetic Variable
+            0x07 => {
+                let rs = cpu.registers[r_instruction.rs as usize].read() & 0b11111;
+                let rt = cpu.registers[r_instruction.rt as usize].read();
+                cpu.write_register(r_instruction.rd as usize, ((rt as i32) >> rs) as u32);
+            }
 
-                if v0 == 1 {
-                    print!("{}", a0 as u32);
+            // Move Conditional on Zero
+            0x0a => {
+                let rs = cpu.registers[r_instruction.rs as usize].read();
+                if cpu.registers[r_instruction.rt as usize].read() == 0 {
+                    cpu.write_register(r_instruction.rd as usize, rs);
                 }
+            }
 
-                if v0 == 4 {
-                    let text = utils::get_text(cpu, a0);
-                    print!("{:}", text);
+            // Move Conditional on Not Zero
+            0x0b => {
+                let rs = cpu.registers[r_instruction.rs as usize].read();
+                if cpu.registers[r_instruction.rt as usize].read() != 0 {
+                    cpu.write_register(r_instruction.rd as usize, rs);
                 }
+            }
 
-                if v0 == 5 {
-                    let mut input = String::new();
-                    std::io::stdin().read_line(&mut input).unwrap();
-                    let input: u32 = input.trim().parse().unwrap();
-                    cpu.registers[2].write(input);
-                }
+            // Move From HI
+            0x10 => {
+                cpu.write_register(r_instruction.rd as usize, cpu.hi);
+            }
 
-                if v0 == 10 {
-                    std::process::exit(0);
-                }
+            // Move To HI
+            0x11 => {
+                cpu.hi = cpu.registers[r_instruction.rs as usize].read();
+            }
 
-                if v0 == 11 {
-                    let the_char = a0 as u8 as char;
-                    print!("{:}", the_char);
-                }
+            // Move From LO
+            0x12 => {
+                cpu.write_register(r_instruction.rd as usize, cpu.lo);
+            }
 
-                io::stdout().flush().unwrap();
+            // Move To LO
+            0x13 => {
+                cpu.lo = cpu.registers[r_instruction.rs as usize].read();
             }
-            _ => println!("unknown"),
-        }
-    }
-}
 
-mod utils {
-    use crate::CPU;
-
-    pub fn get_text(cpu: &CPU, address: u32) -> String {
-        let mut relative = address % 4;
-        let mut address = address;
-        let mut text = String::new();
-        let mut must_loop = true;
-        loop {
-            if relative != 0 {
-                address -= relative;
+            // Multiply: HI gets the upper 32 bits of the product, LO the lower
+            0x18 => {
+                let rs = cpu.registers[r_instruction.rs as usize].read() as i32 as i64;
+                let rt = cpu.registers[r_instruction.rt as usize].read() as i32 as i64;
+                let product = (rs * rt) as u64;
+                cpu.hi = (product >> 32) as u32;
+                cpu.lo = product as u32;
             }
 
-            let value = cpu.memory.read(address);
+            // Multiply Unsigned
+            0x19 => {
+                let rs = cpu.registers[r_instruction.rs as usize].read() as u64;
+                let rt = cpu.registers[r_instruction.rt as usize].read() as u64;
+                let product = rs * rt;
+                cpu.hi = (product >> 32) as u32;
+                cpu.lo = product as u32;
+            }
 
-            let mut byte_chain = Vec::<u8>::new();
+            // Divide: LO gets the quotient, HI the remainder
+            0x1a => {
+                let rs = cpu.registers[r_instruction.rs as usize].read() as i32;
+                let rt = cpu.registers[r_instruction.rt as usize].read() as i32;
 
-            for i in (relative as usize)..4 {
-                let byte = ((value >> (i * 8)) & 0xFF) as u8;
-                if byte == 0 {
-                    must_loop = false;
-                    break
+                if cpu.check_divisor(rt as u32) {
+                    cpu.lo = rs.wrapping_div(rt) as u32;
+                    cpu.hi = rs.wrapping_rem(rt) as u32;
                 }
-                byte_chain.push(byte);
             }
 
-            relative = 0;
+            // Divide Unsigned
+            0x1b => {
+                let rs = cpu.registers[r_instruction.rs as usize].read();
+                let rt = cpu.registers[r_instruction.rt as usize].read();
 
-            let char_chain = latin1_to_string(&byte_chain);
+                if cpu.check_divisor(rt) {
+                    cpu.lo = rs / rt;
+                    cpu.hi = rs % rt;
+                }
+            }
 
-            text.push_str(&char_chain);
+            // Jump Register
+            0x08 => {
+                let rs = cpu.registers[r_instruction.rs as usize].read();
+                cpu.run_branch_delayed();
+                cpu.pc = rs;
+                cpu.jump = true;
+            }
 
-            if !must_loop {
-                break;
+            // Jump and Link Register
+            0x09 => {
+                let rs = cpu.registers[r_instruction.rs as usize].read();
+                let ra = cpu.pc + 8;
+                cpu.run_branch_delayed();
+                cpu.write_register(r_instruction.link_register() as usize, ra);
+                cpu.pc = rs;
+                cpu.jump = true;
             }
 
-            address += 4;
+            // Syscall
+            0x0c => syscall::execute(cpu),
+            _ => cpu.reserved_instruction(),
         }
-
-        text
-    }
-
-    fn latin1_to_string(s: &[u8]) -> String {
-        s.iter().map(|&c| c as char).collect()
     }
 }
 
-
-
 #[cfg(test)]
 mod tests {
-    use crate::instructions::r_instructions::utils;
-    use crate::instructions::Executable;
     use crate::instructions::Instruction;
+    use crate::syscall;
 
     #[test]
     fn test_add() {
@@ -309,6 +427,38 @@ mod tests {
         assert_eq!(cpu.registers[instruction.rd as usize].read(), 10);
     }
 
+    #[test]
+    fn test_add_sub_overflow() {
+        for (rs, rt, funct) in [(0x7fff_ffff, 1, 0x20), (0x8000_0000, 1, 0x22)] {
+            let mut cpu = super::CPU::new();
+            cpu.pc = 0x00400008;
+            cpu.registers[1].write(rs);
+            cpu.registers[2].write(rt);
+            cpu.registers[3].write(99);
+
+            super::RTypeInstruction::build(0, 3, 1, 2, 0, funct).execute(&mut cpu);
+
+            assert_eq!(cpu.exit, Some(super::ExitReason::Overflow(0x00400008)));
+            assert_eq!(cpu.registers[3].read(), 99);
+        }
+    }
+
+    #[test]
+    fn test_addu_subu_wrap() {
+        let mut cpu = super::CPU::new();
+        cpu.registers[1].write(0x8000_0000);
+        cpu.registers[2].write(1);
+
+        let subu = super::RTypeInstruction::build(0, 3, 1, 2, 0, 0x23);
+        assert_eq!(subu.name(), "SUBU");
+        subu.execute(&mut cpu);
+        assert_eq!(cpu.registers[3].read(), 0x7fff_ffff);
+
+        super::RTypeInstruction::build(0, 3, 3, 2, 0, 0x21).execute(&mut cpu);
+        assert_eq!(cpu.registers[3].read(), 0x8000_0000);
+        assert_eq!(cpu.exit, None);
+    }
+
     #[test]
     fn test_and() {
         let mut cpu = super::CPU::new();
@@ -372,6 +522,62 @@ mod tests {
         assert_eq!(cpu.registers[instruction.rd as usize].read(), 0);
     }
 
+    #[test]
+    fn test_sltu() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::RTypeInstruction::build(0, 1, 2, 3, 0, 0x2B);
+        cpu.registers[instruction.rs as usize].write(-1i32 as u32);
+        cpu.registers[instruction.rt as usize].write(1);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[instruction.rd as usize].read(), 0);
+
+        cpu.registers[instruction.rs as usize].write(0x7fff_ffff);
+        cpu.registers[instruction.rt as usize].write(0x8000_0000);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[instruction.rd as usize].read(), 1);
+    }
+
+    #[test]
+    fn test_break_code() {
+        let mut cpu = super::CPU::new();
+        cpu.pc = 0x00400008;
+        // break 7 (code 7 << 10 in the word, as GCC emits it)
+        let instruction = super::RTypeInstruction::new(0x0007_000d);
+        assert_eq!(instruction.name(), "BREAK");
+
+        instruction.execute(&mut cpu);
+        assert_eq!(
+            cpu.exit,
+            Some(super::ExitReason::Break {
+                pc: 0x00400008,
+                code: 7 << 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_traps() {
+        let cases = [
+            (0x30, -1i32 as u32, 1, false),
+            (0x31, -1i32 as u32, 1, true),
+            (0x32, -1i32 as u32, 1, true),
+            (0x33, -1i32 as u32, 1, false),
+            (0x34, 5, 5, true),
+            (0x36, 5, 5, false),
+        ];
+
+        for (funct, rs, rt, traps) in cases {
+            let mut cpu = super::CPU::new();
+            cpu.pc = 0x00400010;
+            cpu.registers[1].write(rs);
+            cpu.registers[2].write(rt);
+            super::RTypeInstruction::build(0, 0, 1, 2, 0, funct).execute(&mut cpu);
+
+            let expected = traps.then_some(crate::ExitReason::Trap(0x00400010));
+            assert_eq!(cpu.exit, expected, "funct {:#04x}", funct);
+        }
+    }
+
     #[test]
     fn test_sll() {
         let mut cpu = super::CPU::new();
@@ -395,7 +601,7 @@ mod tests {
         let mut cpu = super::CPU::new();
         let instruction = super::RTypeInstruction::build(0, 1, 2, 3, 2, 0x03);
         let value: u32 = 0b1111_1111_1111_1111_1111_1111_1111_0110; // -10
-        cpu.registers[instruction.rt as usize].write(value as u32);
+        cpu.registers[instruction.rt as usize].write(value);
         instruction.execute(&mut cpu);
         assert_eq!(
             cpu.registers[instruction.rd as usize].read(),
@@ -403,6 +609,216 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sllv() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::RTypeInstruction::build(0, 1, 2, 3, 0, 0x04);
+        cpu.registers[instruction.rs as usize].write(34); // only the low five bits count
+        cpu.registers[instruction.rt as usize].write(0b1111);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[instruction.rd as usize].read(), 0b111100);
+    }
+
+    #[test]
+    fn test_srlv() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::RTypeInstruction::build(0, 1, 2, 3, 0, 0x06);
+        cpu.registers[instruction.rs as usize].write(4);
+        cpu.registers[instruction.rt as usize].write(0xffff_fff0);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[instruction.rd as usize].read(), 0x0fff_ffff);
+    }
+
+    #[test]
+    fn test_srav() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::RTypeInstruction::build(0, 1, 2, 3, 0, 0x07);
+        cpu.registers[instruction.rs as usize].write(4);
+        cpu.registers[instruction.rt as usize].write(0xffff_fff0);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[instruction.rd as usize].read(), 0xffff_ffff);
+    }
+
+    #[test]
+    fn test_rotr() {
+        let mut cpu = super::CPU::new();
+        // rotr $t0, $t1, 8
+        let instruction = super::RTypeInstruction::new(0x0029_4202);
+        assert_eq!(instruction.name(), "ROTR");
+        cpu.registers[9].write(0x1234_5678);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[8].read(), 0x7812_3456);
+    }
+
+    #[test]
+    fn test_rotrv() {
+        let mut cpu = super::CPU::new();
+        // rotrv $t0, $t1, $t2
+        let instruction = super::RTypeInstruction::new(0x0149_4046);
+        assert_eq!(instruction.name(), "ROTRV");
+        cpu.registers[9].write(0x8000_0001);
+        cpu.registers[10].write(33); // only the low five bits count
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.registers[8].read(), 0xc000_0000);
+    }
+
+    #[test]
+    fn test_variable_shift_by_32_is_no_shift() {
+        let mut cpu = super::CPU::new();
+        cpu.registers[1].write(32);
+        cpu.registers[2].write(0x8000_0001);
+        for funct in [0x04, 0x06, 0x07] {
+            super::RTypeInstruction::build(0, 3, 1, 2, 0, funct).execute(&mut cpu);
+            assert_eq!(cpu.registers[3].read(), 0x8000_0001);
+        }
+    }
+
+    #[test]
+    fn test_div() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::RTypeInstruction::build(0, 0, 2, 3, 0, 0x1a);
+        cpu.registers[2].write(-7i32 as u32);
+        cpu.registers[3].write(2);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.lo, -3i32 as u32);
+        assert_eq!(cpu.hi, -1i32 as u32);
+    }
+
+    #[test]
+    fn test_mult() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::RTypeInstruction::build(0, 0, 2, 3, 0, 0x18);
+        cpu.registers[2].write(-3i32 as u32);
+        cpu.registers[3].write(0x4000_0000);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.hi, 0xffff_ffff);
+        assert_eq!(cpu.lo, 0x4000_0000);
+    }
+
+    #[test]
+    fn test_multu() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::RTypeInstruction::build(0, 0, 2, 3, 0, 0x19);
+        cpu.registers[2].write(0xffff_ffff);
+        cpu.registers[3].write(0xffff_ffff);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.hi, 0xffff_fffe);
+        assert_eq!(cpu.lo, 1);
+    }
+
+    #[test]
+    fn test_movz_movn() {
+        let mut cpu = super::CPU::new();
+        cpu.registers[1].write(0x55);
+        cpu.registers[3].write(0xaa);
+
+        // rt ($v0) is zero: MOVZ moves, MOVN keeps rd
+        super::RTypeInstruction::build(0, 3, 1, 2, 0, 0x0b).execute(&mut cpu);
+        assert_eq!(cpu.registers[3].read(), 0xaa);
+        super::RTypeInstruction::build(0, 3, 1, 2, 0, 0x0a).execute(&mut cpu);
+        assert_eq!(cpu.registers[3].read(), 0x55);
+
+        cpu.registers[1].write(0x77);
+        cpu.registers[2].write(1);
+        super::RTypeInstruction::build(0, 3, 1, 2, 0, 0x0a).execute(&mut cpu);
+        assert_eq!(cpu.registers[3].read(), 0x55);
+        super::RTypeInstruction::build(0, 3, 1, 2, 0, 0x0b).execute(&mut cpu);
+        assert_eq!(cpu.registers[3].read(), 0x77);
+    }
+
+    #[test]
+    fn test_mult_then_mflo() {
+        let mut cpu = super::CPU::new();
+        cpu.registers[2].write(-6i32 as u32);
+        cpu.registers[3].write(7);
+        super::RTypeInstruction::build(0, 0, 2, 3, 0, 0x18).execute(&mut cpu);
+        super::RTypeInstruction::build(0, 4, 0, 0, 0, 0x12).execute(&mut cpu);
+        assert_eq!(cpu.registers[4].read(), -42i32 as u32);
+    }
+
+    #[test]
+    fn test_div_then_mfhi() {
+        let mut cpu = super::CPU::new();
+        cpu.registers[2].write(17);
+        cpu.registers[3].write(5);
+        super::RTypeInstruction::build(0, 0, 2, 3, 0, 0x1a).execute(&mut cpu);
+        super::RTypeInstruction::build(0, 4, 0, 0, 0, 0x10).execute(&mut cpu);
+        assert_eq!(cpu.registers[4].read(), 2);
+    }
+
+    #[test]
+    fn test_mthi_mtlo() {
+        let mut cpu = super::CPU::new();
+        cpu.registers[2].write(0xaaaa);
+        cpu.registers[3].write(0x5555);
+        super::RTypeInstruction::build(0, 0, 2, 0, 0, 0x11).execute(&mut cpu);
+        super::RTypeInstruction::build(0, 0, 3, 0, 0, 0x13).execute(&mut cpu);
+        assert_eq!((cpu.hi, cpu.lo), (0xaaaa, 0x5555));
+    }
+
+    #[test]
+    fn test_divu() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::RTypeInstruction::build(0, 0, 2, 3, 0, 0x1b);
+        cpu.registers[2].write(-7i32 as u32);
+        cpu.registers[3].write(2);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.lo, 0x7fff_fffc);
+        assert_eq!(cpu.hi, 1);
+    }
+
+    #[test]
+    fn test_div_by_zero() {
+        let instruction = super::RTypeInstruction::build(0, 0, 2, 3, 0, 0x1a);
+
+        let mut cpu = super::CPU::new();
+        cpu.hi = 1;
+        cpu.lo = 2;
+        instruction.execute(&mut cpu);
+        assert_eq!((cpu.hi, cpu.lo, cpu.exit), (1, 2, None));
+
+        let mut cpu = super::CPU::new();
+        cpu.pc = 0x00400004;
+        cpu.set_divide_by_zero(crate::DivideByZero::Trap);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.exit, Some(super::ExitReason::Trap(0x00400004)));
+
+        let mut cpu = super::CPU::new();
+        cpu.pc = 0x00400004;
+        cpu.set_divide_by_zero(crate::DivideByZero::Abort);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.exit, Some(super::ExitReason::DivideByZero(0x00400004)));
+    }
+
+    #[test]
+    fn test_jalr() {
+        let mut cpu = super::CPU::new();
+        cpu.pc = 0x00400000;
+        // jalr $t0, which links through $ra
+        let instruction = super::RTypeInstruction::new(0x0100f809);
+        assert_eq!(instruction.name(), "JALR");
+
+        cpu.registers[8].write(0x00400100);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 0x00400100);
+        assert_eq!(cpu.registers[31].read(), 0x00400008);
+    }
+
+    #[test]
+    fn test_jalr_rd_zero_links_ra() {
+        let mut cpu = super::CPU::new();
+        cpu.pc = 0x00400000;
+        let instruction = super::RTypeInstruction::build(0, 0, 8, 0, 0, 0x09);
+        assert_eq!(instruction.name(), "JALR");
+
+        cpu.registers[8].write(0x00400100);
+        assert_eq!(instruction.decode(&mut cpu), "JALR rd 31, rs 8: 0x00400100");
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.pc, 0x00400100);
+        assert_eq!(cpu.read_register(0), 0);
+        assert_eq!(cpu.registers[31].read(), 0x00400008);
+    }
+
     #[test]
     fn test_jr() {
         let mut cpu = super::CPU::new();
@@ -439,7 +855,7 @@ mod tests {
         let mut word: u32 = 0;
         let mut store_address = 0x00400000;
         for (i, &byte) in text.iter().enumerate() {
-            word = word | (byte as u32) << (i % 4) * 8;
+            word |= (byte as u32) << ((i % 4) * 8);
             if (i + 1) % 4 == 0 {
                 cpu.memory.write(store_address, word);
                 store_address += 4;
@@ -448,6 +864,15 @@ mod tests {
         }
         instruction.execute(&mut cpu);
 
-        assert_eq!(utils::get_text(&cpu, data_address), "Hello");
+        assert_eq!(syscall::get_text(&mut cpu, data_address), "Hello");
+    }
+
+    #[test]
+    fn test_syscall_v0_is_10() {
+        let mut cpu = super::CPU::new();
+        let instruction = super::RTypeInstruction::new(0x0c);
+        cpu.registers[2].write(10);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.exit, Some(super::ExitReason::Exited(0)));
     }
 }
@@ -0,0 +1,206 @@
+use crate::CPU;
+
+use crate::instructions::Executable;
+use crate::instructions::Instruction;
+
+// SPECIAL2 (opcode 0x1c): R-type layout, selected by funct
+#[derive(Clone)]
+pub struct Special2Instruction {
+    pub opcode: u8,
+    name: String,
+    pub rs: u8,
+    pub rt: u8,
+    pub rd: u8,
+    funct: Special2Function,
+}
+
+impl Instruction for Special2Instruction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn decode(&self, cpu: &mut CPU) -> String {
+        let rs_value = cpu.read_register(self.rs as usize);
+        let rt_value = cpu.read_register(self.rt as usize);
+        format!(
+            "{} rs {}: {}, rt {}: {}, rd {}",
+            self.name, self.rs, rs_value, self.rt, rt_value, self.rd
+        )
+    }
+
+    fn execute(&self, cpu: &mut CPU) {
+        self.funct.execute(self.clone(), cpu);
+    }
+}
+
+impl Special2Instruction {
+    pub fn new(instruction: u32) -> Special2Instruction {
+        let funct = (instruction & 0b111111) as u8;
+        Special2Instruction {
+            opcode: (instruction >> 26) as u8,
+            name: Special2Function::new(funct).name.clone(),
+            rs: ((instruction >> 21) & 0b11111) as u8,
+            rt: ((instruction >> 16) & 0b11111) as u8,
+            rd: ((instruction >> 11) & 0b11111) as u8,
+            funct: Special2Function::new(funct),
+        }
+    }
+
+    pub fn build(rd: u8, rs: u8, rt: u8, funct: u8) -> Special2Instruction {
+        Special2Instruction {
+            opcode: 0b011100,
+            name: Special2Function::new(funct).name.clone(),
+            rs,
+            rt,
+            rd,
+            funct: Special2Function::new(funct),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Special2Function {
+    funct: u8,
+    name: String,
+}
+
+impl Special2Function {
+    fn new(funct: u8) -> Special2Function {
+        Special2Function {
+            funct,
+            name: match funct {
+                0x00 => String::from("MADD"),
+                0x01 => String::from("MADDU"),
+                0x02 => String::from("MUL"),
+                0x04 => String::from("MSUB"),
+                0x05 => String::from("MSUBU"),
+                0x20 => String::from("CLZ"),
+                0x21 => String::from("CLO"),
+                _ => String::from("UNKNOWN"),
+            },
+        }
+    }
+}
+
+impl Executable<Special2Instruction> for Special2Function {
+    fn execute(&self, instruction: Special2Instruction, cpu: &mut CPU) {
+        match self.funct {
+            // MADD, MADDU, MSUB, MSUBU: HI:LO plus or minus the 64-bit product
+            0x00 | 0x01 | 0x04 | 0x05 => {
+                let rs = cpu.read_register(instruction.rs as usize);
+                let rt = cpu.read_register(instruction.rt as usize);
+                let product = if self.funct & 1 == 0 {
+                    (rs as i32 as i64 * rt as i32 as i64) as u64
+                } else {
+                    rs as u64 * rt as u64
+                };
+
+                let accumulator = (cpu.hi as u64) << 32 | cpu.lo as u64;
+                let result = if self.funct & 0x04 == 0 {
+                    accumulator.wrapping_add(product)
+                } else {
+                    accumulator.wrapping_sub(product)
+                };
+                cpu.hi = (result >> 32) as u32;
+                cpu.lo = result as u32;
+            }
+
+            // MUL: the low word only; HI and LO are left alone
+            0x02 => {
+                let rs = cpu.read_register(instruction.rs as usize);
+                let rt = cpu.read_register(instruction.rt as usize);
+                cpu.write_register(instruction.rd as usize, rs.wrapping_mul(rt));
+            }
+
+            // CLZ: 32 for zero
+            0x20 => {
+                let rs = cpu.read_register(instruction.rs as usize);
+                cpu.write_register(instruction.rd as usize, rs.leading_zeros());
+            }
+
+            // CLO
+            0x21 => {
+                let rs = cpu.read_register(instruction.rs as usize);
+                cpu.write_register(instruction.rd as usize, rs.leading_ones());
+            }
+            _ => cpu.reserved_instruction(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instructions::{self, Instruction};
+    use crate::CPU;
+
+    #[test]
+    fn test_mul() {
+        let mut cpu = CPU::new();
+        cpu.write_register(9, -3i32 as u32);
+        cpu.write_register(10, 0x4000_0001);
+
+        super::Special2Instruction::build(8, 9, 10, 0x02).execute(&mut cpu);
+
+        assert_eq!(cpu.read_register(8), 0x3fff_fffd);
+        assert_eq!((cpu.hi, cpu.lo), (0, 0));
+    }
+
+    #[test]
+    fn test_madd_msub() {
+        let mut cpu = CPU::new();
+        cpu.hi = 0;
+        cpu.lo = 10;
+        cpu.write_register(9, -2i32 as u32);
+        cpu.write_register(10, 3);
+
+        // 10 + -6 = 4
+        super::Special2Instruction::build(0, 9, 10, 0x00).execute(&mut cpu);
+        assert_eq!((cpu.hi, cpu.lo), (0, 4));
+
+        // 4 - -6 - -6 = 16
+        super::Special2Instruction::build(0, 9, 10, 0x04).execute(&mut cpu);
+        super::Special2Instruction::build(0, 9, 10, 0x04).execute(&mut cpu);
+        assert_eq!((cpu.hi, cpu.lo), (0, 16));
+    }
+
+    #[test]
+    fn test_maddu_msubu_carry_into_hi() {
+        let mut cpu = CPU::new();
+        cpu.lo = 0xffff_ffff;
+        cpu.write_register(9, 0xffff_ffff);
+        cpu.write_register(10, 2);
+
+        // 0xffffffff + 0x1_fffffffe
+        super::Special2Instruction::build(0, 9, 10, 0x01).execute(&mut cpu);
+        assert_eq!((cpu.hi, cpu.lo), (2, 0xffff_fffd));
+
+        super::Special2Instruction::build(0, 9, 10, 0x05).execute(&mut cpu);
+        assert_eq!((cpu.hi, cpu.lo), (0, 0xffff_ffff));
+    }
+
+    #[test]
+    fn test_clz() {
+        let mut cpu = CPU::new();
+        let instruction = super::Special2Instruction::build(8, 9, 8, 0x20);
+
+        for (value, count) in [(0, 32), (1, 31), (0x0000_8000, 16), (0x8000_0000, 0)] {
+            cpu.write_register(9, value);
+            instruction.execute(&mut cpu);
+            assert_eq!(cpu.read_register(8), count);
+        }
+    }
+
+    #[test]
+    fn test_clo() {
+        let mut cpu = CPU::new();
+        // clo $t0, $t1
+        let instruction = instructions::get_instruction(0x7128_4021);
+        assert_eq!(instruction.name(), "CLO");
+
+        for (value, count) in [(0xffff_ffff, 32), (0xffff_0000, 16), (0x7fff_ffff, 0)] {
+            cpu.write_register(9, value);
+            instruction.execute(&mut cpu);
+            assert_eq!(cpu.read_register(8), count);
+        }
+    }
+}
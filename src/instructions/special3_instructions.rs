@@ -0,0 +1,214 @@
+use crate::CPU;
+
+use crate::instructions::Executable;
+use crate::instructions::Instruction;
+
+// SPECIAL3 (opcode 0x1f): MIPS32r2 bit manipulation. BSHFL (funct 0x20)
+// instructions are further selected by the sa field; EXT and INS use rd and
+// sa as the msb and lsb of the bit field.
+#[derive(Clone)]
+pub struct Special3Instruction {
+    pub opcode: u8,
+    name: String,
+    pub rs: u8,
+    pub rt: u8,
+    pub rd: u8,
+    pub sa: u8,
+    funct: Special3Function,
+}
+
+impl Instruction for Special3Instruction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn decode(&self, cpu: &mut CPU) -> String {
+        let rs_value = cpu.read_register(self.rs as usize);
+        let rt_value = cpu.read_register(self.rt as usize);
+        format!(
+            "{} rs {}: {}, rt {}: {}, rd {}, sa {}",
+            self.name, self.rs, rs_value, self.rt, rt_value, self.rd, self.sa
+        )
+    }
+
+    fn execute(&self, cpu: &mut CPU) {
+        self.funct.execute(self.clone(), cpu);
+    }
+}
+
+impl Special3Instruction {
+    pub fn new(instruction: u32) -> Special3Instruction {
+        let funct = (instruction & 0b111111) as u8;
+        let sa = ((instruction >> 6) & 0b11111) as u8;
+        Special3Instruction {
+            opcode: (instruction >> 26) as u8,
+            name: Special3Function::new(funct, sa).name.clone(),
+            rs: ((instruction >> 21) & 0b11111) as u8,
+            rt: ((instruction >> 16) & 0b11111) as u8,
+            rd: ((instruction >> 11) & 0b11111) as u8,
+            sa,
+            funct: Special3Function::new(funct, sa),
+        }
+    }
+
+    pub fn build(rd: u8, rs: u8, rt: u8, sa: u8, funct: u8) -> Special3Instruction {
+        Special3Instruction {
+            opcode: 0b011111,
+            name: Special3Function::new(funct, sa).name.clone(),
+            rs,
+            rt,
+            rd,
+            sa,
+            funct: Special3Function::new(funct, sa),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Special3Function {
+    funct: u8,
+    sa: u8,
+    name: String,
+}
+
+impl Special3Function {
+    fn new(funct: u8, sa: u8) -> Special3Function {
+        Special3Function {
+            funct,
+            sa,
+            name: match (funct, sa) {
+                (0x00, _) => String::from("EXT"),
+                (0x04, _) => String::from("INS"),
+                (0x20, 0x02) => String::from("WSBH"),
+                (0x20, 0x10) => String::from("SEB"),
+                (0x20, 0x18) => String::from("SEH"),
+                _ => String::from("UNKNOWN"),
+            },
+        }
+    }
+}
+
+impl Executable<Special3Instruction> for Special3Function {
+    fn execute(&self, instruction: Special3Instruction, cpu: &mut CPU) {
+        match (self.funct, self.sa) {
+            // EXT: rd holds the field size minus one
+            (0x00, lsb) => {
+                let rs = cpu.read_register(instruction.rs as usize) as u64;
+                let mask = (1u64 << (instruction.rd + 1)) - 1;
+                cpu.write_register(instruction.rt as usize, ((rs >> lsb) & mask) as u32);
+            }
+
+            // INS: rd holds the msb of the field in rt
+            (0x04, lsb) => {
+                let rs = cpu.read_register(instruction.rs as usize) as u64;
+                let rt = cpu.read_register(instruction.rt as usize) as u64;
+                let size = (instruction.rd + 1).saturating_sub(lsb);
+                let mask = ((1u64 << size) - 1) << lsb;
+                let result = (rt & !mask) | ((rs << lsb) & mask);
+                cpu.write_register(instruction.rt as usize, result as u32);
+            }
+
+            // WSBH: swaps the bytes of each halfword
+            (0x20, 0x02) => {
+                let rt = cpu.read_register(instruction.rt as usize);
+                let swapped = (rt & 0x00ff_00ff) << 8 | (rt & 0xff00_ff00) >> 8;
+                cpu.write_register(instruction.rd as usize, swapped);
+            }
+
+            // SEB
+            (0x20, 0x10) => {
+                let rt = cpu.read_register(instruction.rt as usize);
+                cpu.write_register(instruction.rd as usize, rt as u8 as i8 as u32);
+            }
+
+            // SEH
+            (0x20, 0x18) => {
+                let rt = cpu.read_register(instruction.rt as usize);
+                cpu.write_register(instruction.rd as usize, rt as u16 as i16 as u32);
+            }
+            _ => cpu.reserved_instruction(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instructions::{self, Instruction};
+    use crate::CPU;
+
+    #[test]
+    fn test_ext() {
+        let mut cpu = CPU::new();
+        // ext $t0, $t1, 4, 8
+        let instruction = instructions::get_instruction(0x7d28_3900);
+        assert_eq!(instruction.name(), "EXT");
+
+        cpu.write_register(9, 0x1234_5678);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.read_register(8), 0x67);
+
+        // the whole word
+        super::Special3Instruction::build(31, 9, 8, 0, 0x00).execute(&mut cpu);
+        assert_eq!(cpu.read_register(8), 0x1234_5678);
+    }
+
+    #[test]
+    fn test_ins() {
+        let mut cpu = CPU::new();
+        // ins $t0, $t1, 8, 8
+        let instruction = instructions::get_instruction(0x7d28_7a04);
+        assert_eq!(instruction.name(), "INS");
+
+        cpu.write_register(8, 0xffff_ffff);
+        cpu.write_register(9, 0x1234_56ab);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.read_register(8), 0xffff_abff);
+
+        // the whole word
+        super::Special3Instruction::build(31, 9, 8, 0, 0x04).execute(&mut cpu);
+        assert_eq!(cpu.read_register(8), 0x1234_56ab);
+    }
+
+    #[test]
+    fn test_wsbh() {
+        let mut cpu = CPU::new();
+        // wsbh $t0, $t1
+        let instruction = instructions::get_instruction(0x7c09_40a0);
+        assert_eq!(instruction.name(), "WSBH");
+
+        cpu.write_register(9, 0x1122_3344);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.read_register(8), 0x2211_4433);
+    }
+
+    #[test]
+    fn test_seb() {
+        let mut cpu = CPU::new();
+        // seb $t0, $t1
+        let instruction = instructions::get_instruction(0x7c09_4420);
+        assert_eq!(instruction.name(), "SEB");
+
+        cpu.write_register(9, 0x1234_5680);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.read_register(8), 0xffff_ff80);
+
+        cpu.write_register(9, 0xffff_ff7f);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.read_register(8), 0x7f);
+    }
+
+    #[test]
+    fn test_seh() {
+        let mut cpu = CPU::new();
+        let instruction = super::Special3Instruction::build(8, 0, 9, 0x18, 0x20);
+        assert_eq!(instruction.name(), "SEH");
+
+        cpu.write_register(9, 0x0000_8001);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.read_register(8), 0xffff_8001);
+
+        cpu.write_register(9, 0xffff_7fff);
+        instruction.execute(&mut cpu);
+        assert_eq!(cpu.read_register(8), 0x7fff);
+    }
+}
@@ -0,0 +1,2709 @@
+pub mod assembler;
+pub mod backend;
+pub mod breakpoints;
+pub mod builder;
+pub mod checks;
+pub mod config;
+pub mod console;
+pub mod cp0;
+pub mod cp1;
+pub mod debugger;
+pub mod device;
+pub mod disk;
+pub mod dma;
+pub mod elf;
+pub mod energy;
+pub mod error;
+pub mod faults;
+pub mod files;
+pub mod hooks;
+pub mod image;
+pub mod instructions;
+pub mod loader;
+pub mod microstep;
+pub mod mmio_console;
+pub mod random;
+pub mod rtc;
+pub mod selftest;
+pub mod snapshot;
+pub mod spim;
+pub mod syscall;
+pub mod terminal;
+pub mod tlb;
+pub mod trace;
+
+use crate::breakpoints::{Breakpoint, Breakpoints, Condition, Watch, Watchpoint};
+use crate::builder::CpuBuilder;
+use crate::checks::Warning;
+use crate::console::{Console, GuestIo};
+use crate::cp0::{Cp0, Exception};
+use crate::cp1::Cp1;
+use crate::device::{Bus, Device, Mapping};
+use crate::energy::EnergyMeter;
+use crate::error::Error;
+use crate::faults::{Action, BitFlipper, Fault, FaultInjector, Flip, Injection};
+use crate::files::FileTable;
+use crate::hooks::{AccessKind, Event, Hook, MemoryAccess};
+use crate::image::SymbolTable;
+use crate::loader::Layout;
+use crate::random::Random;
+use crate::syscall::Syscall;
+use crate::rtc::Clock;
+use crate::snapshot::MachineState;
+use crate::terminal::Terminal;
+use crate::tlb::{Tlb, TlbFault};
+use crate::trace::{Retired, Tracer};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+#[derive(Clone)]
+struct Register {
+    value: u32,
+}
+
+impl Register {
+    fn new() -> Register {
+        Register { value: 0 }
+    }
+
+    fn read(&self) -> u32 {
+        self.value
+    }
+
+    fn write(&mut self, value: u32) {
+        self.value = value;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Region {
+    pub name: String,
+    pub start: u32,
+    pub end: u32,
+    pub executable: bool,
+}
+
+impl Region {
+    fn new(name: &str, start: u32, end: u32, executable: bool) -> Region {
+        Region {
+            name: String::from(name),
+            start,
+            end,
+            executable,
+        }
+    }
+
+    pub fn contains(&self, address: u32) -> bool {
+        address >= self.start && address < self.end
+    }
+}
+
+// Byte order for assembling halfwords and words from memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+// 4 KiB, allocated the first time anything in it is written
+type Page = [u8; Memory::PAGE_SIZE];
+
+struct Memory {
+    // Shared with forks until either side writes them
+    pages: HashMap<u32, Rc<Page>>,
+    endian: Endian,
+    regions: Vec<Region>,
+    // Windows that go to devices instead of pages, for loads and stores
+    devices: Vec<Mapping>,
+    // Start and break of the heap, once the guest has asked for one
+    heap: Option<(u32, u32)>,
+    generation: u64,
+    layout: Layout,
+}
+
+impl Memory {
+    const STACK_SIZE: u32 = 0x00100000;
+    const PAGE_SHIFT: u32 = 12;
+    const PAGE_SIZE: usize = 1 << Self::PAGE_SHIFT;
+
+    fn new() -> Memory {
+        Memory::with_layout(Layout::default())
+    }
+
+    fn with_layout(layout: Layout) -> Memory {
+        let stack_top = layout.stack_top;
+        let stack = Region::new("stack", stack_top.saturating_sub(Self::STACK_SIZE), stack_top, false);
+        Memory {
+            pages: HashMap::new(),
+            endian: Endian::Little,
+            devices: Vec::new(),
+            heap: None,
+            regions: vec![stack],
+            generation: 0,
+            layout,
+        }
+    }
+
+    // A copy that shares every page until one side writes to it. Devices
+    // stay behind.
+    fn fork(&self) -> Memory {
+        Memory {
+            pages: self.pages.clone(),
+            endian: self.endian,
+            regions: self.regions.clone(),
+            devices: Vec::new(),
+            heap: self.heap,
+            generation: self.generation,
+            layout: self.layout,
+        }
+    }
+
+    // Back to an empty address space, keeping the allocation
+    fn clear(&mut self) {
+        self.pages.clear();
+        self.regions.truncate(1);
+        self.heap = None;
+        self.generation = 0;
+    }
+
+    // The page and the byte within it
+    fn locate(address: u32) -> (u32, usize) {
+        let offset = address & ((1 << Self::PAGE_SHIFT) - 1);
+        (address >> Self::PAGE_SHIFT, offset as usize)
+    }
+
+    // The word at `address`
+    fn read(&self, address: u32) -> u32 {
+        self.read_u32(address)
+    }
+
+    // Bytes never written read as zero
+    fn read_u8(&self, address: u32) -> u8 {
+        let (page, offset) = Self::locate(address);
+        self.pages.get(&page).map_or(0, |page| page[offset])
+    }
+
+    fn read_bytes<const N: usize>(&self, address: u32) -> [u8; N] {
+        std::array::from_fn(|i| self.read_u8(address.wrapping_add(i as u32)))
+    }
+
+    // Wider reads gather bytes in the memory's byte order, so they may start
+    // anywhere and span two words
+    fn read_u16(&self, address: u32) -> u16 {
+        let bytes = self.read_bytes(address);
+        match self.endian {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn read_u32(&self, address: u32) -> u32 {
+        let bytes = self.read_bytes(address);
+        match self.endian {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        }
+    }
+
+    fn write(&mut self, address: u32, value: u32) {
+        self.write_u32(address, value);
+    }
+
+    fn write_u8(&mut self, address: u32, byte: u8) {
+        let (page, offset) = Self::locate(address);
+        let page = self
+            .pages
+            .entry(page)
+            .or_insert_with(|| Rc::new([0; Self::PAGE_SIZE]));
+        Rc::make_mut(page)[offset] = byte;
+        self.generation += 1;
+    }
+
+    fn write_bytes(&mut self, address: u32, bytes: &[u8]) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.write_u8(address.wrapping_add(i as u32), byte);
+        }
+    }
+
+    fn write_u16(&mut self, address: u32, value: u16) {
+        let bytes = match self.endian {
+            Endian::Little => value.to_le_bytes(),
+            Endian::Big => value.to_be_bytes(),
+        };
+        self.write_bytes(address, &bytes);
+    }
+
+    fn write_u32(&mut self, address: u32, value: u32) {
+        let bytes = match self.endian {
+            Endian::Little => value.to_le_bytes(),
+            Endian::Big => value.to_be_bytes(),
+        };
+        self.write_bytes(address, &bytes);
+    }
+
+    fn attach(&mut self, mapping: Mapping) -> Result<(), Error> {
+        let taken = self
+            .devices
+            .iter()
+            .any(|other| other.start < mapping.end && mapping.start < other.end);
+        if taken {
+            return Err(Error::DeviceOverlap {
+                start: mapping.start,
+                end: mapping.end,
+            });
+        }
+
+        self.devices.push(mapping);
+        Ok(())
+    }
+
+    // Where a `size`-byte access sits within its word
+    fn lane_shift(&self, address: u32, size: u32) -> u32 {
+        match self.endian {
+            Endian::Little => (address % 4) * 8,
+            Endian::Big => (4 - size - address % 4) * 8,
+        }
+    }
+
+    // A load from a device window; None for ordinary memory. Device reads
+    // count as a change, since a polled status register may differ next time.
+    fn read_device(&mut self, address: u32, size: u32) -> Option<u32> {
+        let shift = self.lane_shift(address, size);
+        let mapping = self.devices.iter_mut().find(|mapping| mapping.contains(address))?;
+
+        let word = mapping.device.read((address - mapping.start) & !3);
+        self.generation += 1;
+        Some((word >> shift) & (u32::MAX >> (32 - size * 8)))
+    }
+
+    // A store to a device window; false for ordinary memory
+    fn write_device(&mut self, address: u32, size: u32, value: u32) -> bool {
+        let shift = self.lane_shift(address, size);
+        let Some(mapping) = self.devices.iter_mut().find(|mapping| mapping.contains(address)) else {
+            return false;
+        };
+
+        let value = (value & (u32::MAX >> (32 - size * 8))) << shift;
+        mapping.device.write((address - mapping.start) & !3, value);
+        self.generation += 1;
+        true
+    }
+
+    // Ticks every device; returns the interrupt lines they hold raised
+    fn tick_devices(&mut self) -> u32 {
+        // Out of the way while the devices get at the pages
+        let mut devices = std::mem::take(&mut self.devices);
+        let mut lines = 0;
+        for mapping in &mut devices {
+            mapping.device.tick();
+            mapping.device.access_memory(self);
+            lines |= mapping.device.interrupts();
+        }
+        self.devices = devices;
+        lines
+    }
+
+    fn add_region(&mut self, name: &str, start: u32, end: u32, executable: bool) {
+        self.regions.push(Region::new(name, start, end, executable));
+    }
+
+    // The first region sharing an address with [start, end)
+    fn overlapping(&self, start: u32, end: u32) -> Option<&Region> {
+        self.regions
+            .iter()
+            .find(|region| region.start < end && start < region.end)
+    }
+
+    // Moves the break by `amount` bytes, rounded up to a word, and returns
+    // the old one. Fails with the would-be break when the heap would drop
+    // below its start or pass `limit`. The first call puts the heap past the
+    // last data segment below the stack.
+    fn sbrk(&mut self, amount: i32, limit: u32) -> Result<u32, u32> {
+        let (start, old) = self.heap.unwrap_or_else(|| {
+            let stack = self.regions[0].start;
+            let data_end = self
+                .regions
+                .iter()
+                .filter(|region| !region.executable && region.end <= stack)
+                .map(|region| region.end)
+                .max()
+                .unwrap_or(self.layout.data_base);
+            let start = (data_end + 7) & !7;
+            (start, start)
+        });
+
+        let new = old as i64 + ((amount as i64 + 3) & !3);
+        if new < start as i64 || new > limit as i64 {
+            return Err(new as u32);
+        }
+        let new = new as u32;
+
+        match self.regions.iter_mut().find(|region| region.name == "heap") {
+            Some(heap) => heap.end = new,
+            None => self.add_region("heap", start, new, false),
+        }
+        self.heap = Some((start, new));
+        Ok(old)
+    }
+
+    fn heap_end(&self) -> Option<u32> {
+        self.heap.map(|(_, end)| end)
+    }
+
+    fn region(&self, address: u32) -> Option<&Region> {
+        self.regions.iter().find(|region| region.contains(address))
+    }
+
+    fn has_code(&self) -> bool {
+        self.regions.iter().any(|region| region.executable)
+    }
+
+    fn is_executable(&self, address: u32) -> bool {
+        self.regions
+            .iter()
+            .any(|region| region.executable && region.contains(address))
+    }
+
+    // Every nonzero aligned word with its address, in no particular order.
+    // Zero words can't be told apart from memory nothing wrote.
+    fn words(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.pages.keys().flat_map(move |&page| {
+            let base = page << Self::PAGE_SHIFT;
+            (0..Self::PAGE_SIZE as u32)
+                .step_by(4)
+                .map(move |offset| (base | offset, self.read(base | offset)))
+                .filter(|&(_, value)| value != 0)
+        })
+    }
+
+    // Every stored word whose value falls inside [start, end), in address order
+    fn find_references(&self, start: u32, end: u32) -> Vec<(u32, u32)> {
+        let mut references: Vec<(u32, u32)> = self
+            .words()
+            .filter(|&(_, value)| value >= start && value < end)
+            .collect();
+        references.sort();
+        references
+    }
+
+    fn load_segment(
+        &mut self,
+        name: &str,
+        address: u32,
+        bytes: &[u8],
+        executable: bool,
+    ) -> Result<(), Error> {
+        let end = segment_end(name, address, bytes.len())?;
+        self.write_bytes(address, bytes);
+        self.add_region(name, address, end, executable);
+        Ok(())
+    }
+}
+
+// Where a segment of `length` bytes at `address` ends, if it fits below 4 GiB
+fn segment_end(name: &str, address: u32, length: usize) -> Result<u32, Error> {
+    u32::try_from(length)
+        .ok()
+        .and_then(|length| address.checked_add(length))
+        .ok_or_else(|| Error::PastEnd {
+            segment: name.to_string(),
+            address,
+        })
+}
+
+// A number as the command line, the debugger and fault specs write it:
+// decimal or 0x hex
+pub fn parse_number(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+impl Bus for Memory {
+    fn read_u8(&self, address: u32) -> u8 {
+        Memory::read_u8(self, address)
+    }
+
+    fn write_u8(&mut self, address: u32, byte: u8) {
+        Memory::write_u8(self, address, byte);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The guest called the exit syscall.
+    Exited(i32),
+    /// The user pressed Ctrl-C while the guest was waiting for input.
+    Interrupted,
+    /// The run executed as many instructions as its limit allows.
+    InstructionLimit,
+    /// Control came back to this loop head with nothing changed since the last visit.
+    IdleLoop(u32),
+    /// A jump or branch left the executable regions.
+    WildJump { from: u32, to: u32 },
+    /// A halfword or word access to an address that isn't a multiple of its size.
+    UnalignedAccess(u32),
+    /// A user mode access to this kernel address, and nothing handled it.
+    AddressError(u32),
+    /// The instruction at this address doesn't decode, and nothing handled
+    /// the Reserved Instruction exception.
+    ReservedInstruction(u32),
+    /// No valid TLB entry maps this address, and nothing handled it.
+    TlbMiss(u32),
+    /// A store to this address hit a TLB entry without the D bit, and nothing
+    /// handled it.
+    TlbModified(u32),
+    /// A trap was raised at this address and nothing handled it.
+    Trap(u32),
+    /// DIV/DIVU by zero at this address, with DivideByZero::Abort.
+    DivideByZero(u32),
+    /// ADD, ADDI or SUB overflowed at this address and nothing handled it.
+    Overflow(u32),
+    /// A BREAK instruction; resuming continues after it.
+    Break { pc: u32, code: u32 },
+    /// Breakpoint `number` at this address, before its instruction ran;
+    /// resuming runs it.
+    Breakpoint { pc: u32, number: usize },
+    /// Watchpoint `number` saw the instruction at `pc` make this access; the
+    /// instruction ran, and resuming goes on from the next one.
+    Watchpoint {
+        pc: u32,
+        number: usize,
+        access: MemoryAccess,
+    },
+    /// Execution went past the end of the code to this address without an
+    /// exit syscall. MARS ends such programs normally.
+    EndOfText(u32),
+    /// The heap and the stack met: sbrk asked for a break past the stack
+    /// pointer, or the stack pointer went below the break.
+    HeapCollision(u32),
+}
+
+impl ExitReason {
+    // The status a command line run ends with: the guest's own for an exit
+    // syscall, 130 for Ctrl-C as shells do, 124 for running out of
+    // instructions and 1 for anything that went wrong
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ExitReason::Exited(code) => *code,
+            ExitReason::EndOfText(_) => 0,
+            ExitReason::Interrupted => 130,
+            // As timeout(1) does
+            ExitReason::InstructionLimit => 124,
+            ExitReason::IdleLoop(_)
+            | ExitReason::WildJump { .. }
+            | ExitReason::UnalignedAccess(_)
+            | ExitReason::AddressError(_)
+            | ExitReason::ReservedInstruction(_)
+            | ExitReason::TlbMiss(_)
+            | ExitReason::TlbModified(_)
+            | ExitReason::Trap(_)
+            | ExitReason::Break { .. }
+            | ExitReason::Breakpoint { .. }
+            | ExitReason::Watchpoint { .. }
+            | ExitReason::DivideByZero(_)
+            | ExitReason::Overflow(_)
+            | ExitReason::HeapCollision(_) => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExitReason::Exited(code) => write!(f, "exited with code {}", code),
+            ExitReason::Interrupted => write!(f, "interrupted"),
+            ExitReason::InstructionLimit => write!(f, "instruction limit reached"),
+            ExitReason::IdleLoop(pc) => write!(f, "idle loop detected at {:#010x}", pc),
+            ExitReason::WildJump { from, to } => {
+                write!(f, "wild jump from {:#010x} to {:#010x}", from, to)
+            }
+            ExitReason::UnalignedAccess(address) => {
+                write!(f, "unaligned access to {:#010x}", address)
+            }
+            ExitReason::AddressError(address) => {
+                write!(f, "user mode access to kernel address {:#010x}", address)
+            }
+            ExitReason::ReservedInstruction(pc) => {
+                write!(f, "reserved instruction at {:#010x}", pc)
+            }
+            ExitReason::TlbMiss(address) => write!(f, "TLB miss on {:#010x}", address),
+            ExitReason::TlbModified(address) => {
+                write!(f, "store to clean TLB page at {:#010x}", address)
+            }
+            ExitReason::Trap(pc) => write!(f, "unhandled trap at {:#010x}", pc),
+            ExitReason::DivideByZero(pc) => write!(f, "division by zero at {:#010x}", pc),
+            ExitReason::Overflow(pc) => write!(f, "arithmetic overflow at {:#010x}", pc),
+            ExitReason::Break { pc, code } => write!(f, "break {} at {:#010x}", code, pc),
+            ExitReason::Breakpoint { pc, number } => {
+                write!(f, "breakpoint {} at {:#010x}", number, pc)
+            }
+            ExitReason::Watchpoint { pc, number, access } => {
+                let (kind, preposition) = match access.kind {
+                    AccessKind::Load => ("load", "from"),
+                    AccessKind::Store => ("store", "to"),
+                };
+                write!(
+                    f,
+                    "watchpoint {}: {} of {:#x} {} {:#010x} at {:#010x}",
+                    number, kind, access.value, preposition, access.address, pc
+                )
+            }
+            ExitReason::EndOfText(address) => {
+                write!(f, "dropped off the end of the code at {:#010x}", address)
+            }
+            ExitReason::HeapCollision(address) => {
+                write!(f, "heap and stack collided at {:#010x}", address)
+            }
+        }
+    }
+}
+
+// Where general exceptions go (Status.BEV clear)
+pub const EXCEPTION_VECTOR: u32 = 0x80000180;
+// Where TLB misses go outside exception level
+pub const TLB_REFILL_VECTOR: u32 = 0x80000000;
+
+// What an unaligned halfword or word access does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    Fault,
+    // Split into byte accesses, as some toolchains and teaching material assume
+    Emulate,
+}
+
+// What DIV/DIVU do with a zero divisor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivideByZero {
+    // Architecturally undefined: HI and LO are left as they were
+    Undefined,
+    Trap,
+    // Stop with a diagnostic, to catch the bug where it happens
+    Abort,
+}
+
+// A call recorded on the shadow stack: where it was made and where it went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub call_site: u32,
+    pub target: u32,
+}
+
+// An instruction run by CPU::step and what it changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Executed {
+    pub pc: u32,
+    pub word: u32,
+    pub mnemonic: String,
+    // Register numbers and the values they held before it ran
+    pub operands: String,
+    // Registers it wrote, with their new values
+    pub writes: Vec<(usize, u32)>,
+    // Address and new value of a store
+    pub store: Option<(u32, u32)>,
+}
+
+// The outcome of CPU::step. Nothing is executed when the step only entered
+// an exception handler, e.g. for an interrupt or a fetch that missed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub executed: Option<Executed>,
+    pub exit: Option<ExitReason>,
+}
+
+pub struct CPU {
+    registers: Vec<Register>,
+    // Shadow register sets; the entry for the current set (SRSCtl.CSS) is
+    // stale while that set lives in `registers`
+    register_sets: Vec<Vec<Register>>,
+    cp0: Cp0,
+    cp1: Cp1,
+    memory: Memory,
+    pc: u32,
+    hi: u32,
+    lo: u32,
+    entry: u32,
+    executed: u64,
+    // Instructions a run may execute; None runs until the program stops
+    instruction_limit: Option<u64>,
+    jump: bool,
+    // Set while a branch runs its delay slot, for Cause.BD
+    in_delay_slot: bool,
+    // An exception was taken; the next pc is this vector
+    vectoring: Option<u32>,
+    exit: Option<ExitReason>,
+    io: GuestIo,
+    loop_heads: HashMap<u32, u64>,
+    shadow_stack: Vec<Frame>,
+    warnings: Vec<Warning>,
+    // MIPS I: a loaded value only lands after the next instruction
+    load_delay: bool,
+    pending_load: Option<(usize, u32)>,
+    retiring_load: Option<(usize, u32)>,
+    // Word linked by LL; any store to it makes the next SC fail
+    link: Option<u32>,
+    alignment: Alignment,
+    unaligned_accesses: u64,
+    divide_by_zero: DivideByZero,
+    symbols: SymbolTable,
+    faults: FaultInjector,
+    bit_flips: BitFlipper,
+    energy: EnergyMeter,
+    tracer: Option<Tracer>,
+    hooks: Vec<Hook>,
+    // Filled in by the next instruction while CPU::step runs
+    observed: Option<Option<Executed>>,
+    // Time for the time and sleep syscalls
+    clock: Clock,
+    // Host files opened by the guest
+    files: FileTable,
+    random: Random,
+    // Services by $v0 code, the built-in ones included
+    syscalls: HashMap<u32, Syscall>,
+    // Register values every run starts with, besides $gp and $sp
+    presets: Vec<(usize, u32)>,
+    // The program's argv, put on the stack at the start of every run
+    arguments: Vec<String>,
+    breakpoints: Breakpoints,
+    // Where a breakpoint or step() last stopped the machine; resuming there
+    // runs the instruction instead of stopping again
+    stopped_at: Option<u32>,
+    // The first watched access of the instruction running
+    watched: Option<(usize, MemoryAccess)>,
+}
+
+impl CPU {
+    pub fn new() -> CPU {
+        let mut cpu = CPU {
+            registers: vec![Register::new(); 32],
+            register_sets: vec![vec![Register::new(); 32]],
+            cp0: Cp0::new(),
+            cp1: Cp1::new(),
+            memory: Memory::new(),
+            pc: 0,
+            hi: 0,
+            lo: 0,
+            entry: loader::TEXT_BASE,
+            executed: 0,
+            instruction_limit: None,
+            jump: false,
+            in_delay_slot: false,
+            vectoring: None,
+            exit: None,
+            io: GuestIo::new(Box::new(Terminal::new())),
+            loop_heads: HashMap::new(),
+            shadow_stack: Vec::new(),
+            warnings: Vec::new(),
+            load_delay: false,
+            pending_load: None,
+            retiring_load: None,
+            link: None,
+            alignment: Alignment::Fault,
+            unaligned_accesses: 0,
+            divide_by_zero: DivideByZero::Undefined,
+            symbols: SymbolTable::default(),
+            faults: FaultInjector::default(),
+            bit_flips: BitFlipper::default(),
+            energy: EnergyMeter::default(),
+            tracer: None,
+            hooks: Vec::new(),
+            observed: None,
+            clock: Clock::default(),
+            files: FileTable::new(),
+            random: Random::default(),
+            syscalls: syscall::builtins(),
+            presets: Vec::new(),
+            arguments: Vec::new(),
+            breakpoints: Breakpoints::default(),
+            stopped_at: None,
+            watched: None,
+        };
+
+        cpu.preset_registers();
+        cpu
+    }
+
+    // A machine with a memory map, entry point or registers of its own
+    pub fn builder() -> CpuBuilder {
+        CpuBuilder::new()
+    }
+
+    // Laid out for `layout`, with nothing loaded yet
+    fn with_layout(layout: Layout, presets: Vec<(usize, u32)>) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.entry = layout.text_base;
+        cpu.memory = Memory::with_layout(layout);
+        cpu.presets = presets;
+        cpu.preset_registers();
+        cpu
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.memory.layout
+    }
+
+    fn preset_registers(&mut self) {
+        self.write_register(28, self.memory.layout.global_pointer);
+        self.write_register(29, self.memory.layout.stack_top.wrapping_sub(4));
+        if !self.arguments.is_empty() {
+            self.push_arguments();
+        }
+        for (register, value) in self.presets.clone() {
+            self.write_register(register, value);
+        }
+    }
+
+    // SPIM's startup stack: argc at $sp, then the argv pointers and an empty
+    // envp, each ending in a null, with the strings above them. $a0, $a1 and
+    // $a2 hold argc, argv and envp too.
+    fn push_arguments(&mut self) {
+        let count = self.arguments.len() as u32;
+        let size: usize = self.arguments.iter().map(|argument| argument.len() + 1).sum();
+        let strings = self.memory.layout.stack_top.wrapping_sub(size as u32) & !3;
+        let sp = strings.wrapping_sub(4 * (count + 3)) & !7;
+        let argv = sp.wrapping_add(4);
+        let envp = argv.wrapping_add(4 * (count + 1));
+
+        let mut address = strings;
+        for (index, argument) in self.arguments.clone().iter().enumerate() {
+            self.memory.write_bytes(address, argument.as_bytes());
+            self.memory.write_u8(address.wrapping_add(argument.len() as u32), 0);
+            self.memory.write(argv.wrapping_add(4 * index as u32), address);
+            address = address.wrapping_add(argument.len() as u32 + 1);
+        }
+        self.memory.write(sp, count);
+        self.memory.write(envp.wrapping_sub(4), 0);
+        self.memory.write(envp, 0);
+
+        self.write_register(29, sp);
+        self.write_register(4, count);
+        self.write_register(5, argv);
+        self.write_register(6, envp);
+    }
+
+    // argv for the program, argv[0] included; it's on the stack from now on
+    // and again after every reset
+    pub fn set_arguments(&mut self, arguments: Vec<String>) {
+        self.arguments = arguments;
+        self.preset_registers();
+    }
+
+    pub fn read_register(&self, register: usize) -> u32 {
+        if register == 0 {
+            return 0;
+        }
+
+        self.registers[register].read()
+    }
+
+    pub fn write_register(&mut self, register: usize, value: u32) {
+        if register == 0 {
+            return;
+        }
+
+        self.registers[register].write(value);
+        self.notify(Event::RegisterWrite(register, value));
+    }
+
+    // The machine's state between instructions, to restore later or to
+    // compare against a golden one
+    pub fn snapshot(&self) -> MachineState {
+        let values = |registers: &[Register]| registers.iter().map(Register::read).collect();
+        MachineState {
+            pc: self.pc,
+            hi: self.hi,
+            lo: self.lo,
+            registers: values(&self.registers),
+            shadow_sets: self.register_sets.iter().map(|set| values(set)).collect(),
+            cp0: self.cp0.clone(),
+            cp1: self.cp1.clone(),
+            entry: self.entry,
+            executed: self.executed,
+            pending_load: self.pending_load,
+            link: self.link,
+            regions: self.memory.regions.clone(),
+            heap: self.memory.heap,
+            pages: self
+                .memory
+                .pages
+                .iter()
+                .map(|(&number, page)| (number, page.to_vec()))
+                .collect(),
+        }
+    }
+
+    // Puts the machine in a state from snapshot(), which may come from
+    // another machine. Nothing changes if the state doesn't fit.
+    pub fn restore(&mut self, state: &MachineState) -> Result<(), Error> {
+        let invalid = |reason: &str| Err(Error::State(String::from(reason)));
+        let sets_fit = state.shadow_sets.iter().all(|set| set.len() == 32);
+        if state.registers.len() != 32 || state.shadow_sets.is_empty() || !sets_fit {
+            return invalid("a register set doesn't have 32 registers");
+        }
+        if state.regions.is_empty() {
+            return invalid("the stack region is missing");
+        }
+        let mut pages = HashMap::new();
+        for (&number, bytes) in &state.pages {
+            let Ok(page) = Page::try_from(bytes.as_slice()) else {
+                return invalid("a memory page has the wrong size");
+            };
+            pages.insert(number, Rc::new(page));
+        }
+
+        let registers = |values: &[u32]| values.iter().map(|&value| Register { value }).collect();
+        self.pc = state.pc;
+        self.hi = state.hi;
+        self.lo = state.lo;
+        self.registers = registers(&state.registers);
+        self.register_sets = state.shadow_sets.iter().map(|set| registers(set)).collect();
+        self.cp0 = state.cp0.clone();
+        self.cp1 = state.cp1.clone();
+        self.entry = state.entry;
+        self.executed = state.executed;
+        self.pending_load = state.pending_load;
+        self.link = state.link;
+        self.memory.regions = state.regions.clone();
+        self.memory.heap = state.heap;
+        self.memory.pages = pages;
+        self.memory.generation += 1;
+
+        self.jump = false;
+        self.in_delay_slot = false;
+        self.vectoring = None;
+        self.exit = None;
+        self.retiring_load = None;
+        self.loop_heads.clear();
+        self.shadow_stack.clear();
+        Ok(())
+    }
+
+    // A copy of the machine to run on its own, e.g. one per test input
+    // after loading the program once. Memory is shared until written, so
+    // forking costs little. The fork gets the terminal as its console and
+    // has no devices, tracer, hooks or open files; a virtual clock is copied
+    // rather than shared.
+    pub fn fork(&self) -> CPU {
+        CPU {
+            registers: self.registers.clone(),
+            register_sets: self.register_sets.clone(),
+            cp0: self.cp0.clone(),
+            cp1: self.cp1.clone(),
+            memory: self.memory.fork(),
+            pc: self.pc,
+            hi: self.hi,
+            lo: self.lo,
+            entry: self.entry,
+            executed: self.executed,
+            instruction_limit: self.instruction_limit,
+            jump: self.jump,
+            in_delay_slot: self.in_delay_slot,
+            vectoring: self.vectoring,
+            exit: self.exit,
+            io: GuestIo::new(Box::new(Terminal::new())),
+            loop_heads: self.loop_heads.clone(),
+            shadow_stack: self.shadow_stack.clone(),
+            warnings: self.warnings.clone(),
+            load_delay: self.load_delay,
+            pending_load: self.pending_load,
+            retiring_load: self.retiring_load,
+            link: self.link,
+            alignment: self.alignment,
+            unaligned_accesses: self.unaligned_accesses,
+            divide_by_zero: self.divide_by_zero,
+            symbols: self.symbols.clone(),
+            faults: self.faults.clone(),
+            bit_flips: self.bit_flips.clone(),
+            energy: self.energy.clone(),
+            tracer: None,
+            hooks: Vec::new(),
+            observed: None,
+            clock: match &self.clock {
+                Clock::Virtual(time) => Clock::fixed(time.get()),
+                Clock::Host => Clock::Host,
+            },
+            files: self.files.fork(),
+            random: self.random.clone(),
+            syscalls: self.syscalls.clone(),
+            presets: self.presets.clone(),
+            arguments: self.arguments.clone(),
+            breakpoints: self.breakpoints.clone(),
+            stopped_at: self.stopped_at,
+            watched: None,
+        }
+    }
+
+    pub fn add_hook(&mut self, hook: Hook) {
+        self.hooks.push(hook);
+    }
+
+    fn notify(&mut self, event: Event) {
+        if self.hooks.is_empty() {
+            return;
+        }
+
+        let mut hooks = std::mem::take(&mut self.hooks);
+        for hook in hooks.iter_mut() {
+            hook.notify(self, &event);
+        }
+        self.hooks = hooks;
+    }
+
+    // Destination of a load, held back one instruction when modelling the
+    // MIPS I load delay
+    fn write_loaded(&mut self, register: usize, value: u32) {
+        if self.load_delay {
+            self.pending_load = Some((register, value));
+        } else {
+            self.write_register(register, value);
+        }
+    }
+
+    // Lands the load issued by the previous instruction
+    fn retire_load(&mut self) {
+        if let Some((register, value)) = self.retiring_load.take() {
+            self.write_register(register, value);
+        }
+    }
+
+    pub fn set_instruction_limit(&mut self, limit: Option<u64>) {
+        self.instruction_limit = limit;
+    }
+
+    pub fn set_load_delay(&mut self, enabled: bool) {
+        self.load_delay = enabled;
+    }
+
+    pub fn set_divide_by_zero(&mut self, behavior: DivideByZero) {
+        self.divide_by_zero = behavior;
+    }
+
+    // Whether a division may go ahead; a zero divisor is handled here
+    fn check_divisor(&mut self, divisor: u32) -> bool {
+        if divisor != 0 {
+            return true;
+        }
+
+        match self.divide_by_zero {
+            DivideByZero::Undefined => {}
+            DivideByZero::Trap => self.raise(Exception::Trap, ExitReason::Trap(self.pc)),
+            DivideByZero::Abort => self.exit = Some(ExitReason::DivideByZero(self.pc)),
+        }
+        false
+    }
+
+    // Takes an exception for the instruction at pc (the branch, when in a
+    // delay slot). With a handler installed at the vector, control goes there
+    // once the instruction finishes; without one the run stops with
+    // `unhandled`.
+    fn raise(&mut self, exception: Exception, unhandled: ExitReason) {
+        self.raise_to(EXCEPTION_VECTOR, exception, unhandled);
+    }
+
+    fn raise_to(&mut self, vector: u32, exception: Exception, unhandled: ExitReason) {
+        if !self.memory.is_executable(vector) {
+            self.exit = Some(unhandled);
+            return;
+        }
+
+        self.cp0.enter_exception(exception, self.pc, self.in_delay_slot);
+        self.enter_shadow_set();
+        self.vectoring = Some(vector);
+    }
+
+    // For encodings no instruction module implements
+    fn reserved_instruction(&mut self) {
+        self.raise(
+            Exception::ReservedInstruction,
+            ExitReason::ReservedInstruction(self.pc),
+        );
+    }
+
+    // Takes a pending interrupt before the instruction at pc runs. Without
+    // a handler at the vector the guest can't take it, so it stays pending.
+    fn take_interrupt(&mut self) {
+        if !self.cp0.interrupt_pending() || !self.memory.is_executable(EXCEPTION_VECTOR) {
+            return;
+        }
+
+        self.cp0.enter_exception(Exception::Interrupt, self.pc, false);
+        self.enter_shadow_set();
+        self.pc = EXCEPTION_VECTOR;
+    }
+
+    // External interrupt lines 0-5, for devices. The interrupt is taken
+    // before the next instruction if the guest has it enabled.
+    pub fn raise_irq(&mut self, line: u32) {
+        self.cp0.set_irq(line, true);
+    }
+
+    pub fn lower_irq(&mut self, line: u32) {
+        self.cp0.set_irq(line, false);
+    }
+
+    // ERET: back to EPC with EXL cleared; there is no delay slot
+    fn return_from_exception(&mut self) {
+        self.cp0.status &= !Cp0::STATUS_EXL;
+        self.leave_shadow_set();
+        self.link = None;
+        self.pc = self.cp0.epc;
+        self.jump = true;
+    }
+
+    // Byte order of every halfword and word access, fetches included
+    pub fn set_endian(&mut self, endian: Endian) {
+        self.memory.endian = endian;
+    }
+
+    pub fn endian(&self) -> Endian {
+        self.memory.endian
+    }
+
+    pub fn set_alignment(&mut self, alignment: Alignment) {
+        self.alignment = alignment;
+    }
+
+    // Unaligned word accesses emulated so far
+    pub fn unaligned_accesses(&self) -> u64 {
+        self.unaligned_accesses
+    }
+
+    // Whether a `size`-byte access may go ahead; false when it raised an
+    // address error (AdEL for loads, AdES for stores). User mode may not
+    // touch kseg addresses, and unaligned accesses fault unless emulated.
+    fn check_address(&mut self, address: u32, size: u32, exception: Exception) -> bool {
+        let privileged = address >= 0x8000_0000 && self.cp0.is_user_mode();
+        if address.is_multiple_of(size) && !privileged {
+            return true;
+        }
+
+        if privileged {
+            self.cp0.bad_vaddr = address;
+            self.raise(exception, ExitReason::AddressError(address));
+            return false;
+        }
+
+        if self.alignment == Alignment::Fault {
+            self.cp0.bad_vaddr = address;
+            self.raise(exception, ExitReason::UnalignedAccess(address));
+            return false;
+        }
+
+        self.unaligned_accesses += 1;
+        true
+    }
+
+    // Gives kuseg a TLB of `entries` entries, for guest kernels that manage
+    // their own address spaces. Without it every address maps to itself.
+    pub fn enable_mmu(&mut self, entries: usize) {
+        self.cp0.tlb = Tlb::new(entries);
+    }
+
+    // The physical address for a fetch, load or store; None when translation
+    // raised a TLB exception
+    fn translate(&mut self, address: u32, store: bool) -> Option<u32> {
+        let fault = match self.cp0.translate(address, store) {
+            Ok(physical) => return Some(physical),
+            Err(fault) => fault,
+        };
+
+        let exception = match fault {
+            TlbFault::Modified => Exception::TlbModified,
+            _ if store => Exception::TlbStore,
+            _ => Exception::TlbLoad,
+        };
+        let unhandled = match fault {
+            TlbFault::Modified => ExitReason::TlbModified(address),
+            _ => ExitReason::TlbMiss(address),
+        };
+        // Nested misses go to the general vector
+        let vector = match fault {
+            TlbFault::Refill if self.cp0.status & Cp0::STATUS_EXL == 0 => TLB_REFILL_VECTOR,
+            _ => EXCEPTION_VECTOR,
+        };
+
+        self.cp0.enter_tlb_fault(address);
+        self.raise_to(vector, exception, unhandled);
+        None
+    }
+
+    fn fetch(&mut self, address: u32) -> Option<u32> {
+        let physical = self.translate(address, false)?;
+        Some(self.memory.read(physical))
+    }
+
+    // Zero-extended load of 1, 2 or 4 bytes in the machine's byte order
+    fn load(&mut self, address: u32, size: u32) -> Option<u32> {
+        if !self.check_address(address, size, Exception::AddressLoad) {
+            return None;
+        }
+        let physical = self.translate(address, false)?;
+
+        let value = match self.memory.read_device(physical, size) {
+            Some(value) => value,
+            None => match size {
+                1 => self.memory.read_u8(physical) as u32,
+                2 => self.memory.read_u16(physical) as u32,
+                _ => self.memory.read_u32(physical),
+            },
+        };
+
+        self.accessed(MemoryAccess {
+            kind: AccessKind::Load,
+            address,
+            size,
+            value,
+        });
+        Some(value)
+    }
+
+    fn store(&mut self, address: u32, size: u32, value: u32) {
+        if !self.check_address(address, size, Exception::AddressStore) {
+            return;
+        }
+
+        if self.link == Some(address - address % 4) {
+            self.link = None;
+        }
+
+        let Some(physical) = self.translate(address, true) else {
+            return;
+        };
+
+        if !self.memory.write_device(physical, size, value) {
+            match size {
+                1 => self.memory.write_u8(physical, value as u8),
+                2 => self.memory.write_u16(physical, value as u16),
+                _ => self.memory.write_u32(physical, value),
+            }
+        }
+
+        self.accessed(MemoryAccess {
+            kind: AccessKind::Store,
+            address,
+            size,
+            value,
+        });
+    }
+
+    // A byte a syscall reads or writes for the guest. Watchpoints and hooks
+    // see it as an access by the syscall instruction.
+    fn read_guest_u8(&mut self, address: u32) -> u8 {
+        let byte = self.memory.read_u8(address);
+        self.accessed(MemoryAccess {
+            kind: AccessKind::Load,
+            address,
+            size: 1,
+            value: byte as u32,
+        });
+        byte
+    }
+
+    fn write_guest_u8(&mut self, address: u32, byte: u8) {
+        self.memory.write_u8(address, byte);
+        self.accessed(MemoryAccess {
+            kind: AccessKind::Store,
+            address,
+            size: 1,
+            value: byte as u32,
+        });
+    }
+
+    // Every guest load and store ends up here once it went through, the
+    // syscalls' included
+    fn accessed(&mut self, access: MemoryAccess) {
+        if self.watched.is_none() {
+            self.watched = self
+                .breakpoints
+                .watched(&access)
+                .map(|number| (number, access));
+        }
+        self.notify(Event::MemoryAccess(access));
+    }
+
+    // Where the time (30) and sleep (32) syscalls get the time from; hand
+    // the same clock to an Rtc so the guest sees one time
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.clock.clone()
+    }
+
+    // Seed for the random number syscalls; the same seed gives the same
+    // numbers on every run
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.random = Random::new(seed);
+    }
+
+    // Adds the service for syscall `code`, or replaces the built-in one. The
+    // handler runs in place of the syscall instruction.
+    pub fn register_syscall(&mut self, code: u32, handler: impl Fn(&mut CPU) + 'static) {
+        self.syscalls.insert(code, Rc::new(handler));
+    }
+
+    // Ends the run with `reason` once the current instruction finishes
+    pub fn stop(&mut self, reason: ExitReason) {
+        self.exit = Some(reason);
+    }
+
+    // Confines the file syscalls to paths under `root`
+    pub fn set_sandbox(&mut self, root: PathBuf) {
+        self.files.set_root(root);
+    }
+
+    // Maps a device at [start, end) for loads and stores. Instructions are
+    // never fetched from devices.
+    pub fn attach(&mut self, start: u32, end: u32, device: Box<dyn Device>) -> Result<(), Error> {
+        self.memory.attach(Mapping { start, end, device })
+    }
+
+    // Provides `count` shadow sets on top of the normal one (MIPS32R2 allows 15)
+    pub fn set_shadow_sets(&mut self, count: usize) {
+        let count = count.min(15);
+        self.register_sets.resize(count + 1, vec![Register::new(); 32]);
+        self.cp0.set_srs_field(Cp0::HSS_SHIFT, count as u32);
+    }
+
+    pub fn read_shadow_register(&self, set: usize, register: usize) -> u32 {
+        if set == self.cp0.srs_field(Cp0::CSS_SHIFT) as usize {
+            return self.read_register(register);
+        }
+
+        match self.register_sets.get(set) {
+            Some(registers) if register != 0 => registers[register].read(),
+            _ => 0,
+        }
+    }
+
+    pub fn write_shadow_register(&mut self, set: usize, register: usize, value: u32) {
+        if set == self.cp0.srs_field(Cp0::CSS_SHIFT) as usize {
+            return self.write_register(register, value);
+        }
+
+        if let Some(registers) = self.register_sets.get_mut(set) {
+            registers[register].write(value);
+        }
+    }
+
+    // Exception/interrupt entry: the handler runs in SRSCtl.ESS and the
+    // interrupted set is remembered in PSS
+    pub fn enter_shadow_set(&mut self) {
+        let current = self.cp0.srs_field(Cp0::CSS_SHIFT);
+        let exception = self.cp0.srs_field(Cp0::ESS_SHIFT);
+
+        self.cp0.set_srs_field(Cp0::PSS_SHIFT, current);
+        self.switch_register_set(exception as usize);
+    }
+
+    // ERET: back to the set saved in PSS
+    pub fn leave_shadow_set(&mut self) {
+        let previous = self.cp0.srs_field(Cp0::PSS_SHIFT);
+        self.switch_register_set(previous as usize);
+    }
+
+    fn switch_register_set(&mut self, set: usize) {
+        let current = self.cp0.srs_field(Cp0::CSS_SHIFT) as usize;
+        if set == current || set >= self.register_sets.len() {
+            return;
+        }
+
+        let incoming = std::mem::take(&mut self.register_sets[set]);
+        self.register_sets[current] = std::mem::replace(&mut self.registers, incoming);
+        self.cp0.set_srs_field(Cp0::CSS_SHIFT, set as u32);
+    }
+
+    // Puts the machine back to its post-construction state so it can run
+    // another program or input. Settings (load delay, alignment, shadow set
+    // count, ...) are kept, and so is memory unless `clear_memory` is set.
+    pub fn reset(&mut self, clear_memory: bool) {
+        self.switch_register_set(0);
+        for register in self.registers.iter_mut() {
+            register.write(0);
+        }
+        for set in self.register_sets.iter_mut() {
+            set.fill(Register::new());
+        }
+
+        let shadow_sets = self.cp0.srs_field(Cp0::HSS_SHIFT);
+        let tlb_entries = self.cp0.tlb.len();
+        self.cp0 = Cp0::new();
+        self.cp0.set_srs_field(Cp0::HSS_SHIFT, shadow_sets);
+        self.cp0.tlb = Tlb::new(tlb_entries);
+        self.cp1 = Cp1::new();
+        self.files.close_all();
+
+        if clear_memory {
+            self.memory.clear();
+            self.symbols.clear();
+            self.entry = self.memory.layout.text_base;
+        }
+
+        self.pc = 0;
+        self.hi = 0;
+        self.lo = 0;
+        self.executed = 0;
+        self.jump = false;
+        self.in_delay_slot = false;
+        self.vectoring = None;
+        self.exit = None;
+        self.loop_heads.clear();
+        self.shadow_stack.clear();
+        self.warnings.clear();
+        self.pending_load = None;
+        self.retiring_load = None;
+        self.link = None;
+        self.unaligned_accesses = 0;
+        self.faults.reset();
+        self.bit_flips.reset();
+        self.random.reset();
+        self.energy = EnergyMeter::default();
+        self.stopped_at = None;
+
+        self.preset_registers();
+    }
+
+    // Stops runs before the instruction at `address`, when `condition` holds
+    // if there is one. Returns the breakpoint's number.
+    pub fn add_breakpoint(&mut self, address: u32, condition: Option<Condition>) -> usize {
+        self.breakpoints.add(address, condition)
+    }
+
+    pub fn remove_breakpoint(&mut self, number: usize) -> bool {
+        self.breakpoints.remove(number)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        self.breakpoints.list()
+    }
+
+    // Stops runs after an instruction that loads or stores (as `watch`
+    // says) any of the `length` bytes from `address`. Returns the
+    // watchpoint's number, which remove_breakpoint takes too.
+    pub fn add_watchpoint(&mut self, address: u32, length: u32, watch: Watch) -> usize {
+        self.breakpoints.watch(address, length, watch)
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        self.breakpoints.watchpoints()
+    }
+
+    pub fn set_faults(&mut self, faults: Vec<Fault>) {
+        self.faults = FaultInjector::new(faults);
+    }
+
+    // The faults injected during the last run, in order
+    pub fn injections(&self) -> &[Injection] {
+        self.faults.log()
+    }
+
+    pub fn set_bit_flips(&mut self, bit_flips: BitFlipper) {
+        self.bit_flips = bit_flips;
+    }
+
+    // The memory bits flipped during the last run, in order
+    pub fn flips(&self) -> &[Flip] {
+        self.bit_flips.log()
+    }
+
+    // Flips a bit of some word of data memory the program has touched
+    fn flip_bit(&mut self) {
+        let mut words: Vec<(u32, u32)> = self
+            .memory
+            .words()
+            .filter(|&(address, _)| !self.memory.is_executable(address))
+            .collect();
+        words.sort();
+
+        if let Some((address, value)) = self.bit_flips.flip(self.executed, &words) {
+            self.memory.write(address, value);
+        }
+    }
+
+    // Starts keeping what the program prints, with the input it reads
+    pub fn record_console(&mut self) {
+        self.io.record();
+    }
+
+    // What the program printed, with the input it read, since record_console;
+    // empty if it was never called
+    pub fn console(&self) -> &str {
+        self.io.transcript()
+    }
+
+    // Where the console syscalls read and write, instead of stdio
+    pub fn set_console(&mut self, console: Box<dyn Console>) {
+        self.io.set_console(console);
+    }
+
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    // Instruction counts by energy class for the last run
+    pub fn energy(&self) -> &EnergyMeter {
+        &self.energy
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    // "main+0x14" for an address past a symbol in the same region
+    pub fn symbol_at(&self, address: u32) -> Option<String> {
+        let (name, offset) = self.symbols.nearest(address)?;
+        let region = self.memory.region(address)?;
+        if !region.contains(address - offset) {
+            return None;
+        }
+
+        Some(if offset == 0 { name } else { format!("{}+{:#x}", name, offset) })
+    }
+
+    // " <main+0x14>" when the address has a symbol, for appending to it
+    fn label(&self, address: u32) -> String {
+        self.symbol_at(address)
+            .map(|symbol| format!(" <{}>", symbol))
+            .unwrap_or_default()
+    }
+
+    pub fn cp0(&self) -> &Cp0 {
+        &self.cp0
+    }
+
+    pub fn pc(&self) -> u32 {
+        self.pc
+    }
+
+    pub fn entry(&self) -> u32 {
+        self.entry
+    }
+
+    pub fn set_entry(&mut self, entry: u32) {
+        self.entry = entry;
+    }
+
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    // Runs the program from its entry point. Err is for the host failing
+    // the run (the trace can't be written); whatever the guest does ends
+    // with an ExitReason.
+    pub fn run(&mut self) -> Result<ExitReason, Error> {
+        self.start();
+        self.resume()
+    }
+
+    // Points the machine at the entry point with fresh run bookkeeping
+    pub fn start(&mut self) {
+        self.pc = self.entry;
+        self.executed = 0;
+        self.loop_heads.clear();
+        self.shadow_stack.clear();
+        self.warnings.clear();
+        self.pending_load = None;
+        self.link = None;
+        self.faults.reset();
+        self.bit_flips.reset();
+        self.energy = EnergyMeter::default();
+        self.stopped_at = None;
+    }
+
+    // Runs from the current pc until something stops the machine, or until
+    // the trace can't be written
+    pub fn resume(&mut self) -> Result<ExitReason, Error> {
+        let reason = loop {
+            if let Some(reason) = self.step_instruction() {
+                break reason;
+            }
+            self.check_trace()?;
+        };
+
+        self.flush_trace()?;
+        Ok(reason)
+    }
+
+    // Fails once the tracer couldn't write a record
+    fn check_trace(&mut self) -> Result<(), Error> {
+        match self.tracer.as_mut().and_then(Tracer::take_error) {
+            Some(error) => Err(Error::Trace(error)),
+            None => Ok(()),
+        }
+    }
+
+    fn flush_trace(&mut self) -> Result<(), Error> {
+        match &mut self.tracer {
+            Some(tracer) => tracer.flush().map_err(Error::Trace),
+            None => Ok(()),
+        }
+    }
+
+    // Runs one instruction, reporting what it was and what it did
+    pub fn step(&mut self) -> Step {
+        self.observed = Some(None);
+        let exit = self.step_instruction();
+        let executed = self.observed.take().flatten();
+        self.stopped_at = Some(self.pc);
+        Step { executed, exit }
+    }
+
+    // Runs the instruction at pc, with the run loop's bookkeeping
+    fn step_instruction(&mut self) -> Option<ExitReason> {
+        let resumed = self.stopped_at.take() == Some(self.pc);
+        if !resumed && !self.breakpoints.is_empty() {
+            if let Some(number) = self.breakpoints.hit(self, self.pc) {
+                self.stopped_at = Some(self.pc);
+                return Some(ExitReason::Breakpoint { pc: self.pc, number });
+            }
+        }
+
+        self.cp0.tick();
+        let lines = self.memory.tick_devices();
+        self.cp0.set_device_interrupts(lines);
+        self.take_interrupt();
+
+        let pc = self.pc;
+        // Ran past the last instruction; MARS calls this dropping off the bottom
+        let mapped = self.cp0.translate(pc, false);
+        if self.memory.has_code() && mapped.is_ok_and(|pc| !self.memory.is_executable(pc)) {
+            return Some(ExitReason::EndOfText(pc));
+        }
+
+        let Some(mut word) = self.fetch(pc) else {
+            // The fetch itself missed in the TLB
+            if let Some(reason) = self.exit.take() {
+                return Some(reason);
+            }
+            self.pc = self.vectoring.take().unwrap_or(pc);
+            return None;
+        };
+        self.retiring_load = self.pending_load.take();
+
+        let mut duplicate = false;
+        if !self.faults.is_empty() {
+            let name = instructions::get_instruction(word).name().to_string();
+            match self.faults.check(self.executed, pc, word, &name) {
+                Some(Action::Skip) => {
+                    self.executed += 1;
+                    self.pc += 4;
+                    self.retire_load();
+                    return None;
+                }
+                Some(Action::Duplicate) => duplicate = true,
+                Some(Action::Corrupt(mask)) => word ^= mask,
+                None => {}
+            }
+        }
+
+        if let Some((register, _)) = self.retiring_load {
+            if checks::reads(word).contains(&register) {
+                self.warn(pc, checks::load_delay_message(word, register));
+            }
+        }
+
+        let instruction = instructions::get_instruction(word);
+
+        // Registers before, and the address of a store, for the trace and
+        // for step()
+        let observing = self.tracer.is_some() || self.observed.is_some();
+        let traced = observing.then(|| {
+            let registers: Vec<u32> = (0..32).map(|register| self.read_register(register)).collect();
+            let store = matches!(word >> 26, 0x28..=0x2e | 0x38 | 0x39).then(|| {
+                let rs = ((word >> 21) & 0b11111) as usize;
+                self.read_register(rs).wrapping_add(word as u16 as i16 as u32)
+            });
+            (registers, store)
+        });
+        // Decoded before it runs, so the values are its inputs
+        let decoded = self.observed.is_some().then(|| instruction.decode(self));
+        self.notify(Event::PreInstruction(pc, word));
+        self.watched = None;
+
+        instruction.execute(self);
+        // Only straight-line instructions are run twice
+        let straight = !self.jump && self.vectoring.is_none() && self.exit.is_none();
+        if duplicate && straight && self.pc == pc {
+            instruction.execute(self);
+        }
+        self.executed += 1;
+        self.energy.record(word);
+        self.retire_load();
+
+        if let Some((before, store)) = traced {
+            self.trace(pc, word, instruction.name(), &before, store, decoded);
+        }
+        self.notify(Event::PostInstruction(pc, word));
+
+        if self.bit_flips.is_enabled() && self.bit_flips.is_due(self.executed) {
+            self.flip_bit();
+        }
+
+        if let Some(reason) = self.exit.take() {
+            if let ExitReason::Break { .. } = reason {
+                self.pc += 4;
+            }
+            return Some(reason);
+        }
+
+        // The stack grew down into the heap
+        if let Some(end) = self.memory.heap_end() {
+            let sp = self.read_register(29);
+            if sp < end {
+                return Some(ExitReason::HeapCollision(sp));
+            }
+        }
+
+        if let Some(vector) = self.vectoring.take() {
+            self.jump = false;
+            self.pc = vector;
+        } else if !self.jump {
+            self.pc += 4;
+        } else {
+            self.jump = false;
+        }
+
+        if self.pc != pc.wrapping_add(4) {
+            self.track_call(pc, word);
+
+            // Unmapped targets fault when they are fetched instead
+            let target = self.cp0.translate(self.pc, false);
+            let outside = target.is_ok_and(|target| !self.memory.is_executable(target));
+            if self.memory.has_code() && outside {
+                return Some(ExitReason::WildJump { from: pc, to: self.pc });
+            }
+        }
+
+        if let Some((number, access)) = self.watched.take() {
+            return Some(ExitReason::Watchpoint { pc, number, access });
+        }
+
+        // A loop waiting on an interrupt isn't stuck
+        if self.pc <= pc && !self.cp0.interrupts_enabled() && self.is_idle_loop() {
+            return Some(ExitReason::IdleLoop(self.pc));
+        }
+
+        if self.instruction_limit.is_some_and(|limit| self.executed >= limit) {
+            return Some(ExitReason::InstructionLimit);
+        }
+
+        None
+    }
+
+    fn trace(
+        &mut self,
+        pc: u32,
+        word: u32,
+        name: &str,
+        before: &[u32],
+        store: Option<u32>,
+        decoded: Option<String>,
+    ) {
+        let writes: Vec<(usize, u32)> = (1..32)
+            .filter(|&register| self.read_register(register) != before[register])
+            .map(|register| (register, self.read_register(register)))
+            .collect();
+        let store = store.map(|address| {
+            let physical = self.cp0.translate(address, true).unwrap_or(address);
+            (address, self.memory.read(physical - physical % 4))
+        });
+
+        if self.tracer.is_some() {
+            let symbol = self.symbol_at(pc);
+            if let Some(tracer) = &mut self.tracer {
+                tracer.record(&Retired {
+                    pc,
+                    word,
+                    name,
+                    symbol: symbol.as_deref(),
+                    writes: &writes,
+                    store,
+                });
+            }
+        }
+
+        if let (Some(observed), Some(decoded)) = (&mut self.observed, decoded) {
+            let operands = decoded.strip_prefix(name).unwrap_or(&decoded).trim();
+            *observed = Some(Executed {
+                pc,
+                word,
+                mnemonic: name.to_string(),
+                operands: operands.to_string(),
+                writes,
+                store,
+            });
+        }
+    }
+
+    // Called after every taken branch or jump
+    fn track_call(&mut self, pc: u32, word: u32) {
+        let opcode = word >> 26;
+        let rs = (word >> 21) & 0b11111;
+        let rt = (word >> 16) & 0b11111;
+        let funct = word & 0b111111;
+
+        match (opcode, funct) {
+            // JAL, JALR, BLTZAL/BGEZAL and their likely forms
+            (3, _) | (0, 0x09) => self.shadow_stack.push(Frame {
+                call_site: pc,
+                target: self.pc,
+            }),
+            (1, _) if (16..=19).contains(&rt) => self.shadow_stack.push(Frame {
+                call_site: pc,
+                target: self.pc,
+            }),
+
+            // JR $ra
+            (0, 0x08) if rs == 31 => {
+                self.shadow_stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    // Delay slot misuse seen while running, once per address
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    fn warn(&mut self, address: u32, message: String) {
+        if !self.warnings.iter().any(|warning| warning.address == address) {
+            self.warnings.push(Warning { address, message });
+        }
+    }
+
+    pub fn backtrace(&self) -> &[Frame] {
+        &self.shadow_stack
+    }
+
+    // The instruction at `address` with the register values it works on
+    pub fn describe(&mut self, address: u32) -> String {
+        let word = self.memory.read(address);
+        let instruction = instructions::get_instruction(word).decode(self);
+        format!("{:#010x}{}: {}", address, self.label(address), instruction)
+    }
+
+    // Explains an ExitReason::WildJump: the jump, the register it went
+    // through (if any) and the calls that led there
+    pub fn wild_jump_report(&mut self, from: u32, to: u32) -> String {
+        let word = self.memory.read(from);
+        let instruction = instructions::get_instruction(word).decode(self);
+
+        let mut report = format!(
+            "wild jump to {:#010x} from {:#010x}: {}\n",
+            to, from, instruction
+        );
+
+        // JR/JALR jump through rs
+        if word >> 26 == 0 && matches!(word & 0b111111, 0x08 | 0x09) {
+            let rs = ((word >> 21) & 0b11111) as usize;
+            report.push_str(&format!(
+                "  through ${} = {:#010x}\n",
+                instructions::REGISTER_NAMES[rs],
+                self.read_register(rs)
+            ));
+        }
+
+        report.push_str("backtrace (most recent call first):\n");
+        report.push_str(&format!("  #0 {:#010x}{}\n", from, self.label(from)));
+        for (depth, frame) in self.shadow_stack.iter().rev().enumerate() {
+            report.push_str(&format!(
+                "  #{} {:#010x}{} called {:#010x}{}\n",
+                depth + 1,
+                frame.call_site,
+                self.label(frame.call_site),
+                frame.target,
+                self.label(frame.target)
+            ));
+        }
+
+        report
+    }
+
+    // Called on every backward branch or jump. The loop is idle when its head
+    // is reached again with the same registers (HI, LO, the FPRs and the FP
+    // condition codes too), no memory written and no input consumed since the previous visit.
+    fn is_idle_loop(&mut self) -> bool {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for register in &self.registers {
+            register.read().hash(&mut hasher);
+        }
+        (self.hi, self.lo).hash(&mut hasher);
+        for register in 0..32 {
+            self.cp1.read(register).hash(&mut hasher);
+        }
+        self.cp1.fcsr.hash(&mut hasher);
+        self.memory.generation.hash(&mut hasher);
+        self.io.reads().hash(&mut hasher);
+        let state = hasher.finish();
+
+        self.loop_heads.insert(self.pc, state) == Some(state)
+    }
+
+    // Branch likely: the delay slot only runs when the branch is taken and is
+    // skipped otherwise
+    fn branch_likely(&mut self, taken: bool, offset: i16) {
+        if taken {
+            self.run_branch_delayed();
+            self.pc = self.pc.wrapping_add((offset as u32) << 2);
+        } else {
+            self.pc = self.pc.wrapping_add(4);
+        }
+    }
+
+    fn run_branch_delayed(&mut self) {
+        // A miss here is taken on the branch, with Cause.BD set
+        self.in_delay_slot = true;
+        let slot = self.fetch(self.pc + 4);
+        self.in_delay_slot = false;
+        let Some(branch_delayed_instruction) = slot else {
+            return;
+        };
+
+        // The branch itself was just fetched, so it is mapped
+        let branch = self.cp0.translate(self.pc, false).unwrap_or(self.pc);
+        let word = self.memory.read(branch);
+        if let Some(message) = checks::delay_slot(word, branch_delayed_instruction) {
+            self.warn(self.pc, message);
+        }
+
+        // The branch was the load's delay slot; the slot sees the value
+        self.retire_load();
+
+        let branch_delayed_instruction = instructions::get_instruction(branch_delayed_instruction);
+
+        self.in_delay_slot = true;
+        branch_delayed_instruction.execute(self);
+        self.in_delay_slot = false;
+    }
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for CPU {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Registers:")?;
+        for (i, register) in self.registers.iter().enumerate() {
+            writeln!(f, "Register {}: {}", i, register.read())?;
+        }
+        writeln!(f, "CP0:")?;
+        write!(f, "{}", self.cp0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AccessKind, Condition, Endian, ExitReason, Frame, Memory, MemoryAccess, Watch, CPU,
+    };
+    use crate::console::ScriptedConsole;
+    use crate::cp0::Cp0;
+    use crate::device::Device;
+    use crate::faults::{BitFlipper, Fault};
+    use crate::rtc::Clock;
+    use crate::trace::{TraceFormat, Tracer};
+
+    fn cpu_with_text(words: &[u32]) -> CPU {
+        let mut cpu = CPU::new();
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory.load_segment("text", 0x00400000, &bytes, true).unwrap();
+        cpu
+    }
+
+    // mfc0 $k0, $14 / addiu $k0, $k0, 4 / mtc0 $k0, $14 / eret
+    const SKIPPING_HANDLER: [u32; 4] = [0x401a7000, 0x275a0004, 0x409a7000, 0x42000018];
+
+    fn install_handler(cpu: &mut CPU, words: &[u32]) {
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory.load_segment("ktext", super::EXCEPTION_VECTOR, &bytes, true).unwrap();
+    }
+
+    #[test]
+    fn test_trap_without_handler_stops() {
+        // teq $zero, $zero
+        let mut cpu = cpu_with_text(&[0x00000034]);
+        assert_eq!(cpu.run().unwrap(), ExitReason::Trap(0x00400000));
+    }
+
+    #[test]
+    fn test_trap_vectors_to_handler_and_eret_returns() {
+        // teq $zero, $zero / addi $t0, $zero, 1 / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[0x00000034, 0x20080001, 0x2002000a, 0x0000000c]);
+        install_handler(&mut cpu, &SKIPPING_HANDLER);
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(8), 1);
+        assert_eq!(cpu.cp0.epc, 0x00400004);
+        assert_eq!(cpu.cp0.exception_code(), 13);
+        assert_eq!(cpu.cp0.status & Cp0::STATUS_EXL, 0);
+    }
+
+    #[test]
+    fn test_reserved_instruction_without_handler_stops() {
+        // addi $t0, $zero, 1 / SPECIAL funct 0x05, which nothing decodes
+        let mut cpu = cpu_with_text(&[0x20080001, 0x00000005]);
+        assert_eq!(cpu.run().unwrap(), ExitReason::ReservedInstruction(0x00400004));
+
+        // An unused primary opcode
+        let mut cpu = cpu_with_text(&[0xfc000000]);
+        assert_eq!(cpu.run().unwrap(), ExitReason::ReservedInstruction(0x00400000));
+    }
+
+    #[test]
+    fn test_reserved_instruction_vectors_to_handler() {
+        // SPECIAL funct 0x05 / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[0x00000005, 0x2002000a, 0x0000000c]);
+        install_handler(&mut cpu, &SKIPPING_HANDLER);
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.cp0.exception_code(), 10);
+    }
+
+    #[test]
+    fn test_instruction_limit() {
+        // loop: addi $t0, $t0, 1 / beq $zero, $zero, loop / nop
+        let mut cpu = cpu_with_text(&[0x21080001, 0x1000fffe, 0]);
+        cpu.set_instruction_limit(Some(10));
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::InstructionLimit);
+        assert_eq!(cpu.executed(), 10);
+    }
+
+    #[test]
+    fn test_step() {
+        // addi $t0, $zero, 5 / sw $t0, 4($gp) / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[0x20080005, 0xaf880004, 0x2002000a, 0x0000000c]);
+        cpu.start();
+
+        let step = cpu.step();
+        let executed = step.executed.unwrap();
+        assert_eq!((executed.pc, executed.word), (0x00400000, 0x20080005));
+        assert_eq!(executed.mnemonic, "ADDI");
+        assert_eq!(executed.operands, "rs 0: 0, rt 8: 0, imm 5");
+        assert_eq!(executed.writes, vec![(8, 5)]);
+        assert_eq!(executed.store, None);
+        assert_eq!(step.exit, None);
+
+        let executed = cpu.step().executed.unwrap();
+        assert!(executed.writes.is_empty());
+        assert_eq!(executed.store, Some((0x10008004, 5)));
+
+        cpu.step();
+        let step = cpu.step();
+        assert_eq!(step.executed.unwrap().mnemonic, "SYSCALL");
+        assert_eq!(step.exit, Some(ExitReason::Exited(0)));
+    }
+
+    #[test]
+    fn test_unwritable_trace_fails_the_run() {
+        struct Full;
+        impl std::io::Write for Full {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("no space left"))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // addi $t0, $zero, 5 / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[0x20080005, 0x2002000a, 0x0000000c]);
+        cpu.set_tracer(Tracer::new(TraceFormat::Spike, Box::new(Full)));
+
+        let error = cpu.run().unwrap_err();
+        assert_eq!(error.to_string(), "cannot write the trace: no space left");
+        // It stopped after the first instruction
+        assert_eq!(cpu.pc(), 0x00400004);
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        // addi $t0, $zero, 5 / sw $t0, 4($gp) / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[0x20080005, 0xaf880004, 0x2002000a, 0x0000000c]);
+        cpu.add_breakpoint(0x00400004, Some(Condition::parse("$t0 == 6").unwrap()));
+        let number = cpu.add_breakpoint(0x00400008, Some(Condition::parse("$t0 == 5").unwrap()));
+
+        assert_eq!(
+            cpu.run().unwrap(),
+            ExitReason::Breakpoint {
+                pc: 0x00400008,
+                number
+            }
+        );
+        assert_eq!(cpu.memory.read_u32(0x10008004), 5);
+        assert_eq!(cpu.resume().unwrap(), ExitReason::Exited(0));
+
+        assert!(cpu.remove_breakpoint(number));
+        assert!(!cpu.remove_breakpoint(number));
+        assert_eq!(cpu.breakpoints().len(), 1);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        // addi $t0, $zero, 5 / sw $t0, 4($gp) / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[0x20080005, 0xaf880004, 0x2002000a, 0x0000000c]);
+        cpu.add_watchpoint(0x10008004, 4, Watch::Read);
+        let number = cpu.add_watchpoint(0x10008006, 1, Watch::Write);
+
+        let access = MemoryAccess {
+            kind: AccessKind::Store,
+            address: 0x10008004,
+            size: 4,
+            value: 5,
+        };
+        assert_eq!(
+            cpu.run().unwrap(),
+            ExitReason::Watchpoint {
+                pc: 0x00400004,
+                number,
+                access
+            }
+        );
+        // The store went through and the run stopped after it
+        assert_eq!(cpu.memory.read_u32(0x10008004), 5);
+        assert_eq!(cpu.pc(), 0x00400008);
+        assert_eq!(cpu.resume().unwrap(), ExitReason::Exited(0));
+    }
+
+    #[test]
+    fn test_watchpoint_sees_syscall_writes() {
+        // lui $a0, 0x1001 / addi $a1, $zero, 8 / addi $v0, $zero, 8 / syscall /
+        // addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[
+            0x3c041001, 0x20050008, 0x20020008, 0x0000000c, 0x2002000a, 0x0000000c,
+        ]);
+        cpu.set_console(Box::new(ScriptedConsole::new("hello\n")));
+        let number = cpu.add_watchpoint(0x10010002, 1, Watch::Write);
+
+        // read_string stopped at its third byte, after it wrote them all
+        assert_eq!(
+            cpu.run().unwrap(),
+            ExitReason::Watchpoint {
+                pc: 0x0040000c,
+                number,
+                access: MemoryAccess {
+                    kind: AccessKind::Store,
+                    address: 0x10010002,
+                    size: 1,
+                    value: b'l' as u32,
+                }
+            }
+        );
+        assert_eq!(cpu.memory.read_u8(0x10010004), b'o');
+        assert_eq!(cpu.resume().unwrap(), ExitReason::Exited(0));
+    }
+
+    #[test]
+    fn test_forks_share_memory_until_written() {
+        // addi $v0, $zero, 5 / syscall / sw $v0, 0($gp) / addi $a0, $v0, 1 /
+        // addi $v0, $zero, 1 / syscall / addi $v0, $zero, 10 / syscall
+        let mut base = cpu_with_text(&[
+            0x20020005, 0x0000000c, 0xaf820000, 0x20440001, 0x20020001, 0x0000000c, 0x2002000a,
+            0x0000000c,
+        ]);
+        base.memory.write(0x10008000, 99);
+
+        let mut outputs = Vec::new();
+        for input in ["1\n", "41\n"] {
+            let mut fork = base.fork();
+            let console = ScriptedConsole::new(input);
+            outputs.push(console.output());
+            fork.set_console(Box::new(console));
+
+            assert_eq!(fork.run().unwrap(), ExitReason::Exited(0));
+            assert!(std::rc::Rc::ptr_eq(
+                &fork.memory.pages[&0x00400],
+                &base.memory.pages[&0x00400]
+            ));
+            assert!(!std::rc::Rc::ptr_eq(
+                &fork.memory.pages[&0x10008],
+                &base.memory.pages[&0x10008]
+            ));
+        }
+
+        assert_eq!(*outputs[0].borrow(), "2");
+        assert_eq!(*outputs[1].borrow(), "42");
+        assert_eq!(base.memory.read(0x10008000), 99);
+    }
+
+    #[test]
+    fn test_end_of_text() {
+        // addi $t0, $zero, 1 / nop / nop / addi $t0, $t0, 1, and no exit
+        let mut cpu = cpu_with_text(&[0x20080001, 0, 0, 0x21080001]);
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::EndOfText(0x00400010));
+        assert_eq!(cpu.executed(), 4);
+        assert_eq!(cpu.read_register(8), 2);
+        assert_eq!(ExitReason::EndOfText(0x00400010).exit_code(), 0);
+    }
+
+    #[test]
+    fn test_timer_interrupt() {
+        // addi $t0, $zero, 5 / mtc0 $t0, Compare / loop: beq $zero, $zero, loop / nop
+        let mut cpu = cpu_with_text(&[0x20080005, 0x40885800, 0x1000ffff, 0]);
+        // mtc0 $zero, Compare / addi $v0, $zero, 10 / syscall
+        install_handler(&mut cpu, &[0x40805800, 0x2002000a, 0x0000000c]);
+        cpu.start();
+        cpu.cp0.status = Cp0::STATUS_IE | Cp0::CAUSE_IP7;
+
+        assert_eq!(cpu.resume().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.cp0.exception_code(), 0);
+        assert_eq!(cpu.cp0.epc, 0x00400008);
+        assert_eq!(cpu.cp0.cause & Cp0::CAUSE_IP7, 0);
+    }
+
+    #[test]
+    fn test_masked_timer_stays_pending() {
+        // addi $t0, $zero, 3 / mtc0 $t0, Compare / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[0x20080003, 0x40885800, 0x2002000a, 0x0000000c]);
+        install_handler(&mut cpu, &SKIPPING_HANDLER);
+        cpu.start();
+        cpu.cp0.status = Cp0::STATUS_IE;
+        cpu.cp0.count = 0;
+
+        assert_eq!(cpu.resume().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.cp0.count, 4);
+        assert_eq!(cpu.cp0.cause & Cp0::CAUSE_TI, Cp0::CAUSE_TI);
+    }
+
+    #[test]
+    fn test_irq_is_taken_when_unmasked() {
+        // addi $t0, $zero, 1 / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[0x20080001, 0x2002000a, 0x0000000c]);
+        // addi $t0, $zero, 2 / addi $v0, $zero, 10 / syscall
+        install_handler(&mut cpu, &[0x20080002, 0x2002000a, 0x0000000c]);
+        cpu.start();
+        cpu.cp0.status = Cp0::STATUS_IE | 1 << 12;
+
+        // IP3, masked
+        cpu.raise_irq(1);
+        assert_eq!(cpu.resume().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(8), 1);
+
+        // IP4
+        cpu.start();
+        cpu.cp0.status = Cp0::STATUS_IE | 1 << 12;
+        cpu.raise_irq(2);
+        assert_eq!(cpu.resume().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(8), 2);
+        assert_eq!(cpu.cp0.epc, 0x00400000);
+    }
+
+    #[test]
+    fn test_lower_irq_keeps_timer_pending() {
+        let mut cpu = CPU::new();
+
+        cpu.raise_irq(3);
+        cpu.lower_irq(3);
+        assert_eq!(cpu.cp0.cause, 0);
+
+        cpu.cp0.compare = 1;
+        cpu.cp0.tick();
+        cpu.raise_irq(5);
+        cpu.lower_irq(5);
+        assert_eq!(cpu.cp0.cause & Cp0::CAUSE_IP7, Cp0::CAUSE_IP7);
+    }
+
+    #[test]
+    fn test_unmapped_fetch_with_mmu_stops() {
+        let mut cpu = cpu_with_text(&[0x2002000a, 0x0000000c]);
+        cpu.enable_mmu(4);
+        assert_eq!(cpu.run().unwrap(), ExitReason::TlbMiss(0x00400000));
+    }
+
+    #[test]
+    fn test_tlb_refill_handler_maps_the_page() {
+        let mut cpu = CPU::new();
+        cpu.enable_mmu(4);
+        // lui $k0, 0x0040 / ori $k0, $k0, 0x0406 (frame 0x10010000, D, V) /
+        // mtc0 $k0, EntryLo0 / mtc0 $k0, EntryLo1 / tlbwr / eret
+        let refill: [u32; 6] = [
+            0x3c1a0040, 0x375a0406, 0x409a1000, 0x409a1800, 0x42000006, 0x42000018,
+        ];
+        let bytes: Vec<u8> = refill.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory.load_segment("refill", super::TLB_REFILL_VECTOR, &bytes, true).unwrap();
+        // lw $t0, 0x10($zero) / addi $v0, $zero, 10 / syscall, from kseg0
+        let kernel: [u32; 3] = [0x8c080010, 0x2002000a, 0x0000000c];
+        let bytes: Vec<u8> = kernel.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory.load_segment("kernel", 0x80001000, &bytes, true).unwrap();
+        cpu.memory.write(0x10010010, 0x1234);
+
+        cpu.start();
+        cpu.pc = 0x80001000;
+        assert_eq!(cpu.resume().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(8), 0x1234);
+        assert_eq!(cpu.cp0.exception_code(), 2);
+        assert_eq!(cpu.cp0.bad_vaddr, 0x10);
+        assert_eq!(cpu.cp0.epc, 0x80001000);
+    }
+
+    #[test]
+    fn test_overflow_vectors_to_handler() {
+        // add $t0, $t1, $t1 / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[0x01294020, 0x2002000a, 0x0000000c]);
+        install_handler(&mut cpu, &SKIPPING_HANDLER);
+        cpu.start();
+        cpu.write_register(9, 0x4000_0000);
+        cpu.write_register(8, 7);
+
+        assert_eq!(cpu.resume().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(8), 7);
+        assert_eq!(cpu.cp0.exception_code(), 12);
+        // the handler moved EPC past the ADD
+        assert_eq!(cpu.cp0.epc, 0x00400004);
+    }
+
+    #[test]
+    fn test_unaligned_load_raises_adel() {
+        // lw $t0, 2($gp) / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[0x8f880002, 0x2002000a, 0x0000000c]);
+        install_handler(&mut cpu, &SKIPPING_HANDLER);
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.cp0.exception_code(), 4);
+        assert_eq!(cpu.cp0.bad_vaddr, 0x10008002);
+    }
+
+    #[test]
+    fn test_user_mode_store_to_kseg_raises_ades() {
+        let mut cpu = CPU::new();
+        cpu.cp0.status = Cp0::STATUS_UM;
+
+        cpu.store(0x8000_1000, 4, 1);
+
+        assert_eq!(cpu.exit, Some(ExitReason::AddressError(0x8000_1000)));
+        assert_eq!(cpu.cp0.bad_vaddr, 0x8000_1000);
+        assert_eq!(cpu.memory.read(0x8000_1000), 0);
+
+        // Kernel mode may
+        cpu.exit = None;
+        cpu.cp0.status = 0;
+        cpu.store(0x8000_1000, 4, 1);
+        assert_eq!(cpu.memory.read(0x8000_1000), 1);
+    }
+
+    #[test]
+    fn test_exception_in_delay_slot_points_epc_at_branch() {
+        // beq $zero, $zero, 2 / teq $zero, $zero (slot)
+        let mut cpu = cpu_with_text(&[0x10000002, 0x00000034]);
+        // The handler is a BREAK, to look at the state on entry
+        install_handler(&mut cpu, &[0x0000000d]);
+
+        assert_eq!(
+            cpu.run().unwrap(),
+            ExitReason::Break {
+                pc: super::EXCEPTION_VECTOR,
+                code: 0
+            }
+        );
+        assert_eq!(cpu.cp0.epc, 0x00400000);
+        assert_ne!(cpu.cp0.cause & Cp0::CAUSE_BD, 0);
+    }
+
+    #[test]
+    fn test_idle_jump_to_self() {
+        // j . / nop
+        let mut cpu = cpu_with_text(&[0x08100000, 0x00000000]);
+        assert_eq!(cpu.run().unwrap(), ExitReason::IdleLoop(0x00400000));
+    }
+
+    #[test]
+    fn test_idle_loop_without_progress() {
+        // loop: addi $t0, $zero, 1 / j loop / nop
+        let mut cpu = cpu_with_text(&[0x20080001, 0x08100000, 0x00000000]);
+        assert_eq!(cpu.run().unwrap(), ExitReason::IdleLoop(0x00400000));
+    }
+
+    #[test]
+    fn test_wild_jump_through_register() {
+        // jal func / nop / nop / func: jr $t0 / nop
+        let mut cpu = cpu_with_text(&[0x0C100003, 0x00000000, 0x00000000, 0x01000008, 0x00000000]);
+
+        let reason = cpu.run().unwrap();
+
+        assert_eq!(reason, ExitReason::WildJump { from: 0x0040000c, to: 0 });
+        assert_eq!(
+            cpu.backtrace(),
+            &[Frame {
+                call_site: 0x00400000,
+                target: 0x0040000c
+            }]
+        );
+        let report = cpu.wild_jump_report(0x0040000c, 0);
+        assert!(report.contains("through $t0 = 0x00000000"));
+        assert!(report.contains("#1 0x00400000 called 0x0040000c"));
+    }
+
+    #[test]
+    fn test_call_and_return_is_not_wild() {
+        // jal func / nop / addi $v0, $zero, 10 / syscall / func: jr $ra / nop
+        let mut cpu = cpu_with_text(&[
+            0x0C100004, 0x00000000, 0x2002000a, 0x0000000c, 0x03e00008, 0x00000000,
+        ]);
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert!(cpu.backtrace().is_empty());
+    }
+
+    #[test]
+    fn test_load_delay() {
+        // lw $t0, 0($sp) ; addu $t1, $t0, $zero ; addu $t2, $t0, $zero ; exit
+        let mut cpu = cpu_with_text(&[
+            0x8fa80000, 0x01004821, 0x01005021, 0x2002000a, 0x0000000c,
+        ]);
+        cpu.memory.write(0x7fffeffc, 7);
+        cpu.set_load_delay(true);
+
+        cpu.run().unwrap();
+
+        assert_eq!(cpu.read_register(9), 0);
+        assert_eq!(cpu.read_register(10), 7);
+        assert_eq!(cpu.warnings()[0].address, 0x00400004);
+    }
+
+    #[test]
+    fn test_load_before_branch_visible_in_delay_slot() {
+        // lw $t0, 0($sp) ; beq $zero, $zero, 1 ; addu $t1, $t0, $zero ; nop ; exit
+        let mut cpu = cpu_with_text(&[
+            0x8fa80000, 0x10000001, 0x01004821, 0, 0x2002000a, 0x0000000c,
+        ]);
+        cpu.memory.write(0x7fffeffc, 7);
+        cpu.set_load_delay(true);
+
+        cpu.run().unwrap();
+
+        assert_eq!(cpu.read_register(9), 7);
+    }
+
+    #[test]
+    fn test_reset_runs_again() {
+        // addi $t0, $t0, 1 ; exit
+        let mut cpu = cpu_with_text(&[0x21080001, 0x2002000a, 0x0000000c]);
+        cpu.set_shadow_sets(1);
+
+        cpu.run().unwrap();
+        cpu.reset(false);
+        assert_eq!(cpu.read_register(8), 0);
+        assert_eq!(cpu.read_register(29), 0x7fffeffc);
+        assert_eq!(cpu.cp0.srs_field(Cp0::HSS_SHIFT), 1);
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(8), 1);
+    }
+
+    #[test]
+    fn test_arguments_on_the_stack() {
+        let mut cpu = cpu_with_text(&[0x2002000a, 0x0000000c]);
+        cpu.set_arguments(vec![String::from("prog"), String::from("ab")]);
+
+        let sp = cpu.read_register(29);
+        assert_eq!(sp, 0x7fffefe0);
+        assert_eq!(cpu.read_register(4), 2);
+        assert_eq!(cpu.memory.read(sp), 2);
+        assert_eq!(cpu.read_register(5), sp + 4);
+        assert_eq!(cpu.memory.read(sp + 4), 0x7fffeff8);
+        assert_eq!(cpu.memory.read(sp + 8), 0x7fffeffd);
+        assert_eq!(cpu.memory.read(sp + 12), 0);
+        assert_eq!(cpu.read_register(6), sp + 16);
+        assert_eq!(cpu.memory.read(sp + 16), 0);
+        assert_eq!(cpu.memory.read_u8(0x7fffeffd), b'a');
+        assert_eq!(cpu.memory.read_u8(0x7fffefff), 0);
+
+        // Back in place for the next run, even over cleared memory
+        cpu.run().unwrap();
+        cpu.reset(true);
+        assert_eq!(cpu.read_register(29), sp);
+        assert_eq!(cpu.memory.read_u8(0x7fffeff8), b'p');
+    }
+
+    #[test]
+    fn test_reset_clears_memory() {
+        let mut cpu = cpu_with_text(&[0x2002000a, 0x0000000c]);
+        cpu.set_entry(0x00400004);
+
+        cpu.reset(true);
+
+        assert!(!cpu.memory.has_code());
+        assert_eq!(cpu.memory.read(0x00400000), 0);
+        assert_eq!(cpu.entry(), 0x00400000);
+        assert!(cpu.memory.region(0x7fffeffc).is_some());
+    }
+
+    #[test]
+    fn test_fault_injection() {
+        // addi $t0, $t0, 1 (x3) ; exit
+        let text = [0x21080001, 0x21080001, 0x21080001, 0x2002000a, 0x0000000c];
+
+        let mut cpu = cpu_with_text(&text);
+        cpu.set_faults(vec![Fault::parse("skip:0x00400004").unwrap()]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.read_register(8), 2);
+        assert_eq!(cpu.injections().len(), 1);
+
+        let mut cpu = cpu_with_text(&text);
+        cpu.set_faults(vec![Fault::parse("dup:addi#3").unwrap()]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.read_register(8), 4);
+
+        // addi $t0, $t0, 1 -> addi $t0, $t0, 3
+        let mut cpu = cpu_with_text(&text);
+        cpu.set_faults(vec![Fault::parse("corrupt=2:0x00400000").unwrap()]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.read_register(8), 5);
+        assert_eq!(cpu.memory.read(0x00400000), 0x21080001);
+    }
+
+    #[test]
+    fn test_bit_flip_in_data() {
+        // lw $t0, 0($gp) ; addi $t1, $zero, 0 ; lw $t2, 0($gp) ; exit
+        let mut cpu = cpu_with_text(&[
+            0x8f880000, 0x20090000, 0x8f8a0000, 0x2002000a, 0x0000000c,
+        ]);
+        cpu.memory.load_segment("data", 0x10008000, &[0x55; 4], false).unwrap();
+        cpu.set_bit_flips(BitFlipper::new(3, 0.0, vec![1]));
+
+        cpu.run().unwrap();
+
+        let flip = &cpu.flips()[0];
+        assert_eq!((flip.cycle, flip.address, flip.before), (1, 0x10008000, 0x55555555));
+        assert_eq!(cpu.read_register(8), 0x55555555);
+        assert_eq!(cpu.read_register(10), 0x55555555 ^ 1 << flip.bit);
+    }
+
+    #[test]
+    fn test_shadow_set_switch_on_entry_and_return() {
+        let mut cpu = CPU::new();
+        cpu.set_shadow_sets(2);
+        cpu.cp0.set_srs_field(Cp0::ESS_SHIFT, 2);
+        cpu.write_register(8, 1);
+
+        cpu.enter_shadow_set();
+        assert_eq!(cpu.cp0.srs_field(Cp0::CSS_SHIFT), 2);
+        assert_eq!(cpu.cp0.srs_field(Cp0::PSS_SHIFT), 0);
+        assert_eq!(cpu.read_register(8), 0);
+        cpu.write_register(8, 2);
+
+        cpu.leave_shadow_set();
+        assert_eq!(cpu.cp0.srs_field(Cp0::CSS_SHIFT), 0);
+        assert_eq!(cpu.read_register(8), 1);
+        assert_eq!(cpu.read_shadow_register(2, 8), 2);
+    }
+
+    #[test]
+    fn test_counting_loop_is_not_idle() {
+        // addi $t0, $zero, 3 / loop: addi $t0, $t0, -1 / bne $t0, $zero, loop / nop
+        // addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[
+            0x20080003, 0x2108ffff, 0x1500fffe, 0x00000000, 0x2002000a, 0x0000000c,
+        ]);
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+    }
+
+    #[test]
+    fn test_floating_point_loop_is_not_idle() {
+        // lui $t0, 0x4040 / mtc1 $t0, $f0 / lui $t0, 0x3f80 / mtc1 $t0, $f1 /
+        // mtc1 $zero, $f2 / loop: sub.s $f0, $f0, $f1 / c.lt.s $f2, $f0 /
+        // bc1t loop / nop / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[
+            0x3c084040, 0x44880000, 0x3c083f80, 0x44880800, 0x44801000, 0x46010001, 0x4600103c,
+            0x4501fffd, 0x00000000, 0x2002000a, 0x0000000c,
+        ]);
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.cp1.read_single(0), 0.0);
+    }
+
+    #[test]
+    fn test_condition_code_loop_is_not_idle() {
+        // The second visit to loop differs only in condition code 0
+        // b first / nop / loop: bc1t done / nop / c.eq.s $f0, $f0 /
+        // first: j loop / nop / nop / done: addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[
+            0x10000004, 0x00000000, 0x45010005, 0x00000000, 0x46000032, 0x08100002, 0x00000000,
+            0x00000000, 0x2002000a, 0x0000000c,
+        ]);
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+    }
+
+    #[test]
+    fn test_memory_allocates_pages_on_first_write() {
+        let mut memory = Memory::new();
+        assert_eq!(memory.read(0xfffffffc), 0);
+        assert!(memory.pages.is_empty());
+
+        memory.write(0x10010ffc, 1);
+        memory.write(0x10011000, 2);
+        memory.write(0xfffffffc, 3);
+
+        assert_eq!(memory.pages.len(), 3);
+        assert_eq!(memory.read(0x10010ffc), 1);
+        assert_eq!(memory.read(0x10011000), 2);
+        assert_eq!(memory.read(0xfffffffc), 3);
+        assert_eq!(memory.find_references(1, 3), vec![(0x10010ffc, 1), (0x10011000, 2)]);
+    }
+
+    // Offset 0 holds the last word written, offset 4 counts ticks
+    #[derive(Default)]
+    struct Latch {
+        value: u32,
+        ticks: u32,
+    }
+
+    impl Device for Latch {
+        fn read(&mut self, offset: u32) -> u32 {
+            match offset {
+                0 => self.value,
+                _ => self.ticks,
+            }
+        }
+
+        fn write(&mut self, offset: u32, value: u32) {
+            if offset == 0 {
+                self.value = value;
+            }
+        }
+
+        fn tick(&mut self) {
+            self.ticks += 1;
+        }
+    }
+
+    #[test]
+    fn test_device_loads_and_stores() {
+        // lui $t0, 0xffff / addi $t1, $zero, 0x41 / sb $t1, 1($t0) / lw $t2, 0($t0) /
+        // lw $t3, 4($t0) / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[
+            0x3c08ffff, 0x20090041, 0xa1090001, 0x8d0a0000, 0x8d0b0004, 0x2002000a, 0x0000000c,
+        ]);
+        cpu.attach(0xffff0000, 0xffff0010, Box::<Latch>::default())
+            .unwrap();
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(10), 0x4100);
+        assert_eq!(cpu.read_register(11), 5);
+        assert_eq!(cpu.memory.read(0xffff0000), 0);
+
+        assert!(cpu
+            .attach(0xffff000c, 0xffff0020, Box::<Latch>::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_big_endian_memory() {
+        let mut memory = Memory::new();
+        memory.endian = Endian::Big;
+
+        memory.write_u32(0x10010000, 0x1122_3344);
+        assert_eq!(memory.read_u8(0x10010000), 0x11);
+        assert_eq!(memory.read_u16(0x10010002), 0x3344);
+
+        memory.write_u16(0x10010001, 0xaabb);
+        assert_eq!(memory.read(0x10010000), 0x11aa_bb44);
+    }
+
+    #[test]
+    fn test_big_endian_lb_lw() {
+        // lw $t0, 0($gp) / lb $t1, 1($gp) / addi $v0, $zero, 10 / syscall
+        let words = [0x8f880000u32, 0x83890001, 0x2002000a, 0x0000000c];
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes()).collect();
+        let mut cpu = CPU::new();
+        cpu.set_endian(Endian::Big);
+        cpu.memory.load_segment("text", 0x00400000, &bytes, true).unwrap();
+        cpu.memory.load_segment("data", 0x10008000, &[0x12, 0x80, 0x56, 0x78], false).unwrap();
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(8), 0x1280_5678);
+        assert_eq!(cpu.read_register(9), 0xffff_ff80);
+    }
+
+    #[test]
+    fn test_time_and_sleep_syscalls() {
+        // addi $a0, $zero, 250 / addi $v0, $zero, 32 / syscall /
+        // addi $v0, $zero, 30 / syscall / addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[
+            0x200400fa, 0x20020020, 0x0000000c, 0x2002001e, 0x0000000c, 0x2002000a, 0x0000000c,
+        ]);
+        cpu.set_clock(Clock::fixed(0x2_ffff_ff10));
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(4), 0x0000_000a);
+        assert_eq!(cpu.read_register(5), 3);
+    }
+
+    #[test]
+    fn test_sbrk_grows_the_heap_past_data() {
+        // addi $a0, $zero, 10 / addi $v0, $zero, 9 / syscall / addu $t0, $v0, $zero /
+        // addi $a0, $zero, 4 / addi $v0, $zero, 9 / syscall / addu $t1, $v0, $zero /
+        // addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[
+            0x2004000a, 0x20020009, 0x0000000c, 0x00404021, 0x20040004, 0x20020009, 0x0000000c,
+            0x00404821, 0x2002000a, 0x0000000c,
+        ]);
+        cpu.memory.load_segment("data", 0x10010000, b"hello", false).unwrap();
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(8), 0x10010008);
+        assert_eq!(cpu.read_register(9), 0x10010014);
+        assert_eq!(cpu.memory.heap_end(), Some(0x10010018));
+        assert_eq!(cpu.memory.region(0x10010010).unwrap().name, "heap");
+    }
+
+    #[test]
+    fn test_sbrk_into_the_stack() {
+        // lui $a0, 0x7000 / addi $v0, $zero, 9 / syscall
+        let mut cpu = cpu_with_text(&[0x3c047000, 0x20020009, 0x0000000c]);
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::HeapCollision(0x80010000));
+    }
+
+    #[test]
+    fn test_stack_below_the_break() {
+        // addi $a0, $zero, 16 / addi $v0, $zero, 9 / syscall / addu $sp, $v0, $zero /
+        // addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with_text(&[
+            0x20040010, 0x20020009, 0x0000000c, 0x0040e821, 0x2002000a, 0x0000000c,
+        ]);
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::HeapCollision(0x10010000));
+    }
+
+    #[test]
+    fn test_memory_byte_lanes() {
+        let mut memory = Memory::new();
+        memory.write_u32(0x10010000, 0x4433_2211);
+        memory.write_u8(0x10010001, 0xaa);
+        memory.write_u16(0x10010002, 0xccbb);
+
+        assert_eq!(memory.read(0x10010000), 0xccbb_aa11);
+        assert_eq!(memory.read_u8(0x10010003), 0xcc);
+        assert_eq!(memory.read_u16(0x10010002), 0xccbb);
+    }
+
+    #[test]
+    fn test_memory_access_spanning_words() {
+        let mut memory = Memory::new();
+        memory.write_u32(0x10010002, 0x4433_2211);
+
+        assert_eq!(memory.read(0x10010000), 0x2211_0000);
+        assert_eq!(memory.read(0x10010004), 0x0000_4433);
+        assert_eq!(memory.read_u32(0x10010002), 0x4433_2211);
+        assert_eq!(memory.read_u16(0x10010003), 0x3322);
+    }
+}
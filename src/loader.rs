@@ -0,0 +1,969 @@
+use std::path::Path;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use serde::Deserialize;
+
+use crate::assembler;
+use crate::elf;
+use crate::error::Error;
+use crate::image::{Image, Segment, MAGIC};
+use crate::instructions;
+use crate::{segment_end, Endian, CPU};
+
+pub const TEXT_BASE: u32 = 0x00400000;
+pub const DATA_BASE: u32 = 0x10010000;
+pub const GLOBAL_POINTER: u32 = 0x10008000;
+pub const STACK_TOP: u32 = 0x7ffff000;
+
+// Where a machine puts flat programs and its stack. The default is the MARS
+// memory map; other linker scripts and bare-metal boards differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layout {
+    pub text_base: u32,
+    pub data_base: u32,
+    pub global_pointer: u32,
+    // One past the stack's highest word; $sp starts a word below
+    pub stack_top: u32,
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout {
+            text_base: TEXT_BASE,
+            data_base: DATA_BASE,
+            global_pointer: GLOBAL_POINTER,
+            stack_top: STACK_TOP,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // <name>.text + <name>.data word dumps
+    Split,
+    // Our own single-file image, see image.rs
+    Container,
+    Elf,
+    IntelHex,
+    // MARS "Hexadecimal Text": one 8-digit word per line
+    HexText,
+    // Memory initialization files for FPGA block RAM, as MARS exports them:
+    // Altera/Intel .mif and Xilinx .coe
+    Mif,
+    Coe,
+    Raw,
+    // MARS/SPIM assembly source, see assembler.rs
+    Assembly,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "split" => Some(Format::Split),
+            "rim" => Some(Format::Container),
+            "elf" => Some(Format::Elf),
+            "ihex" => Some(Format::IntelHex),
+            "hextext" => Some(Format::HexText),
+            "mif" => Some(Format::Mif),
+            "coe" => Some(Format::Coe),
+            "raw" => Some(Format::Raw),
+            "asm" => Some(Format::Assembly),
+            _ => None,
+        }
+    }
+}
+
+pub fn detect(path: &str) -> Result<Format, Error> {
+    let stem = split_stem(path);
+    if !Path::new(path).is_file() || path.ends_with(".text") || path.ends_with(".data") {
+        if Path::new(&format!("{}.text", stem)).is_file() {
+            return Ok(Format::Split);
+        }
+        return Err(Error::NotFound {
+            path: path.to_string(),
+            stem: stem.to_string(),
+        });
+    }
+
+    if path.ends_with(".asm") || path.ends_with(".s") {
+        return Ok(Format::Assembly);
+    }
+
+    let bytes = read_bytes(path)?;
+    Ok(sniff(&bytes))
+}
+
+fn sniff(bytes: &[u8]) -> Format {
+    if bytes.starts_with(MAGIC) {
+        return Format::Container;
+    }
+    if bytes.starts_with(b"\x7fELF") {
+        return Format::Elf;
+    }
+
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(_) => return Format::Raw,
+    };
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .peekable();
+    if lines.peek().is_none() {
+        return Format::Raw;
+    }
+
+    let lines: Vec<&str> = lines.collect();
+    if lines.iter().all(|line| line.starts_with(':')) {
+        return Format::IntelHex;
+    }
+    if text.contains("memory_initialization_vector") {
+        return Format::Coe;
+    }
+    if lines.iter().any(|line| line.eq_ignore_ascii_case("content"))
+        && lines.iter().any(|line| line.eq_ignore_ascii_case("begin"))
+    {
+        return Format::Mif;
+    }
+    if lines
+        .iter()
+        .all(|line| line.len() == 8 && line.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return Format::HexText;
+    }
+
+    Format::Raw
+}
+
+pub fn load(cpu: &mut CPU, path: &str, format: Format) -> Result<Image, Error> {
+    let mut image = read_as(path, format, cpu.endian())?;
+    place(&mut image, format, cpu.layout());
+    load_image(cpu, &image, &namespace(path))?;
+    Ok(image)
+}
+
+// Installs an already read image as the machine's main program
+pub fn load_image(cpu: &mut CPU, image: &Image, namespace: &str) -> Result<(), Error> {
+    install(cpu, image, namespace, false)?;
+    cpu.set_entry(image.entry);
+    if let Some(endian) = image.endian {
+        cpu.set_endian(endian);
+    }
+    Ok(())
+}
+
+// Loads one more image next to the ones already there, e.g. the routine under
+// test beside a fixed driver. The entry point stays the first image's, and
+// the segments must not overlap anything already loaded.
+pub fn add(cpu: &mut CPU, path: &str, format: Format) -> Result<Image, Error> {
+    let mut image = read_as(path, format, cpu.endian())?;
+    place(&mut image, format, cpu.layout());
+    install(cpu, &image, &namespace(path), true)?;
+    Ok(image)
+}
+
+// Places a file's bytes as they are at `address`, beside whatever is already
+// loaded: a boot ROM, a kernel blob or a table of data. The entry point is
+// left alone.
+pub fn add_raw(cpu: &mut CPU, path: &str, address: u32) -> Result<Image, Error> {
+    let mut image = Image::with_text(read_bytes(path)?);
+    image.entry = address;
+    image.segments[0].name = String::from("raw");
+    image.segments[0].address = address;
+    install(cpu, &image, &namespace(path), true)?;
+    Ok(image)
+}
+
+// Formats without addresses are read at the MARS bases; they belong at the
+// machine's own
+fn place(image: &mut Image, format: Format, layout: &Layout) {
+    if !matches!(
+        format,
+        Format::Split | Format::HexText | Format::Mif | Format::Coe | Format::Raw
+    ) {
+        return;
+    }
+
+    image.entry = layout.text_base;
+    for segment in image.segments.iter_mut() {
+        segment.address = if segment.executable {
+            layout.text_base
+        } else {
+            layout.data_base
+        };
+    }
+}
+
+// Regions of added images are named "<namespace>:<segment>"
+fn install(cpu: &mut CPU, image: &Image, namespace: &str, added: bool) -> Result<(), Error> {
+    for segment in image.segments.iter().filter(|segment| !segment.bytes.is_empty()) {
+        let end = segment_end(&segment.name, segment.address, segment.bytes.len())?;
+        if let Some(region) = cpu.memory.overlapping(segment.address, end) {
+            return Err(Error::Overlap {
+                namespace: namespace.to_string(),
+                segment: segment.name.clone(),
+                address: segment.address,
+                region: region.name.clone(),
+            });
+        }
+    }
+
+    for segment in &image.segments {
+        let name = if added {
+            format!("{}:{}", namespace, segment.name)
+        } else {
+            segment.name.clone()
+        };
+        cpu.memory.load_segment(&name, segment.address, &segment.bytes, segment.executable)?;
+    }
+    cpu.symbols.add(namespace, &image.symbols);
+    Ok(())
+}
+
+// "examples/08.sort.text" -> "08.sort", "build/driver.rim" -> "driver"
+fn namespace(path: &str) -> String {
+    let name = Path::new(split_stem(path))
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    [".rim", ".hex", ".ihex", ".bin", ".mif", ".coe", ".asm", ".s"]
+        .iter()
+        .find_map(|extension| name.strip_suffix(extension))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+// An image for a little-endian machine
+pub fn read(path: &str, format: Format) -> Result<Image, Error> {
+    read_as(path, format, Endian::Little)
+}
+
+// An image laid out for a machine of the given byte order. Word dumps hold
+// words, so their bytes follow the machine; byte-addressed formats are
+// taken as they are, and ELF and .rim files say which order they need.
+pub fn read_as(path: &str, format: Format, endian: Endian) -> Result<Image, Error> {
+    let malformed = |reason: String| Error::Malformed {
+        path: path.to_string(),
+        reason,
+    };
+
+    let mut image = match format {
+        Format::Split => read_split(split_stem(path), endian),
+        Format::Container => {
+            Image::from_bytes(&read_bytes(path)?).map_err(|error| malformed(error.to_string()))
+        }
+        Format::Elf => elf::parse(&read_bytes(path)?).map_err(malformed),
+        Format::IntelHex => read_intel_hex(&read_text(path)?).map_err(malformed),
+        Format::HexText => read_hex_text(&read_text(path)?).map_err(malformed),
+        Format::Mif => read_mif(&read_text(path)?).map_err(malformed),
+        Format::Coe => read_coe(&read_text(path)?).map_err(malformed),
+        Format::Assembly => assembler::assemble(&read_text(path)?, endian).map_err(malformed),
+        Format::Raw => {
+            let mut text = words_from_bytes(&read_bytes(path)?);
+            let big = is_big_endian_dump(&text);
+            if big {
+                text.iter_mut().for_each(|word| *word = word.swap_bytes());
+            }
+            if big != (endian == Endian::Big) {
+                eprintln!(
+                    "warning: {} looks {}-endian, swapping its byte order",
+                    path,
+                    if big { "big" } else { "little" }
+                );
+            }
+            Ok(Image::with_text(bytes_from_words(&text)))
+        }
+    }?;
+
+    let words = matches!(
+        format,
+        Format::Split | Format::HexText | Format::Mif | Format::Coe | Format::Raw
+    );
+    if words && endian == Endian::Big {
+        for segment in image.segments.iter_mut() {
+            segment.bytes.chunks_mut(4).for_each(|word| word.reverse());
+        }
+    }
+    Ok(image)
+}
+
+// Assembly files linked into one program, in the order given
+pub fn link(paths: &[&str], endian: Endian) -> Result<Image, Error> {
+    let sources = paths
+        .iter()
+        .map(|path| read_text(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let sources: Vec<&str> = sources.iter().map(String::as_str).collect();
+    assembler::link(&sources, endian).map_err(|(index, reason)| Error::Malformed {
+        path: paths[index].to_string(),
+        reason,
+    })
+}
+
+fn read_bytes(path: &str) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|error| Error::io(path, error))
+}
+
+fn read_text(path: &str) -> Result<String, Error> {
+    std::fs::read_to_string(path).map_err(|error| Error::io(path, error))
+}
+
+// Accepts either the common stem ("prog") or one of the pair ("prog.text")
+fn split_stem(path: &str) -> &str {
+    path.strip_suffix(".text")
+        .or_else(|| path.strip_suffix(".data"))
+        .unwrap_or(path)
+}
+
+// Warns when the dump's byte order isn't the machine's
+fn read_split(file_path: &str, endian: Endian) -> Result<Image, Error> {
+    let data = read_bytes(&format!("{}.data", file_path))?;
+    let text = read_bytes(&format!("{}.text", file_path))?;
+
+    let mut text = words_from_bytes(&text);
+    let mut data = words_from_bytes(&data);
+
+    let big = is_big_endian_dump(&text);
+    if big {
+        text.iter_mut().for_each(|word| *word = word.swap_bytes());
+        data.iter_mut().for_each(|word| *word = word.swap_bytes());
+    }
+    if big != (endian == Endian::Big) {
+        eprintln!(
+            "warning: {}.text looks {}-endian, swapping the byte order of both segments",
+            file_path,
+            if big { "big" } else { "little" }
+        );
+    }
+
+    let mut image = Image::with_text(bytes_from_words(&text));
+    image.segments.push(Segment {
+        name: String::from("data"),
+        address: DATA_BASE,
+        bytes: bytes_from_words(&data),
+        executable: false,
+    });
+    Ok(image)
+}
+
+fn read_hex_text(text: &str) -> Result<Image, String> {
+    let words = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| u32::from_str_radix(line, 16))
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|error| error.to_string())?;
+
+    Ok(Image::with_text(bytes_from_words(&words)))
+}
+
+// Addresses count words from the start of the dump:
+//
+//   WIDTH = 32;  ADDRESS_RADIX = HEX;  DATA_RADIX = HEX;
+//   CONTENT BEGIN
+//     00000000 : 20080005;
+//     [00000001..00000003] : 00000000;
+//     00000004 : 0000000c 2002000a;
+//   END;
+fn read_mif(text: &str) -> Result<Image, String> {
+    // Comments run from "--" to the end of the line or between two '%'
+    let text: String = text
+        .lines()
+        .map(|line| line.split("--").next().unwrap_or_default())
+        .collect::<Vec<&str>>()
+        .join("\n")
+        .split('%')
+        .step_by(2)
+        .collect();
+
+    let upper = text.to_ascii_uppercase();
+    let (header, content) = upper
+        .split_once("CONTENT")
+        .ok_or("no CONTENT section")?;
+    let content = content
+        .trim_start()
+        .strip_prefix("BEGIN")
+        .ok_or("CONTENT without BEGIN")?;
+    let content = match content.rfind("END") {
+        Some(end) => &content[..end],
+        None => return Err(String::from("CONTENT without END")),
+    };
+
+    let mut depth = None;
+    let mut address_radix = 16;
+    let mut data_radix = 16;
+    for statement in header.split(';') {
+        let Some((key, value)) = statement.split_once('=') else {
+            continue;
+        };
+        match key.trim() {
+            "WIDTH" if value.trim() != "32" => {
+                return Err(format!("only 32-bit words are supported, not {}", value.trim()))
+            }
+            // At most a word for every 4 bytes of the address space
+            "DEPTH" => {
+                let words = value.trim().parse::<usize>().ok().filter(|&words| words <= 1 << 30);
+                depth = Some(words.ok_or_else(|| format!("invalid DEPTH {}", value.trim()))?);
+            }
+            "ADDRESS_RADIX" => address_radix = radix(value.trim())?,
+            "DATA_RADIX" => data_radix = radix(value.trim())?,
+            _ => {}
+        }
+    }
+    let depth = depth.ok_or("no DEPTH")?;
+
+    let mut words: Vec<u32> = Vec::new();
+    for entry in content.split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (addresses, values) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("expected 'address : value', found '{}'", entry))?;
+        let values = values
+            .split_whitespace()
+            .map(|value| u32::from_str_radix(value, data_radix))
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(|_| format!("invalid value in '{}'", entry))?;
+        let address = |text: &str| {
+            usize::from_str_radix(text.trim(), address_radix)
+                .map_err(|_| format!("invalid address in '{}'", entry))
+        };
+
+        // A range repeats its one value; a single address takes a run of them
+        let addresses = addresses.trim();
+        let (start, count) = match addresses
+            .strip_prefix('[')
+            .and_then(|range| range.strip_suffix(']'))
+            .and_then(|range| range.split_once(".."))
+        {
+            Some((first, last)) => {
+                let (first, last) = (address(first)?, address(last)?);
+                if values.len() != 1 || last < first {
+                    return Err(format!("invalid range entry '{}'", entry));
+                }
+                (first, last - first + 1)
+            }
+            None => (address(addresses)?, values.len()),
+        };
+
+        if start.checked_add(count).is_none_or(|end| end > depth) {
+            return Err(format!("'{}' is past DEPTH {}", entry, depth));
+        }
+        if words.len() < start + count {
+            words.resize(start + count, 0);
+        }
+        for offset in 0..count {
+            words[start + offset] = values[offset % values.len()];
+        }
+    }
+
+    Ok(Image::with_text(bytes_from_words(&words)))
+}
+
+//   memory_initialization_radix=16;
+//   memory_initialization_vector=
+//   20080005,
+//   0000000c;
+fn read_coe(text: &str) -> Result<Image, String> {
+    // Lines starting with ';' are comments
+    let text: String = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with(';'))
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    let mut data_radix = 10;
+    let mut words = None;
+    for statement in text.split(';') {
+        let Some((key, value)) = statement.split_once('=') else {
+            continue;
+        };
+        match key.trim().to_ascii_lowercase().as_str() {
+            "memory_initialization_radix" => {
+                data_radix = match value.trim() {
+                    "2" | "10" | "16" => value.trim().parse().unwrap_or(10),
+                    other => return Err(format!("unsupported radix {}", other)),
+                }
+            }
+            "memory_initialization_vector" => {
+                let values = value
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|value| !value.is_empty())
+                    .map(|value| u32::from_str_radix(value, data_radix))
+                    .collect::<Result<Vec<u32>, _>>()
+                    .map_err(|error| format!("memory_initialization_vector: {}", error))?;
+                words = Some(values);
+            }
+            _ => {}
+        }
+    }
+
+    let words = words.ok_or("no memory_initialization_vector")?;
+    Ok(Image::with_text(bytes_from_words(&words)))
+}
+
+// A MIF radix name
+fn radix(name: &str) -> Result<u32, String> {
+    match name {
+        "HEX" => Ok(16),
+        "DEC" | "UNS" => Ok(10),
+        "OCT" => Ok(8),
+        "BIN" => Ok(2),
+        _ => Err(format!("unsupported radix {}", name)),
+    }
+}
+
+fn read_intel_hex(text: &str) -> Result<Image, String> {
+    let mut image = Image {
+        entry: TEXT_BASE,
+        ..Image::default()
+    };
+    let mut base: u32 = 0;
+
+    for (number, line) in text.lines().map(str::trim).enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+
+        let record =
+            parse_hex_record(line).map_err(|error| format!("line {}: {}", number + 1, error))?;
+        let kind = record[3];
+        let offset = (record[1] as u32) << 8 | record[2] as u32;
+        let payload = &record[4..];
+
+        let expected = match kind {
+            0x02 | 0x04 => Some(2),
+            0x05 => Some(4),
+            _ => None,
+        };
+        if let Some(expected) = expected.filter(|&expected| payload.len() != expected) {
+            return Err(format!(
+                "line {}: record type {:#04x} needs {} data bytes, found {}",
+                number + 1,
+                kind,
+                expected,
+                payload.len()
+            ));
+        }
+
+        match kind {
+            // Data, appended to the previous segment when contiguous
+            0x00 => {
+                let address = base.wrapping_add(offset);
+                match image.segments.last_mut() {
+                    Some(segment)
+                        if segment.address as u64 + segment.bytes.len() as u64
+                            == address as u64 =>
+                    {
+                        segment.bytes.extend_from_slice(payload)
+                    }
+                    _ => image.segments.push(Segment {
+                        name: String::from("image"),
+                        address,
+                        bytes: payload.to_vec(),
+                        executable: true,
+                    }),
+                }
+            }
+
+            // End of file
+            0x01 => break,
+
+            // Extended segment address
+            0x02 => base = ((payload[0] as u32) << 8 | payload[1] as u32) << 4,
+
+            // Extended linear address
+            0x04 => base = ((payload[0] as u32) << 8 | payload[1] as u32) << 16,
+
+            // Start segment address (CS:IP has no meaning here)
+            0x03 => {}
+
+            // Start linear address
+            0x05 => image.entry = BigEndian::read_u32(payload),
+
+            _ => {
+                return Err(format!(
+                    "line {}: unknown record type {:#04x}",
+                    number + 1,
+                    kind
+                ))
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+// Decodes ":LLAAAATT<data>CC" into [LL, AA, AA, TT, data...], checking length and checksum
+fn parse_hex_record(line: &str) -> Result<Vec<u8>, String> {
+    let digits = line
+        .strip_prefix(':')
+        .ok_or("record does not start with ':'")?;
+    if digits.len() % 2 != 0 || digits.len() < 10 {
+        return Err(String::from("truncated record"));
+    }
+
+    let bytes = (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| String::from("invalid hex digit"))?;
+
+    if bytes.len() != bytes[0] as usize + 5 {
+        return Err(String::from("record length mismatch"));
+    }
+    if bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) != 0 {
+        return Err(String::from("bad checksum"));
+    }
+
+    let mut record = bytes;
+    record.pop();
+    Ok(record)
+}
+
+// Word dumps carry no byte-order marker. If more of the text segment decodes
+// to plausible instructions with its bytes swapped, the dump was big-endian.
+fn is_big_endian_dump(text: &[u32]) -> bool {
+    let plausible = text.iter().filter(|&&word| is_plausible(word)).count();
+    let swapped = text
+        .iter()
+        .filter(|&&word| is_plausible(word.swap_bytes()))
+        .count();
+
+    swapped > plausible
+}
+
+// Stricter than "known": unused fields must be zero and jumps must stay in
+// the user text area.
+fn is_plausible(word: u32) -> bool {
+    let opcode = word >> 26;
+    let rt = (word >> 16) & 0b11111;
+    let shamt = (word >> 6) & 0b11111;
+    let funct = word & 0b111111;
+
+    match opcode {
+        0 => instructions::is_known(word) && (shamt == 0 || funct <= 0x03),
+        1 => matches!(rt, 0 | 1 | 16 | 17),
+        2 | 3 => (0x00400000..0x10000000).contains(&((word & 0x3FFFFFF) << 2)),
+        _ => instructions::is_known(word),
+    }
+}
+
+// Packs a segment into little-endian words. A trailing partial word (from
+// .byte/.half data) is kept, zero-padded, rather than dropped.
+fn words_from_bytes(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks(4)
+        .map(|chunk| {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            LittleEndian::read_u32(&word)
+        })
+        .collect()
+}
+
+fn bytes_from_words(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        add, add_raw, is_big_endian_dump, load, read_coe, read_intel_hex, read_mif, sniff,
+        words_from_bytes, Format,
+    };
+    use crate::error::Error;
+    use crate::image::{Image, Symbol};
+    use crate::{Endian, ExitReason, CPU};
+
+    #[test]
+    fn test_words_from_bytes() {
+        let bytes = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08];
+        assert_eq!(words_from_bytes(&bytes), vec![0x04030201, 0x08070605]);
+    }
+
+    #[test]
+    fn test_words_from_bytes_keeps_trailing_bytes() {
+        // .word 1, .half 0x1234, .byte 0x56
+        let bytes = [0x01, 0x00, 0x00, 0x00, 0x34, 0x12, 0x56];
+        assert_eq!(words_from_bytes(&bytes), vec![0x00000001, 0x00561234]);
+    }
+
+    #[test]
+    fn test_words_from_bytes_empty() {
+        assert_eq!(words_from_bytes(&[]), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_is_big_endian_dump() {
+        // addi $t0, $zero, 3 / addi $t1, $zero, 4 / add $s0, $t0, $t1 / syscall
+        let text: Vec<u32> = vec![0x20080003, 0x20090004, 0x01098020, 0x0000000c];
+        let swapped: Vec<u32> = text.iter().map(|word| word.swap_bytes()).collect();
+
+        assert!(!is_big_endian_dump(&text));
+        assert!(is_big_endian_dump(&swapped));
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b"RIMG\x01\x00\x00\x00"), Format::Container);
+        assert_eq!(sniff(b"\x7fELF\x01\x01\x01"), Format::Elf);
+        assert_eq!(
+            sniff(b":0400000003000820D1\n:00000001FF\n"),
+            Format::IntelHex
+        );
+        assert_eq!(sniff(b"20080003\n20090004\n"), Format::HexText);
+        assert_eq!(
+            sniff(b"WIDTH = 32;\nCONTENT\nBEGIN\n0 : 20080003;\nEND;\n"),
+            Format::Mif
+        );
+        assert_eq!(
+            sniff(b"memory_initialization_radix=16;\nmemory_initialization_vector=\n0;\n"),
+            Format::Coe
+        );
+        assert_eq!(sniff(&[0x03, 0x00, 0x08, 0x20]), Format::Raw);
+        assert_eq!(sniff(b"hello world\n"), Format::Raw);
+    }
+
+    #[test]
+    fn test_read_intel_hex() {
+        let text = ":020000040040BA\n:0400000003000820D1\n:0400040000000C00EC\n:0400000500400000B7\n:00000001FF\n";
+
+        let image = read_intel_hex(text).unwrap();
+
+        assert_eq!(image.entry, 0x00400000);
+        assert_eq!(image.segments.len(), 1);
+        assert_eq!(image.segments[0].address, 0x00400000);
+        assert_eq!(
+            image.segments[0].bytes,
+            vec![0x03, 0x00, 0x08, 0x20, 0x00, 0x00, 0x0c, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_read_mif() {
+        let text = "-- MARS memory dump
+DEPTH = 1024;
+WIDTH = 32;
+ADDRESS_RADIX = HEX;
+DATA_RADIX = HEX;
+CONTENT
+BEGIN
+00000000 : 20080003;  % the first instruction %
+[00000001..00000002] : 00000000;
+00000003 : 2002000a 0000000c;
+END;
+";
+
+        let image = read_mif(text).unwrap();
+
+        assert_eq!(
+            words_from_bytes(&image.segments[0].bytes),
+            [0x20080003, 0, 0, 0x2002000a, 0x0000000c]
+        );
+        assert_eq!(
+            read_mif("WIDTH = 8;\nCONTENT BEGIN\nEND;").unwrap_err(),
+            "only 32-bit words are supported, not 8"
+        );
+        assert_eq!(
+            read_mif("WIDTH = 32;\nCONTENT BEGIN\nEND;").unwrap_err(),
+            "no DEPTH"
+        );
+        assert_eq!(
+            read_mif("DEPTH = 4;\nCONTENT BEGIN\n[0..FFFFFFFF] : 0;\nEND;").unwrap_err(),
+            "'[0..FFFFFFFF] : 0' is past DEPTH 4"
+        );
+        assert_eq!(
+            read_mif("DEPTH = 4;\nCONTENT BEGIN\n3 : 1 2;\nEND;").unwrap_err(),
+            "'3 : 1 2' is past DEPTH 4"
+        );
+    }
+
+    #[test]
+    fn test_read_coe() {
+        let text = "; MARS memory dump
+memory_initialization_radix=16;
+memory_initialization_vector=
+20080003,
+2002000a,
+0000000c;
+";
+
+        let image = read_coe(text).unwrap();
+
+        assert_eq!(
+            words_from_bytes(&image.segments[0].bytes),
+            [0x20080003, 0x2002000a, 0x0000000c]
+        );
+        assert_eq!(
+            read_coe("memory_initialization_radix=2;\nmemory_initialization_vector=101,11;")
+                .map(|image| words_from_bytes(&image.segments[0].bytes))
+                .unwrap(),
+            [5, 3]
+        );
+    }
+
+    #[test]
+    fn test_read_intel_hex_bad_checksum() {
+        let error = read_intel_hex(":0400000003000820D2\n").unwrap_err();
+
+        assert_eq!(error, "line 1: bad checksum");
+    }
+
+    #[test]
+    fn test_read_intel_hex_short_records() {
+        assert_eq!(
+            read_intel_hex(":00000004FC\n").unwrap_err(),
+            "line 1: record type 0x04 needs 2 data bytes, found 0"
+        );
+        assert_eq!(
+            read_intel_hex(":020000050040B9\n").unwrap_err(),
+            "line 1: record type 0x05 needs 4 data bytes, found 2"
+        );
+    }
+
+    #[test]
+    fn test_read_intel_hex_top_of_memory() {
+        let text = ":02000004FFFFFC\n:04FFFC0001020304F7\n:020000040000FA\n:0400000005060708E2\n";
+
+        let image = read_intel_hex(text).unwrap();
+
+        assert_eq!(image.segments.len(), 2);
+        assert_eq!(image.segments[0].address, 0xfffffffc);
+        assert_eq!(image.segments[1].address, 0);
+    }
+
+    #[test]
+    fn test_load_installs_segments() {
+        let mut cpu = CPU::new();
+
+        let image = load(&mut cpu, "examples/02.hello", Format::Split).unwrap();
+
+        assert_eq!(image.segments.len(), 2);
+        assert_eq!(cpu.memory.region(0x00400000).unwrap().name, "text");
+        assert_eq!(cpu.memory.region(0x10010000).unwrap().name, "data");
+    }
+
+    #[test]
+    fn test_add_second_image() {
+        let mut cpu = CPU::new();
+        load(&mut cpu, "examples/02.hello", Format::Split).unwrap();
+
+        let mut image = Image::with_text(vec![0; 8]);
+        image.entry = 0x00500000;
+        image.segments[0].address = 0x00500000;
+        image.symbols.push(Symbol {
+            name: String::from("routine"),
+            address: 0x00500004,
+        });
+        let path = std::env::temp_dir().join("rustinmips_test_add_second_image.rim");
+        std::fs::write(&path, image.to_bytes()).unwrap();
+
+        add(&mut cpu, path.to_str().unwrap(), Format::Container).unwrap();
+
+        assert_eq!(cpu.entry(), 0x00400000);
+        assert_eq!(
+            cpu.memory.region(0x00500000).unwrap().name,
+            "rustinmips_test_add_second_image:text"
+        );
+        assert_eq!(
+            cpu.symbols()
+                .lookup("rustinmips_test_add_second_image::routine")
+                .unwrap(),
+            0x00500004
+        );
+    }
+
+    #[test]
+    fn test_add_overlapping_image() {
+        let mut cpu = CPU::new();
+        load(&mut cpu, "examples/02.hello", Format::Split).unwrap();
+
+        let error = add(&mut cpu, "examples/08.sort", Format::Split).unwrap_err();
+
+        assert!(matches!(error, Error::Overlap { address: 0x00400000, .. }));
+        assert_eq!(
+            error.to_string(),
+            "08.sort: segment 'text' at 0x00400000 overlaps region 'text'"
+        );
+    }
+
+    #[test]
+    fn test_add_raw() {
+        // addi $v0, $zero, 10 / syscall
+        let path = std::env::temp_dir().join("rustinmips_test_add_raw.bin");
+        std::fs::write(&path, [0x0a, 0x00, 0x02, 0x20, 0x0c, 0x00, 0x00, 0x00]).unwrap();
+        let path = path.to_str().unwrap();
+        let mut cpu = CPU::new();
+
+        let image = add_raw(&mut cpu, path, 0x00800000).unwrap();
+        assert_eq!(image.segments[0].bytes.len(), 8);
+        assert_eq!(
+            cpu.memory.region(0x00800004).unwrap().name,
+            "rustinmips_test_add_raw:raw"
+        );
+        assert!(matches!(
+            add_raw(&mut cpu, path, 0x00800004),
+            Err(Error::Overlap { .. })
+        ));
+
+        cpu.set_entry(0x00800000);
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+
+        // Past the top of memory, and nothing of it gets written
+        let error = add_raw(&mut cpu, path, 0xfffffffc).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "segment 'raw' at 0xfffffffc runs past the end of the address space"
+        );
+        assert_eq!(cpu.memory.read_u8(0xfffffffc), 0);
+    }
+
+    #[test]
+    fn test_load_sets_entry() {
+        let mut cpu = CPU::new();
+        let mut image = Image::with_text(vec![0; 8]);
+        image.entry = 0x00400004;
+        let path = std::env::temp_dir().join("rustinmips_test_load_sets_entry.rim");
+        std::fs::write(&path, image.to_bytes()).unwrap();
+
+        load(&mut cpu, path.to_str().unwrap(), Format::Container).unwrap();
+
+        assert_eq!(cpu.entry(), 0x00400004);
+    }
+
+    #[test]
+    fn test_load_split_at_layout_bases() {
+        let mut cpu = CPU::builder()
+            .text_base(0x80000000)
+            .data_base(0x80100000)
+            .build();
+
+        let image = load(&mut cpu, "examples/02.hello", Format::Split).unwrap();
+
+        assert_eq!(image.entry, 0x80000000);
+        assert_eq!(cpu.entry(), 0x80000000);
+        assert_eq!(cpu.memory.region(0x80000000).unwrap().name, "text");
+        assert_eq!(cpu.memory.region(0x80100000).unwrap().name, "data");
+        assert!(cpu.memory.region(0x00400000).is_none());
+    }
+
+    #[test]
+    fn test_load_split_big_endian() {
+        let mut little = CPU::new();
+        let mut big = CPU::builder().endian(Endian::Big).build();
+
+        load(&mut little, "examples/02.hello", Format::Split).unwrap();
+        load(&mut big, "examples/02.hello", Format::Split).unwrap();
+
+        // Same words, each stored most significant byte first
+        assert_eq!(big.memory.read(0x00400000), little.memory.read(0x00400000));
+        assert_eq!(
+            big.memory.read_u8(0x00400000),
+            little.memory.read_u8(0x00400003)
+        );
+        assert_eq!(big.run().unwrap(), ExitReason::Exited(0));
+    }
+}
@@ -1,184 +1,622 @@
-mod instructions;
-use crate::instructions::Instruction;
+use std::path::Path;
+
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+
+use rustinmips::checks;
+use rustinmips::config::Config;
+use rustinmips::console::Streams;
+use rustinmips::debugger::Debugger;
+use rustinmips::disk::{self, Disk};
+use rustinmips::dma::{self, Dma};
+use rustinmips::energy::EnergyModel;
+use rustinmips::error::Error;
+use rustinmips::faults::{BitFlipper, Fault};
+use rustinmips::instructions;
+use rustinmips::loader::{self, Format};
+use rustinmips::mmio_console::{self, MmioConsole};
+use rustinmips::rtc::{self, Rtc};
+use rustinmips::selftest;
+use rustinmips::spim::{self, SpimLog};
+use rustinmips::trace::{TraceFormat, Tracer};
+use rustinmips::{parse_number, Alignment, DivideByZero, Endian, ExitReason, CPU};
+
+enum Mode {
+    Run,
+    Debug,
+    Asm,
+    Disasm,
+    Check,
+}
 
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
-use std::io::Cursor;
+/// A MIPS32 emulator. Without a command the program is run.
+#[derive(Parser)]
+#[command(name = "rustinmips", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    options: Options,
+}
 
-#[derive(Clone)]
-struct Register {
-    value: u32,
+#[derive(Subcommand)]
+enum Command {
+    /// Run the program (the default)
+    Run(Options),
+    /// Run it under the debugger
+    Debug(Options),
+    /// List the instructions of its code segments, objdump-style
+    Disasm(Options),
+    /// Convert it to a .rim container; several .asm files are linked into one
+    Asm {
+        /// The container to write (default: the program's name with .rim)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Report delay slot and load delay hazards
+    Check(Options),
+    /// Run the built-in instruction tests
+    Selftest,
 }
 
-impl Register {
-    fn new() -> Register {
-        Register { value: 0 }
-    }
+#[derive(Args)]
+struct Options {
+    /// The program, then any others to load beside it
+    #[arg(value_name = "PROGRAM")]
+    programs: Vec<String>,
+    /// split, rim, elf, ihex, hextext, mif, coe, raw or asm
+    #[arg(long, value_name = "NAME", value_parser = parse_format)]
+    format: Option<Format>,
+    /// The machine's memory map, devices and syscalls (TOML)
+    #[arg(long, value_name = "FILE")]
+    config: Option<String>,
+    /// Start somewhere other than the program's entry
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+    entry: Option<u32>,
+    /// The machine's byte order (little unless the program says otherwise)
+    #[arg(long, value_name = "big|little", value_parser = parse_endian)]
+    endian: Option<Endian>,
+    /// Place a file's bytes at ADDRESS; with no program, the first one is
+    /// where the run starts
+    #[arg(long = "raw", value_name = "FILE@ADDRESS", value_parser = parse_raw)]
+    raws: Vec<(String, u32)>,
+    /// Stop after N instructions
+    #[arg(long, value_name = "N", value_parser = parse_limit)]
+    max_instructions: Option<u64>,
+    /// Write an instruction trace
+    #[arg(long, value_name = "FILE")]
+    trace: Option<String>,
+    /// The trace's layout: spike or gem5
+    #[arg(
+        long,
+        value_name = "NAME",
+        value_parser = parse_trace_format,
+        default_value = "spike"
+    )]
+    trace_format: TraceFormat,
+    /// Console input from a file instead of the terminal
+    #[arg(long, value_name = "FILE")]
+    stdin: Option<String>,
+    /// Console output to a file instead of the terminal
+    #[arg(long, value_name = "FILE")]
+    stdout: Option<String>,
+    /// Print the instruction count and how the run ended
+    #[arg(long)]
+    summary: bool,
+    /// Compare the run with a SPIM register log
+    #[arg(long = "compare-spim", value_name = "FILE")]
+    spim_log: Option<String>,
+    /// Estimate the energy the run used
+    #[arg(long)]
+    energy: bool,
+    /// The same, with these per-class weights
+    #[arg(long, value_name = "WEIGHTS", value_parser = parse_energy_weights)]
+    energy_weights: Option<EnergyModel>,
+    /// Emulate the MIPS I load delay slot
+    #[arg(long)]
+    load_delay: bool,
+    /// What DIV and DIVU by zero do: undefined, trap or abort
+    #[arg(
+        long = "div-by-zero",
+        value_name = "MODE",
+        value_parser = parse_divide_by_zero,
+        default_value = "undefined"
+    )]
+    divide_by_zero: DivideByZero,
+    /// What unaligned accesses do: fault or emulate
+    #[arg(
+        long = "unaligned",
+        value_name = "MODE",
+        value_parser = parse_alignment,
+        default_value = "fault"
+    )]
+    alignment: Alignment,
+    /// Inject a fault (repeatable)
+    #[arg(long = "fault", value_name = "FAULT", value_parser = parse_fault)]
+    faults: Vec<Fault>,
+    /// Seed for the random bit flips
+    #[arg(long, value_name = "SEED", default_value_t = 0)]
+    flip_seed: u64,
+    /// Chance of a bit flip per instruction, 0 to 1
+    #[arg(long, value_name = "RATE", value_parser = parse_flip_rate, default_value_t = 0.0)]
+    flip_rate: f64,
+    /// Flip a bit after this many instructions (repeatable)
+    #[arg(long = "flip-at", value_name = "CYCLE")]
+    flip_cycles: Vec<u64>,
+    /// Seed for the random number syscalls
+    #[arg(long, value_name = "SEED")]
+    random_seed: Option<u64>,
+    /// Map the memory-mapped console
+    #[arg(long)]
+    mmio_console: bool,
+    /// Map the real-time clock
+    #[arg(long)]
+    rtc: bool,
+    /// Map the DMA controller
+    #[arg(long)]
+    dma: bool,
+    /// Map a disk backed by this image file
+    #[arg(long, value_name = "FILE")]
+    disk: Option<String>,
+    /// Confine the file syscalls to this directory
+    #[arg(long, value_name = "DIRECTORY")]
+    sandbox: Option<String>,
+    /// Translate kuseg through a 16-entry TLB
+    #[arg(long)]
+    mmu: bool,
+    /// Translate kuseg through a TLB of N entries, 1 to 64
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..=64))]
+    tlb_entries: Option<u64>,
+    /// Shadow register sets, 0 to 15
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(0..=15),
+        default_value_t = 0
+    )]
+    shadow_sets: u64,
+    /// Run the program with argc and argv on the stack
+    #[arg(last = true, value_name = "ARGUMENTS")]
+    arguments: Vec<String>,
+}
 
-    fn read(&self) -> u32 {
-        self.value
+fn main() {
+    let cli = Cli::parse();
+    let (mode, options, output) = match cli.command {
+        None => (Mode::Run, cli.options, None),
+        Some(Command::Run(options)) => (Mode::Run, options, None),
+        Some(Command::Debug(options)) => (Mode::Debug, options, None),
+        Some(Command::Disasm(options)) => (Mode::Disasm, options, None),
+        Some(Command::Asm { output, options }) => (Mode::Asm, options, output),
+        Some(Command::Check(options)) => (Mode::Check, options, None),
+        Some(Command::Selftest) => {
+            let (report, passed) = selftest::run();
+            print!("{}", report);
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+    };
+    let Options {
+        programs,
+        format,
+        config,
+        entry,
+        endian,
+        raws,
+        max_instructions: instruction_limit,
+        trace,
+        trace_format,
+        stdin,
+        stdout,
+        summary,
+        spim_log,
+        energy,
+        energy_weights,
+        load_delay,
+        divide_by_zero,
+        alignment,
+        faults,
+        flip_seed,
+        flip_rate,
+        flip_cycles,
+        random_seed,
+        mmio_console,
+        rtc,
+        dma,
+        disk,
+        sandbox,
+        mmu,
+        tlb_entries,
+        shadow_sets,
+        arguments,
+    } = options;
+    let energy = energy_weights.or(energy.then(EnergyModel::default));
+    let tlb_entries = tlb_entries.map(|entries| entries as usize).or(mmu.then_some(16));
+    let arguments = (!arguments.is_empty()).then_some(arguments);
+
+    // The first program is the main one; any others are loaded beside it.
+    // Raw files alone are enough to run.
+    let mut programs = programs.into_iter();
+    let program = programs.next();
+    let extra: Vec<String> = programs.collect();
+    let needs_program = matches!(mode, Mode::Asm | Mode::Disasm) || raws.is_empty();
+    if program.is_none() && needs_program {
+        Cli::command()
+            .error(ErrorKind::MissingRequiredArgument, "a program is required")
+            .exit();
     }
 
-    fn write(&mut self, value: u32) {
-        self.value = value;
+    let format = program.as_ref().map(|program| {
+        match format.map(Ok).unwrap_or_else(|| loader::detect(program)) {
+            Ok(format) => format,
+            Err(error) => fail(&error.to_string()),
+        }
+    });
+
+    if let (Mode::Asm, Some(program), Some(format)) = (&mode, &program, format) {
+        let image = if extra.is_empty() {
+            loader::read_as(program, format, endian.unwrap_or_default())
+        } else if format == Format::Assembly
+            && extra.iter().all(|path| matches!(loader::detect(path), Ok(Format::Assembly)))
+        {
+            let paths: Vec<&str> = std::iter::once(program).chain(&extra).map(String::as_str).collect();
+            loader::link(&paths, endian.unwrap_or_default())
+        } else {
+            fail("only assembly files can be linked together")
+        };
+        let mut image = image.unwrap_or_else(|error| fail(&error.to_string()));
+        if image.endian.is_none() && endian == Some(Endian::Big) {
+            image.endian = endian;
+        }
+        if let Some(entry) = entry {
+            image.entry = entry;
+        }
+        let output = output.unwrap_or_else(|| {
+            let stem = program.trim_end_matches(".text").trim_end_matches(".data");
+            format!("{}.rim", stem)
+        });
+        if let Err(error) = std::fs::write(&output, image.to_bytes()) {
+            fail(&format!("{}: {}", output, error));
+        }
+        return;
     }
-}
-
-struct Memory {
-    data: Vec<u32>,
-    stack_pointer: u32,
-    global_pointer: u32,
-}
 
-impl Memory {
-    const STACK_POINTER: u32 = 0x7fffeffc;
-    const GLOBAL_POINTER: u32 = 0x10008000;
-
-    fn new(size: usize) -> Memory {
-        Memory {
-            data: vec![0; 2u64.pow(32) as usize],
-            stack_pointer: Self::STACK_POINTER,
-            global_pointer: Self::GLOBAL_POINTER,
+    if let (Mode::Disasm, Some(program), Some(format)) = (&mode, &program, format) {
+        let image = loader::read_as(program, format, endian.unwrap_or_default())
+            .unwrap_or_else(|error| fail(&error.to_string()));
+        let big = image.endian.or(endian) == Some(Endian::Big);
+        // "<main+0x8>" for the nearest symbol at or before an address
+        let symbol = |address: u32| {
+            image
+                .symbols
+                .iter()
+                .filter(|symbol| symbol.address <= address)
+                .max_by_key(|symbol| symbol.address)
+                .map(|symbol| match address - symbol.address {
+                    0 => format!(" <{}>", symbol.name),
+                    offset => format!(" <{}+{:#x}>", symbol.name, offset),
+                })
+                .unwrap_or_default()
+        };
+        for segment in image.segments.iter().filter(|segment| segment.executable) {
+            println!("Disassembly of {} ({:#010x}):", segment.name, segment.address);
+            for (i, word) in segment.bytes.chunks(4).enumerate() {
+                let mut bytes = [0; 4];
+                bytes[..word.len()].copy_from_slice(word);
+                let word = if big {
+                    u32::from_be_bytes(bytes)
+                } else {
+                    u32::from_le_bytes(bytes)
+                };
+                let address = segment.address.wrapping_add(i as u32 * 4);
+                for symbol in image.symbols.iter().filter(|symbol| symbol.address == address) {
+                    println!("\n{:08x} <{}>:", address, symbol.name);
+                }
+                let target = instructions::target(word, address).map(symbol).unwrap_or_default();
+                println!(
+                    "{:8x}:\t{:08x}\t{}{}",
+                    address,
+                    word,
+                    instructions::disassemble(word, address),
+                    target
+                );
+            }
+            println!();
         }
+        return;
     }
 
-    fn read(&self, address: u32) -> u32 {
-        self.data[address as usize]
+    let mut cpu = match config {
+        Some(path) => Config::load(&path)
+            .and_then(|config| config.build())
+            .unwrap_or_else(|error| fail(&error.to_string())),
+        None => CPU::new(),
+    };
+    if let Some(endian) = endian {
+        cpu.set_endian(endian);
+    }
+    cpu.set_shadow_sets(shadow_sets as usize);
+    if let Some(entries) = tlb_entries {
+        cpu.enable_mmu(entries);
+    }
+    cpu.set_load_delay(load_delay);
+    cpu.set_instruction_limit(instruction_limit);
+    cpu.set_alignment(alignment);
+    cpu.set_divide_by_zero(divide_by_zero);
+    cpu.set_faults(faults);
+    if mmio_console {
+        let console = Box::new(MmioConsole::stdio());
+        if let Err(error) = cpu.attach(mmio_console::BASE, mmio_console::END, console) {
+            fail(&error.to_string());
+        }
+    }
+    if rtc {
+        let device = Box::new(Rtc::new(cpu.clock()));
+        if let Err(error) = cpu.attach(rtc::BASE, rtc::END, device) {
+            fail(&error.to_string());
+        }
+    }
+    if dma {
+        if let Err(error) = cpu.attach(dma::BASE, dma::END, Box::new(Dma::new())) {
+            fail(&error.to_string());
+        }
+    }
+    if stdin.is_some() || stdout.is_some() {
+        match Streams::files(stdin.as_deref().map(Path::new), stdout.as_deref().map(Path::new)) {
+            Ok(streams) => cpu.set_console(Box::new(streams)),
+            Err(error) => fail(&format!("cannot open console file: {}", error)),
+        }
+    }
+    if let Some(seed) = random_seed {
+        cpu.set_random_seed(seed);
+    }
+    if let Some(root) = sandbox {
+        cpu.set_sandbox(root.into());
+    }
+    if let Some(path) = disk {
+        let device = Disk::open(&path)
+            .unwrap_or_else(|error| fail(&format!("cannot open disk '{}': {}", path, error)));
+        if let Err(error) = cpu.attach(disk::BASE, disk::END, Box::new(device)) {
+            fail(&error.to_string());
+        }
     }
 
-    fn read_byte(&self, address: u32) -> u32 {
-        self.data[address as usize] as u32
+    // Read before running so a bad log doesn't waste the run
+    let spim_log = spim_log.map(|path| {
+        std::fs::read_to_string(&path)
+            .map_err(|error| Error::io(&path, error))
+            .and_then(|text| SpimLog::parse(&text))
+            .unwrap_or_else(|error| match error {
+                Error::Io { .. } => fail(&error.to_string()),
+                _ => fail(&format!("{}: {}", path, error)),
+            })
+    });
+    if spim_log.is_some() {
+        cpu.record_console();
     }
 
-    fn write(&mut self, address: u32, value: u32) {
-        self.data[address as usize] = value;
+    if let Some(path) = trace {
+        let file = std::fs::File::create(&path)
+            .unwrap_or_else(|error| fail(&format!("{}: {}", path, error)));
+        let output = Box::new(std::io::BufWriter::new(file));
+        cpu.set_tracer(Tracer::new(trace_format, output));
     }
+    cpu.set_bit_flips(BitFlipper::new(flip_seed, flip_rate, flip_cycles));
 
-    fn load_text(&mut self, text: Vec<u32>) {
-        let mut initial_text_address = 0x00400000;
-        for (_, word) in text.iter().enumerate() {
-            self.write(initial_text_address, *word);
-            initial_text_address += 4;
+    if let (Some(program), Some(format)) = (&program, format) {
+        if let Err(error) = loader::load(&mut cpu, program, format) {
+            fail(&error.to_string());
         }
     }
+    // An ELF file or container that names its byte order has had its way
+    if let Some(endian) = endian.filter(|&endian| endian != cpu.endian()) {
+        fail(&format!(
+            "{} is {}-endian, not {}-endian",
+            program.unwrap_or_default(),
+            if endian == Endian::Big { "little" } else { "big" },
+            if endian == Endian::Big { "big" } else { "little" },
+        ));
+    }
 
-    fn load_data(&mut self, data: Vec<u32>) {
-        let mut initial_data_address = 0x10010000;
-        for (_, word) in data.iter().enumerate() {
-            self.write(initial_data_address, *word);
-            initial_data_address += 4;
+    for path in &extra {
+        let result = loader::detect(path).and_then(|format| loader::add(&mut cpu, path, format));
+        if let Err(error) = result {
+            fail(&error.to_string());
         }
     }
-}
 
-pub struct CPU {
-    registers: Vec<Register>,
-    memory: Memory,
-    pc: u32,
-    jump: bool,
-}
+    for (path, address) in &raws {
+        if let Err(error) = loader::add_raw(&mut cpu, path, *address) {
+            fail(&error.to_string());
+        }
+    }
+    if let (None, Some((_, address))) = (&program, raws.first()) {
+        cpu.set_entry(*address);
+    }
 
-impl CPU {
-    fn new() -> CPU {
-        let mut cpu = CPU {
-            registers: vec![Register::new(); 32],
-            memory: Memory::new(1024),
-            pc: 0,
-            jump: false,
-        };
+    if let Some(entry) = entry {
+        cpu.set_entry(entry);
+    }
 
-        cpu.write_register(28, cpu.memory.global_pointer);
-        cpu.write_register(29, cpu.memory.stack_pointer);
-        cpu
+    if let Some(arguments) = arguments {
+        let name = program.clone().or_else(|| raws.first().map(|(path, _)| path.clone()));
+        cpu.set_arguments(name.into_iter().chain(arguments).collect());
     }
 
-    fn read_register(&self, register: usize) -> u32 {
-        if register == 0 {
-            return 0;
+    if let Mode::Check = mode {
+        let mut warnings = checks::delay_slots(&cpu);
+        if load_delay {
+            warnings.extend(checks::load_delays(&cpu));
+            warnings.sort_by_key(|warning| warning.address);
         }
-
-        self.registers[register].read()
+        for warning in &warnings {
+            println!("{}", warning);
+        }
+        std::process::exit(if warnings.is_empty() { 0 } else { 1 });
     }
 
-    fn write_register(&mut self, register: usize, value: u32) {
-        if register == 0 {
-            return;
-        }
+    let reason = match mode {
+        Mode::Debug => Debugger::new().repl(&mut cpu),
+        _ => cpu.run().unwrap_or_else(|error| fail(&error.to_string())),
+    };
 
-        self.registers[register].write(value);
+    for injection in cpu.injections() {
+        eprintln!("injected: {}", injection);
     }
 
-    fn run(&mut self) {
-        self.pc = 0x00400000;
-        let mut count = 0;
-        loop {
-            let instruction = self.memory.read(self.pc);
+    for flip in cpu.flips() {
+        eprintln!("flipped: {}", flip);
+    }
 
-            if instruction == 0 {
-                self.pc += 4;
-                continue;
-            }
+    for warning in cpu.warnings() {
+        eprintln!("warning: {}", warning);
+    }
 
-            let instruction = instructions::get_instruction(instruction);
+    if cpu.unaligned_accesses() > 0 {
+        eprintln!("warning: emulated {} unaligned accesses", cpu.unaligned_accesses());
+    }
 
-            instruction.execute(self);
+    match reason {
+        ExitReason::WildJump { from, to } => eprint!("{}", cpu.wild_jump_report(from, to)),
+        ExitReason::DivideByZero(pc)
+        | ExitReason::Trap(pc)
+        | ExitReason::Overflow(pc)
+        | ExitReason::ReservedInstruction(pc) => {
+            eprintln!("{}\n  {}", reason, cpu.describe(pc))
+        }
+        _ => {}
+    }
 
-            if !self.jump {
-                self.pc += 4;
-            } else {
-                self.jump = false;
-            }
+    if summary {
+        let entry = cpu.symbol_at(cpu.entry()).map(|symbol| format!(" <{}>", symbol));
+        eprintln!(
+            "\n-- entry {:#010x}{}, {} instructions executed, {}",
+            cpu.entry(),
+            entry.unwrap_or_default(),
+            cpu.executed(),
+            reason
+        );
+    }
 
-            count += 1;
+    if let Some(model) = energy {
+        eprint!("\n-- estimated energy\n{}", cpu.energy().report(&model));
+    }
 
-            if count > 8000 {
-                std::process::exit(0)
-            }
+    let mut diverged = false;
+    if let Some(log) = &spim_log {
+        let divergences = spim::compare(log, &cpu);
+        eprintln!("\n-- {} divergences from the SPIM log", divergences.len());
+        for divergence in &divergences {
+            eprintln!("{}", divergence);
         }
+        diverged = !divergences.is_empty();
     }
 
-    fn run_branch_delayed(&mut self) {
-        let branch_delayed_instruction = self.memory.read(self.pc + 4);
+    let code = if diverged { 1 } else { reason.exit_code() };
+    std::process::exit(code);
+}
 
-        let branch_delayed_instruction = instructions::get_instruction(branch_delayed_instruction);
+fn parse_address(text: &str) -> Result<u32, String> {
+    parse_number(text).ok_or_else(|| String::from("expected a number, decimal or 0x hex"))
+}
 
-        branch_delayed_instruction.execute(self);
+fn parse_format(name: &str) -> Result<Format, String> {
+    Format::from_name(name).ok_or_else(|| {
+        String::from("expected split, rim, elf, ihex, hextext, mif, coe, raw or asm")
+    })
+}
+
+fn parse_endian(name: &str) -> Result<Endian, String> {
+    match name {
+        "big" => Ok(Endian::Big),
+        "little" => Ok(Endian::Little),
+        _ => Err(String::from("expected big or little")),
     }
 }
 
-impl std::fmt::Display for CPU {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Registers:\n")?;
-        for (i, register) in self.registers.iter().enumerate() {
-            write!(f, "Register {}: {}\n", i, register.read())?;
+fn parse_raw(value: &str) -> Result<(String, u32), String> {
+    match value.rsplit_once('@') {
+        Some((path, address)) if !path.is_empty() => {
+            Ok((path.to_string(), parse_address(address)?))
         }
-        Ok(())
+        _ => Err(String::from("expected FILE@ADDRESS")),
     }
 }
 
-fn read_program_elf(cpu: &mut CPU, file_path: &str) {
-    use std::fs::File;
-    use std::io::Read;
+fn parse_limit(value: &str) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(limit) if limit > 0 => Ok(limit),
+        _ => Err(String::from("expected a positive number")),
+    }
+}
+
+fn parse_trace_format(name: &str) -> Result<TraceFormat, String> {
+    TraceFormat::from_name(name).ok_or_else(|| String::from("expected spike or gem5"))
+}
 
-    let mut data = File::open(format!("{}.data", file_path)).expect("File not found");
-    let mut data_code = Vec::<u32>::new();
-    while let Ok(word) = data.read_u32::<LittleEndian>() {
-        data_code.push(word);
+fn parse_energy_weights(text: &str) -> Result<EnergyModel, String> {
+    EnergyModel::parse(text).map_err(|error| error.to_string())
+}
+
+fn parse_divide_by_zero(name: &str) -> Result<DivideByZero, String> {
+    match name {
+        "undefined" => Ok(DivideByZero::Undefined),
+        "trap" => Ok(DivideByZero::Trap),
+        "abort" => Ok(DivideByZero::Abort),
+        _ => Err(String::from("expected undefined, trap or abort")),
     }
+}
 
-    let mut text = File::open(format!("{}.text", file_path)).expect("File not found");
-    let mut text_code = Vec::<u32>::new();
-    while let Ok(word) = text.read_u32::<LittleEndian>() {
-        text_code.push(word);
+fn parse_alignment(name: &str) -> Result<Alignment, String> {
+    match name {
+        "fault" => Ok(Alignment::Fault),
+        "emulate" => Ok(Alignment::Emulate),
+        _ => Err(String::from("expected fault or emulate")),
     }
+}
 
-    cpu.memory.load_text(text_code);
-    cpu.memory.load_data(data_code);
+fn parse_fault(text: &str) -> Result<Fault, String> {
+    Fault::parse(text).map_err(|error| error.to_string())
 }
 
-fn main() {
-    let mut cpu = CPU::new();
+fn parse_flip_rate(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+        _ => Err(String::from("expected a rate from 0 to 1")),
+    }
+}
+
+fn fail(error: &str) -> ! {
+    eprintln!("error: {}", error);
+    std::process::exit(1);
+}
 
-    read_program_elf(&mut cpu, "./examples/08.sort");
+#[cfg(test)]
+mod tests {
+    use super::{Cli, Command};
+    use clap::{CommandFactory, Parser};
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+
+        // A bare program is run
+        let cli = Cli::try_parse_from(["rustinmips", "prog.asm", "--", "a", "b"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.options.programs, ["prog.asm"]);
+        assert_eq!(cli.options.arguments, ["a", "b"]);
+
+        let cli = Cli::try_parse_from(["rustinmips", "asm", "-o", "out.rim", "a.asm", "b.asm"]);
+        match cli.unwrap().command {
+            Some(Command::Asm { output, options }) => {
+                assert_eq!(output.as_deref(), Some("out.rim"));
+                assert_eq!(options.programs, ["a.asm", "b.asm"]);
+            }
+            _ => panic!("expected asm"),
+        }
 
-    cpu.run();
+        assert!(Cli::try_parse_from(["rustinmips", "--raw", "data.bin", "x.asm"]).is_err());
+        assert!(Cli::try_parse_from(["rustinmips", "--shadow-sets", "16", "x.asm"]).is_err());
+    }
 }
@@ -0,0 +1,282 @@
+use crate::checks;
+use crate::instructions::{self, REGISTER_NAMES};
+use crate::{ExitReason, CPU};
+
+// The datapath stages an instruction goes through, as taught
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Fetch,
+    Decode,
+    RegisterRead,
+    Execute,
+    WriteBack,
+}
+
+// Walks the machine one phase at a time. The instruction really runs in the
+// execute phase; the registers it writes are held back and only land in
+// write back, so each phase shows the state the datapath would.
+pub struct MicroStepper {
+    phase: Phase,
+    pc: u32,
+    word: u32,
+    writes: Vec<(usize, u32)>,
+    hi_lo: Option<(u32, u32)>,
+}
+
+impl MicroStepper {
+    pub fn new() -> MicroStepper {
+        MicroStepper {
+            phase: Phase::Fetch,
+            pc: 0,
+            word: 0,
+            writes: Vec::new(),
+            hi_lo: None,
+        }
+    }
+
+    // The phase the next call to step() performs
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn step(&mut self, cpu: &mut CPU) -> Result<String, ExitReason> {
+        match self.phase {
+            Phase::Fetch => {
+                self.pc = cpu.pc;
+                // A fetch that misses in the TLB takes its exception as a
+                // whole step, the way the real fetch in execute would
+                let Ok(physical) = cpu.cp0.translate(cpu.pc, false) else {
+                    if let Some(reason) = cpu.step_instruction() {
+                        return Err(reason);
+                    }
+                    return Ok(format!(
+                        "fetch      {:#010x}: TLB miss, to {:#010x}",
+                        self.pc, cpu.pc
+                    ));
+                };
+                self.word = cpu.memory.read(physical);
+                self.phase = Phase::Decode;
+                Ok(format!("fetch      {:#010x}: {:#010x}", self.pc, self.word))
+            }
+            Phase::Decode => {
+                self.phase = Phase::RegisterRead;
+                Ok(format!("decode     {}", fields(self.word)))
+            }
+            Phase::RegisterRead => {
+                self.phase = Phase::Execute;
+                let values: Vec<String> = checks::reads(self.word)
+                    .into_iter()
+                    .map(|register| {
+                        format!(
+                            "${} = {:#x}",
+                            REGISTER_NAMES[register],
+                            cpu.read_register(register)
+                        )
+                    })
+                    .collect();
+                Ok(format!("read       {}", list(values)))
+            }
+            Phase::Execute => self.execute(cpu),
+            Phase::WriteBack => {
+                self.phase = Phase::Fetch;
+                Ok(format!("write back {}", self.write_back(cpu)))
+            }
+        }
+    }
+
+    fn execute(&mut self, cpu: &mut CPU) -> Result<String, ExitReason> {
+        let before: Vec<u32> = (0..32).map(|register| cpu.read_register(register)).collect();
+        let (hi, lo) = (cpu.hi, cpu.lo);
+
+        let exit = cpu.step_instruction();
+
+        self.writes = (0..32)
+            .filter(|&register| cpu.read_register(register) != before[register])
+            .map(|register| (register, cpu.read_register(register)))
+            .collect();
+        self.hi_lo = Some((cpu.hi, cpu.lo)).filter(|&after| after != (hi, lo));
+
+        if let Some(reason) = exit {
+            self.phase = Phase::Fetch;
+            return Err(reason);
+        }
+
+        // Hold the results back until write back
+        for &(register, _) in &self.writes {
+            cpu.write_register(register, before[register]);
+        }
+        cpu.hi = hi;
+        cpu.lo = lo;
+
+        self.phase = Phase::WriteBack;
+        Ok(format!("execute    {}", self.operation(cpu)))
+    }
+
+    // What the ALU or memory stage did
+    fn operation(&self, cpu: &CPU) -> String {
+        let opcode = self.word >> 26;
+        let rs = ((self.word >> 21) & 0b11111) as usize;
+        let address = cpu
+            .read_register(rs)
+            .wrapping_add(self.word as u16 as i16 as u32);
+
+        match opcode {
+            0x28..=0x2e => format!(
+                "memory [{:#010x}] <- {:#010x}",
+                address,
+                cpu.cp0
+                    .translate(address - address % 4, true)
+                    .map_or(0, |physical| cpu.memory.read(physical))
+            ),
+            0x20..=0x26 => format!("memory [{:#010x}]", address),
+            _ if checks::is_control_transfer(self.word) => format!("next pc {:#010x}", cpu.pc),
+            _ => String::from("alu"),
+        }
+    }
+
+    fn write_back(&mut self, cpu: &mut CPU) -> String {
+        let mut written = Vec::new();
+
+        for (register, value) in self.writes.drain(..) {
+            cpu.write_register(register, value);
+            written.push(format!("${} <- {:#x}", REGISTER_NAMES[register], value));
+        }
+
+        if let Some((hi, lo)) = self.hi_lo.take() {
+            cpu.hi = hi;
+            cpu.lo = lo;
+            written.push(format!("hi <- {:#x}, lo <- {:#x}", hi, lo));
+        }
+
+        list(written)
+    }
+}
+
+impl Default for MicroStepper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The instruction's fields, split the way its format lays them out
+fn fields(word: u32) -> String {
+    let name = instructions::get_instruction(word).name().to_string();
+    let opcode = word >> 26;
+    let rs = REGISTER_NAMES[((word >> 21) & 0b11111) as usize];
+    let rt = REGISTER_NAMES[((word >> 16) & 0b11111) as usize];
+    let rd = REGISTER_NAMES[((word >> 11) & 0b11111) as usize];
+
+    match opcode {
+        0 => format!(
+            "{} R: rs ${} rt ${} rd ${} shamt {} funct {:#04x}",
+            name,
+            rs,
+            rt,
+            rd,
+            (word >> 6) & 0b11111,
+            word & 0b111111
+        ),
+        2 | 3 => format!("{} J: target {:#09x}", name, word & 0x03ff_ffff),
+        _ => format!(
+            "{} I: opcode {:#04x} rs ${} rt ${} imm {}",
+            name, opcode, rs, rt, word as u16 as i16
+        ),
+    }
+}
+
+fn list(items: Vec<String>) -> String {
+    if items.is_empty() {
+        return String::from("(nothing)");
+    }
+
+    items.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MicroStepper, Phase};
+    use crate::{ExitReason, CPU};
+
+    #[test]
+    fn test_phases_of_addi() {
+        let mut cpu = CPU::new();
+        // addi $t0, $t1, 5
+        cpu.memory.load_segment("text", 0x00400000, &0x21280005u32.to_le_bytes(), true).unwrap();
+        cpu.start();
+        cpu.write_register(9, 2);
+        let mut stepper = MicroStepper::new();
+
+        assert_eq!(
+            stepper.step(&mut cpu).unwrap(),
+            "fetch      0x00400000: 0x21280005"
+        );
+        assert_eq!(
+            stepper.step(&mut cpu).unwrap(),
+            "decode     ADDI I: opcode 0x08 rs $t1 rt $t0 imm 5"
+        );
+        assert_eq!(stepper.step(&mut cpu).unwrap(), "read       $t1 = 0x2");
+        assert_eq!(stepper.step(&mut cpu).unwrap(), "execute    alu");
+
+        // Not written back yet
+        assert_eq!(stepper.phase(), Phase::WriteBack);
+        assert_eq!(cpu.read_register(8), 0);
+
+        assert_eq!(stepper.step(&mut cpu).unwrap(), "write back $t0 <- 0x7");
+        assert_eq!(cpu.read_register(8), 7);
+        assert_eq!(cpu.pc, 0x00400004);
+        assert_eq!(stepper.phase(), Phase::Fetch);
+    }
+
+    #[test]
+    fn test_store_phases() {
+        let mut cpu = CPU::new();
+        // sw $t1, 4($gp)
+        cpu.memory.load_segment("text", 0x00400000, &0xaf890004u32.to_le_bytes(), true).unwrap();
+        cpu.start();
+        cpu.write_register(9, 0xabcd);
+        let mut stepper = MicroStepper::new();
+
+        for _ in 0..3 {
+            stepper.step(&mut cpu).unwrap();
+        }
+
+        assert_eq!(
+            stepper.step(&mut cpu).unwrap(),
+            "execute    memory [0x10008004] <- 0x0000abcd"
+        );
+        assert_eq!(stepper.step(&mut cpu).unwrap(), "write back (nothing)");
+    }
+
+    #[test]
+    fn test_fetch_is_translated() {
+        let mut cpu = CPU::new();
+        cpu.enable_mmu(4);
+        // 0x00400000 maps to the frame at 0x00800000 (global, dirty, valid)
+        cpu.cp0.entry_hi = 0x00400000;
+        cpu.cp0.entry_lo0 = 0x800 << 6 | 0b111;
+        cpu.cp0.entry_lo1 = 0b001;
+        cpu.cp0.tlb_write(false);
+        // addi $t0, $t1, 5
+        cpu.memory
+            .load_segment("text", 0x00800000, &0x21280005u32.to_le_bytes(), true)
+            .unwrap();
+        cpu.start();
+        let mut stepper = MicroStepper::new();
+
+        assert_eq!(
+            stepper.step(&mut cpu).unwrap(),
+            "fetch      0x00400000: 0x21280005"
+        );
+        for _ in 0..4 {
+            stepper.step(&mut cpu).unwrap();
+        }
+        assert_eq!(cpu.read_register(8), 5);
+
+        // The odd page of the pair isn't valid, and nothing handles the miss
+        cpu.pc = 0x00401000;
+        assert_eq!(
+            stepper.step(&mut cpu).unwrap_err(),
+            ExitReason::TlbMiss(0x00401000)
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver};
+
+use crate::device::Device;
+
+// MARS' keyboard and display: a receiver and a transmitter, each with a
+// control register (ready, interrupt enable) and a data register
+pub const BASE: u32 = 0xffff0000;
+pub const END: u32 = 0xffff0010;
+
+const RECEIVER_CONTROL: u32 = 0x0;
+const RECEIVER_DATA: u32 = 0x4;
+const TRANSMITTER_CONTROL: u32 = 0x8;
+const TRANSMITTER_DATA: u32 = 0xc;
+
+const READY: u32 = 1;
+const INTERRUPT_ENABLE: u32 = 1 << 1;
+
+// Instructions the display takes to show a character, as in MARS
+const TRANSMIT_DELAY: u32 = 5;
+
+// MARS raises the keyboard interrupt on Cause bit 8 (IP0) and the display
+// interrupt on bit 9 (IP1)
+const KEYBOARD_INTERRUPT: u32 = 1 << 0;
+const DISPLAY_INTERRUPT: u32 = 1 << 1;
+
+pub struct MmioConsole {
+    input: Receiver<u8>,
+    output: Box<dyn Write>,
+    receiver_control: u32,
+    receiver_data: u32,
+    transmitter_control: u32,
+    // Instructions until the transmitter is ready again
+    busy: u32,
+}
+
+impl MmioConsole {
+    // Keys arrive on `input` whenever they are typed; characters go to
+    // `output` as the guest sends them
+    pub fn new(input: Receiver<u8>, output: Box<dyn Write>) -> MmioConsole {
+        MmioConsole {
+            input,
+            output,
+            receiver_control: 0,
+            receiver_data: 0,
+            transmitter_control: READY,
+            busy: 0,
+        }
+    }
+
+    // Wired to the host's stdin and stdout. Stdin is read on its own thread
+    // so a guest polling the receiver never blocks the emulator.
+    pub fn stdio() -> MmioConsole {
+        let (keys, input) = mpsc::channel();
+        std::thread::spawn(move || {
+            for byte in io::BufReader::new(io::stdin()).bytes() {
+                match byte {
+                    Ok(byte) if keys.send(byte).is_ok() => {}
+                    _ => break,
+                }
+            }
+        });
+
+        MmioConsole::new(input, Box::new(io::stdout()))
+    }
+}
+
+impl Device for MmioConsole {
+    fn read(&mut self, offset: u32) -> u32 {
+        match offset {
+            RECEIVER_CONTROL => self.receiver_control,
+            // Reading the key makes room for the next one
+            RECEIVER_DATA => {
+                self.receiver_control &= !READY;
+                self.receiver_data
+            }
+            TRANSMITTER_CONTROL => self.transmitter_control,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u32, value: u32) {
+        match offset {
+            // Only the interrupt enable bits are writable
+            RECEIVER_CONTROL => {
+                self.receiver_control = self.receiver_control & READY | value & INTERRUPT_ENABLE;
+            }
+            TRANSMITTER_CONTROL => {
+                self.transmitter_control =
+                    self.transmitter_control & READY | value & INTERRUPT_ENABLE;
+            }
+            TRANSMITTER_DATA => {
+                let _ = self.output.write_all(&[value as u8]);
+                let _ = self.output.flush();
+                self.transmitter_control &= !READY;
+                self.busy = TRANSMIT_DELAY;
+            }
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self) {
+        if self.receiver_control & READY == 0 {
+            if let Ok(key) = self.input.try_recv() {
+                self.receiver_data = key as u32;
+                self.receiver_control |= READY;
+            }
+        }
+
+        if self.busy > 0 {
+            self.busy -= 1;
+            if self.busy == 0 {
+                self.transmitter_control |= READY;
+            }
+        }
+    }
+
+    fn interrupts(&self) -> u32 {
+        let raised =
+            |control: u32| control & (READY | INTERRUPT_ENABLE) == READY | INTERRUPT_ENABLE;
+
+        let mut lines = 0;
+        if raised(self.receiver_control) {
+            lines |= KEYBOARD_INTERRUPT;
+        }
+        if raised(self.transmitter_control) {
+            lines |= DISPLAY_INTERRUPT;
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MmioConsole, RECEIVER_CONTROL, RECEIVER_DATA, TRANSMITTER_CONTROL, TRANSMITTER_DATA,
+    };
+    use crate::device::Device;
+    use crate::{ExitReason, CPU};
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn cpu_with(text: &[u32], handler: &[u32]) -> CPU {
+        let mut cpu = CPU::new();
+        let bytes: Vec<u8> = text.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory
+            .load_segment("text", 0x00400000, &bytes, true)
+            .unwrap();
+        let bytes: Vec<u8> = handler.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory
+            .load_segment("ktext", crate::EXCEPTION_VECTOR, &bytes, true)
+            .unwrap();
+        cpu
+    }
+
+    #[test]
+    fn test_guest_writes_the_display() {
+        // lui $t0, 0xffff / addi $t1, $zero, 0x41 / sb $t1, 12($t0) /
+        // addi $v0, $zero, 10 / syscall
+        let mut cpu = cpu_with(
+            &[0x3c08ffff, 0x20090041, 0xa109000c, 0x2002000a, 0x0000000c],
+            &[],
+        );
+        let (_keys, input) = mpsc::channel();
+        let output = Shared::default();
+        let console = MmioConsole::new(input, Box::new(output.clone()));
+        cpu.attach(super::BASE, super::END, Box::new(console))
+            .unwrap();
+
+        assert_eq!(cpu.run().unwrap(), ExitReason::Exited(0));
+        assert_eq!(*output.0.borrow(), b"A");
+    }
+
+    #[test]
+    fn test_keyboard_interrupt() {
+        // lui $t0, 0xffff / addi $t1, $zero, 2 / sw $t1, 0($t0) /
+        // loop: beq $zero, $zero, loop / nop
+        let text = [0x3c08ffff, 0x20090002, 0xad090000, 0x1000ffff, 0];
+        // lui $t0, 0xffff / lw $t2, 4($t0) / addi $v0, $zero, 10 / syscall
+        let handler = [0x3c08ffff, 0x8d0a0004, 0x2002000a, 0x0000000c];
+        let mut cpu = cpu_with(&text, &handler);
+        let (keys, input) = mpsc::channel();
+        let console = MmioConsole::new(input, Box::new(std::io::sink()));
+        cpu.attach(super::BASE, super::END, Box::new(console))
+            .unwrap();
+        keys.send(b'k').unwrap();
+
+        cpu.start();
+        cpu.cp0.status = crate::cp0::Cp0::STATUS_IE | 1 << 8;
+        assert_eq!(cpu.resume().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.read_register(10), b'k' as u32);
+        assert_eq!(cpu.cp0.exception_code(), 0);
+    }
+
+    #[test]
+    fn test_receiver_ready_and_interrupt() {
+        let (keys, input) = mpsc::channel();
+        let mut console = MmioConsole::new(input, Box::new(std::io::sink()));
+        console.write(RECEIVER_CONTROL, 0b11);
+
+        console.tick();
+        assert_eq!(console.read(RECEIVER_CONTROL), 0b10);
+
+        keys.send(b'x').unwrap();
+        console.tick();
+        assert_eq!(console.read(RECEIVER_CONTROL), 0b11);
+        assert_eq!(console.interrupts(), 0b01);
+
+        assert_eq!(console.read(RECEIVER_DATA), b'x' as u32);
+        assert_eq!(console.read(RECEIVER_CONTROL), 0b10);
+        assert_eq!(console.interrupts(), 0);
+    }
+
+    #[test]
+    fn test_transmitter_is_busy_after_a_write() {
+        let (_keys, input) = mpsc::channel();
+        let mut console = MmioConsole::new(input, Box::new(std::io::sink()));
+        assert_eq!(console.read(TRANSMITTER_CONTROL), 1);
+
+        console.write(TRANSMITTER_DATA, b'A' as u32);
+        assert_eq!(console.read(TRANSMITTER_CONTROL), 0);
+
+        for _ in 0..super::TRANSMIT_DELAY {
+            console.tick();
+        }
+        assert_eq!(console.read(TRANSMITTER_CONTROL), 1);
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The guest's random number generators (syscalls 40 to 44). As in MARS each
+// id has its own stream; ids the guest never seeded start from the machine's
+// seed, so the same seed replays a whole run.
+#[derive(Debug, Clone)]
+pub struct Random {
+    seed: u64,
+    generators: HashMap<u32, u64>,
+}
+
+impl Default for Random {
+    // Seeded from the host clock
+    fn default() -> Random {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Random::new(now)
+    }
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random {
+            seed,
+            generators: HashMap::new(),
+        }
+    }
+
+    // Forgets the guest's seeds and everything drawn so far
+    pub fn reset(&mut self) {
+        self.generators.clear();
+    }
+
+    // Syscall 40
+    pub fn set_seed(&mut self, id: u32, seed: u32) {
+        self.generators.insert(id, Self::start(seed as u64));
+    }
+
+    // Syscall 41
+    pub fn next_u32(&mut self, id: u32) -> u32 {
+        (self.next(id) >> 32) as u32
+    }
+
+    // Syscall 42: uniform in [0, bound), 0 for an empty range
+    pub fn below(&mut self, id: u32, bound: u32) -> u32 {
+        ((self.next_u32(id) as u64 * bound as u64) >> 32) as u32
+    }
+
+    // Syscall 43: uniform in [0, 1)
+    pub fn next_f32(&mut self, id: u32) -> f32 {
+        (self.next(id) >> 40) as f32 / (1u32 << 24) as f32
+    }
+
+    // Syscall 44: uniform in [0, 1)
+    pub fn next_f64(&mut self, id: u32) -> f64 {
+        (self.next(id) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // xorshift must not start from zero
+    fn start(seed: u64) -> u64 {
+        seed ^ 0x9e37_79b9_7f4a_7c15
+    }
+
+    fn next(&mut self, id: u32) -> u64 {
+        let seed = self.seed ^ (id as u64).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        let state = self
+            .generators
+            .entry(id)
+            .or_insert_with(|| Self::start(seed));
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Random;
+
+    #[test]
+    fn test_same_seed_same_numbers() {
+        let draw =
+            |random: &mut Random| -> Vec<u32> { (0..4).map(|_| random.next_u32(0)).collect() };
+
+        let mut first = Random::new(7);
+        let mut second = Random::new(7);
+        assert_eq!(draw(&mut first), draw(&mut second));
+
+        // Each id is its own stream
+        let mut other = Random::new(7);
+        let drawn: Vec<u32> = (0..4).map(|_| other.next_u32(1)).collect();
+        assert_ne!(drawn, draw(&mut Random::new(7)));
+
+        // A guest seed wins over the machine's
+        first.set_seed(0, 42);
+        second = Random::new(99);
+        second.set_seed(0, 42);
+        assert_eq!(draw(&mut first), draw(&mut second));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut random = Random::new(1);
+        for _ in 0..1000 {
+            assert!(random.below(3, 10) < 10);
+            assert!((0.0..1.0).contains(&random.next_f32(3)));
+            assert!((0.0..1.0).contains(&random.next_f64(3)));
+        }
+        assert_eq!(random.below(3, 0), 0);
+    }
+}
@@ -0,0 +1,101 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::device::Device;
+
+// Where the clock device is mapped by --rtc
+pub const BASE: u32 = 0xffff0020;
+pub const END: u32 = 0xffff002c;
+
+// Milliseconds since the epoch, low word first; reading the low word
+// latches the high one so the pair is consistent
+const TIME_LOW: u32 = 0x0;
+const TIME_HIGH: u32 = 0x4;
+// Writing a number of milliseconds sleeps that long
+const SLEEP: u32 = 0x8;
+
+// Where the time syscall, the sleep syscall and the clock device get the
+// time from
+#[derive(Clone, Default)]
+pub enum Clock {
+    // The host's wall clock; sleeping really sleeps
+    #[default]
+    Host,
+    // Milliseconds since the epoch that only move when the guest sleeps,
+    // for reproducible runs. Clones share the time.
+    Virtual(Rc<Cell<u64>>),
+}
+
+impl Clock {
+    pub fn fixed(milliseconds: u64) -> Clock {
+        Clock::Virtual(Rc::new(Cell::new(milliseconds)))
+    }
+
+    // Milliseconds since the epoch
+    pub fn now(&self) -> u64 {
+        match self {
+            Clock::Host => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis() as u64),
+            Clock::Virtual(now) => now.get(),
+        }
+    }
+
+    pub fn sleep(&self, milliseconds: u32) {
+        match self {
+            Clock::Host => std::thread::sleep(Duration::from_millis(milliseconds as u64)),
+            Clock::Virtual(now) => now.set(now.get() + milliseconds as u64),
+        }
+    }
+}
+
+pub struct Rtc {
+    clock: Clock,
+    high: u32,
+}
+
+impl Rtc {
+    pub fn new(clock: Clock) -> Rtc {
+        Rtc { clock, high: 0 }
+    }
+}
+
+impl Device for Rtc {
+    fn read(&mut self, offset: u32) -> u32 {
+        match offset {
+            TIME_LOW => {
+                let now = self.clock.now();
+                self.high = (now >> 32) as u32;
+                now as u32
+            }
+            TIME_HIGH => self.high,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u32, value: u32) {
+        if offset == SLEEP {
+            self.clock.sleep(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, Rtc, SLEEP, TIME_HIGH, TIME_LOW};
+    use crate::device::Device;
+
+    #[test]
+    fn test_time_and_sleep() {
+        let clock = Clock::fixed(0x1_0000_fff0);
+        let mut rtc = Rtc::new(clock.clone());
+
+        assert_eq!(rtc.read(TIME_LOW), 0x0000_fff0);
+        rtc.write(SLEEP, 0x20);
+        // Still the high word latched with the first low word
+        assert_eq!(rtc.read(TIME_HIGH), 1);
+        assert_eq!(clock.now(), 0x1_0001_0010);
+        assert_eq!(rtc.read(TIME_LOW), 0x0001_0010);
+    }
+}
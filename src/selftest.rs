@@ -0,0 +1,598 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::instructions;
+use crate::{loader, ExitReason, CPU};
+
+const T0: u32 = 8;
+const T1: u32 = 9;
+const T2: u32 = 10;
+const S7: u32 = 23;
+const GP: u32 = 28;
+const RA: u32 = 31;
+
+// One instruction-level test program. The harness sets the inputs, the body
+// leaves its result in $t0, and an appended check sets $v1 to 1 when $t0
+// holds the expected value ($s7) and exits; a program passes when it exits
+// with $v1 = 1.
+pub struct Case {
+    pub name: &'static str,
+    pub description: &'static str,
+    // Registers set before the program starts
+    inputs: Vec<(u32, u32)>,
+    body: Vec<u32>,
+    expected: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail(String),
+    // The program uses an instruction this build doesn't decode
+    Missing(String),
+}
+
+fn r(funct: u32, rs: u32, rt: u32, rd: u32, shamt: u32) -> u32 {
+    rs << 21 | rt << 16 | rd << 11 | shamt << 6 | funct
+}
+
+fn i(opcode: u32, rs: u32, rt: u32, imm: i16) -> u32 {
+    opcode << 26 | rs << 21 | rt << 16 | imm as u16 as u32
+}
+
+// COP1: fmt, then ft (or a GPR for the moves), fs, fd
+fn f(fmt: u32, ft: u32, fs: u32, fd: u32, funct: u32) -> u32 {
+    0x11 << 26 | fmt << 21 | ft << 16 | fs << 11 | fd << 6 | funct
+}
+
+fn j(opcode: u32, index: u32) -> u32 {
+    opcode << 26 | (loader::TEXT_BASE + index * 4) >> 2 & 0x03ff_ffff
+}
+
+fn case(name: &'static str, description: &'static str, body: &[u32], expected: u32) -> Case {
+    Case {
+        name,
+        description,
+        inputs: Vec::new(),
+        body: body.to_vec(),
+        expected,
+    }
+}
+
+// $t0 = $t1 <funct> $t2; variable shifts shift $t2 by $t1
+fn binary(
+    name: &'static str,
+    description: &'static str,
+    funct: u32,
+    a: u32,
+    b: u32,
+    expected: u32,
+) -> Case {
+    case(name, description, &[r(funct, T1, T2, T0, 0)], expected)
+        .with(T1, a)
+        .with(T2, b)
+}
+
+// $t0 = $t1 <opcode> imm
+fn immediate(
+    name: &'static str,
+    description: &'static str,
+    opcode: u32,
+    a: u32,
+    imm: i16,
+    expected: u32,
+) -> Case {
+    case(name, description, &[i(opcode, T1, T0, imm)], expected).with(T1, a)
+}
+
+// $t0 = $t1 shifted by shamt
+fn shift(
+    name: &'static str,
+    description: &'static str,
+    funct: u32,
+    a: u32,
+    shamt: u32,
+    expected: u32,
+) -> Case {
+    case(name, description, &[r(funct, 0, T1, T0, shamt)], expected).with(T1, a)
+}
+
+// Stores $t1 at offset($gp) with `store` and loads it back with `load`
+fn store_load(
+    name: &'static str,
+    description: &'static str,
+    store: u32,
+    load: u32,
+    offset: i16,
+    value: u32,
+    expected: u32,
+) -> Case {
+    let body = [i(store, GP, T1, offset), i(load, GP, T0, offset)];
+    case(name, description, &body, expected).with(T1, value)
+}
+
+pub fn suite() -> Vec<Case> {
+    vec![
+        binary("ADD", "adds two registers", 0x20, 5, 7, 12),
+        binary("ADDU", "wraps around", 0x21, 0xffff_ffff, 2, 1),
+        binary("SUB", "goes negative", 0x22, 3, 5, 0xffff_fffe),
+        binary("SUBU", "wraps around", 0x23, 0, 1, 0xffff_ffff),
+        binary("AND", "masks bits", 0x24, 0xff0f, 0x0ff0, 0x0f00),
+        binary("OR", "merges bits", 0x25, 0xf000, 0x000f, 0xf00f),
+        binary("XOR", "toggles bits", 0x26, 0xff00, 0x0ff0, 0xf0f0),
+        binary(
+            "NOR",
+            "inverts the or",
+            0x27,
+            0xffff_0000,
+            0x0000_00ff,
+            0x0000_ff00,
+        ),
+        binary("SLT", "compares signed", 0x2a, 0xffff_ffff, 1, 1),
+        binary("SLTU", "compares unsigned", 0x2b, 0xffff_ffff, 1, 0),
+        case(
+            "TEQ",
+            "continues when not equal",
+            &[r(0x34, T1, 0, 0, 7)],
+            0,
+        )
+        .with(T1, 1),
+        case("TNE", "continues when equal", &[r(0x36, T1, T1, 0, 0)], 0).with(T1, 1),
+        case("MOVZ", "moves when rt is zero", &[r(0x0a, T1, 0, T0, 0)], 5).with(T1, 5),
+        case(
+            "MOVN",
+            "keeps rd when rt is zero",
+            &[r(0x0b, T1, 0, T0, 0)],
+            3,
+        )
+        .with(T0, 3)
+        .with(T1, 5),
+        case("MUL", "keeps the low word", &[0x712a_4002], -42i32 as u32)
+            .with(T1, -6i32 as u32)
+            .with(T2, 7),
+        case(
+            "MADD",
+            "accumulates into HI:LO",
+            &[r(0x13, T0, 0, 0, 0), 0x712a_0000, r(0x12, 0, 0, T0, 0)],
+            58,
+        )
+        .with(T0, 100)
+        .with(T1, -6i32 as u32)
+        .with(T2, 7),
+        case(
+            "MSUBU",
+            "borrows from HI",
+            &[0x712a_0005, r(0x10, 0, 0, T0, 0)],
+            0xffff_ffff,
+        )
+        .with(T1, 1)
+        .with(T2, 1),
+        case("CLZ", "counts 32 for zero", &[0x7008_4020], 32),
+        case("CLO", "counts leading ones", &[0x7128_4021], 16).with(T1, 0xffff_0000),
+        case("ROTR", "rotates right", &[0x0029_4202], 0x7812_3456).with(T1, 0x1234_5678),
+        case(
+            "ROTRV",
+            "rotates right by a register",
+            &[0x0149_4046],
+            0xc000_0000,
+        )
+        .with(T1, 0x8000_0001)
+        .with(T2, 33),
+        case(
+            "WSBH",
+            "swaps bytes within halfwords",
+            &[0x7c09_40a0],
+            0x2211_4433,
+        )
+        .with(T1, 0x1122_3344),
+        case("EXT", "extracts a bit field", &[0x7d28_3900], 0x67).with(T1, 0x1234_5678),
+        case("INS", "inserts a bit field", &[0x7d28_7a04], 0xffff_abff)
+            .with(T0, 0xffff_ffff)
+            .with(T1, 0x1234_56ab),
+        case(
+            "SEB",
+            "sign-extends the low byte",
+            &[0x7c09_4420],
+            0xffff_ff80,
+        )
+        .with(T1, 0x1234_5680),
+        case(
+            "SEH",
+            "sign-extends the low half",
+            &[0x7c09_4620],
+            0xffff_8001,
+        )
+        .with(T1, 0x0000_8001),
+        shift("SLL", "shifts left", 0x00, 0x8000_0001, 4, 0x10),
+        shift("SRL", "shifts in zeros", 0x02, 0x8000_0000, 4, 0x0800_0000),
+        shift(
+            "SRA",
+            "shifts in the sign",
+            0x03,
+            0x8000_0000,
+            4,
+            0xf800_0000,
+        ),
+        case(
+            "MULT",
+            "keeps the low word in LO",
+            &[r(0x18, T1, T2, 0, 0), r(0x12, 0, 0, T0, 0)],
+            -42i32 as u32,
+        )
+        .with(T1, -6i32 as u32)
+        .with(T2, 7),
+        case(
+            "MULTU",
+            "keeps the high word in HI",
+            &[r(0x19, T1, T2, 0, 0), r(0x10, 0, 0, T0, 0)],
+            0xffff_fffe,
+        )
+        .with(T1, 0xffff_ffff)
+        .with(T2, 0xffff_ffff),
+        case(
+            "DIV",
+            "leaves the remainder in HI",
+            &[r(0x1a, T1, T2, 0, 0), r(0x10, 0, 0, T0, 0)],
+            -2i32 as u32,
+        )
+        .with(T1, -17i32 as u32)
+        .with(T2, 5),
+        case(
+            "DIVU",
+            "leaves the quotient in LO",
+            &[r(0x1b, T1, T2, 0, 0), r(0x12, 0, 0, T0, 0)],
+            0x3333_3333,
+        )
+        .with(T1, 0xffff_ffff)
+        .with(T2, 5),
+        case(
+            "MTLO",
+            "round-trips through LO",
+            &[r(0x13, T1, 0, 0, 0), r(0x12, 0, 0, T0, 0)],
+            0x1234,
+        )
+        .with(T1, 0x1234),
+        binary("SLLV", "uses the low 5 bits", 0x04, 36, 1, 0x10),
+        binary("SRLV", "shifts in zeros", 0x06, 4, 0x8000_0000, 0x0800_0000),
+        binary(
+            "SRAV",
+            "shifts in the sign",
+            0x07,
+            4,
+            0x8000_0000,
+            0xf800_0000,
+        ),
+        immediate("ADDI", "sign-extends", 0x08, 5, -7, 0xffff_fffe),
+        immediate("ADDIU", "sign-extends", 0x09, 0, -1, 0xffff_ffff),
+        immediate("SLTI", "compares signed", 0x0a, 0xffff_fffe, -1, 1),
+        immediate(
+            "SLTIU",
+            "sign-extends, then compares unsigned",
+            0x0b,
+            5,
+            -1,
+            1,
+        ),
+        immediate("ANDI", "zero-extends", 0x0c, 0xffff_ffff, -0x8000, 0x8000),
+        immediate("ORI", "zero-extends", 0x0d, 0, -0x8000, 0x8000),
+        immediate("XORI", "zero-extends", 0x0e, 0xffff_ffff, -1, 0xffff_0000),
+        case(
+            "LUI",
+            "loads the upper half",
+            &[i(0x0f, 0, T0, 0x1234)],
+            0x1234_0000,
+        ),
+        store_load(
+            "LW",
+            "reads back a stored word",
+            0x2b,
+            0x23,
+            0,
+            0xdead_beef,
+            0xdead_beef,
+        ),
+        store_load("LB", "sign-extends", 0x28, 0x20, 1, 0x1ff, 0xffff_ffff),
+        store_load("LBU", "zero-extends", 0x28, 0x24, 1, 0x1ff, 0xff),
+        store_load("LH", "sign-extends", 0x29, 0x21, 2, 0x1_8001, 0xffff_8001),
+        store_load("LHU", "zero-extends", 0x29, 0x25, 2, 0x1_8001, 0x8001),
+        case(
+            "MTC1",
+            "round-trips through an FPR",
+            &[f(0x04, T1, 2, 0, 0), f(0x00, T0, 2, 0, 0)],
+            0x1234_5678,
+        )
+        .with(T1, 0x1234_5678),
+        case(
+            "LWC1",
+            "loads into an FPR",
+            &[i(0x2b, GP, T1, 0), i(0x31, GP, 4, 0), f(0x00, T0, 4, 0, 0)],
+            0xdead_beef,
+        )
+        .with(T1, 0xdead_beef),
+        case(
+            "SWC1",
+            "stores from an FPR",
+            &[f(0x04, T1, 4, 0, 0), i(0x39, GP, 4, 0), i(0x23, GP, T0, 0)],
+            0xdead_beef,
+        )
+        .with(T1, 0xdead_beef),
+        case(
+            "ADD.S",
+            "adds singles",
+            &[
+                f(0x04, T1, 2, 0, 0),
+                f(0x04, T2, 4, 0, 0),
+                f(0x10, 4, 2, 0, 0x00),
+                f(0x00, T0, 0, 0, 0),
+            ],
+            0x4070_0000,
+        )
+        .with(T1, 0x3fc0_0000)
+        .with(T2, 0x4010_0000),
+        case(
+            "BC1T",
+            "branches on C.LT.S",
+            &[
+                f(0x04, T1, 2, 0, 0),
+                f(0x04, T2, 4, 0, 0),
+                f(0x10, 4, 2, 0, 0x3c),
+                f(0x08, 1, 0, 0, 2),
+                0,
+                i(0x08, 0, T0, 1),
+            ],
+            0,
+        )
+        .with(T1, 0x3fc0_0000)
+        .with(T2, 0x4010_0000),
+        case(
+            "SC",
+            "succeeds right after LL",
+            &[i(0x30, GP, T1, 0), i(0x38, GP, T0, 0)],
+            1,
+        ),
+        case(
+            "SB",
+            "writes one byte lane",
+            &[i(0x2b, GP, T1, 0), i(0x28, GP, 0, 2), i(0x23, GP, T0, 0)],
+            0x1100_3344,
+        )
+        .with(T1, 0x1122_3344),
+        case(
+            "BEQ",
+            "runs its delay slot and skips to the target",
+            &[i(0x04, 0, 0, 2), i(0x08, 0, T0, 7), i(0x08, 0, T0, 0)],
+            7,
+        ),
+        case(
+            "BNE",
+            "falls through when equal",
+            &[i(0x05, 0, 0, 1), 0, i(0x08, 0, T0, 3)],
+            3,
+        ),
+        case(
+            "BLEZ",
+            "takes negative values",
+            &[i(0x06, T1, 0, 2), 0, i(0x08, 0, T0, 1)],
+            0,
+        )
+        .with(T1, 0xffff_ffff),
+        case(
+            "BGTZ",
+            "takes positive values",
+            &[i(0x07, T1, 0, 2), 0, i(0x08, 0, T0, 1)],
+            0,
+        )
+        .with(T1, 1),
+        case(
+            "BLTZ",
+            "takes negative values",
+            &[i(0x01, T1, 0, 2), 0, i(0x08, 0, T0, 1)],
+            0,
+        )
+        .with(T1, 0xffff_ffff),
+        case(
+            "BGEZ",
+            "takes zero",
+            &[i(0x01, 0, 1, 2), 0, i(0x08, 0, T0, 1)],
+            0,
+        ),
+        case(
+            "BGEZAL",
+            "links past the delay slot",
+            &[i(0x01, 0, 0x11, 2), 0, 0, r(0x21, RA, 0, T0, 0)],
+            loader::TEXT_BASE + 8,
+        ),
+        case(
+            "BLTZAL",
+            "links even when not taken",
+            &[i(0x01, 0, 0x10, 2), r(0x21, RA, 0, T0, 0)],
+            loader::TEXT_BASE + 8,
+        ),
+        case(
+            "BEQL",
+            "nullifies the delay slot when not taken",
+            &[i(0x14, 0, T1, 1), i(0x08, 0, T0, 9)],
+            3,
+        )
+        .with(T0, 3)
+        .with(T1, 1),
+        case(
+            "BNEL",
+            "runs the delay slot when taken",
+            &[i(0x15, 0, T1, 1), i(0x08, 0, T0, 9)],
+            9,
+        )
+        .with(T1, 1),
+        case(
+            "BLEZL",
+            "nullifies the delay slot when not taken",
+            &[i(0x16, T1, 0, 1), i(0x08, 0, T0, 9)],
+            3,
+        )
+        .with(T0, 3)
+        .with(T1, 1),
+        case(
+            "BGTZL",
+            "nullifies the delay slot when not taken",
+            &[i(0x17, T1, 0, 1), i(0x08, 0, T0, 9)],
+            3,
+        )
+        .with(T0, 3)
+        .with(T1, 0xffff_ffff),
+        case(
+            "J",
+            "jumps within the region",
+            &[j(0x02, 3), 0, i(0x08, 0, T0, 1)],
+            0,
+        ),
+        case(
+            "JAL",
+            "links past the delay slot",
+            &[j(0x03, 2), 0, r(0x21, RA, 0, T0, 0)],
+            loader::TEXT_BASE + 8,
+        ),
+        case(
+            "JR",
+            "jumps to a register",
+            &[r(0x08, T1, 0, 0, 0), 0, i(0x08, 0, T0, 0)],
+            1,
+        )
+        .with(T0, 1)
+        .with(T1, loader::TEXT_BASE + 3 * 4),
+        case(
+            "JALR",
+            "links into rd",
+            &[r(0x09, T1, 0, T0, 0), 0],
+            loader::TEXT_BASE + 2 * 4,
+        )
+        .with(T1, loader::TEXT_BASE + 2 * 4),
+    ]
+}
+
+impl Case {
+    fn with(mut self, register: u32, value: u32) -> Case {
+        self.inputs.push((register, value));
+        self
+    }
+
+    // The whole program: body, then the check and exit
+    fn program(&self) -> Vec<u32> {
+        let mut program = self.body.clone();
+        program.extend([
+            i(0x05, T0, S7, 2),
+            0,
+            i(0x08, 0, 3, 1),
+            i(0x08, 0, 2, 10),
+            r(0x0c, 0, 0, 0, 0),
+        ]);
+        program
+    }
+
+    pub fn run(&self) -> Outcome {
+        let program = self.program();
+        if let Some(&word) = program.iter().find(|&&word| !instructions::is_known(word)) {
+            return Outcome::Missing(format!("{:#010x} does not decode", word));
+        }
+
+        let mut cpu = CPU::new();
+        let bytes: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+        if let Err(error) = cpu
+            .memory
+            .load_segment("text", loader::TEXT_BASE, &bytes, true)
+        {
+            return Outcome::Fail(error.to_string());
+        }
+        // The cases are straight-line code; a broken branch mustn't hang the suite
+        cpu.set_instruction_limit(Some(1000));
+        cpu.start();
+        for &(register, value) in &self.inputs {
+            cpu.write_register(register as usize, value);
+        }
+        cpu.write_register(S7 as usize, self.expected);
+
+        // A bug in one instruction shouldn't take the whole suite down
+        let reason = match panic::catch_unwind(AssertUnwindSafe(|| cpu.resume())) {
+            Ok(Ok(reason)) => reason,
+            Ok(Err(error)) => return Outcome::Missing(error.to_string()),
+            Err(_) => return Outcome::Missing(String::from("the emulator panicked")),
+        };
+
+        match reason {
+            ExitReason::Exited(0) if cpu.read_register(3) == 1 => Outcome::Pass,
+            // Named, so is_known passed, but not implemented
+            ExitReason::ReservedInstruction(pc) => {
+                Outcome::Missing(format!("reserved instruction at {:#010x}", pc))
+            }
+            ExitReason::Exited(0) => Outcome::Fail(format!(
+                "$t0 = {:#010x}, expected {:#010x}",
+                cpu.read_register(T0 as usize),
+                self.expected
+            )),
+            reason => Outcome::Fail(reason.to_string()),
+        }
+    }
+}
+
+// Runs the whole suite and returns the report and whether everything passed
+pub fn run() -> (String, bool) {
+    // Keep the report readable if an instruction panics
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let outcomes: Vec<(Case, Outcome)> = suite()
+        .into_iter()
+        .map(|case| {
+            let outcome = case.run();
+            (case, outcome)
+        })
+        .collect();
+    panic::set_hook(hook);
+
+    let mut report = String::new();
+    for (case, outcome) in &outcomes {
+        let (status, detail) = match outcome {
+            Outcome::Pass => ("pass", String::new()),
+            Outcome::Fail(detail) => ("FAIL", format!(": {}", detail)),
+            Outcome::Missing(detail) => ("MISSING", format!(": {}", detail)),
+        };
+        report.push_str(&format!(
+            "{:<8}{:<7}{}{}\n",
+            status, case.name, case.description, detail
+        ));
+    }
+
+    let passed = outcomes
+        .iter()
+        .filter(|(_, outcome)| *outcome == Outcome::Pass)
+        .count();
+    report.push_str(&format!("\n{}/{} passed\n", passed, outcomes.len()));
+    (report, passed == outcomes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{case, suite, Outcome};
+
+    fn outcome(name: &str) -> Outcome {
+        suite()
+            .into_iter()
+            .find(|case| case.name == name)
+            .unwrap()
+            .run()
+    }
+
+    #[test]
+    fn test_passing_cases() {
+        for name in ["ADDU", "SRAV", "LB", "SB", "BEQ", "BEQL", "JAL", "JALR"] {
+            assert_eq!(outcome(name), Outcome::Pass, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_wrong_expectation_fails() {
+        let wrong = case("LUI", "", &[0x3c081234], 0);
+
+        assert_eq!(
+            wrong.run(),
+            Outcome::Fail(String::from("$t0 = 0x12340000, expected 0x00000000"))
+        );
+    }
+}
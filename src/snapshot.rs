@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cp0::Cp0;
+use crate::cp1::Cp1;
+use crate::Region;
+
+// Everything a program can observe of the machine between two instructions,
+// from CPU::snapshot. Devices, the console and host files are not part of
+// it, nor are settings such as the load delay or the instruction limit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MachineState {
+    pub pc: u32,
+    pub hi: u32,
+    pub lo: u32,
+    pub registers: Vec<u32>,
+    // By set number; the entry for the current set is stale
+    pub shadow_sets: Vec<Vec<u32>>,
+    pub cp0: Cp0,
+    pub cp1: Cp1,
+    pub entry: u32,
+    pub executed: u64,
+    // A load that lands after the next instruction: register and value
+    pub pending_load: Option<(usize, u32)>,
+    // The word an LL linked
+    pub link: Option<u32>,
+    pub regions: Vec<Region>,
+    // Start and break
+    pub heap: Option<(u32, u32)>,
+    // The pages ever written, by page number; the rest of memory is zero
+    pub pages: BTreeMap<u32, Vec<u8>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MachineState;
+    use crate::{ExitReason, CPU};
+
+    // addi $t0, $zero, 7 / sw $t0, 0($gp) / mthi $t0 / addi $v0, $zero, 10 / syscall
+    fn cpu() -> CPU {
+        let text: [u32; 5] = [0x20080007, 0xaf880000, 0x01000011, 0x2002000a, 0x0000000c];
+        let mut cpu = CPU::new();
+        let bytes: Vec<u8> = text.iter().flat_map(|word| word.to_le_bytes()).collect();
+        cpu.memory
+            .load_segment("text", 0x00400000, &bytes, true)
+            .unwrap();
+        cpu.start();
+        cpu
+    }
+
+    #[test]
+    fn test_restore_resumes_where_the_snapshot_was_taken() {
+        let mut cpu = cpu();
+        cpu.step();
+        cpu.step();
+        let state = cpu.snapshot();
+        assert_eq!(state.pc, 0x00400008);
+        assert_eq!(state.registers[8], 7);
+
+        assert_eq!(cpu.resume().unwrap(), ExitReason::Exited(0));
+        assert_eq!(cpu.snapshot().hi, 7);
+
+        let mut other = CPU::new();
+        other.restore(&state).unwrap();
+        assert_eq!(other.snapshot(), state);
+        assert_eq!(other.memory.read(0x10008000), 7);
+        assert_eq!(other.resume().unwrap(), ExitReason::Exited(0));
+        assert_eq!(other.snapshot(), cpu.snapshot());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut cpu = cpu();
+        cpu.step();
+        let state = cpu.snapshot();
+
+        let json = serde_json::to_string(&state).unwrap();
+        let parsed: MachineState = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, state);
+
+        let mut broken = state;
+        broken.registers.pop();
+        assert!(cpu.restore(&broken).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use crate::error::Error;
+use crate::instructions::REGISTER_NAMES;
+use crate::CPU;
+
+// Window titles of a QtSpim log; each one ends the section before it
+const SECTIONS: [&str; 6] = [
+    "Int Regs",
+    "FP Regs",
+    "User Text Segment",
+    "Kernel Text Segment",
+    "User data segment",
+    "Console",
+];
+
+// Final machine state recorded by QtSpim ("Save Log File") or by spim's
+// register dump. Values are read as hex, which is how both print them.
+#[derive(Debug, Default, PartialEq)]
+pub struct SpimLog {
+    pub registers: [Option<u32>; 32],
+    pub hi: Option<u32>,
+    pub lo: Option<u32>,
+    pub console: Option<String>,
+}
+
+impl SpimLog {
+    pub fn parse(text: &str) -> Result<SpimLog, Error> {
+        let mut log = SpimLog::default();
+        let mut console: Option<Vec<&str>> = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let title = line.trim().to_lowercase();
+            let section = SECTIONS
+                .iter()
+                .find(|section| !line.contains('=') && title.starts_with(&section.to_lowercase()));
+            if let Some(section) = section {
+                if console.is_some() {
+                    log.console = console.take().map(|lines| lines.join("\n"));
+                }
+                if *section == "Console" {
+                    console = Some(Vec::new());
+                }
+                continue;
+            }
+
+            if let Some(lines) = console.as_mut() {
+                lines.push(line);
+                continue;
+            }
+
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => continue,
+            };
+            let invalid = || Error::Parse(format!("line {}: invalid value '{}'", number + 1, value));
+            let value = value.trim_start_matches("0x");
+
+            if name.eq_ignore_ascii_case("HI") {
+                log.hi = Some(u32::from_str_radix(value, 16).map_err(|_| invalid())?);
+            } else if name.eq_ignore_ascii_case("LO") {
+                log.lo = Some(u32::from_str_radix(value, 16).map_err(|_| invalid())?);
+            } else if let Some(register) = general_register(name) {
+                log.registers[register] =
+                    Some(u32::from_str_radix(value, 16).map_err(|_| invalid())?);
+            }
+        }
+
+        if let Some(lines) = console {
+            log.console = Some(lines.join("\n"));
+        }
+
+        if log.registers.iter().all(Option::is_none) && log.console.is_none() {
+            return Err(Error::Parse(String::from(
+                "no registers or console output found in the log",
+            )));
+        }
+        Ok(log)
+    }
+}
+
+// "R8  [t0]" (QtSpim) or "R8  (t0)" (spim)
+fn general_register(name: &str) -> Option<usize> {
+    let number = name.strip_prefix('R')?;
+    let number = number
+        .split(|c: char| c.is_whitespace() || c == '[' || c == '(')
+        .next()?;
+    number.parse().ok().filter(|&register| register < 32)
+}
+
+// A place where this run and the SPIM log disagree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub what: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}\n  spim:       {}\n  rustinmips: {}",
+            self.what, self.expected, self.actual
+        )
+    }
+}
+
+// Compares the state of a finished run with the log. $k0, $k1 and $at are
+// left out: SPIM's startup code and exception handler use them.
+pub fn compare(log: &SpimLog, cpu: &CPU) -> Vec<Divergence> {
+    let mut divergences = Vec::new();
+
+    for (register, expected) in log.registers.iter().enumerate() {
+        let expected = match expected {
+            Some(expected) if ![1, 26, 27].contains(&register) => *expected,
+            _ => continue,
+        };
+        let actual = cpu.read_register(register);
+        if actual != expected {
+            divergences.push(Divergence {
+                what: format!("register ${}", REGISTER_NAMES[register]),
+                expected: format!("{:#010x}", expected),
+                actual: format!("{:#010x}", actual),
+            });
+        }
+    }
+
+    for (name, expected, actual) in [("hi", log.hi, cpu.hi), ("lo", log.lo, cpu.lo)] {
+        match expected {
+            Some(expected) if expected != actual => divergences.push(Divergence {
+                what: format!("register {}", name),
+                expected: format!("{:#010x}", expected),
+                actual: format!("{:#010x}", actual),
+            }),
+            _ => {}
+        }
+    }
+
+    if let Some(expected) = &log.console {
+        divergences.extend(compare_console(expected, cpu.console()));
+    }
+
+    divergences
+}
+
+// The first console line that differs, after the line before it for context
+fn compare_console(expected: &str, actual: &str) -> Option<Divergence> {
+    let expected: Vec<&str> = expected.trim_end().lines().collect();
+    let actual: Vec<&str> = actual.trim_end().lines().collect();
+
+    let line = (0..expected.len().max(actual.len()))
+        .find(|&line| expected.get(line) != actual.get(line))?;
+    let show = |lines: &[&str]| {
+        let context = match line {
+            0 => String::new(),
+            _ => format!("{:?} / ", lines.get(line - 1).unwrap_or(&"")),
+        };
+        match lines.get(line) {
+            Some(text) => format!("{}{:?}", context, text),
+            None => format!("{}(end of output)", context),
+        }
+    };
+
+    Some(Divergence {
+        what: format!("console line {}", line + 1),
+        expected: show(&expected),
+        actual: show(&actual),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, SpimLog};
+    use crate::CPU;
+
+    const LOG: &str = "\
+Int Regs [16]
+PC       = 400030
+HI       = 0
+LO       = 6
+R0  [r0] = 0
+R1  [at] = 10010000
+R2  [v0] = a
+R8  [t0] = 2a
+
+Console
+Enter a number: 6
+Result: 42
+";
+
+    #[test]
+    fn test_parse() {
+        let log = SpimLog::parse(LOG).unwrap();
+
+        assert_eq!(log.registers[2], Some(10));
+        assert_eq!(log.registers[8], Some(0x2a));
+        assert_eq!(log.registers[9], None);
+        assert_eq!(log.lo, Some(6));
+        assert_eq!(
+            log.console.as_deref(),
+            Some("Enter a number: 6\nResult: 42")
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let log = SpimLog::parse(LOG).unwrap();
+        let mut cpu = CPU::new();
+        cpu.write_register(2, 10);
+        cpu.write_register(8, 41);
+        cpu.lo = 6;
+        cpu.record_console();
+        cpu.io.write("Enter a number: 6\nResult: 41\n");
+
+        let divergences = compare(&log, &cpu);
+
+        assert_eq!(divergences.len(), 2);
+        assert_eq!(divergences[0].what, "register $t0");
+        assert_eq!(divergences[0].expected, "0x0000002a");
+        assert_eq!(divergences[1].what, "console line 2");
+        assert_eq!(
+            divergences[1].actual,
+            "\"Enter a number: 6\" / \"Result: 41\""
+        );
+    }
+}
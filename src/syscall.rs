@@ -0,0 +1,487 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use serde::Deserialize;
+
+use crate::console::ReadLine;
+use crate::{ExitReason, CPU};
+
+// A service behind the syscall instruction. It finds its arguments in the
+// registers and leaves its results there, like the built-in ones.
+pub type Syscall = Rc<dyn Fn(&mut CPU)>;
+
+// $v0 picks the service. Unknown services do nothing.
+pub fn execute(cpu: &mut CPU) {
+    let code = cpu.read_register(2);
+    if let Some(syscall) = cpu.syscalls.get(&code).cloned() {
+        syscall(cpu);
+    }
+}
+
+type Builtin = fn(&mut CPU);
+
+// Which built-in services a machine offers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compatibility {
+    // SPIM's plus the MARS additions (time, sleep, printing in hex, binary
+    // and unsigned, random numbers)
+    #[default]
+    Mars,
+    // Only SPIM's, 1 to 17
+    Spim,
+    // None, for bare-metal programs and hosts that register their own
+    None,
+}
+
+pub fn builtins_for(compatibility: Compatibility) -> HashMap<u32, Syscall> {
+    let mut services = builtins();
+    match compatibility {
+        Compatibility::Mars => {}
+        Compatibility::Spim => services.retain(|&code, _| code <= 17),
+        Compatibility::None => services.clear(),
+    }
+    services
+}
+
+// The SPIM and MARS services. Arguments come in $a0, $a1, $a2 and $f12,
+// results go back in $v0 or $f0 unless noted.
+pub fn builtins() -> HashMap<u32, Syscall> {
+    let services: [(u32, Builtin); 27] = [
+        (1, print_int),
+        (2, print_float),
+        (3, print_double),
+        (4, print_string),
+        (5, read_int),
+        (6, read_float),
+        (7, read_double),
+        (8, read_string),
+        (9, sbrk),
+        (10, exit),
+        (11, print_char),
+        (12, read_char),
+        (13, open),
+        (14, read),
+        (15, write),
+        (16, close),
+        (17, exit2),
+        (30, time),
+        (32, sleep),
+        (34, print_hex),
+        (35, print_binary),
+        (36, print_unsigned),
+        (40, set_seed),
+        (41, random_int),
+        (42, random_int_range),
+        (43, random_float),
+        (44, random_double),
+    ];
+
+    services
+        .into_iter()
+        .map(|(code, service)| (code, Rc::new(service) as Syscall))
+        .collect()
+}
+
+fn print_int(cpu: &mut CPU) {
+    let value = cpu.read_register(4) as i32;
+    cpu.io.write(&value.to_string());
+}
+
+fn print_float(cpu: &mut CPU) {
+    let value = cpu.cp1.read_single(12);
+    cpu.io.write(&format!("{:?}", value));
+}
+
+fn print_double(cpu: &mut CPU) {
+    let value = cpu.cp1.read_double(12);
+    cpu.io.write(&format!("{:?}", value));
+}
+
+fn print_string(cpu: &mut CPU) {
+    let address = cpu.read_register(4);
+    let text = get_text(cpu, address);
+    cpu.io.write(&text);
+}
+
+fn read_int(cpu: &mut CPU) {
+    let Some(input) = read_line(cpu, "0") else {
+        return;
+    };
+    let value: i32 = input.trim().parse().unwrap_or(0);
+    cpu.write_register(2, value as u32);
+}
+
+fn read_float(cpu: &mut CPU) {
+    let Some(input) = read_line(cpu, "0") else {
+        return;
+    };
+    cpu.cp1.write_single(0, input.trim().parse().unwrap_or(0.0));
+}
+
+fn read_double(cpu: &mut CPU) {
+    let Some(input) = read_line(cpu, "0") else {
+        return;
+    };
+    cpu.cp1.write_double(0, input.trim().parse().unwrap_or(0.0));
+}
+
+fn read_string(cpu: &mut CPU) {
+    let Some(input) = read_line(cpu, "") else {
+        return;
+    };
+    let address = cpu.read_register(4);
+    let length = cpu.read_register(5);
+
+    // Like SPIM, read at most a1 - 1 characters and always null-terminate
+    let max = (length as usize).saturating_sub(1);
+    let mut bytes: Vec<u8> = input.chars().map(|c| c as u8).take(max).collect();
+    if length > 0 {
+        bytes.push(0);
+    }
+    set_text(cpu, address, &bytes);
+}
+
+// Heap memory: $a0 more bytes, the old break in $v0
+fn sbrk(cpu: &mut CPU) {
+    let amount = cpu.read_register(4) as i32;
+    let sp = cpu.read_register(29);
+    match cpu.memory.sbrk(amount, sp) {
+        Ok(old) => cpu.write_register(2, old),
+        Err(requested) => cpu.stop(ExitReason::HeapCollision(requested)),
+    }
+}
+
+fn exit(cpu: &mut CPU) {
+    cpu.stop(ExitReason::Exited(0));
+}
+
+fn print_char(cpu: &mut CPU) {
+    let the_char = cpu.read_register(4) as u8 as char;
+    cpu.io.write(&the_char.to_string());
+}
+
+fn read_char(cpu: &mut CPU) {
+    let Some(input) = read_line(cpu, "\0") else {
+        return;
+    };
+    let the_char = input.chars().next().unwrap_or('\n');
+    cpu.write_register(2, the_char as u8 as u32);
+}
+
+// File descriptor in $v0, negative on failure
+fn open(cpu: &mut CPU) {
+    let address = cpu.read_register(4);
+    let path = get_text(cpu, address);
+    let flags = cpu.read_register(5);
+    let fd = cpu.files.open(&path, flags).map_or(-1, |fd| fd as i32);
+    cpu.write_register(2, fd as u32);
+}
+
+// Read up to $a2 bytes from descriptor $a0 into $a1. Standard input gives one
+// line per call.
+fn read(cpu: &mut CPU) {
+    let fd = cpu.read_register(4);
+    let buffer = cpu.read_register(5);
+    let max = cpu.read_register(6);
+
+    let bytes = if fd == 0 {
+        let Some(input) = read_line(cpu, "") else {
+            return;
+        };
+        Ok(input.chars().map(|c| c as u8).take(max as usize).collect())
+    } else {
+        cpu.files.read(fd, max)
+    };
+    let count = match bytes {
+        Ok(bytes) => {
+            set_text(cpu, buffer, &bytes);
+            bytes.len() as i32
+        }
+        Err(_) => -1,
+    };
+    cpu.write_register(2, count as u32);
+}
+
+// How much of a write's buffer the host holds at once
+const WRITE_CHUNK: u32 = 4096;
+
+// Write $a2 bytes from $a1 to descriptor $a0, a chunk at a time; standard
+// output and standard error both go to the console
+fn write(cpu: &mut CPU) {
+    let fd = cpu.read_register(4);
+    let buffer = cpu.read_register(5);
+    let length = cpu.read_register(6);
+
+    // At least one pass, so a bad descriptor fails even with nothing to write
+    let mut written = 0;
+    loop {
+        let size = WRITE_CHUNK.min(length - written);
+        let bytes: Vec<u8> = (written..written + size)
+            .map(|i| cpu.read_guest_u8(buffer.wrapping_add(i)))
+            .collect();
+
+        if fd == 1 || fd == 2 {
+            cpu.io.write(&latin1_to_string(&bytes));
+        } else if cpu.files.write(fd, &bytes).is_err() {
+            cpu.write_register(2, -1i32 as u32);
+            return;
+        }
+        written += size;
+        if written == length {
+            break;
+        }
+    }
+    cpu.write_register(2, written);
+}
+
+fn close(cpu: &mut CPU) {
+    let fd = cpu.read_register(4);
+    cpu.files.close(fd);
+}
+
+// Exit with the status in $a0
+fn exit2(cpu: &mut CPU) {
+    let status = cpu.read_register(4) as i32;
+    cpu.stop(ExitReason::Exited(status));
+}
+
+// Milliseconds since the epoch, low word in $a0 and high in $a1
+fn time(cpu: &mut CPU) {
+    let now = cpu.clock.now();
+    cpu.write_register(4, now as u32);
+    cpu.write_register(5, (now >> 32) as u32);
+}
+
+fn sleep(cpu: &mut CPU) {
+    cpu.clock.sleep(cpu.read_register(4));
+}
+
+fn print_hex(cpu: &mut CPU) {
+    let value = cpu.read_register(4);
+    cpu.io.write(&format!("0x{:08x}", value));
+}
+
+fn print_binary(cpu: &mut CPU) {
+    let value = cpu.read_register(4);
+    cpu.io.write(&format!("{:032b}", value));
+}
+
+fn print_unsigned(cpu: &mut CPU) {
+    let value = cpu.read_register(4);
+    cpu.io.write(&value.to_string());
+}
+
+// The random number services take the generator's id in $a0
+fn set_seed(cpu: &mut CPU) {
+    let (id, seed) = (cpu.read_register(4), cpu.read_register(5));
+    cpu.random.set_seed(id, seed);
+}
+
+// The number in $a0
+fn random_int(cpu: &mut CPU) {
+    let value = cpu.random.next_u32(cpu.read_register(4));
+    cpu.write_register(4, value);
+}
+
+// Below $a1, in $a0
+fn random_int_range(cpu: &mut CPU) {
+    let (id, bound) = (cpu.read_register(4), cpu.read_register(5));
+    let value = cpu.random.below(id, bound);
+    cpu.write_register(4, value);
+}
+
+fn random_float(cpu: &mut CPU) {
+    let value = cpu.random.next_f32(cpu.read_register(4));
+    cpu.cp1.write_single(0, value);
+}
+
+fn random_double(cpu: &mut CPU) {
+    let value = cpu.random.next_f64(cpu.read_register(4));
+    cpu.cp1.write_double(0, value);
+}
+
+// A line typed on the console, or `at_eof` once input runs out. None when
+// the user pressed Ctrl-C, which stops the machine.
+fn read_line(cpu: &mut CPU, at_eof: &str) -> Option<String> {
+    match cpu.io.read_line() {
+        ReadLine::Line(input) => Some(input),
+        ReadLine::Eof => Some(String::from(at_eof)),
+        ReadLine::Interrupted => {
+            cpu.stop(ExitReason::Interrupted);
+            None
+        }
+    }
+}
+
+// The NUL-terminated string at `address`
+pub fn get_text(cpu: &mut CPU, address: u32) -> String {
+    let mut bytes = Vec::new();
+    let mut address = address;
+    loop {
+        let byte = cpu.read_guest_u8(address);
+        if byte == 0 {
+            break;
+        }
+        bytes.push(byte);
+        address = address.wrapping_add(1);
+    }
+
+    latin1_to_string(&bytes)
+}
+
+pub fn set_text(cpu: &mut CPU, address: u32, bytes: &[u8]) {
+    for (i, &byte) in bytes.iter().enumerate() {
+        cpu.write_guest_u8(address.wrapping_add(i as u32), byte);
+    }
+}
+
+fn latin1_to_string(s: &[u8]) -> String {
+    s.iter().map(|&c| c as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{builtins_for, execute, get_text, set_text, Compatibility};
+    use crate::{ExitReason, CPU};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn call(cpu: &mut CPU, service: u32, a0: u32) {
+        cpu.registers[2].write(service);
+        cpu.registers[4].write(a0);
+        execute(cpu);
+    }
+
+    #[test]
+    fn test_set_text() {
+        let mut cpu = CPU::new();
+        let data_address = 0x10010002;
+        cpu.memory.write(0x10010000, 0x0000_4241);
+
+        set_text(&mut cpu, data_address, "Hello\0".as_bytes());
+
+        assert_eq!(get_text(&mut cpu, 0x10010000), "ABHello");
+
+        // Text running past the top of memory wraps to address 0
+        set_text(&mut cpu, 0xfffffffe, b"wrap\0");
+        assert_eq!(get_text(&mut cpu, 0xfffffffe), "wrap");
+    }
+
+    #[test]
+    fn test_print_numbers() {
+        let mut cpu = CPU::new();
+        cpu.record_console();
+        call(&mut cpu, 1, -5i32 as u32);
+        call(&mut cpu, 36, -5i32 as u32);
+        call(&mut cpu, 34, 0xbeef);
+        call(&mut cpu, 35, 5);
+        cpu.cp1.write_single(12, 1.5);
+        call(&mut cpu, 2, 0);
+        cpu.cp1.write_double(12, -2.0);
+        call(&mut cpu, 3, 0);
+
+        assert_eq!(
+            cpu.io.transcript(),
+            "-54294967291\
+             0x0000beef\
+             00000000000000000000000000000101\
+             1.5-2.0"
+        );
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let directory = std::env::temp_dir().join(format!("rustinmips-io-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut cpu = CPU::new();
+        cpu.set_sandbox(directory.clone());
+        set_text(&mut cpu, 0x10010000, b"notes.txt\0");
+        set_text(&mut cpu, 0x10010100, b"hi there");
+
+        cpu.registers[5].write(1);
+        call(&mut cpu, 13, 0x10010000);
+        let fd = cpu.registers[2].read();
+        cpu.registers[5].write(0x10010100);
+        cpu.registers[6].write(8);
+        call(&mut cpu, 15, fd);
+        assert_eq!(cpu.registers[2].read(), 8);
+        call(&mut cpu, 16, fd);
+
+        cpu.registers[5].write(0);
+        call(&mut cpu, 13, 0x10010000);
+        let fd = cpu.registers[2].read();
+        cpu.registers[5].write(0x10010200);
+        cpu.registers[6].write(100);
+        call(&mut cpu, 14, fd);
+        assert_eq!(cpu.registers[2].read(), 8);
+        assert_eq!(get_text(&mut cpu, 0x10010200), "hi there");
+
+        // Closed descriptors and paths outside the sandbox fail
+        call(&mut cpu, 16, fd);
+        call(&mut cpu, 14, fd);
+        assert_eq!(cpu.registers[2].read() as i32, -1);
+        set_text(&mut cpu, 0x10010000, b"../notes.txt\0");
+        call(&mut cpu, 13, 0x10010000);
+        assert_eq!(cpu.registers[2].read() as i32, -1);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_write_in_chunks() {
+        let mut cpu = CPU::new();
+        cpu.record_console();
+        set_text(&mut cpu, 0x10010000, &[b'x'; 10000]);
+
+        cpu.registers[5].write(0x10010000);
+        cpu.registers[6].write(10000);
+        call(&mut cpu, 15, 1);
+        assert_eq!(cpu.registers[2].read(), 10000);
+        assert_eq!(cpu.io.transcript(), "x".repeat(10000));
+
+        call(&mut cpu, 15, 9);
+        assert_eq!(cpu.registers[2].read() as i32, -1);
+        cpu.registers[6].write(0);
+        call(&mut cpu, 15, 9);
+        assert_eq!(cpu.registers[2].read() as i32, -1);
+    }
+
+    #[test]
+    fn test_exit2() {
+        let mut cpu = CPU::new();
+        call(&mut cpu, 17, 3);
+        assert_eq!(cpu.exit, Some(ExitReason::Exited(3)));
+    }
+
+    #[test]
+    fn test_compatibility() {
+        assert_eq!(builtins_for(Compatibility::Mars).len(), 27);
+        let spim = builtins_for(Compatibility::Spim);
+        assert!(spim.contains_key(&17) && !spim.contains_key(&30));
+        assert!(builtins_for(Compatibility::None).is_empty());
+    }
+
+    #[test]
+    fn test_registered_syscalls() {
+        let mut cpu = CPU::new();
+        cpu.record_console();
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        cpu.register_syscall(100, move |cpu| {
+            counter.set(counter.get() + 1);
+            let doubled = cpu.read_register(4) * 2;
+            cpu.write_register(2, doubled);
+        });
+        // Replaces print int
+        cpu.register_syscall(1, |cpu| cpu.stop(ExitReason::Exited(42)));
+
+        call(&mut cpu, 100, 21);
+        assert_eq!(cpu.read_register(2), 42);
+        assert_eq!(calls.get(), 1);
+
+        call(&mut cpu, 1, 7);
+        assert_eq!(cpu.exit, Some(ExitReason::Exited(42)));
+        assert_eq!(cpu.io.transcript(), "");
+    }
+}
@@ -0,0 +1,80 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use crate::console::{Console, ReadLine};
+
+// The host's stdin and stdout as a console. When stdin is a tty, reads go
+// through a line editor (arrow keys, history, Ctrl-C); otherwise stdin is
+// read plainly.
+pub struct Terminal {
+    editor: Option<DefaultEditor>,
+    pending: String,
+}
+
+impl Terminal {
+    pub fn new() -> Terminal {
+        let editor = if io::stdin().is_terminal() && io::stdout().is_terminal() {
+            DefaultEditor::new().ok()
+        } else {
+            None
+        };
+
+        Terminal {
+            editor,
+            pending: String::new(),
+        }
+    }
+}
+
+impl Console for Terminal {
+    fn write(&mut self, text: &str) {
+        print!("{}", text);
+        let _ = io::stdout().flush();
+
+        match text.rfind('\n') {
+            Some(index) => self.pending = text[index + 1..].to_string(),
+            None => self.pending.push_str(text),
+        }
+    }
+
+    fn read_line(&mut self) -> ReadLine {
+        let prompt = std::mem::take(&mut self.pending);
+
+        let editor = match self.editor.as_mut() {
+            Some(editor) => editor,
+            None => {
+                let mut line = String::new();
+                return match io::stdin().lock().read_line(&mut line) {
+                    Ok(0) | Err(_) => ReadLine::Eof,
+                    Ok(_) => ReadLine::Line(line),
+                };
+            }
+        };
+
+        // The guest already printed its prompt; move back to column 0 and let
+        // the editor redraw it so it isn't pushed onto a fresh line.
+        if !prompt.is_empty() {
+            print!("\r");
+            let _ = io::stdout().flush();
+        }
+
+        match editor.readline(&prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = editor.add_history_entry(line.as_str());
+                }
+                ReadLine::Line(line + "\n")
+            }
+            Err(ReadlineError::Interrupted) => ReadLine::Interrupted,
+            Err(_) => ReadLine::Eof,
+        }
+    }
+}
+
+impl Default for Terminal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
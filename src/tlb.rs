@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+
+// MIPS32 joint TLB. Each entry maps an even/odd pair of virtual pages,
+// selected by EntryHi.VPN2 and PageMask, to the physical frames in
+// EntryLo0 and EntryLo1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlbEntry {
+    pub page_mask: u32,
+    pub entry_hi: u32,
+    pub entry_lo0: u32,
+    pub entry_lo1: u32,
+}
+
+// Why a translation failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlbFault {
+    // No entry matches; goes to the refill vector
+    Refill,
+    // The matching page's V bit is clear
+    Invalid,
+    // A store to a page whose D bit is clear
+    Modified,
+}
+
+// EntryLo bits
+const GLOBAL: u32 = 1;
+const VALID: u32 = 1 << 1;
+const DIRTY: u32 = 1 << 2;
+const PFN_SHIFT: u32 = 6;
+
+const ASID: u32 = 0xff;
+
+impl TlbEntry {
+    // Bytes per page: 4 KiB unless PageMask makes it bigger
+    fn page_size(&self) -> u32 {
+        ((self.page_mask | 0x1fff) >> 1) + 1
+    }
+
+    fn is_global(&self) -> bool {
+        self.entry_lo0 & self.entry_lo1 & GLOBAL != 0
+    }
+
+    fn matches(&self, address: u32, asid: u32) -> bool {
+        let vpn2 = !(self.page_mask | 0x1fff);
+        (address & vpn2) == (self.entry_hi & vpn2)
+            && (self.is_global() || self.entry_hi & ASID == asid)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tlb {
+    entries: Vec<TlbEntry>,
+}
+
+impl Tlb {
+    // Entries start out mapping distinct kseg0 pages, which are never
+    // translated, so nothing matches until the guest writes them
+    pub fn new(size: usize) -> Tlb {
+        let entries = (0..size as u32)
+            .map(|index| TlbEntry {
+                entry_hi: 0x8000_0000 + index * 0x2000,
+                ..TlbEntry::default()
+            })
+            .collect();
+        Tlb { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // No TLB at all: every address maps to itself
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Out of range indexes wrap, which the architecture leaves undefined
+    pub fn read(&self, index: u32) -> TlbEntry {
+        self.entries[index as usize % self.len()]
+    }
+
+    // The global bit is kept only when both halves have it
+    pub fn write(&mut self, index: u32, mut entry: TlbEntry) {
+        let global = entry.is_global() as u32;
+        entry.entry_lo0 = entry.entry_lo0 & !GLOBAL | global;
+        entry.entry_lo1 = entry.entry_lo1 & !GLOBAL | global;
+
+        let size = self.len();
+        self.entries[index as usize % size] = entry;
+    }
+
+    // TLBP: the entry matching EntryHi's VPN2 and ASID
+    pub fn probe(&self, entry_hi: u32) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.matches(entry_hi, entry_hi & ASID))
+    }
+
+    pub fn translate(&self, address: u32, asid: u32, store: bool) -> Result<u32, TlbFault> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.matches(address, asid))
+            .ok_or(TlbFault::Refill)?;
+
+        let size = entry.page_size();
+        let lo = if address & size != 0 {
+            entry.entry_lo1
+        } else {
+            entry.entry_lo0
+        };
+
+        if lo & VALID == 0 {
+            return Err(TlbFault::Invalid);
+        }
+        if store && lo & DIRTY == 0 {
+            return Err(TlbFault::Modified);
+        }
+
+        let frame = (lo >> PFN_SHIFT) << 12;
+        Ok(frame & !(size - 1) | address & (size - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tlb, TlbEntry, TlbFault};
+
+    // Virtual 0x00400000-0x00401fff to physical 0x00010000 (even page, read
+    // only) and 0x00020000 (odd page, writable), for ASID 1
+    fn entry() -> TlbEntry {
+        TlbEntry {
+            page_mask: 0,
+            entry_hi: 0x0040_0001,
+            entry_lo0: 0x10 << 6 | 0b010,
+            entry_lo1: 0x20 << 6 | 0b110,
+        }
+    }
+
+    #[test]
+    fn test_translate_even_and_odd_pages() {
+        let mut tlb = Tlb::new(4);
+        tlb.write(2, entry());
+
+        assert_eq!(tlb.translate(0x0040_0123, 1, false), Ok(0x0001_0123));
+        assert_eq!(tlb.translate(0x0040_1ffc, 1, true), Ok(0x0002_0ffc));
+        assert_eq!(tlb.probe(0x0040_0001), Some(2));
+    }
+
+    #[test]
+    fn test_faults() {
+        let mut tlb = Tlb::new(4);
+        tlb.write(0, entry());
+
+        assert_eq!(tlb.translate(0x0040_0000, 1, true), Err(TlbFault::Modified));
+        // Another address space
+        assert_eq!(tlb.translate(0x0040_0000, 2, false), Err(TlbFault::Refill));
+        assert_eq!(tlb.translate(0x0050_0000, 1, false), Err(TlbFault::Refill));
+
+        let mut invalid = entry();
+        invalid.entry_lo0 = 0;
+        tlb.write(0, invalid);
+        assert_eq!(tlb.translate(0x0040_0000, 1, false), Err(TlbFault::Invalid));
+    }
+
+    #[test]
+    fn test_large_pages_and_global() {
+        let mut tlb = Tlb::new(1);
+        // 16 KiB pages, global
+        tlb.write(
+            0,
+            TlbEntry {
+                page_mask: 0x6000,
+                entry_hi: 0x1000_0000,
+                entry_lo0: 0x100 << 6 | 0b011,
+                entry_lo1: 0x104 << 6 | 0b011,
+            },
+        );
+
+        assert_eq!(tlb.translate(0x1000_3ffc, 7, false), Ok(0x0010_3ffc));
+        assert_eq!(tlb.translate(0x1000_4000, 9, false), Ok(0x0010_4000));
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::{self, Write};
+
+use crate::energy::Class;
+
+// Instruction trace layouts understood by other simulators' tooling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    // spike -l --log-commits:
+    //   core   0: 3 0x00400000 (0x20080001) r8  0x00000001
+    Spike,
+    // gem5 Exec debug flag:
+    //   1000: system.cpu: T0 : 0x00400000 @main : addi : IntAlu :  D=0x0000000000000001
+    // (the symbol as with the ExecSymbol flag, when there is one)
+    Gem5,
+}
+
+impl TraceFormat {
+    pub fn from_name(name: &str) -> Option<TraceFormat> {
+        match name {
+            "spike" => Some(TraceFormat::Spike),
+            "gem5" => Some(TraceFormat::Gem5),
+            _ => None,
+        }
+    }
+}
+
+// One retired instruction and its architectural effects
+pub struct Retired<'a> {
+    pub pc: u32,
+    pub word: u32,
+    pub name: &'a str,
+    // "main+0x14", if the program has symbols
+    pub symbol: Option<&'a str>,
+    pub writes: &'a [(usize, u32)],
+    // Address and value of a store
+    pub store: Option<(u32, u32)>,
+}
+
+pub struct Tracer {
+    format: TraceFormat,
+    output: Box<dyn Write>,
+    retired: u64,
+    // The first write that failed; nothing is written after it
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    // gem5 ticks are picoseconds; this models a 1 GHz core
+    const TICKS_PER_INSTRUCTION: u64 = 1000;
+
+    pub fn new(format: TraceFormat, output: Box<dyn Write>) -> Tracer {
+        Tracer {
+            format,
+            output,
+            retired: 0,
+            error: None,
+        }
+    }
+
+    pub fn record(&mut self, retired: &Retired) {
+        let line = match self.format {
+            TraceFormat::Spike => spike_line(retired),
+            TraceFormat::Gem5 => gem5_line(self.retired * Self::TICKS_PER_INSTRUCTION, retired),
+        };
+        self.retired += 1;
+
+        if self.error.is_none() {
+            if let Err(error) = writeln!(self.output, "{}", line) {
+                self.error = Some(error);
+            }
+        }
+    }
+
+    // The write that failed since the last call, if one did
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.output.flush(),
+        }
+    }
+}
+
+fn spike_line(retired: &Retired) -> String {
+    let mut line = format!("core   0: 3 {:#010x} ({:#010x})", retired.pc, retired.word);
+    for &(register, value) in retired.writes {
+        line.push_str(&format!(" r{:<2} {:#010x}", register, value));
+    }
+    if let Some((address, value)) = retired.store {
+        line.push_str(&format!(" mem {:#010x} {:#010x}", address, value));
+    }
+    line
+}
+
+fn gem5_line(tick: u64, retired: &Retired) -> String {
+    let op_class = match Class::of(retired.word) {
+        Class::Alu | Class::Shift => "IntAlu",
+        Class::MulDiv => "IntMult",
+        Class::Load => "MemRead",
+        Class::Store => "MemWrite",
+        Class::Branch | Class::Syscall => "No_OpClass",
+    };
+
+    let symbol = retired
+        .symbol
+        .map(|symbol| format!(" @{}", symbol))
+        .unwrap_or_default();
+    let mut line = format!(
+        "{}: system.cpu: T0 : {:#010x}{} : {} : {}",
+        tick,
+        retired.pc,
+        symbol,
+        retired.name.to_lowercase(),
+        op_class
+    );
+    if let Some(&(_, value)) = retired.writes.first() {
+        line.push_str(&format!(" :  D={:#018x}", value));
+    }
+    if let Some((address, _)) = retired.store {
+        line.push_str(&format!(" :  A={:#x}", address));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gem5_line, spike_line, Retired};
+
+    #[test]
+    fn test_spike_line() {
+        let retired = Retired {
+            pc: 0x00400000,
+            word: 0x20080001,
+            name: "ADDI",
+            symbol: Some("main"),
+            writes: &[(8, 1)],
+            store: None,
+        };
+
+        assert_eq!(
+            spike_line(&retired),
+            "core   0: 3 0x00400000 (0x20080001) r8  0x00000001"
+        );
+    }
+
+    #[test]
+    fn test_gem5_store_line() {
+        let retired = Retired {
+            pc: 0x00400004,
+            word: 0xaf880000,
+            name: "SW",
+            symbol: Some("main+0x4"),
+            writes: &[],
+            store: Some((0x10008000, 1)),
+        };
+
+        assert_eq!(
+            gem5_line(1000, &retired),
+            "1000: system.cpu: T0 : 0x00400004 @main+0x4 : sw : MemWrite :  A=0x10008000"
+        );
+    }
+}